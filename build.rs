@@ -7,80 +7,184 @@ use std::fs::File;
 fn main() {
     let qualifiers = [
         "allele",
+        "altitude",
         "anticodon",
+        "ApEinfo_fwdcolor",
+        "ApEinfo_graphicformat",
+        "ApEinfo_revcolor",
+        "artificial_location",
+        "bio_material",
+        "bond_type",
         "bound_moiety",
+        "calculated_mol_wt",
+        "cell_line",
+        "cell_type",
         "chromosome",
+        "circular_RNA",
+        "citation",
+        "clone",
+        "clone_lib",
+        "coded_by",
         "codon_recognized",
         "codon_start",
+        "collected_by",
+        "collection_date",
+        "compare",
         "country",
+        "cultivar",
+        "culture_collection",
         "db_xref",
+        "dev_stage",
+        "direction",
         "EC_number",
+        "ecotype",
+        "environmental_sample",
         "estimated_length",
         "exception",
         "experiment",
+        "focus",
+        "frequency",
         "function",
         "gap_type",
         "gene",
         "gene_synonym",
+        "geo_loc_name",
+        "germline",
+        "haplogroup",
+        "haplotype",
+        "host",
+        "identified_by",
         "inference",
+        "isolate",
         "isolation_source",
+        "lab_host",
         "label",
+        "lat_lon",
         "linkage_evidence",
         "locus_tag",
+        "macronuclear",
         "map",
+        "mating_type",
+        "metagenome_source",
         "mobile_element_type",
+        "mod_base",
         "mol_type",
         "ncRNA_class",
         "nomenclature",
         "note",
         "number",
         "old_locus_tag",
+        "operon",
         "organelle",
         "organism",
+        "PCR_conditions",
+        "PCR_primers",
+        "phenotype",
+        "plasmid",
+        "pop_variant",
         "product",
         "protein_id",
+        "proviral",
         "pseudo",
+        "pseudogene",
+        "rearranged",
         "recombination_class",
+        "region_name",
         "regulatory_class",
+        "replace",
         "ribosomal_slippage",
+        "rpt_family",
         "rpt_type",
+        "rpt_unit_range",
+        "rpt_unit_seq",
+        "satellite",
+        "sec_str_type",
+        "segment",
+        "serotype",
+        "serovar",
+        "sex",
+        "site_type",
+        "specimen_voucher",
         "standard_name",
         "strain",
+        "sub_clone",
+        "sub_species",
         "sub_strain",
+        "submitter_seqid",
+        "tag_peptide",
+        "tissue_lib",
         "tissue_type",
+        "trans_splicing",
         "transcript_id",
-        "translation",
+        "transgenic",
         "transl_except",
         "transl_table",
+        "translation",
+        "type_material",
+        "variety",
     ];
     let feature_kinds = [
+        "3'UTR",
+        "5'UTR",
         "assembly_gap",
+        "Bond",
+        "C_region",
         "CDS",
         "centromere",
-        "C_region",
         "D-loop",
         "D_segment",
         "exon",
+        "gap",
         "gene",
+        "Het",
+        "iDNA",
+        "intron",
         "J_segment",
+        "mat_peptide",
+        "misc_binding",
+        "misc_difference",
         "misc_feature",
         "misc_recomb",
         "misc_RNA",
+        "misc_structure",
         "mobile_element",
+        "modified_base",
         "mRNA",
+        "N_region",
         "ncRNA",
+        "non_std_residue",
+        "old_sequence",
+        "operon",
+        "oriT",
+        "polyA_site",
         "precursor_RNA",
+        "prim_transcript",
+        "primer_bind",
+        "propeptide",
+        "Protein",
         "protein_bind",
+        "Region",
         "regulatory",
-        "repeat_region",
         "rep_origin",
+        "repeat_region",
         "rRNA",
+        "S_region",
+        "SecStr",
+        "sig_peptide",
+        "Site",
         "source",
+        "stem_loop",
+        "STS",
+        "telomere",
         "tmRNA",
+        "transit_peptide",
         "tRNA",
+        "unsure",
+        "V_region",
         "V_segment",
+        "variation",
     ];
-    let file = File::create(&Path::new(&env::var("OUT_DIR").unwrap()).join("atoms.rs")).unwrap();
+    let file = File::create(Path::new(&env::var("OUT_DIR").unwrap()).join("atoms.rs")).unwrap();
     string_cache_codegen::AtomType::new("QualifierKey", "qualifier_key!")
         .atoms(qualifiers.iter())
         .write_to(&file)
@@ -0,0 +1,444 @@
+//! Building records programmatically, without filling in every field of
+//! `Seq` by hand, or from a FASTA file and a table of annotations.
+
+use crate::errors::GbParserError;
+use crate::fasta::FastaReader;
+use crate::seq::{Alphabet, Date, Division, Feature, Location, MoleculeType, Seq, Strand, Topology};
+use crate::{FeatureKind, QualifierKey};
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error(display = "Invalid name: {:?}", _0)]
+    InvalidName(String),
+    #[error(display = "Invalid character {:?} at position {}", _0, _1)]
+    InvalidSequence(char, usize),
+    #[error(display = "Feature {} location {} is outside the sequence", _0, _1)]
+    OutOfBounds(usize, Location),
+    #[error(display = "{}", _0)]
+    Fasta(#[cause] GbParserError),
+    #[error(display = "Annotation table line {}: {}", _0, _1)]
+    Table(usize, String),
+}
+
+/// A feature from an annotation table, which is added by `build` once the
+/// topology is known
+#[derive(Debug, Clone)]
+struct TableRow {
+    line: usize,
+    kind: FeatureKind,
+    // 1-based, inclusive
+    first: i64,
+    last: i64,
+    strand: Strand,
+    qualifiers: Vec<(QualifierKey, Option<String>)>,
+}
+
+/// Builds a `Seq`, e.g.
+///
+/// ```
+/// # use gb_io::builder::SeqBuilder;
+/// let seq = SeqBuilder::new("pUC19")
+///     .circular()
+///     .sequence(&b"tcgcgcgtttcggtgatgacgg"[..])
+///     .definition("Cloning vector pUC19")
+///     .organism("synthetic construct")
+///     .build()
+///     .unwrap();
+/// assert_eq!(seq.len, Some(22));
+/// ```
+///
+/// Unless they're set, the division is `SYN`, the date is today's and the
+/// molecule type is `DNA` (none for proteins). A `source` feature spanning
+/// the whole sequence is added if there isn't one, see
+/// `Seq::ensure_source_feature`.
+#[derive(Debug, Clone)]
+pub struct SeqBuilder {
+    seq: Seq,
+    organism: Option<String>,
+    mol_type: Option<String>,
+    source_feature: bool,
+    rows: Vec<TableRow>,
+}
+
+impl SeqBuilder {
+    pub fn new<S: Into<String>>(name: S) -> SeqBuilder {
+        SeqBuilder {
+            seq: Seq {
+                name: Some(name.into()),
+                division: Division::Syn,
+                ..Seq::empty()
+            },
+            organism: None,
+            mol_type: None,
+            source_feature: true,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Starts from the first record of a FASTA file, taking the name,
+    /// definition and sequence from it
+    pub fn from_fasta(fasta: &[u8]) -> Result<SeqBuilder, BuildError> {
+        let record = FastaReader::new(fasta)
+            .next()
+            .unwrap_or_else(|| Err(GbParserError::SyntaxError("No FASTA record".into())))
+            .map_err(BuildError::Fasta)?;
+        let mut builder = SeqBuilder::new(record.name.unwrap_or_default());
+        builder.seq.definition = record.definition;
+        builder.seq.alphabet = record.alphabet;
+        builder.seq.seq = record.seq;
+        Ok(builder)
+    }
+
+    /// Adds the features listed in a table, one per line, with the columns
+    /// kind, start, end, strand and qualifiers. Columns are separated by
+    /// tabs, or by commas (with double quotes around values containing
+    /// commas). Positions are 1-based and inclusive; on circular records a
+    /// feature with its end before its start wraps around the origin. The
+    /// strand is `+` (the default) or `-`, and qualifiers are written
+    /// `key=value;key=value`, with just the key for flags such as `pseudo`.
+    /// Blank lines, lines starting with `#` and a header line starting with
+    /// `kind` are skipped.
+    ///
+    /// The table is only checked for syntax here: positions are checked
+    /// against the sequence by `build`.
+    pub fn annotation_table(mut self, table: &str) -> Result<Self, BuildError> {
+        for (i, line) in table.lines().enumerate() {
+            let line_no = i + 1;
+            let err = |msg: String| BuildError::Table(line_no, msg);
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = split_fields(line, if line.contains('\t') { '\t' } else { ',' });
+            let field = |i: usize| fields.get(i).map_or("", |f| f.trim());
+            if field(0).eq_ignore_ascii_case("kind") {
+                continue;
+            }
+            if field(0).is_empty() {
+                return Err(err("Missing feature kind".into()));
+            }
+            let position = |i: usize| {
+                field(i)
+                    .parse::<i64>()
+                    .map_err(|_| err(format!("Invalid position {:?}", field(i))))
+            };
+            let strand = match field(3) {
+                "" | "+" | "." | "1" => Strand::Forward,
+                "-" | "-1" => Strand::Reverse,
+                s => return Err(err(format!("Invalid strand {:?}", s))),
+            };
+            let qualifiers = field(4)
+                .split(';')
+                .map(str::trim)
+                .filter(|q| !q.is_empty())
+                .map(|q| match q.split_once('=') {
+                    Some((k, v)) => (k.trim().into(), Some(v.trim().to_string())),
+                    None => (q.into(), None),
+                })
+                .collect();
+            self.rows.push(TableRow {
+                line: line_no,
+                kind: field(0).into(),
+                first: position(1)?,
+                last: position(2)?,
+                strand,
+                qualifiers,
+            });
+        }
+        Ok(self)
+    }
+
+    pub fn circular(mut self) -> Self {
+        self.seq.topology = Topology::Circular;
+        self
+    }
+
+    pub fn linear(mut self) -> Self {
+        self.seq.topology = Topology::Linear;
+        self
+    }
+
+    /// Make this a protein record
+    pub fn protein(mut self) -> Self {
+        self.seq.alphabet = Alphabet::Protein;
+        self
+    }
+
+    pub fn sequence<S: Into<Vec<u8>>>(mut self, seq: S) -> Self {
+        self.seq.seq = seq.into();
+        self
+    }
+
+    pub fn definition<S: Into<String>>(mut self, definition: S) -> Self {
+        self.seq.definition = Some(definition.into());
+        self
+    }
+
+    pub fn accession<S: Into<String>>(mut self, accession: S) -> Self {
+        self.seq.accession = Some(accession.into());
+        self
+    }
+
+    /// The molecule type on the LOCUS line, e.g. `DNA` or `mRNA`
+    pub fn molecule_type<M: Into<MoleculeType>>(mut self, molecule_type: M) -> Self {
+        self.seq.molecule_type = Some(molecule_type.into());
+        self
+    }
+
+    pub fn division<D: Into<Division>>(mut self, division: D) -> Self {
+        self.seq.division = division.into();
+        self
+    }
+
+    /// Also accepts `chrono` and `time` dates, with those features
+    pub fn date<D: Into<Date>>(mut self, date: D) -> Self {
+        self.seq.date = Some(date.into());
+        self
+    }
+
+    /// The organism, for the `source` feature and the SOURCE and ORGANISM
+    /// fields
+    pub fn organism<S: Into<String>>(mut self, organism: S) -> Self {
+        self.organism = Some(organism.into());
+        self
+    }
+
+    /// The `/mol_type` of the `source` feature, if it shouldn't be derived
+    /// from the molecule type
+    pub fn mol_type<S: Into<String>>(mut self, mol_type: S) -> Self {
+        self.mol_type = Some(mol_type.into());
+        self
+    }
+
+    /// Whether to add a `source` feature if there isn't one. On by default.
+    pub fn source_feature(mut self, add: bool) -> Self {
+        self.source_feature = add;
+        self
+    }
+
+    pub fn comment<S: Into<String>>(mut self, comment: S) -> Self {
+        self.seq.comments.push(comment.into());
+        self
+    }
+
+    pub fn add_feature(mut self, feature: Feature) -> Self {
+        self.seq.features.push(feature);
+        self
+    }
+
+    /// Fills in the defaults and checks that the name is a single word, the
+    /// sequence is made up of letters (or `*` for proteins), and feature
+    /// locations lie within the sequence
+    pub fn build(self) -> Result<Seq, BuildError> {
+        let mut seq = self.seq;
+        let name = seq.name.as_deref().unwrap_or("");
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(BuildError::InvalidName(name.into()));
+        }
+        let protein = seq.is_protein();
+        if let Some(i) = seq
+            .seq
+            .iter()
+            .position(|&b| !(b.is_ascii_alphabetic() || protein && b == b'*'))
+        {
+            return Err(BuildError::InvalidSequence(seq.seq[i] as char, i));
+        }
+        for (i, f) in seq.features.iter().enumerate() {
+            // locations we can't get ranges for refer to other records
+            if let Ok(ranges) = f.location.ranges() {
+                if ranges.iter().any(|&(a, b)| a < 0 || b > seq.len()) {
+                    return Err(BuildError::OutOfBounds(i, f.location.clone()));
+                }
+            }
+        }
+        seq.len = Some(seq.seq.len());
+        for row in self.rows {
+            let len = seq.len();
+            if row.first < 1 || row.last < 1 || row.first > len || row.last > len {
+                return Err(BuildError::Table(
+                    row.line,
+                    format!("{}..{} is outside the sequence", row.first, row.last),
+                ));
+            }
+            if row.last < row.first && !seq.is_circular() {
+                return Err(BuildError::Table(
+                    row.line,
+                    format!("{}..{} ends before it starts", row.first, row.last),
+                ));
+            }
+            if row.strand == Strand::Reverse && protein {
+                return Err(BuildError::Table(
+                    row.line,
+                    "Proteins have no strand".into(),
+                ));
+            }
+            let line = row.line;
+            seq.add_feature(
+                row.first - 1,
+                row.last,
+                row.strand,
+                row.kind,
+                row.qualifiers,
+                false,
+            )
+            .map_err(|e| BuildError::Table(line, e.to_string()))?;
+        }
+        if seq.date.is_none() {
+            seq.date = Some(Date::today());
+        }
+        if seq.molecule_type.is_none() && !protein {
+            seq.molecule_type = Some(MoleculeType::Dna);
+        }
+        if self.source_feature {
+            seq.ensure_source_feature(self.organism.as_deref(), self.mol_type.as_deref());
+        }
+        Ok(seq)
+    }
+}
+
+impl Seq {
+    /// Builds a record from a FASTA file and a table of annotations, with
+    /// the defaults of `SeqBuilder`. See `SeqBuilder::annotation_table` for
+    /// the format of the table.
+    pub fn from_fasta_and_table(fasta: &[u8], table: &str) -> Result<Seq, BuildError> {
+        SeqBuilder::from_fasta(fasta)?
+            .annotation_table(table)?
+            .build()
+    }
+}
+
+/// Splits a line of a table, handling double quotes if `delimiter` is a
+/// comma
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    if delimiter != ',' {
+        return line.split(delimiter).map(String::from).collect();
+    }
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build() {
+        let gene = Feature::new(
+            feature_kind!("gene"),
+            Location::simple_range(2, 6),
+            vec![(qualifier_key!("gene"), Some("x".into()))],
+        );
+        let seq = SeqBuilder::new("test")
+            .circular()
+            .sequence(&b"acgtacgt"[..])
+            .date(Date::from_ymd(2020, 1, 1).unwrap())
+            .add_feature(gene.clone())
+            .build()
+            .unwrap();
+        assert_eq!(seq.topology, Topology::Circular);
+        assert_eq!(seq.len, Some(8));
+        assert_eq!(seq.division, Division::Syn);
+        assert_eq!(seq.molecule_type, Some(MoleculeType::Dna));
+        assert_eq!(seq.features.len(), 2);
+        assert_eq!(seq.features[0].kind, feature_kind!("source"));
+        assert_eq!(seq.features[1], gene);
+        let mut out = Vec::new();
+        seq.write(&mut out).unwrap();
+        assert_eq!(crate::reader::parse_slice(&out).unwrap()[0], seq);
+
+        let bare = SeqBuilder::new("p")
+            .protein()
+            .sequence("MKV*")
+            .source_feature(false)
+            .build()
+            .unwrap();
+        assert!(bare.features.is_empty());
+        assert_eq!(bare.molecule_type, None);
+        assert!(bare.date.is_some());
+
+        match SeqBuilder::new("two words").build() {
+            Err(BuildError::InvalidName(_)) => {}
+            r => panic!("{:?}", r),
+        }
+        match SeqBuilder::new("x").sequence("acg tn").build() {
+            Err(BuildError::InvalidSequence(' ', 3)) => {}
+            r => panic!("{:?}", r),
+        }
+        let far = Feature {
+            location: Location::simple_range(2, 9),
+            ..gene
+        };
+        match SeqBuilder::new("x")
+            .sequence("acgtacgt")
+            .add_feature(far)
+            .build()
+        {
+            Err(BuildError::OutOfBounds(0, _)) => {}
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn fasta_and_table() {
+        let fasta = b">pTest test plasmid\nATGAAATAGC\nCCGGG\n";
+        let table = "kind,start,end,strand,qualifiers
+# a comment
+CDS,1,9,+,\"gene=abc;product=a, b\"
+misc_feature,14,2,-,note=wraps;pseudo
+";
+        let mut builder = SeqBuilder::from_fasta(fasta)
+            .unwrap()
+            .annotation_table(table)
+            .unwrap();
+        match builder.clone().build() {
+            Err(BuildError::Table(4, _)) => {}
+            r => panic!("{:?}", r),
+        }
+        builder = builder.circular();
+        let seq = builder.build().unwrap();
+        assert_eq!(seq.name.as_deref(), Some("pTest"));
+        assert_eq!(seq.definition.as_deref(), Some("test plasmid"));
+        let locations: Vec<_> = seq
+            .features
+            .iter()
+            .map(|f| f.location.to_gb_format())
+            .collect();
+        assert_eq!(
+            locations,
+            vec!["1..15", "1..9", "complement(join(14..15,1..2))"]
+        );
+        assert_eq!(
+            seq.features[1].qualifiers,
+            vec![
+                (qualifier_key!("gene"), Some("abc".into())),
+                (qualifier_key!("product"), Some("a, b".into()))
+            ]
+        );
+        assert!(seq.features[2].is_pseudo());
+
+        let tsv = "gene\t1\t20\n";
+        match Seq::from_fasta_and_table(fasta, tsv) {
+            Err(BuildError::Table(1, _)) => {}
+            r => panic!("{:?}", r),
+        }
+        match Seq::from_fasta_and_table(fasta, "gene,one,3\n") {
+            Err(BuildError::Table(1, _)) => {}
+            r => panic!("{:?}", r),
+        }
+        assert!(Seq::from_fasta_and_table(fasta, "gene\t2\t3\t-\n").is_ok());
+    }
+}
@@ -0,0 +1,373 @@
+//! A structured view of COMMENT fields, which are often made up of several
+//! paragraphs and machine-readable blocks such as
+//!
+//! ```text
+//! ##Assembly-Data-START##
+//! Assembly Method       :: Lasergene v. 10
+//! Sequencing Technology :: Sanger dideoxy sequencing
+//! ##Assembly-Data-END##
+//! ```
+//!
+//! `Seq::comments` still holds the text exactly as it was read, this is
+//! only used when inspecting or building comments programmatically.
+
+use std::fmt;
+
+use crate::seq::Seq;
+
+/// One section of a COMMENT
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CommentBlock {
+    /// A paragraph of free text. Line breaks within it are kept as they
+    /// were.
+    Text(String),
+    /// A `##<name>-START##` ... `##<name>-END##` block of `key :: value`
+    /// pairs
+    Structured {
+        name: String,
+        fields: Vec<(String, String)>,
+    },
+}
+
+/// The blocks of a COMMENT. Paragraphs are separated by blank lines when
+/// written, consecutive structured blocks aren't.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Comment {
+    pub blocks: Vec<CommentBlock>,
+}
+
+const START_SUFFIX: &str = "-START##";
+const END_SUFFIX: &str = "-END##";
+
+/// Returns the name of a structured block from its `##name-START##` line
+fn start_name(line: &str) -> Option<&str> {
+    line.trim().strip_prefix("##")?.strip_suffix(START_SUFFIX)
+}
+
+impl Comment {
+    pub fn new() -> Comment {
+        Comment::default()
+    }
+
+    /// Splits the text of a COMMENT into blocks
+    pub fn parse(text: &str) -> Comment {
+        let mut blocks = Vec::new();
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut structured: Option<(String, Vec<(String, String)>)> = None;
+        fn end_paragraph(paragraph: &mut Vec<&str>, blocks: &mut Vec<CommentBlock>) {
+            if !paragraph.is_empty() {
+                blocks.push(CommentBlock::Text(paragraph.join("\n")));
+                paragraph.clear();
+            }
+        }
+        for line in text.lines() {
+            if let Some((name, mut fields)) = structured.take() {
+                let trimmed = line.trim();
+                if trimmed == format!("##{}{}", name, END_SUFFIX) {
+                    blocks.push(CommentBlock::Structured { name, fields });
+                    continue;
+                }
+                match trimmed.find("::") {
+                    Some(i) => fields.push((
+                        trimmed[..i].trim().to_string(),
+                        trimmed[i + 2..].trim().to_string(),
+                    )),
+                    // a value that was wrapped onto the next line
+                    None => {
+                        if let Some(last) = fields.last_mut() {
+                            if !trimmed.is_empty() {
+                                last.1.push(' ');
+                                last.1.push_str(trimmed);
+                            }
+                        }
+                    }
+                }
+                structured = Some((name, fields));
+            } else if let Some(name) = start_name(line) {
+                end_paragraph(&mut paragraph, &mut blocks);
+                structured = Some((name.to_string(), Vec::new()));
+            } else if line.trim().is_empty() {
+                end_paragraph(&mut paragraph, &mut blocks);
+            } else {
+                paragraph.push(line);
+            }
+        }
+        end_paragraph(&mut paragraph, &mut blocks);
+        // an unterminated block, keep what we have
+        if let Some((name, fields)) = structured {
+            blocks.push(CommentBlock::Structured { name, fields });
+        }
+        Comment { blocks }
+    }
+
+    /// Adds a paragraph of free text
+    pub fn push_paragraph<S: Into<String>>(&mut self, text: S) -> &mut Self {
+        self.blocks.push(CommentBlock::Text(text.into()));
+        self
+    }
+
+    /// Adds a structured block
+    pub fn push_structured<S: Into<String>>(
+        &mut self,
+        name: S,
+        fields: Vec<(String, String)>,
+    ) -> &mut Self {
+        self.blocks.push(CommentBlock::Structured {
+            name: name.into(),
+            fields,
+        });
+        self
+    }
+
+    /// The fields of the first structured block called `name`
+    pub fn structured(&self, name: &str) -> Option<&[(String, String)]> {
+        self.blocks.iter().find_map(|b| match *b {
+            CommentBlock::Structured {
+                name: ref n,
+                ref fields,
+            } if n == name => Some(&fields[..]),
+            _ => None,
+        })
+    }
+
+    /// The paragraphs of free text
+    pub fn paragraphs(&self) -> impl Iterator<Item = &str> {
+        self.blocks.iter().filter_map(|b| match *b {
+            CommentBlock::Text(ref t) => Some(t.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Comment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut prev_structured = None;
+        for block in &self.blocks {
+            let structured = matches!(*block, CommentBlock::Structured { .. });
+            match prev_structured {
+                Some(true) if structured => writeln!(f)?,
+                Some(_) => write!(f, "\n\n")?,
+                None => {}
+            }
+            match *block {
+                CommentBlock::Text(ref t) => write!(f, "{}", t)?,
+                CommentBlock::Structured {
+                    ref name,
+                    ref fields,
+                } => {
+                    let width = fields.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+                    writeln!(f, "##{}{}", name, START_SUFFIX)?;
+                    for (k, v) in fields {
+                        writeln!(f, "{:<width$} :: {}", k, v, width = width)?;
+                    }
+                    write!(f, "##{}{}", name, END_SUFFIX)?;
+                }
+            }
+            prev_structured = Some(structured);
+        }
+        Ok(())
+    }
+}
+
+/// The review status NCBI gives RefSeq records, from statements like
+/// `REVIEWED REFSEQ: This record has been curated by NCBI staff.`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum RefSeqStatus {
+    Inferred,
+    Model,
+    Predicted,
+    Provisional,
+    Validated,
+    Reviewed,
+    Wgs,
+    GenomeAnnotation,
+    Pipeline,
+}
+
+impl RefSeqStatus {
+    /// Parses the word(s) before `REFSEQ:`
+    fn from_prefix(s: &str) -> Option<RefSeqStatus> {
+        use self::RefSeqStatus::*;
+        let res = match s {
+            "INFERRED" => Inferred,
+            "MODEL" => Model,
+            "PREDICTED" => Predicted,
+            "PROVISIONAL" => Provisional,
+            "VALIDATED" => Validated,
+            "REVIEWED" => Reviewed,
+            "WGS" => Wgs,
+            "GENOME ANNOTATION" => GenomeAnnotation,
+            "PIPELINE" => Pipeline,
+            _ => return None,
+        };
+        Some(res)
+    }
+}
+
+/// Standard statements about the status of a record, see
+/// `Seq::record_status`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum RecordStatus {
+    /// GenBank staff couldn't verify the sequence or annotation, also
+    /// marked by an `UNVERIFIED:` DEFINITION
+    Unverified,
+    /// GenBank staff are still waiting on the submitter, e.g. for
+    /// publication of the data
+    Pending,
+    RefSeq(RefSeqStatus),
+}
+
+impl Seq {
+    /// Detects the standard status statements in COMMENT (and DEFINITION),
+    /// such as those marking unverified or provisional RefSeq records
+    pub fn record_status(&self) -> Vec<RecordStatus> {
+        let mut res = Vec::new();
+        let unverified_definition = self
+            .definition
+            .as_ref()
+            .is_some_and(|d| d.trim_start().starts_with("UNVERIFIED:"));
+        if unverified_definition {
+            res.push(RecordStatus::Unverified);
+        }
+        for comment in &self.comments {
+            // statements may be wrapped over several lines
+            let text = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+            let lower = text.to_lowercase();
+            if (lower.contains("genbank staff is unable to verify")
+                || lower.contains("this record is unverified"))
+                && !res.contains(&RecordStatus::Unverified)
+            {
+                res.push(RecordStatus::Unverified);
+            }
+            if lower.contains("genbank staff") && lower.contains("waiting") {
+                res.push(RecordStatus::Pending);
+            }
+            for line in comment.lines() {
+                if let Some(i) = line.find(" REFSEQ:") {
+                    if let Some(status) = RefSeqStatus::from_prefix(line[..i].trim()) {
+                        res.push(RecordStatus::RefSeq(status));
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// All COMMENT fields of the record, split into blocks
+    pub fn comment(&self) -> Comment {
+        let mut res = Comment::new();
+        for c in &self.comments {
+            res.blocks.extend(Comment::parse(c).blocks);
+        }
+        res
+    }
+
+    /// Replaces the COMMENT fields of the record with `comment`
+    pub fn set_comment(&mut self, comment: &Comment) {
+        self.comments.clear();
+        if !comment.blocks.is_empty() {
+            self.comments.push(comment.to_string());
+        }
+    }
+
+    /// Adds a paragraph to the end of the last COMMENT, leaving the existing
+    /// text untouched
+    pub fn append_comment_paragraph(&mut self, text: &str) {
+        match self.comments.last_mut() {
+            Some(last) if !last.trim().is_empty() => {
+                last.push_str("\n\n");
+                last.push_str(text);
+            }
+            Some(last) => *last = text.to_string(),
+            None => self.comments.push(text.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::parse_file;
+
+    #[test]
+    fn structured() {
+        let s = &parse_file("tests/biopython_tests/KF527485.gb").unwrap()[0];
+        let c = s.comment();
+        assert_eq!(c.blocks.len(), 1);
+        let fields = c.structured("Assembly-Data").unwrap();
+        assert_eq!(
+            fields[0],
+            ("Assembly Method".to_string(), "Lasergene v. 10".to_string())
+        );
+        assert_eq!(c.to_string(), s.comments[0]);
+    }
+
+    #[test]
+    fn paragraphs() {
+        let s = &parse_file("tests/biopython_tests/DS830848.gb").unwrap()[0];
+        let c = s.comment();
+        assert_eq!(c.paragraphs().count(), c.blocks.len());
+        assert!(c.blocks.len() > 1);
+        assert!(c
+            .paragraphs()
+            .next()
+            .unwrap()
+            .starts_with("This Wikel tick"));
+        assert_eq!(Comment::parse(&c.to_string()), c);
+    }
+
+    #[test]
+    fn record_status() {
+        let s = &parse_file("tests/biopython_tests/NC_000932.gb").unwrap()[0];
+        assert_eq!(
+            s.record_status(),
+            vec![RecordStatus::RefSeq(RefSeqStatus::Reviewed)]
+        );
+        let s = &parse_file("tests/biopython_tests/NT_019265.gb").unwrap()[0];
+        assert_eq!(
+            s.record_status(),
+            vec![RecordStatus::RefSeq(RefSeqStatus::GenomeAnnotation)]
+        );
+        let s = Seq {
+            definition: Some("UNVERIFIED: Homo sapiens clone 1".into()),
+            comments: vec!["GenBank staff is unable to verify sequence and/or\nannotation provided by the submitter.".into()],
+            ..Seq::empty()
+        };
+        assert_eq!(s.record_status(), vec![RecordStatus::Unverified]);
+        let s = Seq {
+            comments: vec![
+                "GenBank staff are still waiting for the\nsubmitter to release this record.".into(),
+            ],
+            ..Seq::empty()
+        };
+        assert_eq!(s.record_status(), vec![RecordStatus::Pending]);
+        assert!(Seq::empty().record_status().is_empty());
+    }
+
+    #[test]
+    fn build_and_append() {
+        let mut c = Comment::new();
+        c.push_paragraph("First paragraph.")
+            .push_structured("A", vec![("k".into(), "v".into())])
+            .push_structured("B", vec![("long key".into(), "v".into())])
+            .push_paragraph("Last.");
+        let text = c.to_string();
+        assert_eq!(
+            text,
+            "First paragraph.\n\n##A-START##\nk :: v\n##A-END##\n##B-START##\n\
+             long key :: v\n##B-END##\n\nLast."
+        );
+        assert_eq!(Comment::parse(&text), c);
+
+        let mut s = Seq::empty();
+        s.set_comment(&c);
+        s.append_comment_paragraph("Appended.");
+        assert_eq!(s.comments.len(), 1);
+        assert!(s.comments[0].starts_with(&text));
+        assert_eq!(s.comment().paragraphs().last(), Some("Appended."));
+    }
+}
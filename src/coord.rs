@@ -0,0 +1,87 @@
+//! Newtypes for the two coordinate systems in play. Positions in this crate
+//! are 0-based, with exclusive ends, while GenBank files (and most people)
+//! count from 1, with inclusive ends. Using these at the boundaries makes
+//! it explicit which one a number is in.
+
+use std::fmt;
+
+use crate::seq::{Location, LocationError};
+
+/// A 0-based position, as used by `Location` and `Seq`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct ZeroBased(pub i64);
+
+/// A 1-based position, as written in GenBank files
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct OneBased(pub i64);
+
+impl ZeroBased {
+    pub fn to_one_based(self) -> OneBased {
+        OneBased(self.0 + 1)
+    }
+}
+
+impl OneBased {
+    pub fn to_zero_based(self) -> ZeroBased {
+        ZeroBased(self.0 - 1)
+    }
+}
+
+impl From<OneBased> for ZeroBased {
+    fn from(p: OneBased) -> ZeroBased {
+        p.to_zero_based()
+    }
+}
+
+impl From<ZeroBased> for OneBased {
+    fn from(p: ZeroBased) -> OneBased {
+        p.to_one_based()
+    }
+}
+
+impl fmt::Display for ZeroBased {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for OneBased {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Location {
+    /// A range from `first` to `last` inclusive, as in `first..last` in a
+    /// GenBank file
+    pub fn from_one_based(first: OneBased, last: OneBased) -> Location {
+        Location::simple_range(first.to_zero_based().0, last.0)
+    }
+
+    /// The first and last bases covered, both inclusive, as they would be
+    /// written in a GenBank file. See `find_bounds` for the 0-based
+    /// equivalent.
+    pub fn one_based_bounds(&self) -> Result<(OneBased, OneBased), LocationError> {
+        let (start, end) = self.find_bounds()?;
+        Ok((ZeroBased(start).to_one_based(), OneBased(end)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(OneBased::from(ZeroBased(0)), OneBased(1));
+        assert_eq!(ZeroBased::from(OneBased(10)), ZeroBased(9));
+        let l = Location::from_one_based(OneBased(5), OneBased(12));
+        assert_eq!(l, Location::simple_range(4, 12));
+        assert_eq!(l.to_gb_format(), "5..12");
+        assert_eq!(l.one_based_bounds().unwrap(), (OneBased(5), OneBased(12)));
+        let single = Location::from_one_based(OneBased(3), OneBased(3));
+        assert_eq!(single.to_gb_format(), "3");
+    }
+}
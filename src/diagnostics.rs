@@ -0,0 +1,61 @@
+//! Reporting of recoverable problems, such as features that had to be
+//! skipped, so that applications can show them to users rather than them
+//! only being logged.
+
+use std::fmt;
+
+/// A recoverable problem encountered while parsing or manipulating a record
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Index of the feature concerned, if any
+    pub feature: Option<usize>,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(message: S) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            feature: None,
+        }
+    }
+
+    pub fn for_feature<S: Into<String>>(feature: usize, message: S) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            feature: Some(feature),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.feature {
+            Some(i) => write!(f, "feature {}: {}", i, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Receives diagnostics as they occur
+pub trait DiagnosticSink {
+    fn report(&mut self, diagnostic: Diagnostic);
+}
+
+impl DiagnosticSink for Vec<Diagnostic> {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.push(diagnostic);
+    }
+}
+
+/// Logs diagnostics with `warn!`. Used by the methods that don't take a
+/// sink.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogSink;
+
+impl DiagnosticSink for LogSink {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        warn!("{}", diagnostic);
+    }
+}
@@ -0,0 +1,656 @@
+//! Display hints (colours, labels and arrows) understood by plasmid editors.
+//!
+//! Editors store styling in nonstandard qualifiers, and each one uses its own
+//! convention. `Feature::display_hints` holds the styling independently of
+//! them, so it can be set on any feature and written in whichever editor's
+//! dialect is wanted. The parser only fills it in when asked to, and the
+//! writer leaves the qualifiers alone unless the hints have changed, so
+//! round-tripping a file is unaffected.
+//!
+//! `PlasmidMap` is a flattened summary of a record in the shape expected by
+//! most JavaScript plasmid viewers, and `Seq::render_text_map` draws a
+//! similar summary for the terminal.
+
+use std::cmp;
+use std::io::{self, Write};
+
+use crate::seq::{Feature, QualifierKey, Seq, Strand};
+
+/// Which way an editor should draw the arrow for a feature
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Arrow {
+    Forward,
+    Reverse,
+    Both,
+    None,
+}
+
+/// The editor conventions we know how to read and write
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dialect {
+    /// `/ApEinfo_fwdcolor`, `/ApEinfo_revcolor` and `/label`, also used by
+    /// Benchling
+    ApE,
+    /// `/label` and `/note="color: #rrggbb; direction: RIGHT"`
+    SnapGene,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DisplayHints {
+    /// Usually an HTML-style colour such as `#ff0000`
+    pub color: Option<String>,
+    pub label: Option<String>,
+    pub arrow: Option<Arrow>,
+}
+
+impl DisplayHints {
+    pub fn is_empty(&self) -> bool {
+        self.color.is_none() && self.label.is_none() && self.arrow.is_none()
+    }
+}
+
+/// Parses a SnapGene-style note like `color: #ff0000; direction: RIGHT`.
+/// Returns `None` if the note contains anything else, since then it's
+/// probably a real note.
+fn parse_snapgene_note(note: &str) -> Option<(Option<&str>, Option<Arrow>)> {
+    let mut color = None;
+    let mut arrow = None;
+    for part in note.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        let mut kv = part.splitn(2, ':').map(str::trim);
+        match (kv.next(), kv.next()) {
+            (Some("color"), Some(c)) => color = Some(c),
+            (Some("direction"), Some(d)) => {
+                arrow = Some(match d {
+                    "RIGHT" => Arrow::Forward,
+                    "LEFT" => Arrow::Reverse,
+                    "BOTH" => Arrow::Both,
+                    "NONE" => Arrow::None,
+                    _ => return None,
+                })
+            }
+            _ => return None,
+        }
+    }
+    if color.is_none() && arrow.is_none() {
+        None
+    } else {
+        Some((color, arrow))
+    }
+}
+
+fn is_display_qualifier(key: &QualifierKey, value: &Option<String>) -> bool {
+    match *key {
+        qualifier_key!("label")
+        | qualifier_key!("ApEinfo_fwdcolor")
+        | qualifier_key!("ApEinfo_revcolor")
+        | qualifier_key!("ApEinfo_graphicformat") => true,
+        qualifier_key!("note") => value
+            .as_ref()
+            .map(|v| parse_snapgene_note(v).is_some())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+impl Feature {
+    /// Collects display hints from whichever editor-specific qualifiers are
+    /// present. For ApE colours, the one matching the feature's strand is
+    /// preferred.
+    pub fn qualifier_display_hints(&self) -> DisplayHints {
+        let reverse = self.location.strand() == Strand::Reverse;
+        let (fwd, rev) = (
+            self.qualifier_values(qualifier_key!("ApEinfo_fwdcolor")).next(),
+            self.qualifier_values(qualifier_key!("ApEinfo_revcolor")).next(),
+        );
+        let mut color = if reverse { rev.or(fwd) } else { fwd.or(rev) }.map(String::from);
+        let mut arrow = None;
+        for note in self.qualifier_values(qualifier_key!("note")) {
+            if let Some((c, a)) = parse_snapgene_note(note) {
+                color = color.or_else(|| c.map(String::from));
+                arrow = arrow.or(a);
+            }
+        }
+        DisplayHints {
+            color,
+            label: self
+                .qualifier_values(qualifier_key!("label"))
+                .next()
+                .map(String::from),
+            arrow,
+        }
+    }
+
+    /// `display_hints` if set, otherwise those given by the qualifiers
+    pub fn effective_display_hints(&self) -> DisplayHints {
+        match self.display_hints {
+            Some(ref hints) => hints.clone(),
+            None => self.qualifier_display_hints(),
+        }
+    }
+
+    /// Sets `display_hints` and replaces any existing display qualifiers (of
+    /// either dialect) with `hints`, written using the conventions of
+    /// `dialect`. ApE has no equivalent of `arrow`, so it's only kept in
+    /// `display_hints` in that case.
+    pub fn set_display_hints(&mut self, hints: &DisplayHints, dialect: Dialect) {
+        self.display_hints = Some(hints.clone());
+        self.write_display_qualifiers(hints, dialect);
+    }
+
+    /// The feature as the writer should write it: with its display
+    /// qualifiers rewritten from `display_hints` in `dialect`, or if that's
+    /// `None`, in ApE's dialect only if the hints differ from the
+    /// qualifiers. Returns `None` if the feature can be written as it is.
+    pub(crate) fn with_display_qualifiers(&self, dialect: Option<Dialect>) -> Option<Feature> {
+        let hints = self.display_hints.as_ref()?;
+        if dialect.is_none() && *hints == self.qualifier_display_hints() {
+            return None;
+        }
+        let mut f = self.clone();
+        f.write_display_qualifiers(hints, dialect.unwrap_or(Dialect::ApE));
+        Some(f)
+    }
+
+    fn write_display_qualifiers(&mut self, hints: &DisplayHints, dialect: Dialect) {
+        // keep the original spellings pointing at the same qualifiers
+        let kept: Vec<_> = self
+            .qualifiers
+            .iter()
+            .map(|(k, v)| !is_display_qualifier(k, v))
+            .collect();
+        if let Some(ref mut spelling) = self.original_spelling {
+            spelling.qualifiers.retain(|q| kept.get(q.0) == Some(&true));
+            for q in &mut spelling.qualifiers {
+                q.0 = kept[..q.0].iter().filter(|&&k| k).count();
+            }
+        }
+        self.qualifiers.retain(|(k, v)| !is_display_qualifier(k, v));
+        if let Some(ref label) = hints.label {
+            self.qualifiers
+                .push((qualifier_key!("label"), Some(label.clone())));
+        }
+        match dialect {
+            Dialect::ApE => {
+                if let Some(ref color) = hints.color {
+                    for key in &[
+                        qualifier_key!("ApEinfo_fwdcolor"),
+                        qualifier_key!("ApEinfo_revcolor"),
+                    ] {
+                        self.qualifiers.push((key.clone(), Some(color.clone())));
+                    }
+                }
+            }
+            Dialect::SnapGene => {
+                let mut parts = Vec::new();
+                if let Some(ref color) = hints.color {
+                    parts.push(format!("color: {}", color));
+                }
+                if let Some(arrow) = hints.arrow {
+                    let direction = match arrow {
+                        Arrow::Forward => "RIGHT",
+                        Arrow::Reverse => "LEFT",
+                        Arrow::Both => "BOTH",
+                        Arrow::None => "NONE",
+                    };
+                    parts.push(format!("direction: {}", direction));
+                }
+                if !parts.is_empty() {
+                    self.qualifiers
+                        .push((qualifier_key!("note"), Some(parts.join("; "))));
+                }
+            }
+        }
+    }
+}
+
+/// A feature as drawn by a plasmid viewer
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MapFeature {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub kind: String,
+    /// 0-based, inclusive
+    pub start: i64,
+    /// 0-based, exclusive. Less than `start` for features crossing the origin
+    pub end: i64,
+    /// 1 or -1
+    pub strand: i8,
+    pub color: Option<String>,
+}
+
+/// The simple JSON document consumed by JS plasmid viewers
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PlasmidMap {
+    pub name: String,
+    pub length: i64,
+    pub circular: bool,
+    pub features: Vec<MapFeature>,
+}
+
+impl PlasmidMap {
+    /// Write as JSON, without requiring `serde_json`
+    pub fn write_json<T: Write>(&self, mut file: T) -> io::Result<()> {
+        write!(file, "{{\"name\":")?;
+        write_json_str(&mut file, &self.name)?;
+        write!(
+            file,
+            ",\"length\":{},\"circular\":{},\"features\":[",
+            self.length, self.circular
+        )?;
+        for (i, f) in self.features.iter().enumerate() {
+            if i > 0 {
+                write!(file, ",")?;
+            }
+            write!(file, "{{\"name\":")?;
+            write_json_str(&mut file, &f.name)?;
+            write!(file, ",\"type\":")?;
+            write_json_str(&mut file, &f.kind)?;
+            write!(
+                file,
+                ",\"start\":{},\"end\":{},\"strand\":{},\"color\":",
+                f.start, f.end, f.strand
+            )?;
+            match f.color {
+                Some(ref c) => write_json_str(&mut file, c)?,
+                None => write!(file, "null")?,
+            }
+            write!(file, "}}")?;
+        }
+        write!(file, "]}}")
+    }
+}
+
+fn write_json_str<T: Write>(mut file: T, s: &str) -> io::Result<()> {
+    write!(file, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(file, "\\\"")?,
+            '\\' => write!(file, "\\\\")?,
+            '\n' => write!(file, "\\n")?,
+            '\r' => write!(file, "\\r")?,
+            '\t' => write!(file, "\\t")?,
+            c if (c as u32) < 0x20 => write!(file, "\\u{:04x}", c as u32)?,
+            c => write!(file, "{}", c)?,
+        }
+    }
+    write!(file, "\"")
+}
+
+impl Seq {
+    /// Flattens this record for display in a plasmid viewer. Features are
+    /// named after their display label, falling back to `/gene`, `/product`,
+    /// `/locus_tag` and finally the feature kind. Features whose bounds can't
+    /// be determined are skipped with a warning.
+    pub fn plasmid_map(&self) -> PlasmidMap {
+        let features = self
+            .features
+            .iter()
+            .filter_map(|f| {
+                let (start, end) = match f.location.find_bounds() {
+                    Ok(bounds) => bounds,
+                    Err(e) => {
+                        warn!("Skipping feature, can't determine bounds: {}", e);
+                        return None;
+                    }
+                };
+                let hints = f.effective_display_hints();
+                let name = hints
+                    .label
+                    .or_else(|| {
+                        [
+                            qualifier_key!("gene"),
+                            qualifier_key!("product"),
+                            qualifier_key!("locus_tag"),
+                        ]
+                        .iter()
+                        .find_map(|k| f.qualifier_values(k.clone()).next())
+                        .map(String::from)
+                    })
+                    .unwrap_or_else(|| f.kind.to_string());
+                Some(MapFeature {
+                    name,
+                    kind: f.kind.to_string(),
+                    start,
+                    end,
+                    strand: if f.location.strand() == Strand::Reverse { -1 } else { 1 },
+                    color: hints.color,
+                })
+            })
+            .collect();
+        PlasmidMap {
+            name: self
+                .name
+                .clone()
+                .or_else(|| self.accession.clone())
+                .unwrap_or_default(),
+            length: self.len(),
+            circular: self.is_circular(),
+            features,
+        }
+    }
+}
+
+impl Seq {
+    /// Draws a `width` character wide diagram of the features, one or more
+    /// per line, for quick inspection in a terminal. Circular sequences are
+    /// unrolled at the origin. Forward features are drawn with `>` and
+    /// reverse ones with `<`, labelled inside the bar if there's room and
+    /// next to it otherwise.
+    pub fn render_text_map(&self, width: usize) -> String {
+        let width = width.max(10);
+        let map = self.plasmid_map();
+        let len = map.length.max(1);
+        // Columns covered by a range, always at least one
+        let span = |start: i64, end: i64| {
+            let scale = |pos: i64| pos.clamp(0, len) as usize * width;
+            let a = cmp::min(scale(start) / len as usize, width - 1);
+            let b = scale(end).div_ceil(len as usize).clamp(a + 1, width);
+            (a, b)
+        };
+
+        let mut out = format!(
+            "{} ({} bp, {})\n",
+            map.name,
+            map.length,
+            if map.circular { "circular" } else { "linear" }
+        );
+        let end_label = map.length.to_string();
+        out.push('1');
+        out.push_str(&format!("{:>1$}\n", end_label, width - 1));
+        out.extend((0..width).map(|i| if i % 10 == 0 { '|' } else { '-' }));
+        out.push('\n');
+
+        // rows of the diagram, each is a line of chars plus the occupied ranges
+        type Row = (Vec<char>, Vec<(usize, usize)>);
+        let mut rows: Vec<Row> = Vec::new();
+        for f in &map.features {
+            let segments = if f.end > f.start {
+                vec![span(f.start, f.end)]
+            } else {
+                vec![span(f.start, len), span(0, f.end)]
+            };
+            // label the largest segment, inside if it fits, otherwise just
+            // after or before it
+            let label: Vec<char> = f.name.chars().collect();
+            let (seg, &(a, b)) = segments
+                .iter()
+                .enumerate()
+                .max_by_key(|&(_, &(a, b))| b - a)
+                .unwrap();
+            let (label_start, extent) = if b - a >= label.len() + 2 {
+                (a + (b - a - label.len()) / 2, (a, b))
+            } else if b + 1 + label.len() <= width {
+                (b + 1, (a, b + 1 + label.len()))
+            } else if a > label.len() {
+                (a - 1 - label.len(), (a - 1 - label.len(), b))
+            } else {
+                (a, (a, b))
+            };
+            let mut occupied = segments.clone();
+            occupied[seg] = extent;
+            let free = |used: &[(usize, usize)]| {
+                occupied
+                    .iter()
+                    .all(|&(x, y)| used.iter().all(|&(u, v)| y <= u || x >= v))
+            };
+            let row = match rows.iter().position(|(_, used)| free(used)) {
+                Some(i) => i,
+                None => {
+                    rows.push((vec![' '; width], Vec::new()));
+                    rows.len() - 1
+                }
+            };
+            let (line, used) = &mut rows[row];
+            let arrow = if f.strand < 0 { '<' } else { '>' };
+            for &(x, y) in &segments {
+                for c in &mut line[x..y] {
+                    *c = arrow;
+                }
+            }
+            let label_room = &mut line[label_start..extent.1];
+            for (c, &l) in label_room.iter_mut().zip(&label) {
+                *c = l;
+            }
+            used.extend(occupied);
+        }
+        for (line, _) in rows {
+            out.extend(line);
+            let trimmed = out.trim_end_matches(' ').len();
+            out.truncate(trimmed);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Location;
+
+    fn feature(location: Location, qualifiers: &[(&str, &str)]) -> Feature {
+        Feature::new(
+            feature_kind!("misc_feature"),
+            location,
+            qualifiers
+                .iter()
+                .map(|&(k, v)| (QualifierKey::from(k), Some(v.to_string())))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn ape_hints() {
+        let q = [
+            ("label", "lacZ"),
+            ("ApEinfo_fwdcolor", "#ff0000"),
+            ("ApEinfo_revcolor", "#00ff00"),
+        ];
+        let f = feature(Location::simple_range(0, 10), &q);
+        assert_eq!(
+            f.qualifier_display_hints(),
+            DisplayHints {
+                color: Some("#ff0000".into()),
+                label: Some("lacZ".into()),
+                arrow: None,
+            }
+        );
+        let f = feature(Location::Complement(Box::new(Location::simple_range(0, 10))), &q);
+        assert_eq!(f.qualifier_display_hints().color.as_deref(), Some("#00ff00"));
+        let spliced = Location::from_gb_format("join(complement(11..20),complement(1..5))");
+        let f = feature(spliced.unwrap(), &q);
+        assert_eq!(f.qualifier_display_hints().color.as_deref(), Some("#00ff00"));
+    }
+
+    #[test]
+    fn snapgene_hints() {
+        let f = feature(
+            Location::simple_range(0, 10),
+            &[
+                ("note", "a real note"),
+                ("note", "color: #993366; direction: LEFT"),
+            ],
+        );
+        let hints = f.qualifier_display_hints();
+        assert_eq!(hints.color.as_deref(), Some("#993366"));
+        assert_eq!(hints.arrow, Some(Arrow::Reverse));
+        assert_eq!(hints.label, None);
+    }
+
+    #[test]
+    fn convert_dialect() {
+        let mut f = feature(
+            Location::simple_range(0, 10),
+            &[
+                ("note", "a real note"),
+                ("label", "ori"),
+                ("ApEinfo_fwdcolor", "#ff0000"),
+                ("ApEinfo_revcolor", "#ff0000"),
+                ("ApEinfo_graphicformat", "arrow_data {{0 1 2 0 0 -1} {} 0}"),
+            ],
+        );
+        let hints = DisplayHints {
+            arrow: Some(Arrow::Forward),
+            ..f.qualifier_display_hints()
+        };
+        f.set_display_hints(&hints, Dialect::SnapGene);
+        assert_eq!(
+            f.qualifiers,
+            vec![
+                (qualifier_key!("note"), Some("a real note".into())),
+                (qualifier_key!("label"), Some("ori".into())),
+                (
+                    qualifier_key!("note"),
+                    Some("color: #ff0000; direction: RIGHT".into())
+                ),
+            ]
+        );
+        assert_eq!(f.qualifier_display_hints(), hints);
+        f.set_display_hints(&hints, Dialect::ApE);
+        assert_eq!(
+            f.qualifier_display_hints(),
+            DisplayHints {
+                arrow: None,
+                ..hints
+            }
+        );
+        assert_eq!(f.qualifiers.len(), 4);
+    }
+
+    #[test]
+    fn write_display_hints() {
+        use crate::reader::SeqReader;
+        use crate::writer::SeqWriter;
+
+        let seq = Seq {
+            seq: vec![b'a'; 10],
+            features: vec![
+                feature(
+                    Location::simple_range(0, 10),
+                    &[("label", "ori"), ("ApEinfo_fwdcolor", "#ff0000")],
+                ),
+                feature(Location::simple_range(2, 8), &[("gene", "bla")]),
+            ],
+            ..Seq::empty()
+        };
+        let write = |seq: &Seq, dialect: Option<Dialect>| {
+            let mut out = Vec::new();
+            let mut writer = SeqWriter::new(&mut out);
+            if let Some(d) = dialect {
+                writer.display_dialect(d);
+            }
+            writer.write(seq).unwrap();
+            out
+        };
+        let original = write(&seq, None);
+        let mut reader = SeqReader::new(&original[..]);
+        reader.read_display_hints(true);
+        let mut read = reader.next().unwrap().unwrap();
+        assert_eq!(
+            read.features[0].display_hints,
+            Some(seq.features[0].qualifier_display_hints())
+        );
+        assert_eq!(read.features[1].display_hints, None);
+        // unchanged hints leave the qualifiers alone
+        let plain = SeqReader::new(&original[..]).next().unwrap().unwrap();
+        assert_eq!(write(&read, None), write(&plain, None));
+
+        // hints can be attached to any feature, and are kept when the
+        // dialect can't express them
+        read.features[1].display_hints = Some(DisplayHints {
+            arrow: Some(Arrow::Reverse),
+            ..DisplayHints::default()
+        });
+        let ape = String::from_utf8(write(&read, None)).unwrap();
+        assert!(!ape.contains("direction"));
+        let snapgene = String::from_utf8(write(&read, Some(Dialect::SnapGene))).unwrap();
+        assert!(snapgene.contains("/note=\"color: #ff0000\"\n"));
+        assert!(snapgene.contains("/gene=\"bla\"\n                     /note=\"direction: LEFT\""));
+        assert!(!snapgene.contains("ApEinfo"));
+    }
+
+    #[test]
+    fn plasmid_map_json() {
+        let seq = Seq {
+            name: Some("pTest".into()),
+            topology: crate::seq::Topology::Circular,
+            seq: vec![b'a'; 10],
+            features: vec![
+                feature(
+                    Location::Join(vec![
+                        Location::simple_range(7, 10),
+                        Location::simple_range(0, 3),
+                    ]),
+                    &[("label", "say \"ori\""), ("ApEinfo_fwdcolor", "#ff0000")],
+                ),
+                Feature {
+                    kind: feature_kind!("CDS"),
+                    ..feature(
+                        Location::Complement(Box::new(Location::simple_range(3, 6))),
+                        &[("gene", "bla")],
+                    )
+                },
+                feature(
+                    Location::from_gb_format("join(complement(2..3),complement(6..8))").unwrap(),
+                    &[("label", "exons")],
+                ),
+            ],
+            ..Seq::empty()
+        };
+        let map = seq.plasmid_map();
+        assert_eq!(map.features[0].start, 7);
+        assert_eq!(map.features[0].end, 3);
+        assert_eq!(map.features[1].strand, -1);
+        assert_eq!(map.features[2].strand, -1);
+        let mut out = Vec::new();
+        map.write_json(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"name":"pTest","length":10,"circular":true,"features":["#,
+                r##"{"name":"say \"ori\"","type":"misc_feature","start":7,"end":3,"strand":1,"color":"#ff0000"},"##,
+                r#"{"name":"bla","type":"CDS","start":3,"end":6,"strand":-1,"color":null},"#,
+                r#"{"name":"exons","type":"misc_feature","start":1,"end":8,"strand":-1,"color":null}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn text_map() {
+        let seq = Seq {
+            name: Some("pTest".into()),
+            topology: crate::seq::Topology::Circular,
+            seq: vec![b'a'; 100],
+            features: vec![
+                feature(Location::simple_range(0, 50), &[("label", "lacZ")]),
+                feature(
+                    Location::Complement(Box::new(Location::simple_range(40, 60))),
+                    &[("label", "bla")],
+                ),
+                feature(
+                    Location::Join(vec![
+                        Location::simple_range(90, 100),
+                        Location::simple_range(0, 5),
+                    ]),
+                    &[("label", "ori")],
+                ),
+            ],
+            ..Seq::empty()
+        };
+        assert_eq!(
+            seq.render_text_map(20),
+            concat!(
+                "pTest (100 bp, circular)\n",
+                "1                100\n",
+                "|---------|---------\n",
+                ">>>lacZ>>>\n",
+                "        <<<< bla\n",
+                ">             ori >>\n",
+            )
+        );
+    }
+}
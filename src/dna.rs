@@ -41,6 +41,39 @@ pub fn revcomp(seq: &[u8]) -> Vec<u8> {
     seq.iter().rev().map(comp).collect()
 }
 
+/// The bases an IUPAC code can stand for, as a bitmask of A, C, G and T
+fn iupac_bits(base: u8) -> u8 {
+    const A: u8 = 1;
+    const C: u8 = 2;
+    const G: u8 = 4;
+    const T: u8 = 8;
+    match base.to_ascii_uppercase() {
+        b'A' => A,
+        b'C' => C,
+        b'G' => G,
+        b'T' | b'U' => T,
+        b'R' => A | G,
+        b'Y' => C | T,
+        b'S' => C | G,
+        b'W' => A | T,
+        b'K' => G | T,
+        b'M' => A | C,
+        b'B' => C | G | T,
+        b'D' => A | G | T,
+        b'H' => A | C | T,
+        b'V' => A | C | G,
+        b'N' => A | C | G | T,
+        _ => 0,
+    }
+}
+
+/// Whether `base`, which must be unambiguous, matches the IUPAC code
+/// `pattern`
+pub fn iupac_match(pattern: u8, base: u8) -> bool {
+    let b = iupac_bits(base);
+    b.count_ones() == 1 && iupac_bits(pattern) & b != 0
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -48,4 +81,12 @@ mod test {
     fn test_revcomp() {
         assert_eq!(b"gAtCnN"[..], revcomp(b"NnGaTc"));
     }
+
+    #[test]
+    fn test_iupac_match() {
+        assert!(iupac_match(b'N', b'a'));
+        assert!(iupac_match(b'r', b'G'));
+        assert!(!iupac_match(b'R', b'C'));
+        assert!(!iupac_match(b'A', b'N'));
+    }
 }
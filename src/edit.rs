@@ -0,0 +1,597 @@
+//! Undoable editing of a `Seq`.
+//!
+//! `EditSession` wraps a `Seq` and records every change made through it, so
+//! that changes can be undone and summarised in a COMMENT.
+//! `Seq::apply_mutations` applies a batch of annotated point mutations.
+
+use crate::seq::{simplify, After, Before, Feature, Location, Seq};
+
+/// Moves a location to make room for `len` bases inserted before `pos`.
+/// Features which span `pos` are extended. Used by `EditSession::insert`.
+pub(crate) fn insert_location(l: &Location, pos: i64, len: i64) -> Location {
+    use Location::*;
+    let map = |ls: &[Location]| ls.iter().map(|l| insert_location(l, pos, len)).collect();
+    match *l {
+        Range((a, before), (b, after)) => {
+            let a = if a >= pos { a + len } else { a };
+            let b = if b > pos { b + len } else { b };
+            Range((a, before), (b, after))
+        }
+        Between(a, b) => Between(
+            if a >= pos { a + len } else { a },
+            if b >= pos { b + len } else { b },
+        ),
+        Complement(ref l) => Complement(Box::new(insert_location(l, pos, len))),
+        Join(ref ls) => Join(map(ls)),
+        Order(ref ls) => Order(map(ls)),
+        Bond(ref ls) => Bond(map(ls)),
+        OneOf(ref ls) => OneOf(map(ls)),
+        External(..) | Gap(..) => l.clone(),
+    }
+}
+
+/// Removes the bases `start..end` from a location, shifting everything after
+/// them back. Returns `None` if nothing is left. Ends which were cut off are
+/// marked as partial. Used by `EditSession::delete`.
+pub(crate) fn delete_location(l: &Location, start: i64, end: i64) -> Option<Location> {
+    use Location::*;
+    let removed = end - start;
+    let map = |v: i64| {
+        if v <= start {
+            v
+        } else if v >= end {
+            v - removed
+        } else {
+            start
+        }
+    };
+    let filter = |ls: &[Location]| {
+        let res: Vec<_> = ls
+            .iter()
+            .filter_map(|l| delete_location(l, start, end))
+            .collect();
+        if res.is_empty() {
+            None
+        } else {
+            Some(res)
+        }
+    };
+    let res = match *l {
+        Range((a, Before(before)), (b, After(after))) => {
+            let (new_a, new_b) = (map(a), map(b));
+            if new_a >= new_b {
+                return None;
+            }
+            let before = before || (a >= start && a < end);
+            let after = after || (b > start && b <= end);
+            Range((new_a, Before(before)), (new_b, After(after)))
+        }
+        Between(a, b) => {
+            if (a >= start && a < end) || (b >= start && b < end) {
+                return None;
+            }
+            Between(map(a), map(b))
+        }
+        Complement(ref l) => Complement(Box::new(delete_location(l, start, end)?)),
+        Join(ref ls) => Join(filter(ls)?),
+        Order(ref ls) => Order(filter(ls)?),
+        Bond(ref ls) => Bond(filter(ls)?),
+        OneOf(ref ls) => OneOf(filter(ls)?),
+        External(..) | Gap(..) => l.clone(),
+    };
+    simplify(res).ok()
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MutationError {
+    #[error(
+        display = "Expected {} at position {}, found {}",
+        expected,
+        position,
+        found
+    )]
+    ReferenceMismatch {
+        position: i64,
+        expected: String,
+        found: String,
+    },
+    #[error(display = "Mutation at position {} is outside the sequence", _0)]
+    OutOfBounds(i64),
+    #[error(display = "Mutation at position {} overlaps another", _0)]
+    Overlap(i64),
+}
+
+impl Seq {
+    /// Applies a set of `(position, reference, alternative)` changes, with
+    /// 0-based positions in the current sequence. Each reference is checked
+    /// against the sequence (ignoring case) before anything is changed.
+    /// Features are moved for insertions and deletions as in `EditSession`,
+    /// and each change is annotated: substitutions with a `variation`
+    /// feature, indels with a `misc_difference`, both with a `/replace`
+    /// giving the original bases.
+    pub fn apply_mutations(
+        &mut self,
+        mutations: &[(i64, &[u8], &[u8])],
+    ) -> Result<(), MutationError> {
+        let mut sorted = mutations.to_vec();
+        sorted.sort_by_key(|m| m.0);
+        let mut last_end = 0;
+        for &(position, reference, _) in &sorted {
+            let end = position + reference.len() as i64;
+            if position < 0 || end > self.len() {
+                return Err(MutationError::OutOfBounds(position));
+            }
+            if position < last_end {
+                return Err(MutationError::Overlap(position));
+            }
+            last_end = end;
+            let found = &self.seq[position as usize..end as usize];
+            if !found.eq_ignore_ascii_case(reference) {
+                return Err(MutationError::ReferenceMismatch {
+                    position,
+                    expected: String::from_utf8_lossy(reference).into(),
+                    found: String::from_utf8_lossy(found).into(),
+                });
+            }
+        }
+
+        // apply from the end, so earlier positions stay valid
+        for &(position, reference, alternative) in sorted.iter().rev() {
+            let (r, a) = (reference.len(), alternative.len());
+            let p = position as usize;
+            if r == a {
+                self.seq[p..p + r].copy_from_slice(alternative);
+                continue;
+            }
+            self.seq.splice(p..p + r, alternative.iter().cloned());
+            let end = position + r as i64;
+            self.features = std::mem::take(&mut self.features)
+                .into_iter()
+                .filter_map(|f| {
+                    let mut l = f.location.clone();
+                    if r > 0 {
+                        l = delete_location(&l, position, end)?;
+                    }
+                    if a > 0 {
+                        l = insert_location(&l, position, a as i64);
+                    }
+                    Some(Feature { location: l, ..f })
+                })
+                .collect();
+        }
+        if self.len.is_some() {
+            self.len = Some(self.seq.len());
+        }
+
+        let mut shift = 0;
+        for &(position, reference, alternative) in &sorted {
+            let (r, a) = (reference.len() as i64, alternative.len() as i64);
+            let start = position + shift;
+            shift += a - r;
+            let (kind, location) = if r == a {
+                (feature_kind!("variation"), Location::simple_range(start, start + a))
+            } else if a > 0 {
+                (feature_kind!("misc_difference"), Location::simple_range(start, start + a))
+            } else if start > 0 {
+                (feature_kind!("misc_difference"), Location::Between(start - 1, start))
+            } else {
+                (feature_kind!("misc_difference"), Location::single(0))
+            };
+            let reference = String::from_utf8_lossy(reference).to_lowercase();
+            let note = format!(
+                "{} changed to {} at position {}",
+                if reference.is_empty() { "-" } else { &reference },
+                if a == 0 {
+                    "-".to_string()
+                } else {
+                    String::from_utf8_lossy(alternative).to_lowercase()
+                },
+                position + 1
+            );
+            self.features.push(Feature::new(
+                kind,
+                location,
+                vec![
+                    (qualifier_key!("replace"), Some(reference)),
+                    (qualifier_key!("note"), Some(note)),
+                ],
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A single change made to a `Seq`. Coordinates are 0-based.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Edit {
+    Insert { position: i64, bases: Vec<u8> },
+    Delete { start: i64, bases: Vec<u8> },
+    AddFeature { index: usize, feature: Feature },
+    RemoveFeature { index: usize, feature: Feature },
+    ReplaceFeature { index: usize, old: Feature },
+}
+
+impl Edit {
+    /// A one line description of the change, using 1-based coordinates
+    pub fn describe(&self) -> String {
+        let describe_feature = |f: &Feature| format!("{} feature at {}", f.kind, f.location);
+        match *self {
+            Edit::Insert {
+                position,
+                ref bases,
+            } => match position {
+                0 => format!("Inserted {} bp at the start", bases.len()),
+                p => format!("Inserted {} bp after position {}", bases.len(), p),
+            },
+            Edit::Delete { start, ref bases } => format!(
+                "Deleted {} bp at {}..{}",
+                bases.len(),
+                start + 1,
+                start + bases.len() as i64
+            ),
+            Edit::AddFeature { ref feature, .. } => format!("Added {}", describe_feature(feature)),
+            Edit::RemoveFeature { ref feature, .. } => {
+                format!("Removed {}", describe_feature(feature))
+            }
+            Edit::ReplaceFeature { ref old, .. } => format!("Changed {}", describe_feature(old)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Record {
+    edit: Edit,
+    /// For sequence edits, the previous version of every feature that was
+    /// moved or removed, indexed as before the edit. `true` means the
+    /// feature was kept (moved), `false` means it was removed.
+    features: Vec<(usize, Feature, bool)>,
+    old_len: Option<usize>,
+}
+
+/// Wraps a `Seq`, recording each modification so it can be undone.
+///
+/// Positions must lie within the sequence, origin-spanning deletions on
+/// circular sequences should be split in two.
+#[derive(Debug)]
+pub struct EditSession {
+    seq: Seq,
+    history: Vec<Record>,
+}
+
+impl EditSession {
+    pub fn new(seq: Seq) -> EditSession {
+        EditSession {
+            seq,
+            history: Vec::new(),
+        }
+    }
+
+    /// The current state of the sequence
+    pub fn seq(&self) -> &Seq {
+        &self.seq
+    }
+
+    /// Ends the session, returning the edited sequence
+    pub fn into_seq(self) -> Seq {
+        self.seq
+    }
+
+    /// The edits made so far, oldest first
+    pub fn edits(&self) -> impl Iterator<Item = &Edit> {
+        self.history.iter().map(|r| &r.edit)
+    }
+
+    /// Rebuilds the feature table using `f`, which returns `None` for features
+    /// that should be removed. Returns the old versions of everything that
+    /// changed.
+    fn update_features<F>(&mut self, f: F) -> Vec<(usize, Feature, bool)>
+    where
+        F: Fn(&Location) -> Option<Location>,
+    {
+        let mut changed = Vec::new();
+        let old = std::mem::take(&mut self.seq.features);
+        for (i, feature) in old.into_iter().enumerate() {
+            match f(&feature.location) {
+                Some(ref l) if *l == feature.location => self.seq.features.push(feature),
+                Some(location) => {
+                    self.seq.features.push(Feature {
+                        location,
+                        ..feature.clone()
+                    });
+                    changed.push((i, feature, true));
+                }
+                None => changed.push((i, feature, false)),
+            }
+        }
+        changed
+    }
+
+    fn set_len(&mut self) -> Option<usize> {
+        let old_len = self.seq.len;
+        if old_len.is_some() {
+            self.seq.len = Some(self.seq.seq.len());
+        }
+        old_len
+    }
+
+    /// Inserts `bases` before `position`, moving features out of the way.
+    /// Features spanning `position` are extended.
+    pub fn insert(&mut self, position: i64, bases: &[u8]) {
+        assert!(position >= 0 && position <= self.seq.len());
+        let n = bases.len() as i64;
+        let p = position as usize;
+        self.seq.seq.splice(p..p, bases.iter().cloned());
+        let features = self.update_features(|l| Some(insert_location(l, position, n)));
+        let old_len = self.set_len();
+        self.history.push(Record {
+            edit: Edit::Insert {
+                position,
+                bases: bases.to_vec(),
+            },
+            features,
+            old_len,
+        });
+    }
+
+    /// Deletes the bases `start..end`. Features are truncated, or removed if
+    /// they were entirely within the deleted region.
+    pub fn delete(&mut self, start: i64, end: i64) {
+        assert!(start >= 0 && start <= end && end <= self.seq.len());
+        let bases: Vec<u8> = self
+            .seq
+            .seq
+            .drain(start as usize..end as usize)
+            .collect();
+        let features = self.update_features(|l| delete_location(l, start, end));
+        let old_len = self.set_len();
+        self.history.push(Record {
+            edit: Edit::Delete { start, bases },
+            features,
+            old_len,
+        });
+    }
+
+    /// Appends a feature, returning its index
+    pub fn add_feature(&mut self, feature: Feature) -> usize {
+        self.seq.features.push(feature.clone());
+        let index = self.seq.features.len() - 1;
+        self.push_simple(Edit::AddFeature { index, feature });
+        index
+    }
+
+    pub fn remove_feature(&mut self, index: usize) -> Feature {
+        let feature = self.seq.features.remove(index);
+        self.push_simple(Edit::RemoveFeature {
+            index,
+            feature: feature.clone(),
+        });
+        feature
+    }
+
+    /// Replaces the feature at `index`, returning the old one
+    pub fn replace_feature(&mut self, index: usize, feature: Feature) -> Feature {
+        let old = std::mem::replace(&mut self.seq.features[index], feature);
+        self.push_simple(Edit::ReplaceFeature {
+            index,
+            old: old.clone(),
+        });
+        old
+    }
+
+    fn push_simple(&mut self, edit: Edit) {
+        self.history.push(Record {
+            edit,
+            features: Vec::new(),
+            old_len: self.seq.len,
+        });
+    }
+
+    /// Undoes the most recent edit. Returns `false` if there was nothing to
+    /// undo.
+    pub fn undo(&mut self) -> bool {
+        let record = match self.history.pop() {
+            Some(r) => r,
+            None => return false,
+        };
+        match record.edit {
+            Edit::Insert { position, bases } => {
+                let p = position as usize;
+                self.seq.seq.drain(p..p + bases.len());
+            }
+            Edit::Delete { start, bases } => {
+                let p = start as usize;
+                self.seq.seq.splice(p..p, bases);
+            }
+            Edit::AddFeature { index, .. } => {
+                self.seq.features.remove(index);
+            }
+            Edit::RemoveFeature { index, feature } => {
+                self.seq.features.insert(index, feature);
+            }
+            Edit::ReplaceFeature { index, old } => {
+                self.seq.features[index] = old;
+            }
+        }
+        if !record.features.is_empty() {
+            let current = std::mem::take(&mut self.seq.features);
+            let mut current = current.into_iter();
+            let mut changed = record.features.into_iter().peekable();
+            let mut i = 0;
+            loop {
+                if changed.peek().map(|c| c.0) == Some(i) {
+                    let (_, f, kept) = changed.next().unwrap();
+                    if kept {
+                        current.next();
+                    }
+                    self.seq.features.push(f);
+                } else if let Some(f) = current.next() {
+                    self.seq.features.push(f);
+                } else {
+                    break;
+                }
+                i += 1;
+            }
+        }
+        self.seq.len = record.old_len;
+        true
+    }
+
+    /// Describes each edit made so far, oldest first
+    pub fn change_log(&self) -> Vec<String> {
+        self.history
+            .iter()
+            .map(|r| r.edit.describe())
+            .collect()
+    }
+
+    /// Ends the session, appending the change log to the COMMENTs of the
+    /// returned sequence (if anything was changed)
+    pub fn into_seq_with_comment(mut self) -> Seq {
+        let log = self.change_log();
+        if !log.is_empty() {
+            self.seq.comments.push(log.join("\n"));
+        }
+        self.seq
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seq() -> Seq {
+        let f = |l: &str| {
+            Feature::new(
+                feature_kind!("misc_feature"),
+                Location::from_gb_format(l).unwrap(),
+                Vec::new(),
+            )
+        };
+        Seq {
+            seq: b"aaaaaccccctttttggggg".to_vec(),
+            len: Some(20),
+            features: vec![f("1..5"), f("6..10"), f("join(3..7,12..18)"), f("11^12")],
+            ..Seq::empty()
+        }
+    }
+
+    fn locations(s: &Seq) -> Vec<String> {
+        s.features.iter().map(|f| f.location.to_gb_format()).collect()
+    }
+
+    #[test]
+    fn test_insert_location() {
+        let l = |s: &str| Location::from_gb_format(s).unwrap();
+        let i = |s: &str, pos| insert_location(&l(s), pos, 3).to_gb_format();
+        assert_eq!(i("5..10", 10), "5..10");
+        assert_eq!(i("5..10", 4), "8..13");
+        assert_eq!(i("5..10", 5), "5..13");
+        assert_eq!(i("complement(join(1..2,5..6))", 3), "complement(join(1..2,8..9))");
+    }
+
+    #[test]
+    fn test_delete_location() {
+        let l = |s: &str| Location::from_gb_format(s).unwrap();
+        let d = |s: &str, a, b| delete_location(&l(s), a, b).map(|l| l.to_gb_format());
+        assert_eq!(d("5..10", 10, 12).as_deref(), Some("5..10"));
+        assert_eq!(d("5..10", 0, 2).as_deref(), Some("3..8"));
+        assert_eq!(d("5..10", 8, 12).as_deref(), Some("5..>8"));
+        assert_eq!(d("5..10", 2, 6).as_deref(), Some("<3..6"));
+        assert_eq!(d("5..10", 4, 10), None);
+        assert_eq!(d("join(1..2,5..6)", 0, 2).as_deref(), Some("3..4"));
+        assert_eq!(d("join(1..2,5..6)", 2, 4).as_deref(), Some("1..4"));
+    }
+
+    #[test]
+    fn edit_and_undo() {
+        let orig = seq();
+        let mut s = EditSession::new(orig.clone());
+        s.insert(5, b"GG");
+        assert_eq!(&s.seq().seq[..9], b"aaaaaGGcc");
+        assert_eq!(s.seq().len, Some(22));
+        assert_eq!(
+            locations(s.seq()),
+            vec!["1..5", "8..12", "join(3..9,14..20)", "13^14"]
+        );
+        s.delete(0, 7);
+        assert_eq!(
+            locations(s.seq()),
+            vec!["1..5", "join(<1..2,7..13)", "6^7"]
+        );
+        let removed = s.remove_feature(2);
+        assert_eq!(removed.location.to_gb_format(), "6^7");
+        s.add_feature(orig.features[0].clone());
+        s.replace_feature(0, orig.features[1].clone());
+        assert_eq!(
+            s.change_log(),
+            vec![
+                "Inserted 2 bp after position 5",
+                "Deleted 7 bp at 1..7",
+                "Removed misc_feature feature at 6^7",
+                "Added misc_feature feature at 1..5",
+                "Changed misc_feature feature at 1..5",
+            ]
+        );
+        while s.undo() {}
+        assert_eq!(s.seq(), &orig);
+        assert!(s.into_seq_with_comment().comments.is_empty());
+    }
+
+    #[test]
+    fn apply_mutations() {
+        // aaaaa ccccc ttttt ggggg
+        let mut s = seq();
+        s.apply_mutations(&[
+            (12, b"t", b"A"),
+            (6, b"CC", b""),
+            (15, b"", b"aa"),
+        ])
+        .unwrap();
+        assert_eq!(s.seq, b"aaaaacccttAttaaggggg");
+        assert_eq!(s.len, Some(20));
+        assert_eq!(
+            locations(&s)[..4],
+            ["1..5", "6..8", "join(3..>6,10..18)", "9^10"]
+        );
+        let added: Vec<_> = s.features[4..]
+            .iter()
+            .map(|f| (f.kind.to_string(), f.location.to_gb_format(), f.qualifiers[0].1.clone()))
+            .collect();
+        assert_eq!(
+            added,
+            vec![
+                ("misc_difference".to_string(), "6^7".to_string(), Some("cc".to_string())),
+                ("variation".to_string(), "11".to_string(), Some("t".to_string())),
+                ("misc_difference".to_string(), "14..15".to_string(), Some("".to_string())),
+            ]
+        );
+
+        let mut s = seq();
+        assert_eq!(
+            s.apply_mutations(&[(0, b"aa", b"t"), (1, b"a", b"t")]),
+            Err(MutationError::Overlap(1))
+        );
+        assert_eq!(
+            s.apply_mutations(&[(5, b"g", b"t")]),
+            Err(MutationError::ReferenceMismatch {
+                position: 5,
+                expected: "g".into(),
+                found: "c".into()
+            })
+        );
+        assert_eq!(s, seq());
+    }
+
+    #[test]
+    fn change_log_comment() {
+        let mut s = EditSession::new(seq());
+        s.delete(10, 20);
+        s.insert(0, b"t");
+        let s = s.into_seq_with_comment();
+        assert_eq!(
+            s.comments,
+            vec!["Deleted 10 bp at 11..20\nInserted 1 bp at the start"]
+        );
+        assert_eq!(locations(&s), vec!["2..6", "7..11", "4..8"]);
+    }
+}
@@ -0,0 +1,488 @@
+//! Reading EMBL flat files, as distributed by ENA, into the same `Seq` as
+//! GenBank records. Writing them is done by `SeqWriter`, with
+//! `Dialect::Ena`.
+
+use std::io::BufRead;
+
+use crate::errors::GbParserError;
+use crate::reader::parse_feature_table;
+use crate::seq::{Date, Division, Location, MoleculeType, Reference, Seq, Source, Topology};
+
+const MONTHS: &[&str] = &[
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+/// Reads records from an EMBL flat file.
+///
+/// The ID line gives the name (the primary accession), topology, molecule
+/// type, division and length, and `SV` the version. Molecule types are
+/// reduced to the GenBank ones, e.g. `genomic DNA` becomes `DNA`, and the
+/// organism is the OS line followed by the OC lineage on the next line, as
+/// in GenBank's ORGANISM. The date is that of the last `DT` line. Lines
+/// with no GenBank equivalent, such as `DR` cross-references, are skipped.
+#[derive(Debug)]
+pub struct EmblReader<T: BufRead> {
+    input: T,
+    line_number: usize,
+}
+
+impl<T: BufRead> EmblReader<T> {
+    pub fn new(input: T) -> EmblReader<T> {
+        EmblReader {
+            input,
+            line_number: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> GbParserError {
+        GbParserError::SyntaxError(format!("Line {}: {}", self.line_number, message))
+    }
+
+    fn read_record(&mut self) -> Result<Option<Seq>, GbParserError> {
+        let mut record = Record::new();
+        let mut started = false;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.input.read_until(b'\n', &mut line)? == 0 {
+                return if started {
+                    Err(self.error("Unexpected end of file"))
+                } else {
+                    Ok(None)
+                };
+            }
+            self.line_number += 1;
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\r', '\n']);
+            if text.trim().is_empty() {
+                continue;
+            }
+            if text.starts_with("//") {
+                if !started {
+                    return Err(self.error("Record without an ID line"));
+                }
+                return record.finish().map(Some).map_err(|e| self.error(&e));
+            }
+            let (code, content) = match text.char_indices().nth(5) {
+                Some((i, _)) => (text[..2].trim(), &text[i..]),
+                None => (text.get(..2).unwrap_or(text).trim(), ""),
+            };
+            if !started {
+                if code != "ID" {
+                    return Err(self.error("Expected an ID line"));
+                }
+                started = true;
+            }
+            if code.is_empty() && record.in_sequence {
+                record
+                    .seq
+                    .seq
+                    .extend(text.bytes().filter(|b| b.is_ascii_alphabetic()));
+                continue;
+            }
+            record.line(code, content, text).map_err(|e| self.error(&e))?;
+        }
+    }
+}
+
+impl<T: BufRead> Iterator for EmblReader<T> {
+    type Item = Result<Seq, GbParserError>;
+
+    fn next(&mut self) -> Option<Result<Seq, GbParserError>> {
+        self.read_record().transpose()
+    }
+}
+
+/// Parse a whole EMBL file, see `EmblReader`
+pub fn parse_embl(data: &[u8]) -> Result<Vec<Seq>, GbParserError> {
+    EmblReader::new(data).collect()
+}
+
+/// The lines of a record read so far
+struct Record {
+    seq: Seq,
+    accessions: Vec<String>,
+    definition: Vec<String>,
+    keywords: Vec<String>,
+    species: Vec<String>,
+    lineage: Vec<String>,
+    locus_version: Option<String>,
+    reference: Option<Ref>,
+    comment: Vec<String>,
+    // the lines of each comment are joined, so we need to know where one
+    // ends
+    last_code: String,
+    feature_table: Vec<u8>,
+    contig: Vec<String>,
+    in_sequence: bool,
+}
+
+#[derive(Default)]
+struct Ref {
+    number: String,
+    positions: Vec<String>,
+    authors: Vec<String>,
+    consortium: Vec<String>,
+    title: Vec<String>,
+    journal: Vec<String>,
+    remark: Vec<String>,
+    pubmed: Option<String>,
+}
+
+impl Record {
+    fn new() -> Record {
+        Record {
+            seq: Seq::empty(),
+            accessions: Vec::new(),
+            definition: Vec::new(),
+            keywords: Vec::new(),
+            species: Vec::new(),
+            lineage: Vec::new(),
+            locus_version: None,
+            reference: None,
+            comment: Vec::new(),
+            last_code: String::new(),
+            feature_table: Vec::new(),
+            contig: Vec::new(),
+            in_sequence: false,
+        }
+    }
+
+    fn line(&mut self, code: &str, content: &str, text: &str) -> Result<(), String> {
+        if code != "CC" && self.last_code == "CC" {
+            self.end_comment();
+        }
+        match code {
+            "ID" => self.id(content)?,
+            "AC" => self.accessions.extend(
+                content
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(String::from),
+            ),
+            "DE" => self.definition.push(content.trim().to_string()),
+            "KW" => self.keywords.push(content.trim().to_string()),
+            "DT" => {
+                if let Some(date) = content.split_whitespace().next().and_then(parse_date) {
+                    self.seq.date = Some(date);
+                }
+            }
+            "OS" => self.species.push(content.trim().to_string()),
+            "OC" => self.lineage.push(content.trim().to_string()),
+            "RN" => {
+                self.end_reference();
+                let number = content.trim().trim_start_matches('[').trim_end_matches(']');
+                self.reference = Some(Ref {
+                    number: number.to_string(),
+                    ..Ref::default()
+                });
+            }
+            "RP" | "RA" | "RG" | "RT" | "RL" | "RC" | "RX" => {
+                let r = self
+                    .reference
+                    .as_mut()
+                    .ok_or_else(|| format!("{} line outside a reference", code))?;
+                let content = content.trim().to_string();
+                match code {
+                    "RP" => r.positions.push(content),
+                    "RA" => r.authors.push(content),
+                    "RG" => r.consortium.push(content),
+                    "RT" => r.title.push(content),
+                    "RL" => r.journal.push(content),
+                    "RC" => r.remark.push(content),
+                    _ => {
+                        if let Some(id) = content.strip_prefix("PUBMED;") {
+                            r.pubmed = Some(id.trim().trim_end_matches('.').to_string());
+                        }
+                    }
+                }
+            }
+            "CC" => self.comment.push(content.trim_end().to_string()),
+            "FT" => {
+                // FT lines are laid out like GenBank feature tables, apart
+                // from the first two columns
+                self.feature_table.extend_from_slice(b"  ");
+                self.feature_table.extend_from_slice(&text.as_bytes()[2..]);
+                self.feature_table.push(b'\n');
+            }
+            "CO" => self.contig.push(content.trim().to_string()),
+            "SQ" => self.in_sequence = true,
+            _ => {}
+        }
+        self.last_code = code.to_string();
+        Ok(())
+    }
+
+    /// Parses an ID line, either `X56734; SV 1; linear; mRNA; STD; PLN;
+    /// 1859 BP.` or in the pre-2006 form, `X56734 standard; RNA; PLN; 1859
+    /// BP.`
+    fn id(&mut self, content: &str) -> Result<(), String> {
+        let fields: Vec<_> = content.split(';').map(str::trim).collect();
+        let bad = || format!("Can't parse ID line: {}", content);
+        let name = fields[0].split_whitespace().next().ok_or_else(bad)?;
+        self.seq.name = Some(name.to_string());
+        let length = fields.last().ok_or_else(bad)?.trim_end_matches('.');
+        let (length, unit) = length.split_once(' ').ok_or_else(bad)?;
+        if unit.trim() != "BP" {
+            return Err(bad());
+        }
+        self.seq.len = Some(length.parse().map_err(|_| bad())?);
+        if fields.len() >= 7 {
+            if let Some(version) = fields[1].strip_prefix("SV ") {
+                if version != "XXX" {
+                    self.locus_version = Some(format!("{}.{}", name, version));
+                }
+            }
+            self.seq.topology = topology(fields[2]);
+            self.seq.molecule_type = molecule_type(fields[3]);
+            self.seq.division = genbank_division(fields[5]);
+        } else if fields.len() >= 4 {
+            let mol = &fields[fields.len() - 3];
+            if mol.split_whitespace().any(|w| w == "circular") {
+                self.seq.topology = Topology::Circular;
+            }
+            self.seq.molecule_type = molecule_type(mol.trim_start_matches("circular").trim());
+            self.seq.division = genbank_division(fields[fields.len() - 2]);
+        } else {
+            return Err(bad());
+        }
+        Ok(())
+    }
+
+    fn end_comment(&mut self) {
+        if !self.comment.is_empty() {
+            self.seq.comments.push(self.comment.join("\n"));
+            self.comment.clear();
+        }
+    }
+
+    fn end_reference(&mut self) {
+        let r = match self.reference.take() {
+            Some(r) => r,
+            None => return,
+        };
+        let joined = |lines: &[String]| {
+            let text = lines.join(" ");
+            let text = text.trim_end_matches(';');
+            if text.is_empty() {
+                None
+            } else {
+                Some(text.to_string())
+            }
+        };
+        let bases = r
+            .positions
+            .join(",")
+            .split(',')
+            .filter_map(|range| {
+                let (a, b) = range.trim().split_once('-')?;
+                Some((a.parse::<i64>().ok()? - 1, b.parse().ok()?))
+            })
+            .collect();
+        let title = joined(&r.title).unwrap_or_default();
+        let mut reference = Reference {
+            description: r.number.clone(),
+            bases,
+            authors: joined(&r.authors),
+            consortium: joined(&r.consortium),
+            title: title.trim_matches('"').to_string(),
+            journal: Some(r.journal.join(" ")).filter(|j| !j.is_empty()),
+            pubmed: r.pubmed.clone(),
+            remark: joined(&r.remark),
+        };
+        reference.description = reference.description_with_bases(self.seq.alphabet);
+        self.seq.references.push(reference);
+    }
+
+    fn finish(mut self) -> Result<Seq, String> {
+        self.end_comment();
+        self.end_reference();
+        let mut seq = self.seq;
+        if !self.accessions.is_empty() {
+            seq.accession = Some(self.accessions.join(" "));
+        }
+        seq.version = self.locus_version;
+        if !self.definition.is_empty() {
+            seq.definition = Some(self.definition.join(" "));
+        }
+        if !self.keywords.is_empty() {
+            seq.keywords = Some(self.keywords.join(" "));
+        }
+        if !self.species.is_empty() {
+            let species = self.species.join(" ");
+            let mut organism = species.clone();
+            if !self.lineage.is_empty() {
+                organism.push('\n');
+                organism.push_str(&self.lineage.join(" "));
+            }
+            seq.source = Some(Source {
+                source: species,
+                organism: Some(organism),
+            });
+        }
+        seq.features = parse_feature_table(&self.feature_table).map_err(|e| e.to_string())?;
+        if !self.contig.is_empty() {
+            let contig = self.contig.concat();
+            seq.contig = Some(Location::from_gb_format(&contig).map_err(|e| e.to_string())?);
+        }
+        if !seq.seq.is_empty() && seq.len != Some(seq.seq.len()) {
+            return Err(format!(
+                "ID line gives a length of {} but the sequence is {} bp",
+                seq.len.unwrap_or(0),
+                seq.seq.len()
+            ));
+        }
+        Ok(seq)
+    }
+}
+
+fn topology(s: &str) -> Topology {
+    if s == "circular" {
+        Topology::Circular
+    } else {
+        Topology::Linear
+    }
+}
+
+/// The GenBank molecule type for an EMBL one, e.g. `genomic DNA` is `DNA`
+fn molecule_type(s: &str) -> Option<MoleculeType> {
+    let res = match s {
+        "" | "XXX" => return None,
+        "mRNA" | "rRNA" | "tRNA" => s.into(),
+        s if s.ends_with("DNA") => MoleculeType::Dna,
+        s if s.ends_with("RNA") => MoleculeType::Rna,
+        s => s.into(),
+    };
+    Some(res)
+}
+
+/// The GenBank division for an EMBL one, for the few that differ
+fn genbank_division(division: &str) -> Division {
+    match division {
+        "PRO" => Division::Bct,
+        "HUM" => Division::Pri,
+        "MUS" => Division::Rod,
+        "FUN" => Division::Pln,
+        "TGN" => Division::Syn,
+        "UNC" => Division::Una,
+        _ => division.into(),
+    }
+}
+
+/// Parses a date like `12-SEP-1991`
+pub(crate) fn parse_date(s: &str) -> Option<Date> {
+    let mut parts = s.split('-');
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?.to_ascii_uppercase();
+    let month = MONTHS.iter().position(|&m| m == month)? as u32 + 1;
+    let year = parts.next()?.parse().ok()?;
+    Date::from_ymd(year, month, day).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RECORD: &str = "ID   X56734; SV 1; linear; mRNA; STD; PLN; 24 BP.
+XX
+AC   X56734; S46826;
+XX
+DT   12-SEP-1991 (Rel. 29, Created)
+DT   15-MAR-2004 (Rel. 79, Last updated, Version 3)
+XX
+DE   Trifolium repens mRNA for non-cyanogenic beta-glucosidase
+XX
+KW   beta-glucosidase.
+XX
+OS   Trifolium repens (white clover)
+OC   Eukaryota; Viridiplantae; Streptophyta; Embryophyta; Tracheophyta;
+OC   Spermatophyta; Magnoliopsida; eudicotyledons.
+XX
+RN   [1]
+RP   1-24
+RX   PUBMED; 1907511.
+RA   Oxtoby E., Dunn M.A., Pancoro A., Hughes M.A.;
+RT   \"Nucleotide and derived amino acid sequence of the cyanogenic
+RT   beta-glucosidase (linamarase) from white clover\";
+RL   Plant Mol. Biol. 17(2):209-219(1991).
+XX
+DR   MD5; 1e51ca3a5450c43524b9185c236cc5cc.
+XX
+CC   A comment
+CC   on two lines
+XX
+FH   Key             Location/Qualifiers
+FH
+FT   source          1..24
+FT                   /organism=\"Trifolium repens\"
+FT                   /mol_type=\"mRNA\"
+FT   CDS             <1..>24
+FT                   /product=\"beta-glucosidase\"
+FT                   /translation=\"MDFL
+FT                   SSKS\"
+XX
+SQ   Sequence 24 BP; 6 A; 6 C; 6 G; 6 T; 0 other;
+     aaccggttac gtacgtacgt acgt                                        24
+//
+ID   AA03518    standard; DNA; FUN; 4 BP.
+XX
+SQ   Sequence 4 BP;
+     acgt                                                               4
+//
+";
+
+    #[test]
+    fn read() {
+        let seqs = parse_embl(RECORD.as_bytes()).unwrap();
+        assert_eq!(seqs.len(), 2);
+        let seq = &seqs[0];
+        assert_eq!(seq.name.as_deref(), Some("X56734"));
+        assert_eq!(seq.accession.as_deref(), Some("X56734 S46826"));
+        assert_eq!(seq.version.as_deref(), Some("X56734.1"));
+        assert_eq!(seq.molecule_type, Some(MoleculeType::Mrna));
+        assert_eq!(seq.division, Division::Pln);
+        assert_eq!(seq.len, Some(24));
+        assert_eq!(seq.date, Some(Date::from_ymd(2004, 3, 15).unwrap()));
+        assert_eq!(seq.keywords.as_deref(), Some("beta-glucosidase."));
+        let source = seq.source.as_ref().unwrap();
+        assert_eq!(source.source, "Trifolium repens (white clover)");
+        assert!(source
+            .organism
+            .as_ref()
+            .unwrap()
+            .ends_with("\nEukaryota; Viridiplantae; Streptophyta; Embryophyta; Tracheophyta; \
+Spermatophyta; Magnoliopsida; eudicotyledons."));
+        let r = &seq.references[0];
+        assert_eq!(r.description, "1  (bases 1 to 24)");
+        assert_eq!(r.pubmed.as_deref(), Some("1907511"));
+        assert_eq!(
+            r.authors.as_deref(),
+            Some("Oxtoby E., Dunn M.A., Pancoro A., Hughes M.A.")
+        );
+        assert!(r.title.starts_with("Nucleotide and") && r.title.ends_with("white clover"));
+        assert_eq!(
+            r.journal.as_deref(),
+            Some("Plant Mol. Biol. 17(2):209-219(1991).")
+        );
+        assert_eq!(seq.comments, vec!["A comment\non two lines"]);
+        assert_eq!(seq.features.len(), 2);
+        assert_eq!(seq.features[1].location.to_gb_format(), "<1..>24");
+        assert_eq!(
+            seq.features[1]
+                .qualifier_values(qualifier_key!("translation"))
+                .next(),
+            Some("MDFLSSKS")
+        );
+        assert_eq!(seq.seq, b"aaccggttacgtacgtacgtacgt");
+
+        assert_eq!(seqs[1].name.as_deref(), Some("AA03518"));
+        assert_eq!(seqs[1].molecule_type, Some(MoleculeType::Dna));
+        assert_eq!(seqs[1].division, Division::Pln);
+        assert_eq!(seqs[1].seq, b"acgt");
+
+        let wrong_length = b"ID   X; SV 1; linear; DNA; STD; UNC; 5 BP.\nSQ\n     acgt\n//\n";
+        assert!(parse_embl(wrong_length).is_err());
+        assert!(parse_embl(b"AC   X;\n//\n").is_err());
+    }
+}
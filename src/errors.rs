@@ -4,6 +4,36 @@ use std::io;
 pub enum GbParserError {
     #[error(display = "Syntax error: {}", _0)]
     SyntaxError(String),
+    /// A syntax error found by `SeqReader`, with where it was found. `line`
+    /// counts from 1 and `offset` from 0, both from where the reader
+    /// started, and `snippet` is the input from `offset` to the end of the
+    /// line.
+    #[error(
+        display = "Syntax error at line {} (byte {}): {} [{}]",
+        line,
+        offset,
+        message,
+        snippet
+    )]
+    SyntaxErrorAt {
+        message: String,
+        line: usize,
+        offset: u64,
+        snippet: String,
+    },
     #[error(display = "{}", _0)]
     Io(#[cause] io::Error),
+    /// The input went over one of the reader's `Limits`
+    #[error(display = "Limit exceeded: {}", _0)]
+    LimitExceeded(String),
+}
+
+impl GbParserError {
+    /// Whether this is a problem with the input rather than with reading it
+    pub fn is_syntax_error(&self) -> bool {
+        matches!(
+            self,
+            GbParserError::SyntaxError(_) | GbParserError::SyntaxErrorAt { .. }
+        )
+    }
 }
@@ -0,0 +1,253 @@
+//! Reading FASTA files, and FASTA export of the coding sequences in a
+//! record.
+
+use std::io::{self, BufRead, Write};
+
+use crate::errors::GbParserError;
+use crate::seq::{Alphabet, Feature, Seq};
+
+const LINE_WIDTH: usize = 70;
+
+fn write_fasta<W: Write>(mut out: W, header: &str, seq: &[u8]) -> io::Result<()> {
+    writeln!(out, ">{}", header)?;
+    for line in seq.chunks(LINE_WIDTH) {
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Reads records from a (multi-)FASTA file. Each record becomes a `Seq`
+/// named after the first word of its header, with the rest of the header as
+/// its definition. Records containing letters other than IUPAC nucleotide
+/// codes are taken to be proteins. Blank lines and `;` comment lines are
+/// ignored.
+#[derive(Debug)]
+pub struct FastaReader<T: BufRead> {
+    input: T,
+    // the header of the next record, once we've read it
+    header: Option<Vec<u8>>,
+    line_number: usize,
+}
+
+impl<T: BufRead> FastaReader<T> {
+    pub fn new(input: T) -> FastaReader<T> {
+        FastaReader {
+            input,
+            header: None,
+            line_number: 0,
+        }
+    }
+}
+
+impl<T: BufRead> Iterator for FastaReader<T> {
+    type Item = Result<Seq, GbParserError>;
+
+    fn next(&mut self) -> Option<Result<Seq, GbParserError>> {
+        let mut seq = self.header.take().map(|h| header_seq(&h));
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match self.input.read_until(b'\n', &mut line) {
+                Ok(0) => return seq.map(|s| Ok(finish(s))),
+                Ok(_) => self.line_number += 1,
+                Err(e) => return Some(Err(e.into())),
+            }
+            let line = line.strip_suffix(b"\n").unwrap_or(&line);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if let Some(header) = line.strip_prefix(b">") {
+                match seq {
+                    Some(s) => {
+                        self.header = Some(header.to_vec());
+                        return Some(Ok(finish(s)));
+                    }
+                    None => seq = Some(header_seq(header)),
+                }
+            } else if !line.is_empty() && !line.starts_with(b";") {
+                match seq {
+                    Some(ref mut s) => {
+                        s.seq.extend(line.iter().filter(|b| !b.is_ascii_whitespace()))
+                    }
+                    None => {
+                        return Some(Err(GbParserError::SyntaxError(format!(
+                            "Line {}: sequence before header",
+                            self.line_number
+                        ))))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn header_seq(header: &[u8]) -> Seq {
+    let header = String::from_utf8_lossy(header);
+    let mut words = header.trim().splitn(2, char::is_whitespace);
+    Seq {
+        name: words.next().filter(|n| !n.is_empty()).map(String::from),
+        definition: words.next().map(|d| d.trim().to_string()),
+        ..Seq::empty()
+    }
+}
+
+fn finish(mut s: Seq) -> Seq {
+    s.len = Some(s.seq.len());
+    if !s.seq.iter().all(|&b| is_nucleotide(b)) {
+        s.alphabet = Alphabet::Protein;
+    }
+    s
+}
+
+/// Parse a whole (multi-)FASTA file, see `FastaReader`
+pub fn parse_fasta(data: &[u8]) -> Result<Vec<Seq>, GbParserError> {
+    FastaReader::new(data).collect()
+}
+
+fn is_nucleotide(b: u8) -> bool {
+    b"acgtumrwsykvhdbn-.".contains(&b.to_ascii_lowercase())
+}
+
+impl Seq {
+    /// Writes the whole sequence as a FASTA record, with the name (or
+    /// accession) and definition as the header
+    pub fn write_fasta<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut header = self
+            .name
+            .clone()
+            .or_else(|| self.accession.clone())
+            .unwrap_or_else(|| "unknown".into());
+        if let Some(ref d) = self.definition {
+            header.push(' ');
+            header.push_str(&d.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        write_fasta(out, &header, &self.seq)
+    }
+
+    fn cds_features(&self) -> impl Iterator<Item = (usize, &Feature)> {
+        self.features
+            .iter()
+            .filter(|f| f.kind == feature_kind!("CDS") && !f.is_pseudo())
+            .enumerate()
+            .map(|(i, f)| (i + 1, f))
+    }
+
+    /// The FASTA header for the `n`th CDS: its `/protein_id`, `/locus_tag`
+    /// or a name made from the record's name, followed by the gene,
+    /// locus tag, protein id and product in the bracketed style used by NCBI
+    fn cds_header(&self, n: usize, f: &Feature) -> String {
+        let value = |key| f.qualifier_values(key).next();
+        let id = value(qualifier_key!("protein_id"))
+            .or_else(|| value(qualifier_key!("locus_tag")))
+            .map(String::from)
+            .unwrap_or_else(|| {
+                let name = self.accession.as_ref().or(self.name.as_ref());
+                format!("{}_cds_{}", name.map_or("unknown", |n| n.as_str()), n)
+            });
+        let mut header = id;
+        for (key, label) in &[
+            (qualifier_key!("gene"), "gene"),
+            (qualifier_key!("locus_tag"), "locus_tag"),
+            (qualifier_key!("protein_id"), "protein_id"),
+            (qualifier_key!("product"), "protein"),
+        ] {
+            if let Some(v) = value(key.clone()) {
+                header.push_str(&format!(" [{}={}]", label, v.replace('\n', " ")));
+            }
+        }
+        header
+    }
+
+    /// Writes the nucleotide sequence of every CDS as multi-FASTA. Pseudo
+    /// CDSs are skipped, as are any whose location can't be extracted (e.g.
+    /// because it refers to another record), with a warning.
+    pub fn write_cds_fasta<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for (n, f) in self.cds_features() {
+            match self.extract_location(&f.location) {
+                Ok(nt) => write_fasta(&mut out, &self.cds_header(n, f), &nt)?,
+                Err(e) => warn!("Skipping CDS {}: {}", f.location, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the protein sequence of every CDS as multi-FASTA. The
+    /// `/translation` qualifier is used where present, otherwise the CDS is
+    /// translated. Skips the same CDSs as `write_cds_fasta`, as well as any
+    /// which can't be translated.
+    pub fn write_protein_fasta<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for (n, f) in self.cds_features() {
+            let protein = match f.qualifier_values(qualifier_key!("translation")).next() {
+                Some(t) => Ok(t.bytes().filter(|b| !b.is_ascii_whitespace()).collect()),
+                None => self.translate_feature(f),
+            };
+            match protein {
+                Ok(p) => write_fasta(&mut out, &self.cds_header(n, f), &p)?,
+                Err(e) => warn!("Skipping CDS {}: {}", f.location, e),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::parse_slice;
+
+    #[test]
+    fn read_fasta() {
+        let data = b"; comment\n>seq1 first one\nACGT\nNNac\r\n\n>seq2\nMKV*\n";
+        let seqs = parse_fasta(&data[..]).unwrap();
+        assert_eq!(seqs.len(), 2);
+        assert_eq!(seqs[0].name.as_deref(), Some("seq1"));
+        assert_eq!(seqs[0].definition.as_deref(), Some("first one"));
+        assert_eq!(seqs[0].seq, b"ACGTNNac");
+        assert_eq!(seqs[0].len, Some(8));
+        assert_eq!(seqs[0].alphabet, Alphabet::Nucleotide);
+        assert_eq!(seqs[1].definition, None);
+        assert_eq!(seqs[1].alphabet, Alphabet::Protein);
+        assert!(parse_fasta(b"ACGT\n>x\n").is_err());
+        let mut out = Vec::new();
+        seqs[0].write_fasta(&mut out).unwrap();
+        assert_eq!(out, b">seq1 first one\nACGTNNac\n");
+    }
+
+    #[test]
+    fn cds_fasta() {
+        let data =
+            b"LOCUS       test                      20 bp    DNA     linear   UNK 01-JAN-2020
+ACCESSION   X00001
+FEATURES             Location/Qualifiers
+     CDS             1..9
+                     /locus_tag=\"t1\"
+                     /product=\"leader
+                     peptide\"
+     CDS             complement(9..20)
+                     /protein_id=\"P1.1\"
+                     /translation=\"MRKG\"
+     CDS             1..6
+                     /pseudo
+     CDS             4..8
+ORIGIN
+        1 ttgaaatgac ccttacgcat
+//
+";
+        let seq = parse_slice(&data[..]).unwrap().pop().unwrap();
+        let mut out = Vec::new();
+        seq.write_cds_fasta(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ">t1 [locus_tag=t1] [protein=leader peptide]\nttgaaatga\n\
+             >P1.1 [protein_id=P1.1]\natgcgtaagggt\n\
+             >X00001_cds_3\naaatg\n"
+        );
+        let mut out = Vec::new();
+        seq.write_protein_fasta(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ">t1 [locus_tag=t1] [protein=leader peptide]\nMK\n\
+             >P1.1 [protein_id=P1.1]\nMRKG\n\
+             >X00001_cds_3\nK\n"
+        );
+    }
+}
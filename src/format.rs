@@ -0,0 +1,338 @@
+//! Guessing the format of a file from its contents, and reading, writing
+//! and converting records the same way whatever their format, so that tools
+//! can accept whatever they're given.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::embl::EmblReader;
+use crate::errors::GbParserError;
+use crate::fasta::FastaReader;
+use crate::reader::{SeqReader, Sniffed};
+use crate::seq::Seq;
+use crate::writer::{Dialect, SeqWriter};
+
+/// A sequence file format, as returned by `detect_format`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    GenBank,
+    /// GenBank records of protein sequences, as used by NCBI's protein
+    /// database
+    GenPept,
+    Embl,
+    Fasta,
+    Unknown,
+}
+
+impl Format {
+    /// The usual file extension, without a dot
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Format::GenBank => Some("gb"),
+            Format::GenPept => Some("gp"),
+            Format::Embl => Some("embl"),
+            Format::Fasta => Some("fasta"),
+            Format::Unknown => None,
+        }
+    }
+}
+
+/// Guess the format of `data`, which only needs to be the start of the file.
+/// The first line that starts a record decides: `LOCUS` for GenBank (or
+/// GenPept, if the length is in `aa`), `ID` for EMBL and `>` for FASTA.
+/// Lines before it, such as the header of GenBank release files, are ignored.
+pub fn detect_format(data: &[u8]) -> Format {
+    for line in data.split(|&b| b == b'\n') {
+        if line.starts_with(b">") {
+            return Format::Fasta;
+        } else if line.starts_with(b"LOCUS ") {
+            let protein = String::from_utf8_lossy(line)
+                .split_whitespace()
+                .any(|w| w == "aa");
+            return if protein {
+                Format::GenPept
+            } else {
+                Format::GenBank
+            };
+        } else if line.starts_with(b"ID   ") {
+            return Format::Embl;
+        }
+    }
+    Format::Unknown
+}
+
+/// Parse all the records in `data`, whichever format `detect_format` says
+/// it's in
+pub fn parse_any(data: &[u8]) -> Result<Vec<Seq>, GbParserError> {
+    RecordReader::new(data)?.collect()
+}
+
+// How much of the input `RecordReader` looks at to detect its format
+const SNIFF_LEN: usize = 8 * 1024;
+
+#[derive(Debug)]
+enum Inner<T: Read> {
+    GenBank(Box<SeqReader<Sniffed<T>>>),
+    Fasta(FastaReader<BufReader<Sniffed<T>>>),
+    Embl(EmblReader<BufReader<Sniffed<T>>>),
+}
+
+/// Reads `Seq`s from a stream in any supported format, which is detected
+/// from the start of the stream with `detect_format`
+#[derive(Debug)]
+pub struct RecordReader<T: Read> {
+    inner: Inner<T>,
+    format: Format,
+}
+
+impl<T: Read> RecordReader<T> {
+    /// Fails if the format isn't recognized or isn't supported
+    pub fn new(mut input: T) -> Result<RecordReader<T>, GbParserError> {
+        let mut start = Vec::with_capacity(SNIFF_LEN);
+        (&mut input)
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut start)?;
+        let format = detect_format(&start);
+        let input = Cursor::new(start).chain(input);
+        let inner = match format {
+            Format::GenBank | Format::GenPept => Inner::GenBank(Box::new(SeqReader::new(input))),
+            Format::Fasta => Inner::Fasta(FastaReader::new(BufReader::new(input))),
+            Format::Embl => Inner::Embl(EmblReader::new(BufReader::new(input))),
+            Format::Unknown => {
+                return Err(GbParserError::SyntaxError(
+                    "Unrecognized file format".into(),
+                ))
+            }
+        };
+        Ok(RecordReader { inner, format })
+    }
+
+    /// The format that was detected
+    pub fn format(&self) -> Format {
+        self.format
+    }
+}
+
+impl<T: Read> Iterator for RecordReader<T> {
+    type Item = Result<Seq, GbParserError>;
+
+    fn next(&mut self) -> Option<Result<Seq, GbParserError>> {
+        match self.inner {
+            Inner::GenBank(ref mut r) => r.next(),
+            Inner::Fasta(ref mut r) => r.next(),
+            Inner::Embl(ref mut r) => r.next(),
+        }
+    }
+}
+
+impl Seq {
+    /// Writes the record in the given format. FASTA output only has the
+    /// name, definition and sequence. EMBL is written the way ENA does.
+    pub fn write_as<W: Write>(&self, out: W, format: Format) -> io::Result<()> {
+        match format {
+            Format::GenBank | Format::GenPept => self.write(out),
+            Format::Fasta => self.write_fasta(out),
+            Format::Embl => SeqWriter::new(out).dialect(Dialect::Ena).write(self),
+            Format::Unknown => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Can't write {:?} files", format),
+            )),
+        }
+    }
+}
+
+/// Reads every record from `input`, in whatever format it's in, and writes
+/// it to `output` in `format`. Returns the number of records converted.
+pub fn convert<R: Read, W: Write>(
+    input: R,
+    mut output: W,
+    format: Format,
+) -> Result<usize, GbParserError> {
+    let mut n = 0;
+    for seq in RecordReader::new(input)? {
+        seq?.write_as(&mut output, format)?;
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// Settings for `convert_all`
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ConvertOptions {
+    /// Where to write the converted files. By default, each is written next
+    /// to its input.
+    pub output_dir: Option<PathBuf>,
+    /// Replace output files which already exist
+    pub overwrite: bool,
+    /// Convert several files at once. Only has an effect with the `rayon`
+    /// feature.
+    pub parallel: bool,
+}
+
+/// The outcome of converting one file with `convert_all`
+#[derive(Debug)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// The number of records converted, or what went wrong. If there was an
+    /// error, the output file may have been partly written.
+    pub result: Result<usize, GbParserError>,
+}
+
+/// Converts each of `inputs`, in whatever format it's in, to `format`. The
+/// output file has the same name as the input, with the extension for
+/// `format`. Every file is attempted even if some fail, and a report is
+/// returned for each, in the order of `inputs`.
+pub fn convert_all<P: AsRef<Path> + Sync>(
+    inputs: &[P],
+    format: Format,
+    options: &ConvertOptions,
+) -> Vec<FileReport> {
+    let convert_one = |input: &P| {
+        let input = input.as_ref();
+        let mut output = match options.output_dir {
+            Some(ref dir) => dir.join(input.file_name().unwrap_or_default()),
+            None => input.to_path_buf(),
+        };
+        output.set_extension(format.extension().unwrap_or("out"));
+        let result = convert_file(input, &output, format, options.overwrite);
+        FileReport {
+            input: input.to_path_buf(),
+            output,
+            result,
+        }
+    };
+    #[cfg(feature = "rayon")]
+    {
+        if options.parallel {
+            use rayon::prelude::*;
+            return inputs.par_iter().map(convert_one).collect();
+        }
+    }
+    inputs.iter().map(convert_one).collect()
+}
+
+fn convert_file(
+    input: &Path,
+    output: &Path,
+    format: Format,
+    overwrite: bool,
+) -> Result<usize, GbParserError> {
+    if format == Format::Unknown {
+        return Err(GbParserError::SyntaxError("Can't write Unknown files".into()));
+    }
+    if input == output {
+        return Err(GbParserError::SyntaxError(format!(
+            "Output would replace the input {}",
+            input.display()
+        )));
+    }
+    let reader = RecordReader::new(File::open(input)?)?;
+    let file = if overwrite {
+        File::create(output)?
+    } else {
+        File::options().write(true).create_new(true).open(output)?
+    };
+    let mut out = BufWriter::new(file);
+    let mut n = 0;
+    for seq in reader {
+        seq?.write_as(&mut out, format)?;
+        n += 1;
+    }
+    out.flush()?;
+    Ok(n)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Alphabet;
+
+    #[test]
+    fn detect() {
+        let genbank = b"GBVRL1.SEQ          Genetic Sequence Data Bank\n\n\
+LOCUS       a                          4 bp    DNA     linear   UNK 01-JAN-2020\n";
+        assert_eq!(detect_format(genbank), Format::GenBank);
+        let genpept = std::fs::read("tests/biopython_tests/protein_refseq.gb").unwrap();
+        assert_eq!(detect_format(&genpept), Format::GenPept);
+        assert_eq!(
+            detect_format(b"ID   X56734; SV 1; linear; mRNA\n"),
+            Format::Embl
+        );
+        assert_eq!(detect_format(b"\n>x\nACGT\n"), Format::Fasta);
+        assert_eq!(detect_format(b"hello\n"), Format::Unknown);
+
+        let seqs = parse_any(&genpept).unwrap();
+        assert_eq!(seqs[0].alphabet, Alphabet::Protein);
+        assert_eq!(parse_any(b">x\nACGT\n").unwrap()[0].seq, b"ACGT");
+        assert!(parse_any(b"hello\n").is_err());
+    }
+
+    #[test]
+    fn convert_formats() {
+        let data = std::fs::read("tests/biopython_tests/NC_005816.gb").unwrap();
+        let reader = RecordReader::new(&data[..]).unwrap();
+        assert_eq!(reader.format(), Format::GenBank);
+        let seq = reader.map(Result::unwrap).next().unwrap();
+        let mut fasta = Vec::new();
+        assert_eq!(convert(&data[..], &mut fasta, Format::Fasta).unwrap(), 1);
+        let mut reader = RecordReader::new(&fasta[..]).unwrap();
+        assert_eq!(reader.format(), Format::Fasta);
+        let read_back = reader.next().unwrap().unwrap();
+        assert_eq!(read_back.name, seq.name);
+        assert_eq!(read_back.seq, seq.seq);
+        let mut genbank = Vec::new();
+        assert_eq!(
+            convert(&fasta[..], &mut genbank, Format::GenBank).unwrap(),
+            1
+        );
+        assert_eq!(parse_any(&genbank).unwrap()[0].seq, seq.seq);
+        let mut embl = Vec::new();
+        seq.write_as(&mut embl, Format::Embl).unwrap();
+        assert!(embl.starts_with(b"ID   NC_005816; SV 1; circular; genomic DNA; STD; PRO;"));
+        let mut reader = RecordReader::new(&embl[..]).unwrap();
+        assert_eq!(reader.format(), Format::Embl);
+        let read_back = reader.next().unwrap().unwrap();
+        assert_eq!(read_back.seq, seq.seq);
+        assert_eq!(read_back.topology, seq.topology);
+        assert_eq!(read_back.division, seq.division);
+        assert_eq!(read_back.accession, seq.accession);
+        assert_eq!(read_back.version.as_deref(), Some("NC_005816.1"));
+        assert_eq!(read_back.references.len(), seq.references.len());
+        assert_eq!(read_back.features, seq.features);
+    }
+
+    #[test]
+    fn convert_many() {
+        let dir = std::env::temp_dir().join(format!("gb-io-convert-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let inputs = [
+            "tests/biopython_tests/cor6_6.gb",
+            "tests/biopython_tests/does_not_exist.gb",
+            "tests/biopython_tests/NC_005816.gb",
+        ];
+        let options = ConvertOptions {
+            output_dir: Some(dir.clone()),
+            overwrite: true,
+            parallel: true,
+        };
+        let reports = convert_all(&inputs, Format::Fasta, &options);
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].output, dir.join("cor6_6.fasta"));
+        assert_eq!(reports[0].result.as_ref().unwrap(), &6);
+        assert!(reports[1].result.is_err());
+        assert_eq!(reports[2].result.as_ref().unwrap(), &1);
+        let converted = RecordReader::new(File::open(&reports[0].output).unwrap()).unwrap();
+        assert_eq!(converted.format(), Format::Fasta);
+        assert_eq!(converted.count(), 6);
+
+        let options = ConvertOptions {
+            output_dir: Some(dir.clone()),
+            ..ConvertOptions::default()
+        };
+        let reports = convert_all(&inputs[..1], Format::Fasta, &options);
+        assert!(reports[0].result.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
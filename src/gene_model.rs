@@ -0,0 +1,649 @@
+//! Grouping gene, mRNA and CDS features into gene models, deriving the UTRs
+//! and introns implied by them, and finding the regions between genes.
+
+use std::collections::BTreeMap;
+
+use crate::seq::{Feature, FeatureKind, Location, LocationError, QualifierKey, Seq, Strand};
+
+/// The features making up one transcript of a gene, as indices into
+/// `Seq::features`. Features are grouped by `/locus_tag`, or `/gene` if
+/// there's no locus tag.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GeneModel {
+    pub gene: Option<usize>,
+    pub mrna: Option<usize>,
+    pub cds: Option<usize>,
+}
+
+/// The parts of a gene model which aren't annotated explicitly
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GeneStructure {
+    pub five_prime_utr: Option<Location>,
+    pub three_prime_utr: Option<Location>,
+    pub introns: Vec<Location>,
+}
+
+/// The gene, mRNAs and CDSs sharing a key
+type Group = (Option<usize>, Vec<usize>, Vec<usize>);
+
+fn group_key(f: &Feature) -> Option<&str> {
+    f.qualifier_values(qualifier_key!("locus_tag"))
+        .next()
+        .or_else(|| f.qualifier_values(qualifier_key!("gene")).next())
+}
+
+impl Seq {
+    /// The ranges of `l` as `(start, end)` pairs sorted along the forward
+    /// strand, with ranges that continue past the origin of a circular
+    /// sequence shifted by its length so that they stay in order
+    fn sorted_ranges(&self, l: &Location) -> Result<(Strand, Vec<(i64, i64)>), LocationError> {
+        let strand = l.strand();
+        let mut ranges = l
+            .exons()?
+            .iter()
+            .map(Location::find_bounds)
+            .collect::<Result<Vec<_>, _>>()?;
+        if ranges.is_empty() {
+            return Err(LocationError::Empty);
+        }
+        if self.is_circular() {
+            let len = self.len();
+            for i in 1..ranges.len() {
+                let (prev_start, prev_end) = ranges[i - 1];
+                let r = &mut ranges[i];
+                match strand {
+                    Strand::Forward if r.0 < prev_end => *r = (r.0 + len, r.1 + len),
+                    Strand::Reverse if r.1 > prev_start => *r = (r.0 - len, r.1 - len),
+                    _ => {}
+                }
+            }
+        }
+        ranges.sort_unstable();
+        Ok((strand, ranges))
+    }
+
+    /// Groups the gene, mRNA and CDS features into gene models. Each CDS is
+    /// paired with the first mRNA of the same gene which spans it, and each
+    /// mRNA without a CDS (or CDS without an mRNA) makes a model of its own.
+    /// Genes with neither are included on their own.
+    pub fn gene_models(&self) -> Vec<GeneModel> {
+        let mut groups: BTreeMap<&str, Group> = BTreeMap::new();
+        for (i, f) in self.features.iter().enumerate() {
+            let key = match group_key(f) {
+                Some(k) => k,
+                None => continue,
+            };
+            let group = groups.entry(key).or_default();
+            if f.kind == feature_kind!("gene") {
+                group.0.get_or_insert(i);
+            } else if f.kind == feature_kind!("mRNA") {
+                group.1.push(i);
+            } else if f.kind == feature_kind!("CDS") {
+                group.2.push(i);
+            }
+        }
+        let mut models = Vec::new();
+        for (gene, mrnas, cdss) in groups.into_values() {
+            let mut used = vec![false; mrnas.len()];
+            for &cds in &cdss {
+                let mrna = mrnas
+                    .iter()
+                    .position(|&m| self.contains_exons(m, cds))
+                    .or_else(|| (!mrnas.is_empty()).then_some(0));
+                if let Some(m) = mrna {
+                    used[m] = true;
+                }
+                models.push(GeneModel {
+                    gene,
+                    mrna: mrna.map(|m| mrnas[m]),
+                    cds: Some(cds),
+                });
+            }
+            for (&m, used) in mrnas.iter().zip(used) {
+                if !used {
+                    models.push(GeneModel {
+                        gene,
+                        mrna: Some(m),
+                        cds: None,
+                    });
+                }
+            }
+            if mrnas.is_empty() && cdss.is_empty() {
+                models.push(GeneModel {
+                    gene,
+                    mrna: None,
+                    cds: None,
+                });
+            }
+        }
+        models.sort_by_key(|m| m.gene.or(m.mrna).or(m.cds));
+        models
+    }
+
+    /// Whether the mRNA at `mrna` covers the CDS at `cds`
+    fn contains_exons(&self, mrna: usize, cds: usize) -> bool {
+        let bounds = |i: usize| -> Option<(i64, i64)> {
+            let (_, r) = self.sorted_ranges(&self.features[i].location).ok()?;
+            Some((r.first()?.0, r.last()?.1))
+        };
+        match (bounds(mrna), bounds(cds)) {
+            (Some((a, b)), Some((c, d))) => a <= c && d <= b,
+            _ => false,
+        }
+    }
+
+    /// Turns sorted ranges back into a location on this sequence
+    fn ranges_to_location(
+        &self,
+        strand: Strand,
+        ranges: &[(i64, i64)],
+    ) -> Result<Location, LocationError> {
+        let mut parts: Vec<_> = ranges
+            .iter()
+            .map(|&(a, b)| Location::simple_range(a, b))
+            .collect();
+        let l = if parts.len() == 1 {
+            parts.pop().unwrap()
+        } else {
+            Location::Join(parts)
+        };
+        let l = if self.is_circular() {
+            self.wrap_location(l)?
+        } else {
+            l
+        };
+        Ok(match strand {
+            Strand::Forward => l,
+            Strand::Reverse => Location::Complement(Box::new(l)),
+        })
+    }
+
+    /// Works out the UTRs and introns of a gene model. The UTRs are the
+    /// parts of the mRNA outside the CDS, and the introns the gaps between
+    /// the exons of the mRNA, or of the CDS if there's no mRNA. Both
+    /// strands and models spanning the origin of a circular sequence are
+    /// handled.
+    pub fn gene_structure(&self, model: &GeneModel) -> Result<GeneStructure, LocationError> {
+        let transcript = match model.mrna.or(model.cds) {
+            Some(i) => &self.features[i].location,
+            None => return Ok(GeneStructure::default()),
+        };
+        let (strand, exons) = self.sorted_ranges(transcript)?;
+        let mut res = GeneStructure::default();
+        for w in exons.windows(2) {
+            if w[1].0 > w[0].1 {
+                res.introns
+                    .push(self.ranges_to_location(strand, &[(w[0].1, w[1].0)])?);
+            }
+        }
+        if strand == Strand::Reverse {
+            res.introns.reverse();
+        }
+
+        if let (Some(_), Some(cds)) = (model.mrna, model.cds) {
+            let (_, cds) = self.sorted_ranges(&self.features[cds].location)?;
+            let (mut lo, mut hi) = (cds[0].0, cds[cds.len() - 1].1);
+            // move the CDS to the same copy of a circular sequence
+            if self.is_circular() {
+                let len = self.len();
+                while hi <= exons[0].0 {
+                    lo += len;
+                    hi += len;
+                }
+                while lo >= exons[exons.len() - 1].1 {
+                    lo -= len;
+                    hi -= len;
+                }
+            }
+            let before: Vec<_> = exons
+                .iter()
+                .filter(|e| e.0 < lo)
+                .map(|&(a, b)| (a, b.min(lo)))
+                .collect();
+            let after: Vec<_> = exons
+                .iter()
+                .filter(|e| e.1 > hi)
+                .map(|&(a, b)| (a.max(hi), b))
+                .collect();
+            let utr = |ranges: Vec<(i64, i64)>| -> Result<Option<Location>, LocationError> {
+                if ranges.is_empty() {
+                    Ok(None)
+                } else {
+                    self.ranges_to_location(strand, &ranges).map(Some)
+                }
+            };
+            let (five, three) = match strand {
+                Strand::Forward => (before, after),
+                Strand::Reverse => (after, before),
+            };
+            res.five_prime_utr = utr(five)?;
+            res.three_prime_utr = utr(three)?;
+        }
+        Ok(res)
+    }
+
+    /// The regions of at least `min_len` bases not covered by any gene or
+    /// CDS, from the start of the sequence. Features are taken to cover
+    /// everything from their start to their end, including introns. On a
+    /// circular sequence, the region between the last and first genes wraps
+    /// around the origin. Features whose location can't be processed are
+    /// ignored with a warning, and parts of features beyond the ends of the
+    /// sequence are ignored.
+    pub fn intergenic_regions(&self, min_len: i64) -> Vec<Location> {
+        let len = self.len();
+        let mut covered = Vec::new();
+        for f in &self.features {
+            if f.kind != feature_kind!("gene") && f.kind != feature_kind!("CDS") {
+                continue;
+            }
+            match f.location.find_bounds() {
+                Ok((a, b)) if a < b => covered.push((a.clamp(0, len), b.clamp(0, len))),
+                Ok((a, b)) if self.is_circular() => {
+                    covered.push((a.clamp(0, len), len));
+                    covered.push((0, b.clamp(0, len)));
+                }
+                Ok(_) => warn!("Ignoring feature with invalid location: {}", f.location),
+                Err(e) => warn!("Ignoring feature with invalid location: {}", e),
+            }
+        }
+        covered.sort_unstable();
+        let mut gaps = Vec::new();
+        let mut end = 0;
+        for (a, b) in covered {
+            if a > end {
+                gaps.push((end, a));
+            }
+            end = end.max(b);
+        }
+        if end < len {
+            gaps.push((end, len));
+        }
+        // join the regions either side of the origin
+        if self.is_circular() && gaps.len() > 1 {
+            let (first, last) = (gaps[0], gaps[gaps.len() - 1]);
+            if first.0 == 0 && last.1 == len {
+                gaps.remove(0);
+                *gaps.last_mut().unwrap() = (last.0, first.1 + len);
+            }
+        }
+        gaps.into_iter()
+            .filter(|&(a, b)| b - a >= min_len && b > a)
+            .map(|(a, b)| {
+                self.range_to_location(a, if b > len { b - len } else { b })
+                    .expect("gaps are non-empty ranges within the sequence")
+            })
+            .collect()
+    }
+
+    /// Extracts each of the `intergenic_regions` as a record of its own,
+    /// keeping any other annotations in it
+    pub fn intergenic_records(&self, min_len: i64) -> Vec<Seq> {
+        self.intergenic_regions(min_len)
+            .iter()
+            .map(|l| {
+                let (start, end) = l.find_bounds().unwrap(); // always a range or join
+                self.extract_range(start, end)
+            })
+            .collect()
+    }
+
+    /// Adds `5'UTR`, `3'UTR` and `intron` features for every gene model,
+    /// copying `/gene` and `/locus_tag` from the model's features. Models
+    /// whose locations can't be processed are skipped with a warning.
+    /// Returns the number of features added.
+    pub fn add_gene_structure_features(&mut self) -> usize {
+        let mut added = Vec::new();
+        for model in self.gene_models() {
+            let structure = match self.gene_structure(&model) {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("Skipping gene model {:?}: {}", model, e);
+                    continue;
+                }
+            };
+            let source = &self.features[model.mrna.or(model.cds).or(model.gene).unwrap()];
+            let qualifiers: Vec<(QualifierKey, Option<String>)> = source
+                .qualifiers
+                .iter()
+                .filter(|(k, _)| *k == qualifier_key!("gene") || *k == qualifier_key!("locus_tag"))
+                .cloned()
+                .collect();
+            let mut add = |kind: FeatureKind, location: Location| {
+                added.push(Feature::new(kind, location, qualifiers.clone()))
+            };
+            if let Some(l) = structure.five_prime_utr {
+                add(feature_kind!("5'UTR"), l);
+            }
+            for l in structure.introns {
+                add(feature_kind!("intron"), l);
+            }
+            if let Some(l) = structure.three_prime_utr {
+                add(feature_kind!("3'UTR"), l);
+            }
+        }
+        let n = added.len();
+        self.features.extend(added);
+        n
+    }
+
+    /// Assigns `/locus_tag`s numbered in order of position, e.g.
+    /// `PREFIX_00010`, `PREFIX_00020` with a `stride` of 10, which leaves
+    /// room for genes added later. Features already sharing a `/locus_tag`
+    /// (or `/gene`) get the same new tag, as do untagged features lying
+    /// within a gene on the same strand, so that the members of a gene model
+    /// stay together. Only genes and the features making up their products
+    /// (RNAs, CDSs, exons, introns and UTRs) are tagged.
+    ///
+    /// Returns the old tag (if the group had one) and the new one for each
+    /// group, in order of position.
+    pub fn renumber_locus_tags(
+        &mut self,
+        prefix: &str,
+        stride: usize,
+    ) -> Vec<(Option<String>, String)> {
+        let position = |seq: &Seq, f: &Feature| {
+            seq.sorted_ranges(&f.location)
+                .ok()
+                .and_then(|(strand, r)| Some((strand, r.first()?.0, r.last()?.1)))
+        };
+        // (old tag, position, members) of each group
+        let mut groups: Vec<(Option<String>, i64, Vec<usize>)> = Vec::new();
+        let mut by_key: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut untagged = Vec::new();
+        for (i, f) in self.features.iter().enumerate() {
+            if !LOCUS_TAGGED.contains(&f.kind) {
+                continue;
+            }
+            let start = position(self, f).map_or(i64::MAX, |p| p.1);
+            match group_key(f) {
+                Some(key) => {
+                    let g = *by_key.entry(key).or_insert_with(|| {
+                        let old = f.qualifier_values(qualifier_key!("locus_tag")).next();
+                        groups.push((old.map(String::from), start, Vec::new()));
+                        groups.len() - 1
+                    });
+                    groups[g].1 = groups[g].1.min(start);
+                    groups[g].2.push(i);
+                }
+                None => untagged.push(i),
+            }
+        }
+        let genes: Vec<_> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(g, (_, _, members))| members.iter().map(move |&i| (g, i)))
+            .filter(|&(_, i)| self.features[i].kind == feature_kind!("gene"))
+            .filter_map(|(g, i)| Some((g, position(self, &self.features[i])?)))
+            .collect();
+        for i in untagged {
+            let p = position(self, &self.features[i]);
+            let gene = p.and_then(|(strand, start, end)| {
+                genes
+                    .iter()
+                    .find(|(_, (s, a, b))| *s == strand && *a <= start && end <= *b)
+            });
+            match gene {
+                Some(&(g, _)) => groups[g].2.push(i),
+                None => groups.push((None, p.map_or(i64::MAX, |p| p.1), vec![i])),
+            }
+        }
+        groups.sort_by_key(|(_, start, members)| (*start, members.iter().min().copied()));
+
+        let mut mapping = Vec::with_capacity(groups.len());
+        for (n, (old, _, members)) in groups.into_iter().enumerate() {
+            let tag = format!("{}_{:05}", prefix, (n + 1) * stride);
+            for i in members {
+                set_locus_tag(&mut self.features[i], &tag);
+            }
+            mapping.push((old, tag));
+        }
+        mapping
+    }
+}
+
+/// The kinds of feature given a `/locus_tag` by `Seq::renumber_locus_tags`
+const LOCUS_TAGGED: &[FeatureKind] = &[
+    feature_kind!("gene"),
+    feature_kind!("mRNA"),
+    feature_kind!("CDS"),
+    feature_kind!("tRNA"),
+    feature_kind!("rRNA"),
+    feature_kind!("ncRNA"),
+    feature_kind!("tmRNA"),
+    feature_kind!("misc_RNA"),
+    feature_kind!("precursor_RNA"),
+    feature_kind!("exon"),
+    feature_kind!("intron"),
+    feature_kind!("5'UTR"),
+    feature_kind!("3'UTR"),
+];
+
+/// Replaces the feature's `/locus_tag`, or adds one after its `/gene`
+fn set_locus_tag(f: &mut Feature, tag: &str) {
+    let key = qualifier_key!("locus_tag");
+    match f.qualifiers.iter().position(|(k, _)| *k == key) {
+        Some(i) => {
+            f.qualifiers[i].1 = Some(tag.into());
+            let mut n = 0;
+            f.qualifiers.retain(|(k, _)| {
+                n += (*k == key) as usize;
+                *k != key || n == 1
+            });
+        }
+        None => {
+            let at = f
+                .qualifiers
+                .iter()
+                .position(|(k, _)| *k == qualifier_key!("gene"))
+                .map_or(0, |i| i + 1);
+            f.qualifiers.insert(at, (key, Some(tag.into())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Topology;
+
+    fn feature(kind: &str, location: &str, tag: &str) -> Feature {
+        Feature::new(
+            kind.into(),
+            Location::from_gb_format(location).unwrap(),
+            vec![(qualifier_key!("locus_tag"), Some(tag.into()))],
+        )
+    }
+
+    fn formatted(s: &GeneStructure) -> (Option<String>, Option<String>, Vec<String>) {
+        (
+            s.five_prime_utr.as_ref().map(Location::to_gb_format),
+            s.three_prime_utr.as_ref().map(Location::to_gb_format),
+            s.introns.iter().map(Location::to_gb_format).collect(),
+        )
+    }
+
+    #[test]
+    fn gene_structure() {
+        let mut s = Seq {
+            seq: vec![b'a'; 100],
+            features: vec![
+                feature("gene", "10..60", "a"),
+                feature("mRNA", "join(10..20,30..40,50..60)", "a"),
+                feature("CDS", "join(15..20,30..40,50..55)", "a"),
+                feature("mRNA", "complement(join(70..80,90..95))", "b"),
+                feature("CDS", "complement(join(75..80,90..92))", "b"),
+                feature("CDS", "1..5", "c"),
+                feature("misc_feature", "1..5", "c"),
+            ],
+            ..Seq::empty()
+        };
+        let models = s.gene_models();
+        assert_eq!(
+            models,
+            vec![
+                GeneModel {
+                    gene: Some(0),
+                    mrna: Some(1),
+                    cds: Some(2)
+                },
+                GeneModel {
+                    gene: None,
+                    mrna: Some(3),
+                    cds: Some(4)
+                },
+                GeneModel {
+                    gene: None,
+                    mrna: None,
+                    cds: Some(5)
+                },
+            ]
+        );
+        let structures: Vec<_> = models
+            .iter()
+            .map(|m| formatted(&s.gene_structure(m).unwrap()))
+            .collect();
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            structures,
+            vec![
+                (
+                    some("10..14"),
+                    some("56..60"),
+                    vec!["21..29".to_string(), "41..49".to_string()]
+                ),
+                (
+                    some("complement(93..95)"),
+                    some("complement(70..74)"),
+                    vec!["complement(81..89)".to_string()]
+                ),
+                (None, None, vec![]),
+            ]
+        );
+        assert_eq!(s.add_gene_structure_features(), 7);
+        assert_eq!(s.features[7].kind, feature_kind!("5'UTR"));
+        assert_eq!(
+            s.features[7]
+                .qualifier_values(qualifier_key!("locus_tag"))
+                .next(),
+            Some("a")
+        );
+    }
+
+    #[test]
+    fn across_origin() {
+        let s = Seq {
+            seq: vec![b'a'; 100],
+            topology: Topology::Circular,
+            features: vec![
+                feature("mRNA", "join(81..90,96..100,1..20)", "a"),
+                feature("CDS", "join(85..90,96..100,1..10)", "a"),
+            ],
+            ..Seq::empty()
+        };
+        let models = s.gene_models();
+        assert_eq!(models.len(), 1);
+        assert_eq!(
+            formatted(&s.gene_structure(&models[0]).unwrap()),
+            (
+                Some("81..84".to_string()),
+                Some("11..20".to_string()),
+                vec!["91..95".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn intergenic() {
+        let mut s = Seq {
+            seq: b"aaaaaccccctttttgggggaaaaaccccc".to_vec(),
+            features: vec![
+                feature("gene", "6..10", "a"),
+                feature("CDS", "complement(8..12)", "a"),
+                feature("misc_feature", "15..16", "b"),
+                feature("CDS", "join(18..20,24..26)", "b"),
+            ],
+            ..Seq::empty()
+        };
+        let regions = |s: &Seq, min_len| -> Vec<String> {
+            s.intergenic_regions(min_len)
+                .iter()
+                .map(Location::to_gb_format)
+                .collect()
+        };
+        assert_eq!(regions(&s, 1), vec!["1..5", "13..17", "27..30"]);
+        assert_eq!(regions(&s, 5), vec!["1..5", "13..17"]);
+        let records = s.intergenic_records(5);
+        assert_eq!(records[1].seq, b"tttgg");
+        assert_eq!(records[1].features.len(), 1);
+
+        s.topology = Topology::Circular;
+        assert_eq!(regions(&s, 5), vec!["13..17", "join(27..30,1..5)"]);
+        assert_eq!(s.intergenic_records(5)[1].seq, b"ccccaaaaa");
+        s.features.push(feature("gene", "join(29..30,1..2)", "c"));
+        assert_eq!(regions(&s, 1), vec!["3..5", "13..17", "27..28"]);
+        s.features.clear();
+        assert_eq!(regions(&s, 1), vec!["1..30"]);
+
+        // features past the end of the sequence
+        s.features.push(feature("gene", "41..50", "d"));
+        assert_eq!(regions(&s, 1), vec!["1..30"]);
+        s.topology = Topology::Linear;
+        assert_eq!(regions(&s, 1), vec!["1..30"]);
+        s.features.push(feature("gene", "25..40", "e"));
+        assert_eq!(regions(&s, 1), vec!["1..24"]);
+        let empty = Seq {
+            topology: Topology::Circular,
+            features: s.features.clone(),
+            ..Seq::empty()
+        };
+        assert!(empty.intergenic_regions(1).is_empty());
+    }
+
+    #[test]
+    fn renumber_locus_tags() {
+        let untagged = |kind: &str, location: &str| Feature {
+            qualifiers: vec![],
+            ..feature(kind, location, "")
+        };
+        let mut s = Seq {
+            seq: vec![b'a'; 100],
+            features: vec![
+                untagged("source", "1..100"),
+                feature("gene", "50..80", "old2"),
+                feature("CDS", "50..80", "old2"),
+                feature("gene", "complement(10..40)", "old1"),
+                untagged("mRNA", "complement(12..38)"),
+                untagged("CDS", "12..38"),
+                untagged("tRNA", "85..95"),
+            ],
+            ..Seq::empty()
+        };
+        let mapping = s.renumber_locus_tags("ABC", 10);
+        assert_eq!(
+            mapping,
+            vec![
+                (Some("old1".into()), "ABC_00010".into()),
+                (None, "ABC_00020".into()),
+                (Some("old2".into()), "ABC_00030".into()),
+                (None, "ABC_00040".into()),
+            ]
+        );
+        let tags: Vec<_> = s
+            .features
+            .iter()
+            .map(|f| f.qualifier_values(qualifier_key!("locus_tag")).next())
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                None,
+                Some("ABC_00030"),
+                Some("ABC_00030"),
+                Some("ABC_00010"),
+                Some("ABC_00010"),
+                Some("ABC_00020"),
+                Some("ABC_00040"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Simulation of Golden Gate assembly. Type IIS enzymes cut outside their
+//! recognition site, so the sticky ends they leave can be chosen freely and
+//! the sites themselves are removed from the parts being assembled.
+
+use crate::dna::revcomp;
+use crate::seq::{Feature, Seq, Strand, Topology};
+
+/// A Type IIS restriction enzyme. The top strand is cut `cut` bases past the
+/// end of the recognition site, and the bottom strand `overhang` bases
+/// further along, leaving a 5' overhang.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TypeIIS {
+    pub name: &'static str,
+    pub site: &'static [u8],
+    pub cut: i64,
+    pub overhang: i64,
+}
+
+pub const BSAI: TypeIIS = TypeIIS {
+    name: "BsaI",
+    site: b"GGTCTC",
+    cut: 1,
+    overhang: 4,
+};
+
+pub const BSMBI: TypeIIS = TypeIIS {
+    name: "BsmBI",
+    site: b"CGTCTC",
+    cut: 1,
+    overhang: 4,
+};
+
+pub const BBSI: TypeIIS = TypeIIS {
+    name: "BbsI",
+    site: b"GAAGAC",
+    cut: 2,
+    overhang: 4,
+};
+
+pub const SAPI: TypeIIS = TypeIIS {
+    name: "SapI",
+    site: b"GCTCTTC",
+    cut: 1,
+    overhang: 3,
+};
+
+/// A fragment left by digestion with a Type IIS enzyme. `seq` is the top
+/// strand including both overhangs, so it starts with `left` and ends with
+/// `right`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fragment {
+    pub seq: Seq,
+    pub left: Vec<u8>,
+    pub right: Vec<u8>,
+}
+
+impl Fragment {
+    fn new(seq: Seq, overhang: usize) -> Fragment {
+        let len = seq.seq.len();
+        Fragment {
+            left: seq.seq[..overhang].to_ascii_uppercase(),
+            right: seq.seq[len - overhang..].to_ascii_uppercase(),
+            seq,
+        }
+    }
+
+    /// The same fragment, flipped
+    pub fn revcomp(&self) -> Fragment {
+        Fragment {
+            seq: self.seq.revcomp(),
+            left: revcomp(&self.right),
+            right: revcomp(&self.left),
+        }
+    }
+}
+
+impl Seq {
+    /// The start of each overhang left by `enzyme`, sorted
+    fn type_iis_cuts(&self, enzyme: &TypeIIS) -> Vec<i64> {
+        let len = self.len();
+        let site_len = enzyme.site.len() as i64;
+        let mut cuts: Vec<_> = self
+            .find_sites(enzyme.site)
+            .into_iter()
+            .map(|s| match s.strand {
+                Strand::Forward => s.start + site_len + enzyme.cut,
+                Strand::Reverse => s.start - enzyme.cut - enzyme.overhang,
+            })
+            .filter_map(|c| {
+                if self.is_circular() {
+                    Some(c.rem_euclid(len))
+                } else if c >= 0 && c + enzyme.overhang <= len {
+                    Some(c)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts
+    }
+
+    /// Digests with a Type IIS enzyme, returning the fragments with a sticky
+    /// end on both sides, in order. The blunt ends of a linear sequence are
+    /// dropped. Annotations are kept, truncated at the ends of each fragment.
+    /// Proteins aren't cut, so there are no fragments.
+    pub fn type_iis_fragments(&self, enzyme: &TypeIIS) -> Vec<Fragment> {
+        if self.is_protein() {
+            return Vec::new();
+        }
+        let cuts = self.type_iis_cuts(enzyme);
+        let n = enzyme.overhang;
+        let mut bounds: Vec<_> = cuts.windows(2).map(|w| (w[0], w[1] + n)).collect();
+        if self.is_circular() {
+            if let (Some(&first), Some(&last)) = (cuts.first(), cuts.last()) {
+                bounds.push((last, first + self.len() + n));
+            }
+        }
+        bounds
+            .into_iter()
+            .filter(|&(start, end)| end - start >= 2 * n)
+            .map(|(start, end)| {
+                let seq = if end > self.len() {
+                    // may wrap around more than `extract_range` allows
+                    let seq = self.extract_range_seq(start, end).into_owned();
+                    let mut features = self.extract_range(start, 0).features;
+                    let mut head = self.extract_range(0, end - self.len());
+                    let shift = self.len() - start;
+                    features.extend(std::mem::take(&mut head.features).into_iter().map(|f| {
+                        Feature {
+                            location: head.relocate_location(f.location.clone(), shift).unwrap(),
+                            ..f
+                        }
+                    }));
+                    Seq {
+                        seq,
+                        features,
+                        ..Seq::empty()
+                    }
+                } else {
+                    self.extract_range(start, end)
+                };
+                Fragment::new(seq, n as usize)
+            })
+            .collect()
+    }
+}
+
+/// Joins `fragments` whose overhangs match into a circular construct.
+fn ligate(fragments: &[Fragment], overhang: usize) -> Seq {
+    let mut res = Seq {
+        topology: Topology::Linear,
+        ..Seq::empty()
+    };
+    for (i, frag) in fragments.iter().enumerate() {
+        let offset = if i == 0 {
+            0
+        } else {
+            res.seq.truncate(res.seq.len() - overhang);
+            res.seq.len() as i64
+        };
+        for f in &frag.seq.features {
+            // can't fail on a linear sequence
+            let location = res.relocate_location(f.location.clone(), offset).unwrap();
+            res.features.push(Feature {
+                location,
+                ..f.clone()
+            });
+        }
+        res.seq.extend_from_slice(&frag.seq.seq);
+    }
+    res.seq.truncate(res.seq.len() - overhang);
+    res.topology = Topology::Circular;
+    let features = std::mem::take(&mut res.features);
+    for f in features {
+        match res.wrap_location(f.location.clone()) {
+            Ok(location) => {
+                // features within an overhang come from both fragments
+                let f = Feature { location, ..f };
+                if !res.features.contains(&f) {
+                    res.features.push(f);
+                }
+            }
+            Err(e) => warn!("Skipping feature with invalid location: {}", e),
+        }
+    }
+    res.len = Some(res.seq.len());
+    res
+}
+
+/// Simulates a one-pot Golden Gate reaction: each of `parts` is digested
+/// with `enzyme`, and every way of ligating the resulting fragments into a
+/// circle through matching overhangs is returned. Each fragment is used at
+/// most once, in either orientation. Fragments which still contain a
+/// recognition site are left out, since they would be cut again.
+pub fn golden_gate(parts: &[Seq], enzyme: &TypeIIS) -> Vec<Seq> {
+    let fragments: Vec<_> = parts
+        .iter()
+        .flat_map(|p| p.type_iis_fragments(enzyme))
+        .filter(|f| f.seq.find_sites(enzyme.site).is_empty())
+        .map(|f| {
+            let r = f.revcomp();
+            [f, r]
+        })
+        .collect();
+    let mut products = Vec::new();
+    let mut path = Vec::new();
+    for start in 0..fragments.len() {
+        path.push((start, 0));
+        extend(
+            &fragments,
+            &mut path,
+            &mut products,
+            enzyme.overhang as usize,
+        );
+        path.pop();
+    }
+    products
+}
+
+/// Tries to continue the circle in `path`, a list of fragment indices and
+/// orientations. To report each circle once, it must start with its lowest
+/// numbered fragment in the forward orientation.
+fn extend(
+    fragments: &[[Fragment; 2]],
+    path: &mut Vec<(usize, usize)>,
+    products: &mut Vec<Seq>,
+    overhang: usize,
+) {
+    let (first, _) = path[0];
+    let (last, orientation) = *path.last().unwrap();
+    let end = &fragments[last][orientation].right;
+    if *end == fragments[first][0].left {
+        let joined: Vec<_> = path.iter().map(|&(i, o)| fragments[i][o].clone()).collect();
+        products.push(ligate(&joined, overhang));
+        return;
+    }
+    for next in first + 1..fragments.len() {
+        if path.iter().any(|&(i, _)| i == next) {
+            continue;
+        }
+        for o in 0..2 {
+            if fragments[next][o].left == *end {
+                path.push((next, o));
+                extend(fragments, path, products, overhang);
+                path.pop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::{Alphabet, Location};
+
+    fn feature(kind: &str, start: i64, end: i64) -> Feature {
+        Feature::new(kind.into(), Location::simple_range(start, end), vec![])
+    }
+
+    #[test]
+    fn fragments() {
+        let part = Seq {
+            seq: b"ttGGTCTCaAATGccccccGCTTtGAGACCtt".to_vec(),
+            features: vec![feature("CDS", 13, 19)],
+            ..Seq::empty()
+        };
+        let frags = part.type_iis_fragments(&BSAI);
+        assert_eq!(frags.len(), 1);
+        assert_eq!(frags[0].seq.seq, b"AATGccccccGCTT");
+        assert_eq!(frags[0].left, b"AATG");
+        assert_eq!(frags[0].right, b"GCTT");
+        assert_eq!(
+            frags[0].seq.features[0].location,
+            Location::simple_range(4, 10)
+        );
+        let flipped = frags[0].revcomp();
+        assert_eq!(flipped.left, b"AAGC");
+        assert_eq!(flipped.right, b"CATT");
+    }
+
+    #[test]
+    fn protein_fragments() {
+        let protein = Seq {
+            seq: b"GGTCTCAAATGCCCCCCGCTTTGAGACC".to_vec(),
+            alphabet: Alphabet::Protein,
+            ..Seq::empty()
+        };
+        assert!(protein.type_iis_fragments(&BSAI).is_empty());
+        assert!(golden_gate(&[protein], &BSAI).is_empty());
+    }
+
+    #[test]
+    fn assembly() {
+        let cds = Seq {
+            seq: b"GGTCTCaAATGccccccGCTTtGAGACC".to_vec(),
+            features: vec![feature("CDS", 11, 17)],
+            ..Seq::empty()
+        };
+        // given in the opposite orientation
+        let terminator = Seq {
+            seq: b"GGTCTCaAGCGccccccAAGCtGAGACC".to_vec(),
+            features: vec![feature("terminator", 11, 17)],
+            ..Seq::empty()
+        };
+        let backbone = Seq {
+            seq: b"CGCTaaaaaaAATGtGAGACCttttttGGTCTCa".to_vec(),
+            features: vec![
+                feature("rep_origin", 4, 10),
+                feature("misc_feature", 21, 27),
+            ],
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        let products = golden_gate(&[cds, terminator, backbone], &BSAI);
+        assert_eq!(products.len(), 1);
+        let p = &products[0];
+        assert_eq!(p.topology, Topology::Circular);
+        assert_eq!(p.seq, b"AATGccccccGCTTggggggCGCTaaaaaa");
+        let features: Vec<_> = p
+            .features
+            .iter()
+            .map(|f| (f.kind.to_string(), f.location.to_gb_format()))
+            .collect();
+        assert_eq!(
+            features,
+            vec![
+                ("CDS".to_string(), "5..10".to_string()),
+                ("terminator".to_string(), "complement(15..20)".to_string()),
+                ("rep_origin".to_string(), "25..30".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Indexes of the records in multi-record files such as `.gbff`, giving
+//! where each record starts and how long it is, like `samtools faidx` does
+//! for FASTA files. Building one only scans the LOCUS, ACCESSION and VERSION
+//! lines and the `//` ending each record, so it's much quicker than parsing
+//! the file. `IndexedReader` then uses an index to read single records.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::errors::GbParserError;
+use crate::reader::SeqReader;
+use crate::seq::Seq;
+
+/// Where one record is in the file
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IndexEntry {
+    /// The name from the LOCUS line
+    pub name: Option<String>,
+    /// The primary accession, i.e. the first on the ACCESSION line
+    pub accession: Option<String>,
+    /// The accession and version from the VERSION line, e.g. `NC_000913.3`
+    pub version: Option<String>,
+    /// Offset of the LOCUS line from the start of the file
+    pub offset: u64,
+    /// The length of the record in bytes, up to and including the line
+    /// ending after `//`
+    pub length: u64,
+}
+
+impl IndexEntry {
+    fn ids(&self) -> impl Iterator<Item = &str> {
+        self.version
+            .iter()
+            .chain(&self.accession)
+            .chain(&self.name)
+            .map(String::as_str)
+    }
+}
+
+/// The records of a file, which can be looked up by version, accession or
+/// name.
+///
+/// The index is written as text with a line per record, giving its
+/// version, accession, name, offset and length separated by tabs. Missing
+/// ids are left empty.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+    // the first entry with each id
+    ids: HashMap<String, usize>,
+}
+
+impl Index {
+    pub fn new(entries: Vec<IndexEntry>) -> Index {
+        let mut ids = HashMap::new();
+        for (i, e) in entries.iter().enumerate() {
+            for id in e.ids() {
+                ids.entry(id.to_string()).or_insert(i);
+            }
+        }
+        Index { entries, ids }
+    }
+
+    /// Indexes the records of a GenBank file. Offsets count from where
+    /// `input` starts, so for a compressed file they're offsets into the
+    /// decompressed data. Anything between records is skipped, and a last
+    /// record without its `//` runs to the end of the input.
+    pub fn build<R: BufRead>(mut input: R) -> Result<Index, GbParserError> {
+        let mut entries = Vec::new();
+        let mut current: Option<IndexEntry> = None;
+        let mut offset = 0;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let n = input.read_until(b'\n', &mut line)? as u64;
+            if n == 0 {
+                break;
+            }
+            let first_word = |keyword: &[u8]| {
+                let rest = String::from_utf8_lossy(&line[keyword.len()..]);
+                rest.split_whitespace().next().map(String::from)
+            };
+            if line.starts_with(b"LOCUS ") {
+                entries.extend(current.take());
+                current = Some(IndexEntry {
+                    name: first_word(b"LOCUS"),
+                    accession: None,
+                    version: None,
+                    offset,
+                    length: 0,
+                });
+            }
+            offset += n;
+            if let Some(ref mut e) = current {
+                e.length = offset - e.offset;
+                if line.starts_with(b"ACCESSION ") && e.accession.is_none() {
+                    e.accession = first_word(b"ACCESSION");
+                } else if line.starts_with(b"VERSION ") && e.version.is_none() {
+                    e.version = first_word(b"VERSION");
+                } else if line.starts_with(b"//") {
+                    entries.extend(current.take());
+                }
+            }
+        }
+        entries.extend(current);
+        Ok(Index::new(entries))
+    }
+
+    /// Reads an index written by `write`
+    pub fn read<R: BufRead>(input: R) -> Result<Index, GbParserError> {
+        let mut entries = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let bad = || GbParserError::SyntaxError(format!("Line {} of index: {}", i + 1, line));
+            let fields: Vec<_> = line.split('\t').collect();
+            if fields.len() != 5 {
+                return Err(bad());
+            }
+            let id = |s: &str| Some(s.to_string()).filter(|s| !s.is_empty());
+            entries.push(IndexEntry {
+                version: id(fields[0]),
+                accession: id(fields[1]),
+                name: id(fields[2]),
+                offset: fields[3].parse().map_err(|_| bad())?,
+                length: fields[4].parse().map_err(|_| bad())?,
+            });
+        }
+        Ok(Index::new(entries))
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for e in &self.entries {
+            let id = |s: &Option<String>| s.clone().unwrap_or_default();
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}",
+                id(&e.version),
+                id(&e.accession),
+                id(&e.name),
+                e.offset,
+                e.length
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// The record with the given version, accession or name. If several
+    /// records have it, the first is returned.
+    pub fn get(&self, id: &str) -> Option<&IndexEntry> {
+        self.ids.get(id).map(|&i| &self.entries[i])
+    }
+}
+
+/// Where the index of the file at `path` is kept, `path` with `.gbi`
+/// added, e.g. `genomes.gbff.gbi`
+pub fn index_path(path: &Path) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(".gbi");
+    PathBuf::from(p)
+}
+
+/// Parses single records of a large file, seeking straight to them using
+/// an `Index` rather than reading the file from the start
+#[derive(Debug)]
+pub struct IndexedReader<R: Read + Seek> {
+    input: R,
+    index: Index,
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    /// `index` must be of `input`, which must be uncompressed
+    pub fn new(input: R, index: Index) -> IndexedReader<R> {
+        IndexedReader { input, index }
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    /// Parses the record with the given version, accession or name (see
+    /// `Index::get`), or returns `None` if there's no such record
+    pub fn fetch(&mut self, id: &str) -> Result<Option<Seq>, GbParserError> {
+        match self.index.get(id) {
+            Some(e) => {
+                let (offset, length) = (e.offset, e.length);
+                self.fetch_at(offset, length).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the record at `entry`, which needn't be from `index()`
+    pub fn fetch_entry(&mut self, entry: &IndexEntry) -> Result<Seq, GbParserError> {
+        self.fetch_at(entry.offset, entry.length)
+    }
+
+    fn fetch_at(&mut self, offset: u64, length: u64) -> Result<Seq, GbParserError> {
+        self.input.seek(SeekFrom::Start(offset))?;
+        SeqReader::new((&mut self.input).take(length))
+            .next()
+            .unwrap_or_else(|| {
+                Err(GbParserError::SyntaxError(format!(
+                    "No record at offset {}, is the index out of date?",
+                    offset
+                )))
+            })
+    }
+}
+
+impl IndexedReader<BufReader<File>> {
+    /// Opens the file at `path` with the index at `index_path(path)`. If
+    /// there isn't one the file is indexed, which means reading all of it,
+    /// so it's worth saving the index with `Index::write` if the file will
+    /// be opened again.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, GbParserError> {
+        let path = path.as_ref();
+        let mut input = BufReader::new(File::open(path)?);
+        let index = match File::open(index_path(path)) {
+            Ok(f) => Index::read(BufReader::new(f))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let index = Index::build(&mut input)?;
+                input.rewind()?;
+                index
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(IndexedReader::new(input, index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::parse_slice;
+
+    #[test]
+    fn index() {
+        let mut data = b"Some preamble\n".to_vec();
+        data.extend(std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap());
+        data.extend(std::fs::read("tests/biopython_tests/NT_019265.gb").unwrap());
+        let index = Index::build(&data[..]).unwrap();
+        assert_eq!(index.entries().len(), 7);
+        let e = index.get("X62281.1").unwrap();
+        assert_eq!(e.accession.as_deref(), Some("X62281"));
+        assert_eq!(e.name.as_deref(), Some("ATKIN2"));
+        assert_eq!(index.get("X62281"), Some(e));
+        assert_eq!(index.get("ATKIN2"), Some(e));
+        assert!(index.get("X00000").is_none());
+        let record = &data[e.offset as usize..(e.offset + e.length) as usize];
+        assert!(record.starts_with(b"LOCUS       ATKIN2"));
+        assert!(record.ends_with(b"//\n"));
+        let seq = parse_slice(record).unwrap().pop().unwrap();
+        assert_eq!(seq.version.as_deref(), Some("X62281.1  GI:16353"));
+        let last = index.entries().last().unwrap();
+        assert_eq!(last.offset + last.length, data.len() as u64);
+
+        let mut out = Vec::new();
+        index.write(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).starts_with("X55053.1\tX55053\tATCOR66M\t14\t"));
+        assert_eq!(Index::read(&out[..]).unwrap(), index);
+        assert!(Index::read(&b"X1\t\tx\t0\n"[..]).is_err());
+    }
+
+    #[test]
+    fn indexed_reader() {
+        let path = "tests/biopython_tests/cor6_6.gb";
+        let mut reader = IndexedReader::open(path).unwrap();
+        let seq = reader.fetch("M81224.1").unwrap().unwrap();
+        assert_eq!(seq.name.as_deref(), Some("BNAKINI"));
+        let first = reader.fetch("ATCOR66M").unwrap().unwrap();
+        assert_eq!(first.accession.as_deref(), Some("X55053"));
+        assert!(reader.fetch("NC_000913.3").unwrap().is_none());
+
+        let all: Vec<_> = SeqReader::new(File::open(path).unwrap())
+            .map(Result::unwrap)
+            .collect();
+        let entries = reader.index().entries().to_vec();
+        for (seq, e) in all.iter().zip(&entries) {
+            assert_eq!(&reader.fetch_entry(e).unwrap(), seq);
+        }
+
+        let dir = std::env::temp_dir().join(format!("gb-io-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let copy = dir.join("copy.gb");
+        std::fs::copy(path, &copy).unwrap();
+        assert_eq!(index_path(&copy), dir.join("copy.gb.gbi"));
+        let mut stale = entries.clone();
+        stale[1].offset += 1;
+        Index::new(stale)
+            .write(File::create(index_path(&copy)).unwrap())
+            .unwrap();
+        let mut reader = IndexedReader::open(&copy).unwrap();
+        assert!(reader.fetch("X55053").unwrap().is_some());
+        assert!(reader.fetch("X62281").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
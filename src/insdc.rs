@@ -0,0 +1,743 @@
+//! Constants for the feature and qualifier keys defined by the INSDC
+//! feature table definition, so they can be matched against without going
+//! through the `feature_kind!` and `qualifier_key!` macros, along with which
+//! qualifiers each feature key accepts.
+
+use std::collections::BTreeMap;
+
+use crate::seq::{Feature, FeatureKind, QualifierKey, Seq};
+
+macro_rules! keys {
+    ($ty:ident, $mac:ident, $what:expr; $($name:ident = $key:tt;)*) => {
+        $(
+            #[doc = concat!("`", $key, "`")]
+            pub const $name: $ty = $mac!($key);
+        )*
+
+        #[doc = concat!("Every ", $what, " key in the INSDC feature table definition")]
+        pub const ALL: &[$ty] = &[$($name),*];
+    };
+}
+
+/// Feature keys
+pub mod feature_kinds {
+    use super::*;
+
+    keys! {
+        FeatureKind, feature_kind, "feature";
+        THREE_PRIME_UTR = "3'UTR";
+        FIVE_PRIME_UTR = "5'UTR";
+        ASSEMBLY_GAP = "assembly_gap";
+        C_REGION = "C_region";
+        CDS = "CDS";
+        CENTROMERE = "centromere";
+        D_LOOP = "D-loop";
+        D_SEGMENT = "D_segment";
+        EXON = "exon";
+        GAP = "gap";
+        GENE = "gene";
+        IDNA = "iDNA";
+        INTRON = "intron";
+        J_SEGMENT = "J_segment";
+        MAT_PEPTIDE = "mat_peptide";
+        MISC_BINDING = "misc_binding";
+        MISC_DIFFERENCE = "misc_difference";
+        MISC_FEATURE = "misc_feature";
+        MISC_RECOMB = "misc_recomb";
+        MISC_RNA = "misc_RNA";
+        MISC_STRUCTURE = "misc_structure";
+        MOBILE_ELEMENT = "mobile_element";
+        MODIFIED_BASE = "modified_base";
+        MRNA = "mRNA";
+        N_REGION = "N_region";
+        NCRNA = "ncRNA";
+        OLD_SEQUENCE = "old_sequence";
+        OPERON = "operon";
+        ORIT = "oriT";
+        POLYA_SITE = "polyA_site";
+        PRECURSOR_RNA = "precursor_RNA";
+        PRIM_TRANSCRIPT = "prim_transcript";
+        PRIMER_BIND = "primer_bind";
+        PROPEPTIDE = "propeptide";
+        PROTEIN_BIND = "protein_bind";
+        REGULATORY = "regulatory";
+        REP_ORIGIN = "rep_origin";
+        REPEAT_REGION = "repeat_region";
+        RRNA = "rRNA";
+        S_REGION = "S_region";
+        SIG_PEPTIDE = "sig_peptide";
+        SOURCE = "source";
+        STEM_LOOP = "stem_loop";
+        STS = "STS";
+        TELOMERE = "telomere";
+        TMRNA = "tmRNA";
+        TRANSIT_PEPTIDE = "transit_peptide";
+        TRNA = "tRNA";
+        UNSURE = "unsure";
+        V_REGION = "V_region";
+        V_SEGMENT = "V_segment";
+        VARIATION = "variation";
+    }
+
+    /// Returns true if `kind` is one of the feature keys in the current
+    /// INSDC feature table definition. Retired keys such as `promoter` or
+    /// `misc_signal` (now `regulatory` with a `/regulatory_class`) are not
+    /// considered standard.
+    pub fn is_standard(kind: &FeatureKind) -> bool {
+        ALL.contains(kind)
+    }
+
+    // Spellings seen in real files which differ by more than case
+    const ALIASES: &[(&str, FeatureKind)] = &[
+        ("3UTR", THREE_PRIME_UTR),
+        ("5UTR", FIVE_PRIME_UTR),
+        ("D_loop", D_LOOP),
+        ("five_prime_UTR", FIVE_PRIME_UTR),
+        ("three_prime_UTR", THREE_PRIME_UTR),
+    ];
+
+    /// Returns the standard feature key `kind` is a variant of, ignoring
+    /// case and accepting a few common aliases, e.g. `cds` or `Misc_Feature`
+    pub fn canonical(kind: &str) -> Option<FeatureKind> {
+        ALL.iter()
+            .find(|k| k.as_ref().eq_ignore_ascii_case(kind))
+            .or_else(|| {
+                ALIASES
+                    .iter()
+                    .find(|a| a.0.eq_ignore_ascii_case(kind))
+                    .map(|a| &a.1)
+            })
+            .cloned()
+    }
+}
+
+/// Feature keys used by NCBI in GenPept records, which aren't part of the
+/// INSDC definition. `mat_peptide`, `sig_peptide` etc. are standard.
+pub mod protein_feature_kinds {
+    use super::*;
+
+    pub const BOND: FeatureKind = feature_kind!("Bond");
+    pub const HET: FeatureKind = feature_kind!("Het");
+    pub const NON_STD_RESIDUE: FeatureKind = feature_kind!("non_std_residue");
+    pub const PROTEIN: FeatureKind = feature_kind!("Protein");
+    pub const REGION: FeatureKind = feature_kind!("Region");
+    pub const SEC_STR: FeatureKind = feature_kind!("SecStr");
+    pub const SITE: FeatureKind = feature_kind!("Site");
+
+    pub const ALL: &[FeatureKind] = &[BOND, HET, NON_STD_RESIDUE, PROTEIN, REGION, SEC_STR, SITE];
+}
+
+/// Qualifier keys
+pub mod qualifier_keys {
+    use super::*;
+
+    keys! {
+        QualifierKey, qualifier_key, "qualifier";
+        ALLELE = "allele";
+        ALTITUDE = "altitude";
+        ANTICODON = "anticodon";
+        ARTIFICIAL_LOCATION = "artificial_location";
+        BIO_MATERIAL = "bio_material";
+        BOUND_MOIETY = "bound_moiety";
+        CELL_LINE = "cell_line";
+        CELL_TYPE = "cell_type";
+        CHROMOSOME = "chromosome";
+        CIRCULAR_RNA = "circular_RNA";
+        CITATION = "citation";
+        CLONE = "clone";
+        CLONE_LIB = "clone_lib";
+        CODON_START = "codon_start";
+        COLLECTED_BY = "collected_by";
+        COLLECTION_DATE = "collection_date";
+        COMPARE = "compare";
+        COUNTRY = "country";
+        CULTIVAR = "cultivar";
+        CULTURE_COLLECTION = "culture_collection";
+        DB_XREF = "db_xref";
+        DEV_STAGE = "dev_stage";
+        DIRECTION = "direction";
+        EC_NUMBER = "EC_number";
+        ECOTYPE = "ecotype";
+        ENVIRONMENTAL_SAMPLE = "environmental_sample";
+        ESTIMATED_LENGTH = "estimated_length";
+        EXCEPTION = "exception";
+        EXPERIMENT = "experiment";
+        FOCUS = "focus";
+        FREQUENCY = "frequency";
+        FUNCTION = "function";
+        GAP_TYPE = "gap_type";
+        GENE = "gene";
+        GENE_SYNONYM = "gene_synonym";
+        GEO_LOC_NAME = "geo_loc_name";
+        GERMLINE = "germline";
+        HAPLOGROUP = "haplogroup";
+        HAPLOTYPE = "haplotype";
+        HOST = "host";
+        IDENTIFIED_BY = "identified_by";
+        INFERENCE = "inference";
+        ISOLATE = "isolate";
+        ISOLATION_SOURCE = "isolation_source";
+        LAB_HOST = "lab_host";
+        LAT_LON = "lat_lon";
+        LINKAGE_EVIDENCE = "linkage_evidence";
+        LOCUS_TAG = "locus_tag";
+        MACRONUCLEAR = "macronuclear";
+        MAP = "map";
+        MATING_TYPE = "mating_type";
+        METAGENOME_SOURCE = "metagenome_source";
+        MOBILE_ELEMENT_TYPE = "mobile_element_type";
+        MOD_BASE = "mod_base";
+        MOL_TYPE = "mol_type";
+        NCRNA_CLASS = "ncRNA_class";
+        NOTE = "note";
+        NUMBER = "number";
+        OLD_LOCUS_TAG = "old_locus_tag";
+        OPERON = "operon";
+        ORGANELLE = "organelle";
+        ORGANISM = "organism";
+        PCR_CONDITIONS = "PCR_conditions";
+        PCR_PRIMERS = "PCR_primers";
+        PHENOTYPE = "phenotype";
+        PLASMID = "plasmid";
+        POP_VARIANT = "pop_variant";
+        PRODUCT = "product";
+        PROTEIN_ID = "protein_id";
+        PROVIRAL = "proviral";
+        PSEUDO = "pseudo";
+        PSEUDOGENE = "pseudogene";
+        REARRANGED = "rearranged";
+        RECOMBINATION_CLASS = "recombination_class";
+        REGULATORY_CLASS = "regulatory_class";
+        REPLACE = "replace";
+        RIBOSOMAL_SLIPPAGE = "ribosomal_slippage";
+        RPT_FAMILY = "rpt_family";
+        RPT_TYPE = "rpt_type";
+        RPT_UNIT_RANGE = "rpt_unit_range";
+        RPT_UNIT_SEQ = "rpt_unit_seq";
+        SATELLITE = "satellite";
+        SEGMENT = "segment";
+        SEROTYPE = "serotype";
+        SEROVAR = "serovar";
+        SEX = "sex";
+        SPECIMEN_VOUCHER = "specimen_voucher";
+        STANDARD_NAME = "standard_name";
+        STRAIN = "strain";
+        SUB_CLONE = "sub_clone";
+        SUB_SPECIES = "sub_species";
+        SUB_STRAIN = "sub_strain";
+        SUBMITTER_SEQID = "submitter_seqid";
+        TAG_PEPTIDE = "tag_peptide";
+        TISSUE_LIB = "tissue_lib";
+        TISSUE_TYPE = "tissue_type";
+        TRANS_SPLICING = "trans_splicing";
+        TRANSGENIC = "transgenic";
+        TRANSL_EXCEPT = "transl_except";
+        TRANSL_TABLE = "transl_table";
+        TRANSLATION = "translation";
+        TYPE_MATERIAL = "type_material";
+        VARIETY = "variety";
+    }
+
+    /// Returns true if `key` is one of the qualifiers in the current INSDC
+    /// feature table definition
+    pub fn is_standard(key: &QualifierKey) -> bool {
+        ALL.contains(key)
+    }
+
+    /// Returns the standard qualifier key `key` is a variant of, ignoring
+    /// case, e.g. `Locus_Tag`
+    pub fn canonical(key: &str) -> Option<QualifierKey> {
+        ALL.iter()
+            .find(|k| k.as_ref().eq_ignore_ascii_case(key))
+            .cloned()
+    }
+}
+
+/// The qualifiers a feature key accepts
+#[derive(Debug, PartialEq)]
+pub struct QualifierRules {
+    pub kind: FeatureKind,
+    /// Qualifiers which must be present on every feature of this kind
+    pub mandatory: &'static [QualifierKey],
+    /// Qualifiers which may be present
+    pub optional: &'static [QualifierKey],
+}
+
+impl QualifierRules {
+    /// Returns true if `key` may be used on this feature key. `/citation`
+    /// is accepted on every feature.
+    pub fn is_allowed(&self, key: &QualifierKey) -> bool {
+        *key == qualifier_keys::CITATION || self.is_mandatory(key) || self.optional.contains(key)
+    }
+
+    pub fn is_mandatory(&self, key: &QualifierKey) -> bool {
+        self.mandatory.contains(key)
+    }
+}
+
+macro_rules! rules {
+    ($($kind:ident => [$($m:ident),*], [$($o:ident),* $(,)*];)*) => {
+        /// The qualifier rules for every feature key in the INSDC feature
+        /// table definition
+        pub const QUALIFIER_RULES: &[QualifierRules] = &[
+            $(QualifierRules {
+                kind: feature_kinds::$kind,
+                mandatory: &[$(qualifier_keys::$m),*],
+                optional: &[$(qualifier_keys::$o),*],
+            }),*
+        ];
+    };
+}
+
+rules! {
+    THREE_PRIME_UTR => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, STANDARD_NAME, TRANS_SPLICING
+        ];
+    FIVE_PRIME_UTR => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, STANDARD_NAME, TRANS_SPLICING
+        ];
+    ASSEMBLY_GAP => [ESTIMATED_LENGTH, GAP_TYPE],
+        [
+            LINKAGE_EVIDENCE
+        ];
+    C_REGION => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    CDS => [],
+        [
+            ALLELE, ARTIFICIAL_LOCATION, CIRCULAR_RNA, CODON_START, DB_XREF, EC_NUMBER,
+            EXCEPTION, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG,
+            MAP, NOTE, NUMBER, OLD_LOCUS_TAG, OPERON, PRODUCT, PROTEIN_ID, PSEUDO,
+            PSEUDOGENE, RIBOSOMAL_SLIPPAGE, STANDARD_NAME, TRANS_SPLICING,
+            TRANSL_EXCEPT, TRANSL_TABLE, TRANSLATION
+        ];
+    CENTROMERE => [],
+        [
+            DB_XREF, EXPERIMENT, INFERENCE, NOTE, STANDARD_NAME
+        ];
+    D_LOOP => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG
+        ];
+    D_SEGMENT => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    EXON => [],
+        [
+            ALLELE, DB_XREF, EC_NUMBER, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM,
+            INFERENCE, LOCUS_TAG, MAP, NOTE, NUMBER, OLD_LOCUS_TAG, PRODUCT, PSEUDO,
+            PSEUDOGENE, STANDARD_NAME, TRANS_SPLICING
+        ];
+    GAP => [ESTIMATED_LENGTH],
+        [
+            EXPERIMENT, INFERENCE, MAP, NOTE
+        ];
+    GENE => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, PHENOTYPE, PRODUCT, PSEUDO,
+            PSEUDOGENE, STANDARD_NAME, TRANS_SPLICING
+        ];
+    IDNA => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, NUMBER, OLD_LOCUS_TAG, STANDARD_NAME
+        ];
+    INTRON => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, NUMBER, OLD_LOCUS_TAG, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME, TRANS_SPLICING
+        ];
+    J_SEGMENT => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    MAT_PEPTIDE => [],
+        [
+            ALLELE, DB_XREF, EC_NUMBER, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM,
+            INFERENCE, LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME
+        ];
+    MISC_BINDING => [BOUND_MOIETY],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG
+        ];
+    MISC_DIFFERENCE => [],
+        [
+            ALLELE, CLONE, COMPARE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, PHENOTYPE, REPLACE, STANDARD_NAME
+        ];
+    MISC_FEATURE => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, NUMBER, OLD_LOCUS_TAG, PHENOTYPE, PRODUCT, PSEUDO,
+            PSEUDOGENE, STANDARD_NAME
+        ];
+    MISC_RECOMB => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, RECOMBINATION_CLASS, STANDARD_NAME
+        ];
+    MISC_RNA => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME, TRANS_SPLICING
+        ];
+    MISC_STRUCTURE => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, STANDARD_NAME
+        ];
+    MOBILE_ELEMENT => [MOBILE_ELEMENT_TYPE],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, RPT_FAMILY, RPT_TYPE, STANDARD_NAME
+        ];
+    MODIFIED_BASE => [MOD_BASE],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FREQUENCY, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG
+        ];
+    MRNA => [],
+        [
+            ALLELE, ARTIFICIAL_LOCATION, CIRCULAR_RNA, DB_XREF, EXPERIMENT, FUNCTION,
+            GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON,
+            PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME, TRANS_SPLICING
+        ];
+    N_REGION => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    NCRNA => [NCRNA_CLASS],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME, TRANS_SPLICING
+        ];
+    OLD_SEQUENCE => [],
+        [
+            ALLELE, CITATION, COMPARE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM,
+            INFERENCE, LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, REPLACE
+        ];
+    OPERON => [OPERON],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, INFERENCE, MAP, NOTE, PHENOTYPE,
+            PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    ORIT => [],
+        [
+            ALLELE, BOUND_MOIETY, DB_XREF, DIRECTION, EXPERIMENT, GENE, GENE_SYNONYM,
+            INFERENCE, LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, RPT_FAMILY, RPT_TYPE,
+            RPT_UNIT_RANGE, RPT_UNIT_SEQ, STANDARD_NAME
+        ];
+    POLYA_SITE => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG
+        ];
+    PRECURSOR_RNA => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, PRODUCT, STANDARD_NAME,
+            TRANS_SPLICING
+        ];
+    PRIM_TRANSCRIPT => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, STANDARD_NAME
+        ];
+    PRIMER_BIND => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PCR_CONDITIONS, STANDARD_NAME
+        ];
+    PROPEPTIDE => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME
+        ];
+    PROTEIN_BIND => [BOUND_MOIETY],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, STANDARD_NAME
+        ];
+    REGULATORY => [REGULATORY_CLASS],
+        [
+            ALLELE, BOUND_MOIETY, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM,
+            INFERENCE, LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, PHENOTYPE, PSEUDO,
+            PSEUDOGENE, STANDARD_NAME
+        ];
+    REP_ORIGIN => [],
+        [
+            ALLELE, DB_XREF, DIRECTION, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, STANDARD_NAME
+        ];
+    REPEAT_REGION => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, RPT_FAMILY, RPT_TYPE, RPT_UNIT_RANGE,
+            RPT_UNIT_SEQ, SATELLITE, STANDARD_NAME
+        ];
+    RRNA => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME
+        ];
+    S_REGION => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    SIG_PEPTIDE => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME
+        ];
+    SOURCE => [MOL_TYPE, ORGANISM],
+        [
+            ALTITUDE, BIO_MATERIAL, CELL_LINE, CELL_TYPE, CHROMOSOME, CLONE, CLONE_LIB,
+            COLLECTED_BY, COLLECTION_DATE, COUNTRY, CULTIVAR, CULTURE_COLLECTION,
+            DB_XREF, DEV_STAGE, ECOTYPE, ENVIRONMENTAL_SAMPLE, FOCUS, FREQUENCY,
+            GEO_LOC_NAME, GERMLINE, HAPLOGROUP, HAPLOTYPE, HOST, IDENTIFIED_BY, ISOLATE,
+            ISOLATION_SOURCE, LAB_HOST, LAT_LON, MACRONUCLEAR, MAP, MATING_TYPE,
+            METAGENOME_SOURCE, NOTE, ORGANELLE, PCR_PRIMERS, PLASMID, POP_VARIANT,
+            PROVIRAL, REARRANGED, SEGMENT, SEROTYPE, SEROVAR, SEX, SPECIMEN_VOUCHER,
+            STRAIN, SUB_CLONE, SUB_SPECIES, SUB_STRAIN, SUBMITTER_SEQID, TISSUE_LIB,
+            TISSUE_TYPE, TRANSGENIC, TYPE_MATERIAL, VARIETY
+        ];
+    STEM_LOOP => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, STANDARD_NAME
+        ];
+    STS => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, STANDARD_NAME
+        ];
+    TELOMERE => [],
+        [
+            DB_XREF, EXPERIMENT, INFERENCE, NOTE, RPT_TYPE, RPT_UNIT_RANGE,
+            RPT_UNIT_SEQ, STANDARD_NAME
+        ];
+    TMRNA => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME, TAG_PEPTIDE
+        ];
+    TRANSIT_PEPTIDE => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE,
+            STANDARD_NAME
+        ];
+    TRNA => [],
+        [
+            ALLELE, ANTICODON, DB_XREF, EXPERIMENT, FUNCTION, GENE, GENE_SYNONYM,
+            INFERENCE, LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, OPERON, PRODUCT, PSEUDO,
+            PSEUDOGENE, STANDARD_NAME, TRANS_SPLICING
+        ];
+    UNSURE => [],
+        [
+            ALLELE, COMPARE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE,
+            LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, REPLACE
+        ];
+    V_REGION => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    V_SEGMENT => [],
+        [
+            ALLELE, DB_XREF, EXPERIMENT, GENE, GENE_SYNONYM, INFERENCE, LOCUS_TAG, MAP,
+            NOTE, OLD_LOCUS_TAG, PRODUCT, PSEUDO, PSEUDOGENE, STANDARD_NAME
+        ];
+    VARIATION => [],
+        [
+            ALLELE, COMPARE, DB_XREF, EXPERIMENT, FREQUENCY, GENE, GENE_SYNONYM,
+            INFERENCE, LOCUS_TAG, MAP, NOTE, OLD_LOCUS_TAG, PHENOTYPE, PRODUCT, REPLACE,
+            STANDARD_NAME
+        ];
+}
+
+/// Looks up which qualifiers are legal and mandatory on `kind`, returns
+/// `None` for nonstandard feature keys
+pub fn qualifier_rules(kind: &FeatureKind) -> Option<&'static QualifierRules> {
+    QUALIFIER_RULES.iter().find(|r| r.kind == *kind)
+}
+
+/// The namespace of a nonstandard qualifier key, which by convention is the
+/// part before the first `_`, e.g. `ApEinfo` for `ApEinfo_fwdcolor`
+pub fn qualifier_prefix(key: &str) -> &str {
+    key.split('_').next().unwrap()
+}
+
+impl Feature {
+    /// Qualifiers whose keys aren't in the INSDC feature table definition
+    pub fn nonstandard_qualifiers(&self) -> impl Iterator<Item = &(QualifierKey, Option<String>)> {
+        self.qualifiers
+            .iter()
+            .filter(|q| !qualifier_keys::is_standard(&q.0))
+    }
+
+    /// Nonstandard qualifiers in the namespace `prefix`, see
+    /// `qualifier_prefix`
+    pub fn vendor_qualifiers<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = &'a (QualifierKey, Option<String>)> {
+        self.nonstandard_qualifiers()
+            .filter(move |q| qualifier_prefix(&q.0) == prefix)
+    }
+}
+
+impl Seq {
+    /// Groups the distinct nonstandard qualifier keys used by any feature by
+    /// their prefix, e.g. `ApEinfo` => [`ApEinfo_fwdcolor`, `ApEinfo_revcolor`]
+    pub fn vendor_qualifier_groups(&self) -> BTreeMap<String, Vec<QualifierKey>> {
+        let mut groups: BTreeMap<String, Vec<QualifierKey>> = BTreeMap::new();
+        for f in &self.features {
+            for (key, _) in f.nonstandard_qualifiers() {
+                let keys = groups.entry(qualifier_prefix(key).to_string()).or_default();
+                if !keys.contains(key) {
+                    keys.push(key.clone());
+                }
+            }
+        }
+        for keys in groups.values_mut() {
+            keys.sort();
+        }
+        groups
+    }
+
+    /// Removes the nonstandard qualifiers in the namespace `prefix` from
+    /// every feature, returning how many were removed
+    pub fn strip_vendor_qualifiers(&mut self, prefix: &str) -> usize {
+        self.strip_qualifiers_where(|key| qualifier_prefix(key) == prefix)
+    }
+
+    /// Removes every nonstandard qualifier, e.g. before submission,
+    /// returning how many were removed
+    pub fn strip_nonstandard_qualifiers(&mut self) -> usize {
+        self.strip_qualifiers_where(|_| true)
+    }
+
+    fn strip_qualifiers_where<F: Fn(&QualifierKey) -> bool>(&mut self, strip: F) -> usize {
+        let mut removed = 0;
+        for f in &mut self.features {
+            let before = f.qualifiers.len();
+            f.qualifiers
+                .retain(|(key, _)| qualifier_keys::is_standard(key) || !strip(key));
+            removed += before - f.qualifiers.len();
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::feature_kinds::{self, *};
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        assert_eq!(CDS, feature_kind!("CDS"));
+        assert_eq!(&*THREE_PRIME_UTR, "3'UTR");
+        assert_eq!(&*D_LOOP, "D-loop");
+        let kind = feature_kind!("gene");
+        match kind {
+            CDS => panic!(),
+            GENE => {}
+            _ => panic!(),
+        }
+        assert!(feature_kinds::is_standard(&MRNA));
+        assert!(!feature_kinds::is_standard(&"promoter".into()));
+        assert!(!feature_kinds::is_standard(&"my_feature".into()));
+        assert_eq!(feature_kinds::canonical("cds"), Some(CDS));
+        assert_eq!(feature_kinds::canonical("Misc_Feature"), Some(MISC_FEATURE));
+        assert_eq!(feature_kinds::canonical("5utr"), Some(FIVE_PRIME_UTR));
+        assert_eq!(feature_kinds::canonical("promoter"), None);
+        assert_eq!(
+            qualifier_keys::canonical("Locus_Tag"),
+            Some(qualifier_keys::LOCUS_TAG)
+        );
+    }
+
+    #[test]
+    fn rules() {
+        assert_eq!(QUALIFIER_RULES.len(), feature_kinds::ALL.len());
+        for kind in feature_kinds::ALL {
+            let rules = qualifier_rules(kind).unwrap();
+            for key in rules.mandatory.iter().chain(rules.optional) {
+                assert!(qualifier_keys::is_standard(key));
+            }
+        }
+        let source = qualifier_rules(&SOURCE).unwrap();
+        assert!(source.is_mandatory(&qualifier_keys::ORGANISM));
+        assert!(source.is_allowed(&qualifier_keys::STRAIN));
+        assert!(!source.is_allowed(&qualifier_keys::GENE));
+        let cds = qualifier_rules(&CDS).unwrap();
+        assert!(cds.mandatory.is_empty());
+        assert!(cds.is_allowed(&qualifier_keys::TRANSLATION));
+        assert!(cds.is_allowed(&qualifier_keys::CITATION));
+        assert!(!cds.is_allowed(&qualifier_keys::ORGANISM));
+        assert!(qualifier_rules(&"promoter".into()).is_none());
+        assert!(!qualifier_keys::is_standard(&qualifier_key!(
+            "ApEinfo_fwdcolor"
+        )));
+    }
+
+    #[test]
+    fn vendor_qualifiers() {
+        let mut seq = Seq::empty();
+        seq.features.push(Feature::new(
+            CDS,
+            crate::seq::Location::simple_range(0, 3),
+            vec![
+                (qualifier_keys::GENE, Some("a".into())),
+                ("ApEinfo_revcolor".into(), Some("#000".into())),
+                ("ApEinfo_fwdcolor".into(), Some("#fff".into())),
+                ("SnapGene_feature_type".into(), Some("CDS".into())),
+            ],
+        ));
+        seq.features.push(seq.features[0].clone());
+        let groups = seq.vendor_qualifier_groups();
+        assert_eq!(
+            groups.keys().collect::<Vec<_>>(),
+            vec!["ApEinfo", "SnapGene"]
+        );
+        assert_eq!(
+            groups["ApEinfo"],
+            vec![
+                qualifier_key!("ApEinfo_fwdcolor"),
+                qualifier_key!("ApEinfo_revcolor")
+            ]
+        );
+        assert_eq!(seq.features[0].vendor_qualifiers("SnapGene").count(), 1);
+        assert_eq!(seq.strip_vendor_qualifiers("ApEinfo"), 4);
+        assert_eq!(seq.strip_vendor_qualifiers("gene"), 0);
+        assert_eq!(seq.strip_nonstandard_qualifiers(), 2);
+        assert_eq!(
+            seq.features[0].qualifiers,
+            vec![(qualifier_keys::GENE, Some("a".into()))]
+        );
+    }
+}
@@ -0,0 +1,513 @@
+//! Reading INSDSeq XML, as returned by NCBI's efetch with
+//! `rettype=gb&retmode=xml`, into the same `Seq` as GenBank records.
+//! Enabled by the `insdseq` feature.
+
+use std::io::BufRead;
+use std::str;
+
+use crate::embl::parse_date;
+use crate::errors::GbParserError;
+use crate::seq::{
+    Alphabet, Feature, Location, MoleculeType, Reference, Seq, Source, Topology,
+};
+
+/// Reads the `INSDSeq` elements of an `INSDSet` one at a time, so only one
+/// record is held in memory.
+///
+/// Values are kept as NCBI gives them, so unlike in flat files the
+/// definition, source and keywords don't end in a full stop. Keywords are
+/// joined with `; `, the organism is followed by the taxonomy on the next
+/// line as in GenBank's ORGANISM, and the `~` marking line breaks in
+/// comments is replaced by a newline. DBLINK is made from `INSDSeq_xrefs`,
+/// e.g. `BioProject: PRJNA1`.
+#[derive(Debug)]
+pub struct InsdSeqReader<T: BufRead> {
+    input: T,
+    buffer: Vec<u8>,
+}
+
+impl<T: BufRead> InsdSeqReader<T> {
+    pub fn new(input: T) -> InsdSeqReader<T> {
+        InsdSeqReader {
+            input,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<Seq>, GbParserError> {
+        self.buffer.clear();
+        let start = loop {
+            if self.input.read_until(b'>', &mut self.buffer)? == 0 {
+                return match find(&self.buffer, b"<INSDSeq>") {
+                    Some(_) => Err(error("Unexpected end of file")),
+                    None => Ok(None),
+                };
+            }
+            if self.buffer.ends_with(b"</INSDSeq>") {
+                break find(&self.buffer, b"<INSDSeq>")
+                    .ok_or_else(|| error("Unexpected </INSDSeq>"))?;
+            }
+        };
+        let xml = str::from_utf8(&self.buffer[start..]).map_err(|e| error(&e.to_string()))?;
+        let element = parse_element(xml).map_err(|e| error(&e))?;
+        record(&element).map(Some).map_err(|e| error(&e))
+    }
+}
+
+impl<T: BufRead> Iterator for InsdSeqReader<T> {
+    type Item = Result<Seq, GbParserError>;
+
+    fn next(&mut self) -> Option<Result<Seq, GbParserError>> {
+        self.read_record().transpose()
+    }
+}
+
+/// Parse a whole INSDSeq XML document, see `InsdSeqReader`
+pub fn parse_insdseq(data: &[u8]) -> Result<Vec<Seq>, GbParserError> {
+    InsdSeqReader::new(data).collect()
+}
+
+fn error(message: &str) -> GbParserError {
+    GbParserError::SyntaxError(format!("INSDSeq: {}", message))
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Builds a `Seq` from an `INSDSeq` element
+fn record(e: &Element) -> Result<Seq, String> {
+    let text = |name| e.text_of(name);
+    let mut seq = Seq::empty();
+    seq.name = text("INSDSeq_locus");
+    seq.len = match text("INSDSeq_length") {
+        Some(l) => Some(l.parse().map_err(|_| format!("Invalid length: {}", l))?),
+        None => None,
+    };
+    let strandedness = text("INSDSeq_strandedness");
+    match text("INSDSeq_moltype") {
+        Some(ref m) if m == "AA" => seq.alphabet = Alphabet::Protein,
+        m => seq.molecule_type = m.map(|m| molecule_type(&m, strandedness.as_deref())),
+    }
+    if text("INSDSeq_topology").as_deref() == Some("circular") {
+        seq.topology = Topology::Circular;
+    }
+    if let Some(d) = text("INSDSeq_division") {
+        seq.division = d.as_str().into();
+    }
+    seq.date = text("INSDSeq_update-date").and_then(|d| parse_date(&d));
+    seq.definition = text("INSDSeq_definition");
+    if let Some(primary) = text("INSDSeq_primary-accession") {
+        let secondary = e.texts("INSDSeq_secondary-accessions", "INSDSecondary-no");
+        let accessions: Vec<_> = std::iter::once(primary).chain(secondary).collect();
+        seq.accession = Some(accessions.join(" "));
+    }
+    seq.version = text("INSDSeq_accession-version");
+    let keywords = e.texts("INSDSeq_keywords", "INSDKeyword");
+    if !keywords.is_empty() {
+        seq.keywords = Some(keywords.join("; "));
+    }
+    seq.segment = text("INSDSeq_segment");
+    if let Some(source) = text("INSDSeq_source") {
+        let organism = text("INSDSeq_organism").map(|o| match text("INSDSeq_taxonomy") {
+            Some(t) => format!("{}\n{}", o, t),
+            None => o,
+        });
+        seq.source = Some(Source { source, organism });
+    }
+    if let Some(references) = e.child("INSDSeq_references") {
+        for r in references.children_named("INSDReference") {
+            seq.references.push(reference(r, seq.alphabet));
+        }
+    }
+    seq.comments
+        .extend(text("INSDSeq_comment").map(|c| c.replace('~', "\n")));
+    seq.dbsource = text("INSDSeq_source-db");
+    seq.dblink = dblink(e);
+    if let Some(table) = e.child("INSDSeq_feature-table") {
+        for f in table.children_named("INSDFeature") {
+            seq.features.push(feature(f)?);
+        }
+    }
+    if let Some(contig) = text("INSDSeq_contig") {
+        let location = Location::from_gb_format(&contig)
+            .map_err(|e| format!("Invalid contig {}: {}", contig, e))?;
+        seq.contig = Some(location);
+    }
+    if let Some(s) = text("INSDSeq_sequence") {
+        seq.seq = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    }
+    if !seq.seq.is_empty() && seq.len != Some(seq.seq.len()) {
+        return Err(format!(
+            "INSDSeq_length is {} but the sequence is {} long",
+            seq.len.unwrap_or(0),
+            seq.seq.len()
+        ));
+    }
+    Ok(seq)
+}
+
+/// `moltype` with the strandedness as a prefix, e.g. `ss-DNA`, for the types
+/// which can have one in a LOCUS line (`DNA` and `RNA`)
+fn molecule_type(moltype: &str, strandedness: Option<&str>) -> MoleculeType {
+    let prefix = match strandedness {
+        Some("single") => "ss-",
+        Some("double") => "ds-",
+        Some("mixed") => "ms-",
+        _ => "",
+    };
+    match moltype {
+        "DNA" | "RNA" => format!("{}{}", prefix, moltype).as_str().into(),
+        m => m.into(),
+    }
+}
+
+fn reference(r: &Element, alphabet: Alphabet) -> Reference {
+    let text = |name| r.text_of(name);
+    let number = text("INSDReference_reference").unwrap_or_default();
+    let number = number.split_whitespace().next().unwrap_or_default();
+    let position = text("INSDReference_position");
+    let bases = position
+        .iter()
+        .flat_map(|p| p.split(';'))
+        .filter_map(|range| {
+            let (a, b) = range.trim().split_once("..")?;
+            Some((a.parse::<i64>().ok()? - 1, b.parse().ok()?))
+        })
+        .collect();
+    let authors = r.texts("INSDReference_authors", "INSDAuthor");
+    let authors = match authors.split_last() {
+        None => None,
+        Some((last, [])) => Some(last.clone()),
+        Some((last, rest)) => Some(format!("{} and {}", rest.join(", "), last)),
+    };
+    let mut reference = Reference {
+        description: number.to_string(),
+        bases,
+        authors,
+        consortium: text("INSDReference_consortium"),
+        title: text("INSDReference_title").unwrap_or_default(),
+        journal: text("INSDReference_journal"),
+        pubmed: text("INSDReference_pubmed"),
+        remark: text("INSDReference_remark"),
+    };
+    reference.description = reference.description_with_bases(alphabet);
+    reference
+}
+
+/// DBLINK lines from the cross-references, with the ids for each database
+/// on one line
+fn dblink(e: &Element) -> Option<String> {
+    let mut lines: Vec<(String, Vec<String>)> = Vec::new();
+    for x in e.child("INSDSeq_xrefs")?.children_named("INSDXref") {
+        let (db, id) = match (x.text_of("INSDXref_dbname"), x.text_of("INSDXref_id")) {
+            (Some(db), Some(id)) => (db, id),
+            _ => continue,
+        };
+        match lines.last_mut() {
+            Some((last, ids)) if *last == db => ids.push(id),
+            _ => lines.push((db, vec![id])),
+        }
+    }
+    let lines: Vec<_> = lines
+        .into_iter()
+        .map(|(db, ids)| format!("{}: {}", db, ids.join(", ")))
+        .collect();
+    Some(lines.join("\n")).filter(|l| !l.is_empty())
+}
+
+fn feature(f: &Element) -> Result<Feature, String> {
+    let kind = f
+        .text_of("INSDFeature_key")
+        .ok_or("Feature without INSDFeature_key")?;
+    let location = f
+        .text_of("INSDFeature_location")
+        .ok_or("Feature without INSDFeature_location")?;
+    let location = Location::from_gb_format(&location)
+        .map_err(|e| format!("Invalid location {}: {}", location, e))?;
+    let mut qualifiers = Vec::new();
+    if let Some(quals) = f.child("INSDFeature_quals") {
+        for q in quals.children_named("INSDQualifier") {
+            if let Some(name) = q.text_of("INSDQualifier_name") {
+                qualifiers.push((name.as_str().into(), q.text_of("INSDQualifier_value")));
+            }
+        }
+    }
+    Ok(Feature::new(kind.as_str().into(), location, qualifiers))
+}
+
+/// An XML element, with only as much of XML as INSDSeq uses: attributes
+/// are skipped, and text is only kept for elements without children
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// The text of the child called `name`, unless it's empty
+    fn text_of(&self, name: &str) -> Option<String> {
+        let text = self.child(name)?.text.trim();
+        Some(text.to_string()).filter(|t| !t.is_empty())
+    }
+
+    /// The text of each `item` in the child called `list`
+    fn texts(&self, list: &str, item: &str) -> Vec<String> {
+        self.child(list)
+            .map(|l| {
+                l.children_named(item)
+                    .map(|i| i.text.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Parses the first element of `xml`, skipping anything before it
+fn parse_element(mut xml: &str) -> Result<Element, String> {
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        let lt = xml.find('<').ok_or("Unexpected end of XML")?;
+        if let Some(e) = stack.last_mut() {
+            e.text.push_str(&unescape(&xml[..lt])?);
+        }
+        xml = &xml[lt..];
+        let skip = |xml: &str, end: &str| {
+            xml.find(end)
+                .map(|i| i + end.len())
+                .ok_or_else(|| format!("Missing {}", end))
+        };
+        if xml.starts_with("<?") {
+            xml = &xml[skip(xml, "?>")?..];
+        } else if xml.starts_with("<!--") {
+            xml = &xml[skip(xml, "-->")?..];
+        } else if let Some(rest) = xml.strip_prefix("<![CDATA[") {
+            let end = rest.find("]]>").ok_or("Missing ]]>")?;
+            if let Some(e) = stack.last_mut() {
+                e.text.push_str(&rest[..end]);
+            }
+            xml = &rest[end + 3..];
+        } else if xml.starts_with("<!") {
+            xml = &xml[skip(xml, ">")?..];
+        } else {
+            let gt = skip(xml, ">")?;
+            let tag = &xml[1..gt - 1];
+            xml = &xml[gt..];
+            if let Some(name) = tag.strip_prefix('/') {
+                let e = stack
+                    .pop()
+                    .ok_or_else(|| format!("Unexpected </{}>", name))?;
+                if e.name != name.trim() {
+                    return Err(format!("Expected </{}>, found </{}>", e.name, name));
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(e),
+                    None => return Ok(e),
+                }
+            } else {
+                let empty = tag.ends_with('/');
+                let name = tag
+                    .trim_end_matches('/')
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default();
+                let e = Element {
+                    name: name.to_string(),
+                    ..Element::default()
+                };
+                match (empty, stack.last_mut()) {
+                    (false, _) => stack.push(e),
+                    (true, Some(parent)) => parent.children.push(e),
+                    (true, None) => return Ok(e),
+                }
+            }
+        }
+    }
+}
+
+/// Replaces entity and character references
+fn unescape(text: &str) -> Result<String, String> {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        res.push_str(&rest[..amp]);
+        let semi = rest[amp..].find(';').ok_or("Unterminated entity")? + amp;
+        let entity = &rest[amp + 1..semi];
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|d| d.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+                    .ok_or_else(|| format!("Unknown entity &{};", entity))?
+            }
+        };
+        res.push(c);
+        rest = &rest[semi + 1..];
+    }
+    res.push_str(rest);
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Date;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"  ?>
+<!DOCTYPE INSDSet PUBLIC "-//NCBI//INSD INSDSeq/EN" "INSD_INSDSeq.dtd">
+<INSDSet>
+  <INSDSeq>
+    <INSDSeq_locus>ATCOR66M</INSDSeq_locus>
+    <INSDSeq_length>20</INSDSeq_length>
+    <INSDSeq_strandedness>single</INSDSeq_strandedness>
+    <INSDSeq_moltype>mRNA</INSDSeq_moltype>
+    <INSDSeq_topology>linear</INSDSeq_topology>
+    <INSDSeq_division>PLN</INSDSeq_division>
+    <INSDSeq_update-date>02-MAR-1992</INSDSeq_update-date>
+    <INSDSeq_definition>A.thaliana cor6.6 mRNA</INSDSeq_definition>
+    <INSDSeq_primary-accession>X55053</INSDSeq_primary-accession>
+    <INSDSeq_accession-version>X55053.1</INSDSeq_accession-version>
+    <INSDSeq_keywords>
+      <INSDKeyword>antifreeze protein homology</INSDKeyword>
+      <INSDKeyword>cor6.6 gene</INSDKeyword>
+    </INSDSeq_keywords>
+    <INSDSeq_source>thale cress</INSDSeq_source>
+    <INSDSeq_organism>Arabidopsis thaliana</INSDSeq_organism>
+    <INSDSeq_taxonomy>Eukaryota; Viridiplantae</INSDSeq_taxonomy>
+    <INSDSeq_references>
+      <INSDReference>
+        <INSDReference_reference>1</INSDReference_reference>
+        <INSDReference_position>1..20</INSDReference_position>
+        <INSDReference_authors>
+          <INSDAuthor>Gilmour,S.J.</INSDAuthor>
+          <INSDAuthor>Artus,N.N.</INSDAuthor>
+          <INSDAuthor>Thomashow,M.F.</INSDAuthor>
+        </INSDReference_authors>
+        <INSDReference_title>cDNA sequence analysis &amp; expression</INSDReference_title>
+        <INSDReference_journal>Plant Mol. Biol. 18 (1), 13-21 (1992)</INSDReference_journal>
+        <INSDReference_pubmed>1731964</INSDReference_pubmed>
+      </INSDReference>
+    </INSDSeq_references>
+    <INSDSeq_comment>Cor6.6 homologous to KIN1.~Second line.</INSDSeq_comment>
+    <INSDSeq_xrefs>
+      <INSDXref>
+        <INSDXref_dbname>BioProject</INSDXref_dbname>
+        <INSDXref_id>PRJNA1</INSDXref_id>
+      </INSDXref>
+    </INSDSeq_xrefs>
+    <INSDSeq_feature-table>
+      <INSDFeature>
+        <INSDFeature_key>CDS</INSDFeature_key>
+        <INSDFeature_location>&lt;1..>20</INSDFeature_location>
+        <INSDFeature_partial5 value="true"/>
+        <INSDFeature_quals>
+          <INSDQualifier>
+            <INSDQualifier_name>gene</INSDQualifier_name>
+            <INSDQualifier_value>cor6.6</INSDQualifier_value>
+          </INSDQualifier>
+          <INSDQualifier>
+            <INSDQualifier_name>pseudo</INSDQualifier_name>
+          </INSDQualifier>
+        </INSDFeature_quals>
+      </INSDFeature>
+    </INSDSeq_feature-table>
+    <INSDSeq_sequence>aacaaaacacacatcaaaaa</INSDSeq_sequence>
+  </INSDSeq>
+  <INSDSeq>
+    <INSDSeq_locus>P1</INSDSeq_locus>
+    <INSDSeq_length>4</INSDSeq_length>
+    <INSDSeq_moltype>AA</INSDSeq_moltype>
+    <INSDSeq_references>
+      <INSDReference>
+        <INSDReference_reference>1</INSDReference_reference>
+        <INSDReference_position>1..4</INSDReference_position>
+        <INSDReference_title>A protein</INSDReference_title>
+      </INSDReference>
+    </INSDSeq_references>
+    <INSDSeq_sequence>mkvl</INSDSeq_sequence>
+  </INSDSeq>
+</INSDSet>
+"#;
+
+    #[test]
+    fn read_insdseq() {
+        let seqs = parse_insdseq(XML.as_bytes()).unwrap();
+        assert_eq!(seqs.len(), 2);
+        let seq = &seqs[0];
+        assert_eq!(seq.name.as_deref(), Some("ATCOR66M"));
+        assert_eq!(seq.len, Some(20));
+        assert_eq!(seq.molecule_type, Some("mRNA".into()));
+        let moltype = |strandedness: &str, moltype: &str| {
+            let xml = XML
+                .replace(">single<", &format!(">{}<", strandedness))
+                .replace(">mRNA<", &format!(">{}<", moltype));
+            parse_insdseq(xml.as_bytes()).unwrap()[0].molecule_type.clone()
+        };
+        assert_eq!(moltype("single", "DNA"), Some(MoleculeType::SsDna));
+        assert_eq!(moltype("double", "DNA"), Some(MoleculeType::DsDna));
+        assert_eq!(moltype("mixed", "RNA"), Some(MoleculeType::MsRna));
+        assert_eq!(moltype("single", "RNA"), Some(MoleculeType::SsRna));
+        assert_eq!(moltype("not-set", "DNA"), Some(MoleculeType::Dna));
+        assert_eq!(moltype("double", "mRNA"), Some(MoleculeType::Mrna));
+        assert_eq!(seq.date, Some(Date::from_ymd(1992, 3, 2).unwrap()));
+        assert_eq!(seq.accession.as_deref(), Some("X55053"));
+        assert_eq!(seq.version.as_deref(), Some("X55053.1"));
+        assert_eq!(
+            seq.keywords.as_deref(),
+            Some("antifreeze protein homology; cor6.6 gene")
+        );
+        let source = seq.source.as_ref().unwrap();
+        assert_eq!(
+            source.organism.as_deref(),
+            Some("Arabidopsis thaliana\nEukaryota; Viridiplantae")
+        );
+        let r = &seq.references[0];
+        assert_eq!(r.description, "1  (bases 1 to 20)");
+        assert_eq!(r.bases, vec![(0, 20)]);
+        assert_eq!(
+            r.authors.as_deref(),
+            Some("Gilmour,S.J., Artus,N.N. and Thomashow,M.F.")
+        );
+        assert_eq!(r.title, "cDNA sequence analysis & expression");
+        assert_eq!(r.pubmed.as_deref(), Some("1731964"));
+        assert_eq!(
+            seq.comments,
+            vec!["Cor6.6 homologous to KIN1.\nSecond line."]
+        );
+        assert_eq!(seq.dblink.as_deref(), Some("BioProject: PRJNA1"));
+        let f = &seq.features[0];
+        assert_eq!(f.location.to_gb_format(), "<1..>20");
+        assert_eq!(
+            f.qualifiers,
+            vec![
+                (qualifier_key!("gene"), Some("cor6.6".to_string())),
+                (qualifier_key!("pseudo"), None),
+            ]
+        );
+        assert_eq!(seq.seq, b"aacaaaacacacatcaaaaa");
+
+        let protein = &seqs[1];
+        assert!(protein.is_protein());
+        assert_eq!(protein.references[0].description, "1  (residues 1 to 4)");
+
+        assert!(parse_insdseq(&XML.as_bytes()[..XML.len() / 2]).is_err());
+        let wrong = XML.replace("<INSDSeq_length>4<", "<INSDSeq_length>5<");
+        assert!(parse_insdseq(wrong.as_bytes()).is_err());
+    }
+}
@@ -4,10 +4,12 @@
 //! annotations and respecting circular molecules.
 
 // #![warn(missing_docs)]
-#![cfg_attr(feature = "cargo-clippy", allow(clippy::len_without_is_empty))]
-#![cfg_attr(feature = "cargo-clippy", allow(clippy::useless_format))]
-#![cfg_attr(feature = "cargo-clippy", allow(clippy::unreadable_literal))]
-#![cfg_attr(feature = "cargo-clippy", allow(clippy::upper_case_acronyms))]
+#![allow(clippy::len_without_is_empty)]
+#![allow(clippy::useless_format)]
+#![allow(clippy::unreadable_literal)]
+#![allow(clippy::upper_case_acronyms)]
+// err-derive generates its impls inside an anonymous const
+#![allow(non_local_definitions)]
 #![recursion_limit = "128"]
 extern crate circular;
 #[macro_use]
@@ -17,6 +19,16 @@ extern crate itertools;
 extern crate log;
 #[macro_use]
 extern crate nom;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "time")]
+extern crate time;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 #[cfg(feature = "serde")]
 #[macro_use]
 extern crate serde;
@@ -29,10 +41,38 @@ include!(concat!(env!("OUT_DIR"), "/atoms.rs")); // for QualifierKey, FeatureKin
 pub mod seq;
 pub mod reader;
 pub mod writer;
+pub mod display;
+pub mod edit;
+pub mod stats;
+pub mod diagnostics;
+pub mod metadata;
+pub mod comment;
+pub mod insdc;
+pub mod so;
+pub mod translate;
+pub mod fasta;
+pub mod tbl;
+pub mod embl;
+#[cfg(feature = "insdseq")]
+pub mod insdseq;
+pub mod record_set;
+pub mod remap;
+pub mod sites;
+pub mod golden_gate;
+pub mod rotation;
+pub mod gene_model;
+pub mod coord;
+pub mod validate;
+pub mod format;
+pub mod sanitize;
+pub mod builder;
+pub mod overlap;
+pub mod index;
 mod dna;
 
 #[cfg(test)]
 pub mod tests {
+    use crate::diagnostics::Diagnostic;
     use crate::errors::GbParserError;
     use crate::reader::*;
     use crate::seq::*;
@@ -116,6 +156,798 @@ pub mod tests {
         assert_eq!(ls_orchid.len(), 94);
     }
 
+    #[test]
+    fn slice_and_streaming_agree() {
+        init();
+        for f in glob("tests/biopython_tests/*.gb").unwrap() {
+            let f = f.unwrap();
+            let data = ::std::fs::read(&f).unwrap();
+            let streamed = SeqReader::new(&data[..]).collect::<Result<Vec<_>, _>>();
+            // the slice parser isn't as lenient, only compare where it succeeds
+            if let (Ok(streamed), Ok(sliced)) = (streamed, parse_slice(&data)) {
+                assert_eq!(streamed, sliced, "{:?}", f);
+            }
+        }
+    }
+
+    #[test]
+    fn handler_matches_reader() {
+        #[derive(Default)]
+        struct Counter {
+            records: usize,
+            features: usize,
+            bases: usize,
+            names: Vec<Option<String>>,
+        }
+        impl Handler for Counter {
+            fn on_locus(&mut self, header: Seq) {
+                assert!(header.features.is_empty());
+                self.names.push(header.name);
+            }
+            fn on_feature(&mut self, _feature: Feature) {
+                self.features += 1;
+            }
+            fn on_sequence_chunk(&mut self, chunk: &[u8]) {
+                self.bases += chunk.len();
+            }
+            fn on_record_end(&mut self) {
+                self.records += 1;
+            }
+        }
+        init();
+        for f in glob("tests/biopython_tests/*.gb").unwrap() {
+            let f = f.unwrap();
+            let data = ::std::fs::read(&f).unwrap();
+            let seqs = match SeqReader::new(&data[..]).collect::<Result<Vec<_>, _>>() {
+                Ok(seqs) => seqs,
+                Err(_) => continue,
+            };
+            let mut counter = Counter::default();
+            SeqReader::new(&data[..]).parse_with(&mut counter).unwrap();
+            assert_eq!(counter.records, seqs.len(), "{:?}", f);
+            let features: usize = seqs.iter().map(|s| s.features.len()).sum();
+            assert_eq!(counter.features, features, "{:?}", f);
+            let bases: usize = seqs.iter().map(|s| s.seq.len()).sum();
+            assert_eq!(counter.bases, bases, "{:?}", f);
+            let names: Vec<_> = seqs.into_iter().map(|s| s.name).collect();
+            assert_eq!(counter.names, names, "{:?}", f);
+        }
+    }
+
+    #[test]
+    fn tokens_match_reader() {
+        init();
+        for f in glob("tests/biopython_tests/*.gb").unwrap() {
+            let f = f.unwrap();
+            let data = ::std::fs::read(&f).unwrap();
+            let seqs = match SeqReader::new(&data[..]).collect::<Result<Vec<_>, _>>() {
+                Ok(seqs) => seqs,
+                Err(_) => continue,
+            };
+            let mut rebuilt: Vec<(Option<String>, Vec<Feature>, Vec<u8>)> = Vec::new();
+            for t in TokenReader::new(&data[..]) {
+                match t.unwrap() {
+                    Token::Locus(l) => rebuilt.push((l.name, Vec::new(), Vec::new())),
+                    Token::FeatureStart { kind, location } => rebuilt
+                        .last_mut()
+                        .unwrap()
+                        .1
+                        .push(Feature::new(kind, location, Vec::new())),
+                    Token::Qualifier { key, value } => {
+                        let features = &mut rebuilt.last_mut().unwrap().1;
+                        features.last_mut().unwrap().qualifiers.push((key, value));
+                    }
+                    Token::Sequence(s) => rebuilt.last_mut().unwrap().2.extend(s),
+                    _ => {}
+                }
+            }
+            assert_eq!(rebuilt.len(), seqs.len(), "{:?}", f);
+            for (r, s) in rebuilt.into_iter().zip(seqs) {
+                assert_eq!(r, (s.name, s.features, s.seq), "{:?}", f);
+            }
+        }
+    }
+
+    #[test]
+    fn diagnostics_per_record() {
+        let data = b"Some preamble\n\
+LOCUS       a                          4 bp    DNA     linear   UNK 01-JAN-2020
+ORIGIN
+        1 acgt
+//
+LOCUS       b                          4 bp    DNA     linear   UNK 01-JAN-2020
+ORIGIN
+        1 acg
+//
+";
+        let report = SeqReader::new(&data[..]).read_all_with_diagnostics();
+        assert!(!report.is_ok());
+        assert_eq!(report.records.len(), 2);
+        assert_eq!(
+            report.records[0].warnings,
+            vec![Diagnostic::new("Ignoring line: Some preamble")]
+        );
+        assert!(report.records[0].error.is_none());
+        assert!(report.records[1].seq.is_none());
+        assert!(report.records[1].error.is_some());
+        assert_eq!(report.seqs().count(), 1);
+    }
+
+    #[test]
+    fn lenient_ending() {
+        let record = "LOCUS       a                          8 bp    DNA     linear   UNK 01-JAN-2020
+FEATURES             Location/Qualifiers
+     gene            1..3
+ORIGIN
+        1 acgtacgt
+";
+        let parse = |data: &str| {
+            let mut reader = SeqReader::new(data.as_bytes());
+            reader.lenient_ending(true);
+            reader.read_all_with_diagnostics()
+        };
+        let junk = format!("{}//\ngarbage\n\0\0", record);
+        assert!(SeqReader::new(junk.as_bytes()).read_all_with_diagnostics().records[1]
+            .error
+            .is_some());
+        let report = parse(&junk);
+        assert!(report.is_ok());
+        assert_eq!(report.records.len(), 1);
+        assert_eq!(
+            report.warnings,
+            vec![Diagnostic::new("Ignoring 10 bytes of trailing data: [garbage\n\0\0]")]
+        );
+
+        let truncated = format!("{}//\n{}", record, &record[..record.len() - 5]);
+        assert!(SeqReader::new(truncated.as_bytes()).next().unwrap().is_ok());
+        assert!(SeqReader::new(truncated.as_bytes()).nth(1).unwrap().is_err());
+        let report = parse(&truncated);
+        assert!(report.is_ok());
+        assert_eq!(report.records.len(), 2);
+        assert_eq!(report.records[1].seq.as_ref().unwrap().seq, b"acgt");
+        assert_eq!(report.records[1].warnings.len(), 2);
+
+        let cut = &record[..record.find("ORIGIN").unwrap() - 1];
+        let report = parse(cut);
+        assert!(report.is_ok());
+        assert_eq!(report.records[0].seq.as_ref().unwrap().features.len(), 1);
+    }
+
+    #[test]
+    fn error_position() {
+        let data = b"LOCUS       a                          8 bp    DNA     linear   UNK 01-JAN-2020
+ORIGIN
+        1 acgt
+        5 ac!t
+//
+";
+        match SeqReader::new(&data[..]).next().unwrap() {
+            Err(GbParserError::SyntaxErrorAt {
+                line,
+                offset,
+                snippet,
+                ..
+            }) => {
+                assert_eq!(line, 4);
+                assert_eq!(offset as usize, data.iter().position(|&b| b == b'!').unwrap());
+                assert_eq!(snippet, "!t");
+            }
+            r => panic!("{:?}", r),
+        }
+        let e = SeqReader::new(&data[..]).next().unwrap().unwrap_err();
+        assert!(e.is_syntax_error());
+        assert_eq!(
+            e.to_string(),
+            "Syntax error at line 4 (byte 114): Unexpected char '!' (33) in sequence [!t]"
+        );
+    }
+
+    #[test]
+    fn vendor_fixes() {
+        let data = b"LOCUS       pUC19  12 bp    DNA     CIRCULAR    2020-03-01
+features             Location/Qualifiers
+\tgene\t1..6
+\t\t/gene=\"x\"
+origin
+        1 acgtacgtac gt
+//
+";
+        assert!(SeqReader::new(&data[..]).next().unwrap().is_err());
+        let seq = SeqReader::with_vendor_fixes(&data[..])
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(seq.name.as_deref(), Some("pUC19"));
+        assert_eq!(seq.topology, crate::seq::Topology::Circular);
+        assert_eq!(seq.seq, b"acgtacgtacgt");
+        assert_eq!(seq.features.len(), 1);
+        assert_eq!(seq.features[0].location.to_gb_format(), "1..6");
+        assert_eq!(
+            seq.features[0].qualifier_values(qualifier_key!("gene")).next(),
+            Some("x")
+        );
+        let date = seq.date.unwrap();
+        assert_eq!((date.year(), date.month(), date.day()), (2020, 3, 1));
+    }
+
+    #[test]
+    fn recover_errors() {
+        let record = |name: &str, seq: &str| {
+            format!(
+                "LOCUS       {}                          4 bp    DNA     linear   UNK 01-JAN-2020
+ORIGIN
+        1 {}
+//
+",
+                name, seq
+            )
+        };
+        let data = format!(
+            "{}{}{}{}",
+            record("a", "acgt"),
+            record("b", "ac!t"),
+            record("c", "acgtacgt"),
+            record("d", "acgt")
+        );
+        assert!(SeqReader::new(data.as_bytes()).nth(1).unwrap().is_err());
+        let mut reader = SeqReader::new(data.as_bytes());
+        reader.recover_errors(true);
+        let names: Vec<_> = reader.map(|r| r.ok().and_then(|s| s.name)).collect();
+        assert_eq!(names, vec![Some("a".into()), None, None, Some("d".into())]);
+
+        let mut reader = SeqReader::new(data.as_bytes());
+        reader.recover_errors(true);
+        let report = reader.read_all_with_diagnostics();
+        assert_eq!(report.records.len(), 4);
+        assert_eq!(report.seqs().count(), 2);
+    }
+
+    #[test]
+    fn length_mismatch() {
+        use crate::reader::LengthMismatch;
+        let data = b"LOCUS       a                          6 bp    DNA     linear   UNK 01-JAN-2020
+ORIGIN
+        1 acgt
+//
+";
+        let read = |policy| {
+            let mut reader = SeqReader::new(&data[..]);
+            reader.length_mismatch(policy);
+            let mut warnings = Vec::new();
+            let res = reader.next_with_diagnostics(&mut warnings).unwrap();
+            res.map(|seq| (seq.len(), seq.seq, warnings.len()))
+        };
+        assert!(read(LengthMismatch::Error).is_err());
+        assert_eq!(read(LengthMismatch::TrustSequence).unwrap(), (4, b"acgt".to_vec(), 0));
+        assert_eq!(
+            read(LengthMismatch::WarnAndTrustSequence).unwrap(),
+            (4, b"acgt".to_vec(), 1)
+        );
+        assert_eq!(read(LengthMismatch::TrustLocus).unwrap(), (6, b"acgtnn".to_vec(), 0));
+    }
+
+    #[test]
+    fn derive_length() {
+        let record = |locus: &str| {
+            format!("{}\nORIGIN\n        1 acgtacgtac\n//\n", locus)
+        };
+        let read = |locus: &str| {
+            let data = record(locus);
+            let mut reader = SeqReader::new(data.as_bytes());
+            reader.derive_length(true);
+            let mut warnings = Vec::new();
+            let seq = reader.next_with_diagnostics(&mut warnings).unwrap().unwrap();
+            let messages: Vec<_> = warnings.into_iter().map(|w| w.message).collect();
+            (seq, messages)
+        };
+        let (seq, warnings) = read("LOCUS       pUC19    DNA     circular   SYN 01-JAN-2020");
+        assert_eq!(seq.name.as_deref(), Some("pUC19"));
+        assert_eq!(seq.len, Some(10));
+        assert_eq!(seq.topology, Topology::Circular);
+        assert_eq!(seq.division, "SYN".into());
+        assert_eq!(seq.date, Some(Date::from_ymd(2020, 1, 1).unwrap()));
+        assert_eq!(
+            warnings,
+            vec!["No length in LOCUS line, taking the length from the sequence"]
+        );
+        let (seq, warnings) = read("LOCUS       pUC19    1,0 bp    ds-DNA     linear");
+        assert_eq!(seq.len, Some(10));
+        assert_eq!(seq.molecule_type, Some("ds-DNA".into()));
+        assert!(warnings[0].starts_with("Malformed length \"1,0\""));
+        let wrong = "LOCUS       pUC19    12 bp    DNA     linear   SYN 01-JAN-2020";
+        let (seq, warnings) = read(wrong);
+        assert_eq!(seq.len, Some(10));
+        assert_eq!(warnings, vec!["Got 10 bytes of sequence, LOCUS promised 12"]);
+
+        let data = record(wrong);
+        assert!(SeqReader::new(data.as_bytes()).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn strict() {
+        use crate::reader::LengthMismatch;
+        let record = |locus: &str, qualifier: &str, bases: &str| {
+            format!(
+                "{}
+FEATURES             Location/Qualifiers
+     gene            1..4
+                     {}
+ORIGIN
+        1 {}
+//
+",
+                locus, qualifier, bases
+            )
+        };
+        let locus = "LOCUS       a                          4 bp    DNA     linear   UNK 01-JAN-2020";
+        let read = |data: String| {
+            let mut reader = SeqReader::new(data.as_bytes());
+            reader.strict(true).length_mismatch(LengthMismatch::TrustSequence);
+            reader.next().unwrap()
+        };
+        assert!(read(record(locus, "/gene=\"x\"", "acgt")).is_ok());
+        match read(record(locus, "/translation=\"M\"", "acgt")) {
+            Err(GbParserError::SyntaxErrorAt {
+                message,
+                line,
+                snippet,
+                ..
+            }) => {
+                assert_eq!(message, "Qualifier /translation isn't allowed on gene");
+                assert_eq!(line, 3);
+                assert_eq!(snippet, "gene 1..4");
+            }
+            r => panic!("{:?}", r),
+        }
+        assert!(read(record(locus, "/gene=\"x\"", "acg")).is_err());
+        let old = "LOCUS       a        4 bp    DNA             UNK       01-JAN-2020";
+        match read(record(old, "/gene=\"x\"", "acgt")) {
+            Err(GbParserError::SyntaxErrorAt { line: 1, .. }) => {}
+            r => panic!("{:?}", r),
+        }
+        let data = record(old, "/translation=\"M\"", "acg");
+        let mut reader = SeqReader::new(data.as_bytes());
+        reader.length_mismatch(LengthMismatch::TrustSequence);
+        assert!(reader.next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn parser_options() {
+        use crate::reader::{KeyNormalization, ParserOptions};
+        let data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        let mut options = ParserOptions::new();
+        options
+            .skip_sequence(true)
+            .key_normalization(KeyNormalization::Canonicalize);
+        let headers: Vec<_> = SeqReader::with_options(&data[..], &options)
+            .map(Result::unwrap)
+            .collect();
+        let mut reader = SeqReader::new(&data[..]);
+        reader
+            .skip_sequence(true)
+            .key_normalization(KeyNormalization::Canonicalize);
+        assert_eq!(reader.map(Result::unwrap).collect::<Vec<_>>(), headers);
+        let full = parse_slice(&data).unwrap();
+        for (h, s) in headers.iter().zip(&full) {
+            assert!(h.seq.is_empty());
+            assert_eq!(h.len(), s.len());
+            assert_eq!(h.features, s.features);
+        }
+        let mut reader = SeqReader::with_options(&data[..], &options);
+        reader.set_options(&ParserOptions::new());
+        assert_eq!(reader.map(Result::unwrap).collect::<Vec<_>>(), full);
+
+        let junk = [&data[..], b"junk\n"].concat();
+        assert!(SeqReader::new(&junk[..]).any(|r| r.is_err()));
+        let lenient = SeqReader::with_options(&junk[..], &ParserOptions::lenient());
+        assert_eq!(lenient.map(Result::unwrap).count(), full.len());
+
+        // the same as `SeqReader::lazy_sequences`
+        let mut options = ParserOptions::new();
+        options.lazy_sequences(true);
+        let mut reader = options.reader(&data[..]);
+        let mut file = std::io::Cursor::new(&data);
+        let lazy: Vec<_> = SeqReader::new(&data[..])
+            .lazy_sequences()
+            .map(Result::unwrap)
+            .collect();
+        for (s, l) in full.iter().zip(&lazy) {
+            let seq = reader.next().unwrap().unwrap();
+            assert_eq!(&seq, &l.0);
+            assert_eq!(reader.lazy_seq(), l.1);
+            assert_eq!(reader.lazy_seq().unwrap().load(&mut file).unwrap(), s.seq);
+        }
+        assert!(reader.next().is_none());
+        assert_eq!(reader.lazy_seq(), None);
+
+        // vendor fixes are only applied when asked for, and the input is
+        // otherwise passed through
+        let vendor = b"LOCUS       pUC19  12 bp    DNA     CIRCULAR    2020-03-01
+origin
+        1 acgtacgtac gt
+//
+";
+        assert!(ParserOptions::new().reader(&vendor[..]).next().unwrap().is_err());
+        let mut options = ParserOptions::new();
+        options.vendor_fixes(true);
+        let seq = options.reader(&vendor[..]).next().unwrap().unwrap();
+        assert_eq!(seq.topology, crate::seq::Topology::Circular);
+        assert_eq!(seq.seq, b"acgtacgtacgt");
+        let plain: Vec<_> = ParserOptions::new()
+            .reader(&data[..])
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(plain, full);
+    }
+
+    #[test]
+    fn limits() {
+        use crate::reader::Limits;
+        let data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        let read = |limits: Limits| {
+            let mut reader = SeqReader::new(&data[..]);
+            reader.limits(limits);
+            reader.collect::<Result<Vec<_>, _>>()
+        };
+        let exceeded = |limits| match read(limits) {
+            Err(GbParserError::LimitExceeded(msg)) => msg,
+            r => panic!("{:?}", r),
+        };
+        let generous = Limits {
+            max_seq_len: Some(2000),
+            max_features: Some(100),
+            max_qualifier_len: Some(1000),
+            max_buffer: Some(64 * 1024),
+        };
+        assert_eq!(read(generous).unwrap(), parse_slice(&data).unwrap());
+        assert_eq!(
+            exceeded(Limits {
+                max_seq_len: Some(600),
+                ..generous
+            }),
+            "Sequence is longer than 600"
+        );
+        assert_eq!(
+            exceeded(Limits {
+                max_features: Some(2),
+                ..generous
+            }),
+            "More than 2 features"
+        );
+        assert_eq!(
+            exceeded(Limits {
+                max_qualifier_len: Some(50),
+                ..generous
+            }),
+            "/translation of CDS is longer than 50"
+        );
+
+        let mut huge = data[..data.iter().position(|&b| b == b'\n').unwrap() + 1].to_vec();
+        huge.extend_from_slice(b"DEFINITION  ");
+        huge.extend(std::iter::repeat_n(b'x', 200_000));
+        assert!(SeqReader::new(&huge[..]).next().unwrap().is_err());
+        let mut reader = SeqReader::new(&huge[..]);
+        reader.limits(generous);
+        match reader.next().unwrap() {
+            Err(GbParserError::LimitExceeded(msg)) => {
+                assert_eq!(msg, "More than 65536 bytes needed to parse an item")
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn text_encoding() {
+        use crate::reader::TextEncoding;
+        let data = b"LOCUS       a                          4 bp    DNA     linear   UNK 01-JAN-2020
+REFERENCE   1  (bases 1 to 4)
+  AUTHORS   Ord\xf3\xf1ez,J.
+  TITLE     Caf\xc3\xa9
+ORIGIN
+        1 acgt
+//
+";
+        let read = |encoding| {
+            let mut reader = SeqReader::new(&data[..]);
+            reader.text_encoding(encoding);
+            reader.next().unwrap()
+        };
+        assert!(read(TextEncoding::Utf8).is_err());
+        let reference = |seq: Seq| {
+            let r = seq.references[0].clone();
+            (r.authors.unwrap(), r.title)
+        };
+        assert_eq!(
+            reference(read(TextEncoding::Latin1Fallback).unwrap()),
+            ("Ordóñez,J.".to_string(), "Café".to_string())
+        );
+        assert_eq!(
+            reference(read(TextEncoding::Lossy).unwrap()),
+            ("Ord\u{FFFD}\u{FFFD}ez,J.".to_string(), "Café".to_string())
+        );
+
+        // characters split between reads are still decoded as UTF-8
+        struct OneByte<'a>(&'a [u8]);
+        impl std::io::Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.take(1).read(buf).inspect(|&n| self.0 = &self.0[n..])
+            }
+        }
+        let mut reader = SeqReader::new(OneByte(&data[..]));
+        reader.text_encoding(TextEncoding::Latin1Fallback);
+        assert_eq!(
+            reference(reader.next().unwrap().unwrap()),
+            ("Ordóñez,J.".to_string(), "Café".to_string())
+        );
+    }
+
+    #[test]
+    fn master_records() {
+        use crate::reader::{Field, Token, TokenReader};
+        use crate::seq::AccessionRange;
+        let data = "LOCUS       AAAA00000000             203 bp    DNA     linear   CON 01-JAN-2020
+DEFINITION  Some genome, whole genome shotgun sequencing project.
+FEATURES             Location/Qualifiers
+     source          1..203
+                     /organism=\"Some genome\"
+                     /mol_type=\"genomic DNA\"
+WGS         AAAA01000001-AAAA01000203
+WGS_SCAFLD  CM000001-CM000002
+WGS_SCAFLD  DS000001
+//
+";
+        let seq = SeqReader::new(data.as_bytes()).next().unwrap().unwrap();
+        let range = |first: &str, last: &str| AccessionRange {
+            first: first.into(),
+            last: last.into(),
+        };
+        assert_eq!(seq.wgs, vec![range("AAAA01000001", "AAAA01000203")]);
+        assert_eq!(
+            seq.wgs_scafld,
+            vec![range("CM000001", "CM000002"), range("DS000001", "DS000001")]
+        );
+        assert!(seq.tsa.is_empty() && seq.tls.is_empty());
+        assert_eq!(seq.features.len(), 1);
+
+        let tokens: Vec<_> = TokenReader::new(data.as_bytes()).map(Result::unwrap).collect();
+        assert!(tokens.contains(&Token::Field(Field::WGS("AAAA01000001-AAAA01000203".into()))));
+
+        let mut out = Vec::new();
+        seq.write(&mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+        assert!(written.contains("\nWGS_SCAFLD  CM000001-CM000002\nWGS_SCAFLD  DS000001\n"));
+        assert_eq!(SeqReader::new(written.as_bytes()).next().unwrap().unwrap(), seq);
+    }
+
+    #[test]
+    fn legacy_lines() {
+        use crate::seq::BaseCount;
+        let data = "LOCUS       OLD1                      12 bp    DNA     linear   PLN 01-JAN-1995
+DEFINITION  An old record.
+ACCESSION   X00001
+VERSION     X00001.1
+NID         g1234567
+BASE COUNT        3 a      4 c      3 g      1 t      1 others
+ORIGIN
+        1 accgaggtcn ca
+//
+";
+        let seq = SeqReader::new(data.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(seq.nid.as_deref(), Some("g1234567"));
+        let counts = BaseCount {
+            a: 3,
+            c: 4,
+            g: 3,
+            t: 1,
+            other: 1,
+        };
+        assert_eq!(seq.base_count, Some(counts));
+        assert_eq!(BaseCount::new(&seq.seq), counts);
+        assert_eq!(parse_slice(data.as_bytes()).unwrap()[0], seq);
+
+        let mut out = Vec::new();
+        seq.write(&mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+        assert!(written.contains("\nVERSION     X00001.1\nNID         g1234567\n"));
+        let base_count = "BASE COUNT        3 a      4 c      3 g      1 t      1 others";
+        assert!(written.contains(base_count));
+        assert_eq!(SeqReader::new(written.as_bytes()).next().unwrap().unwrap(), seq);
+
+        let wrong = data.replace("3 a      4 c", "4 a      3 c");
+        assert!(SeqReader::new(wrong.as_bytes()).next().unwrap().is_ok());
+        let mut reader = SeqReader::new(wrong.as_bytes());
+        reader.strict(true);
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn lazy_sequences() {
+        let mut data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        data.extend(std::fs::read("tests/biopython_tests/NT_019265.gb").unwrap());
+        let eager: Vec<_> = SeqReader::new(&data[..]).map(Result::unwrap).collect();
+        let lazy: Vec<_> = SeqReader::new(&data[..])
+            .lazy_sequences()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(eager.len(), lazy.len());
+        let mut file = std::io::Cursor::new(&data);
+        for (e, (l, seq)) in eager.iter().zip(&lazy) {
+            assert!(l.seq.is_empty());
+            assert_eq!(l.features, e.features);
+            assert_eq!(l.len(), e.len());
+            match seq {
+                Some(seq) => assert_eq!(seq.load(&mut file).unwrap(), e.seq),
+                None => assert!(e.seq.is_empty()),
+            }
+        }
+        assert!(lazy.last().unwrap().1.is_none());
+        let wrong = crate::reader::LazySeq {
+            offset: 0,
+            len: 10,
+        };
+        assert!(wrong.load(&mut file).is_err());
+
+        // proteins may end with a stop
+        let protein = "LOCUS       X                         10 aa            linear   UNK 01-JAN-2020
+ORIGIN
+        1 makvlsp*ga
+//
+";
+        let eager = SeqReader::new(protein.as_bytes()).next().unwrap().unwrap();
+        let (_, lazy) = SeqReader::new(protein.as_bytes())
+            .lazy_sequences()
+            .next()
+            .unwrap()
+            .unwrap();
+        let mut file = std::io::Cursor::new(protein.as_bytes());
+        assert_eq!(eager.seq, b"makvlsp*ga");
+        assert_eq!(lazy.unwrap().load(&mut file).unwrap(), eager.seq);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+        let data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        let plain: Vec<_> = SeqReader::new(&data[..]).map(Result::unwrap).collect();
+        // bgzip output is several gzip members one after the other
+        let (a, b) = data.split_at(data.len() / 2);
+        let mut gzipped = Vec::new();
+        for part in [a, b] {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(part).unwrap();
+            gzipped.extend(encoder.finish().unwrap());
+        }
+        let read = |data: &[u8]| -> Vec<Seq> {
+            SeqReader::decompressing(data)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        assert_eq!(read(&gzipped), plain);
+        assert_eq!(read(&data), plain);
+
+        // the magic number may not arrive in one read
+        struct OneByte<'a>(&'a [u8]);
+        impl std::io::Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        let read_slowly = |data: &[u8]| -> Vec<Seq> {
+            SeqReader::decompressing(OneByte(data))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        assert_eq!(read_slowly(&gzipped), plain);
+        assert_eq!(read_slowly(&data), plain);
+        assert!(read(b"").is_empty());
+
+        let dir = std::env::temp_dir().join(format!("gb-io-gzip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cor6_6.gb.gz");
+        std::fs::write(&path, &gzipped).unwrap();
+        assert_eq!(parse_file(&path).unwrap(), plain);
+        let report = parse_file_with_diagnostics(&path).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.seqs().cloned().collect::<Vec<_>>(), plain);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn provenance() {
+        let path = "tests/biopython_tests/NC_005816.gb";
+        let data = ::std::fs::read_to_string(path).unwrap();
+        let mut reader = SeqReader::new(data.as_bytes());
+        reader.track_provenance(Some(path));
+        let seq = reader.next().unwrap().unwrap();
+        let lines: Vec<&str> = data.lines().collect();
+        for f in &seq.features {
+            match f.provenance {
+                Some(Provenance::Parsed {
+                    ref source,
+                    line,
+                    offset,
+                }) => {
+                    assert_eq!(source.as_deref(), Some(path));
+                    assert!(lines[line - 1].trim_start().starts_with(&*f.kind));
+                    assert!(data[offset as usize..].starts_with(lines[line - 1]));
+                }
+                ref p => panic!("Unexpected provenance {:?}", p),
+            }
+        }
+        let untracked = SeqReader::new(data.as_bytes()).next().unwrap().unwrap();
+        assert!(untracked.features.iter().all(|f| f.provenance.is_none()));
+    }
+
+    #[test]
+    fn protein_records() {
+        use crate::translate::TranslationError;
+        init();
+        let seq = parse_file("tests/biopython_tests/protein_refseq.gb")
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(seq.name.as_deref(), Some("NP_034640"));
+        assert_eq!(seq.len, Some(182));
+        assert_eq!(seq.alphabet, Alphabet::Protein);
+        assert_eq!(seq.division, crate::seq::Division::Rod);
+        assert_eq!(seq.date, Some(Date::from_ymd(2000, 11, 1).unwrap()));
+        let mut out = Vec::new();
+        seq.write(&mut out).unwrap();
+        assert!(String::from_utf8(out.clone()).unwrap().contains(" 182 aa "));
+        let reparsed = parse_slice(&out).unwrap().pop().unwrap();
+        assert_eq!(reparsed.alphabet, Alphabet::Protein);
+        assert_eq!(reparsed.seq, seq.seq);
+        assert_eq!(
+            seq.dbsource.as_deref(),
+            Some("REFSEQ: accession NM_010510.1")
+        );
+        assert_eq!(reparsed.dbsource, seq.dbsource);
+
+        let seq2 = parse_file("tests/biopython_tests/protein_refseq2.gb")
+            .unwrap()
+            .pop()
+            .unwrap();
+        use crate::insdc::protein_feature_kinds;
+        let sites: Vec<_> = seq2
+            .features
+            .iter()
+            .filter(|f| f.kind == protein_feature_kinds::SITE)
+            .collect();
+        assert_eq!(sites.len(), 3);
+        assert!(sites[0].qualifier_values(qualifier_key!("site_type")).next().is_some());
+        let stop_data = "LOCUS       X                          4 aa            linear   UNK 01-JAN-2020
+ORIGIN
+        1 mak*
+//
+";
+        let stop = SeqReader::new(stop_data.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(stop.seq, b"mak*");
+        assert_eq!(parse_slice(stop_data.as_bytes()).unwrap()[0].seq, b"mak*");
+
+        let complement = Location::Complement(Box::new(Location::simple_range(0, 3)));
+        match seq.extract_location(&complement) {
+            Err(LocationError::Complement(_)) => {}
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(seq.extract_location(&Location::simple_range(0, 3)).unwrap(), b"mnn");
+        let cds = Feature::new(feature_kind!("CDS"), Location::simple_range(0, 3), vec![]);
+        match seq.translate_feature(&cds) {
+            Err(TranslationError::Protein) => {}
+            r => panic!("{:?}", r),
+        }
+        let sites = seq.find_sites(b"MNN");
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0].strand, Strand::Forward);
+        assert_eq!(seq.canonicalize().seq, seq.seq);
+    }
+
     #[test]
     fn biopython_tests() {
         init();
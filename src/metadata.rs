@@ -0,0 +1,652 @@
+//! Typed accessors for record-level metadata, such as the replicon a
+//! sequence comes from (stored as qualifiers on the `source` feature) or
+//! the controlled values used in KEYWORDS.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::seq::{Feature, Location, MoleculeType, QualifierKey, Seq, Source};
+
+/// Values of the `/organelle` qualifier, as listed in the INSDC feature
+/// table definition
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Organelle {
+    Chromatophore,
+    Hydrogenosome,
+    Mitochondrion,
+    /// `mitochondrion:kinetoplast`
+    Kinetoplast,
+    Nucleomorph,
+    Plastid,
+    /// `plastid:apicoplast`
+    Apicoplast,
+    /// `plastid:chloroplast`. Older records just use `chloroplast`.
+    Chloroplast,
+    /// `plastid:chromoplast`
+    Chromoplast,
+    /// `plastid:cyanelle`
+    Cyanelle,
+    /// `plastid:leucoplast`
+    Leucoplast,
+    /// `plastid:proplastid`
+    Proplastid,
+    /// Anything not in the list above
+    Other(String),
+}
+
+impl Organelle {
+    /// The value as it's written in the `/organelle` qualifier
+    pub fn as_str(&self) -> &str {
+        use self::Organelle::*;
+        match *self {
+            Chromatophore => "chromatophore",
+            Hydrogenosome => "hydrogenosome",
+            Mitochondrion => "mitochondrion",
+            Kinetoplast => "mitochondrion:kinetoplast",
+            Nucleomorph => "nucleomorph",
+            Plastid => "plastid",
+            Apicoplast => "plastid:apicoplast",
+            Chloroplast => "plastid:chloroplast",
+            Chromoplast => "plastid:chromoplast",
+            Cyanelle => "plastid:cyanelle",
+            Leucoplast => "plastid:leucoplast",
+            Proplastid => "plastid:proplastid",
+            Other(ref s) => s,
+        }
+    }
+
+    /// True for mitochondria and their subtypes
+    pub fn is_mitochondrial(&self) -> bool {
+        matches!(*self, Organelle::Mitochondrion | Organelle::Kinetoplast)
+    }
+
+    /// True for plastids and their subtypes (chloroplasts etc.)
+    pub fn is_plastid(&self) -> bool {
+        use self::Organelle::*;
+        matches!(
+            *self,
+            Plastid | Apicoplast | Chloroplast | Chromoplast | Cyanelle | Leucoplast | Proplastid
+        )
+    }
+}
+
+impl FromStr for Organelle {
+    type Err = ();
+
+    /// Never fails, unknown values become `Organelle::Other`
+    fn from_str(s: &str) -> Result<Organelle, ()> {
+        use self::Organelle::*;
+        let res = match s.trim().to_ascii_lowercase().as_str() {
+            "chromatophore" => Chromatophore,
+            "hydrogenosome" => Hydrogenosome,
+            "mitochondrion" => Mitochondrion,
+            "mitochondrion:kinetoplast" | "kinetoplast" => Kinetoplast,
+            "nucleomorph" => Nucleomorph,
+            "plastid" => Plastid,
+            "plastid:apicoplast" | "apicoplast" => Apicoplast,
+            "plastid:chloroplast" | "chloroplast" => Chloroplast,
+            "plastid:chromoplast" | "chromoplast" => Chromoplast,
+            "plastid:cyanelle" | "cyanelle" => Cyanelle,
+            "plastid:leucoplast" | "leucoplast" => Leucoplast,
+            "plastid:proplastid" | "proplastid" => Proplastid,
+            _ => Other(s.to_string()),
+        };
+        Ok(res)
+    }
+}
+
+impl fmt::Display for Organelle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+macro_rules! keywords {
+    ($($variant:ident => $text:expr, $doc:expr;)*) => {
+        /// The controlled values used in the KEYWORDS line to mark classes
+        /// of records
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub enum Keyword {
+            $(#[doc = $doc] $variant,)*
+            /// Any other keyword
+            Other(String),
+        }
+
+        impl Keyword {
+            /// The keyword as it's written in the KEYWORDS line
+            pub fn as_str(&self) -> &str {
+                match *self {
+                    $(Keyword::$variant => $text,)*
+                    Keyword::Other(ref s) => s,
+                }
+            }
+        }
+
+        impl FromStr for Keyword {
+            type Err = ();
+
+            /// Never fails, unknown values become `Keyword::Other`
+            fn from_str(s: &str) -> Result<Keyword, ()> {
+                let s = s.trim();
+                $(if s.eq_ignore_ascii_case($text) {
+                    return Ok(Keyword::$variant);
+                })*
+                Ok(Keyword::Other(s.to_string()))
+            }
+        }
+    };
+}
+
+keywords! {
+    Wgs => "WGS", "Whole genome shotgun";
+    Tsa => "TSA", "Transcriptome shotgun assembly";
+    Tls => "TLS", "Targeted locus study";
+    Env => "ENV", "Environmental sample";
+    Est => "EST", "Expressed sequence tag";
+    Gss => "GSS", "Genome survey sequence";
+    Sts => "STS", "Sequence tagged site";
+    Htg => "HTG", "High throughput genomic";
+    HtgsPhase0 => "HTGS_PHASE0", "HTG, phase 0";
+    HtgsPhase1 => "HTGS_PHASE1", "HTG, phase 1";
+    HtgsPhase2 => "HTGS_PHASE2", "HTG, phase 2";
+    HtgsPhase3 => "HTGS_PHASE3", "HTG, phase 3";
+    HtgsDraft => "HTGS_DRAFT", "HTG, draft";
+    HtgsFulltop => "HTGS_FULLTOP", "HTG, full top";
+    HtgsActivefin => "HTGS_ACTIVEFIN", "HTG, actively being finished";
+    HtgsCancelled => "HTGS_CANCELLED", "HTG, cancelled";
+    HtgsPooledMulticlone => "HTGS_POOLED_MULTICLONE", "HTG, pooled multiclone";
+    StandardDraft => "STANDARD_DRAFT", "Genome finishing: standard draft";
+    HighQualityDraft => "HIGH_QUALITY_DRAFT", "Genome finishing: high quality draft";
+    ImprovedHighQualityDraft => "IMPROVED_HIGH_QUALITY_DRAFT",
+        "Genome finishing: improved high quality draft";
+    AnnotationDirectedImprovement => "ANNOTATION_DIRECTED_IMPROVEMENT",
+        "Genome finishing: annotation directed improvement";
+    NoncontiguousFinished => "NONCONTIGUOUS_FINISHED", "Genome finishing: noncontiguous finished";
+    Barcode => "BARCODE", "DNA barcode";
+    Tpa => "TPA", "Third party annotation";
+    RefSeq => "RefSeq", "NCBI reference sequence";
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Which side `Seq::sync_source` treats as correct
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SourceSync {
+    /// Copy the header's ORGANISM to the source feature's `/organism`,
+    /// adding a source feature if there isn't one
+    FromHeader,
+    /// Copy `/organism` to the header's SOURCE and ORGANISM
+    FromFeature,
+}
+
+const TAXON_PREFIX: &str = "taxon:";
+
+/// The `/mol_type` corresponding to the molecule type from the LOCUS line
+fn mol_type_for(molecule_type: Option<&MoleculeType>) -> &'static str {
+    use crate::seq::MoleculeType::*;
+    match molecule_type {
+        None => "unassigned DNA",
+        Some(Dna) | Some(SsDna) | Some(DsDna) | Some(MsDna) => "genomic DNA",
+        Some(Rna) | Some(SsRna) | Some(DsRna) | Some(MsRna) => "genomic RNA",
+        Some(Mrna) => "mRNA",
+        Some(Trna) => "tRNA",
+        Some(Rrna) => "rRNA",
+        Some(Other(m)) => match m.trim().split_once('-') {
+            // strandedness of other types, e.g. ss-mRNA
+            Some((_, m)) if !m.contains('-') => mol_type_for(Some(&MoleculeType::from(m))),
+            _ if m.trim().ends_with("RNA") => "other RNA",
+            _ => "other DNA",
+        },
+        Some(m) if m.is_rna() => "other RNA",
+        Some(_) => "other DNA",
+    }
+}
+
+fn parse_taxon_xref(xref: &str) -> Option<u64> {
+    xref.strip_prefix(TAXON_PREFIX)?.trim().parse().ok()
+}
+
+impl Seq {
+    /// The first `source` feature, which describes the whole record
+    pub fn source_feature(&self) -> Option<&Feature> {
+        self.features
+            .iter()
+            .find(|f| f.kind == feature_kind!("source"))
+    }
+
+    pub fn source_feature_mut(&mut self) -> Option<&mut Feature> {
+        self.features
+            .iter_mut()
+            .find(|f| f.kind == feature_kind!("source"))
+    }
+
+    /// The `/organelle` the sequence comes from, `None` for nuclear or
+    /// prokaryotic sequences (or if there's no source feature)
+    pub fn organelle(&self) -> Option<Organelle> {
+        self.source_feature()
+            .and_then(|f| f.qualifier_values(qualifier_key!("organelle")).next())
+            .map(|v| v.parse().unwrap()) // can't fail
+    }
+
+    /// The name of the plasmid the sequence comes from, from `/plasmid`
+    pub fn plasmid(&self) -> Option<&str> {
+        self.source_feature()
+            .and_then(|f| f.qualifier_values(qualifier_key!("plasmid")).next())
+    }
+
+    /// The name of the chromosome the sequence comes from, from
+    /// `/chromosome`
+    pub fn chromosome(&self) -> Option<&str> {
+        self.source_feature()
+            .and_then(|f| f.qualifier_values(qualifier_key!("chromosome")).next())
+    }
+
+    /// Makes the SOURCE/ORGANISM header agree with the `/organism` of the
+    /// source feature, for records built programmatically where the two
+    /// have drifted apart. Only the first line of ORGANISM (the organism
+    /// name) is affected, the taxonomic lineage below it is kept. The header
+    /// has no equivalent of `/db_xref`, so that's left alone. Returns `true`
+    /// if anything changed.
+    pub fn sync_source(&mut self, direction: SourceSync) -> bool {
+        match direction {
+            SourceSync::FromHeader => {
+                let organism = match self.source {
+                    Some(ref s) => match s.organism {
+                        Some(ref o) => o.lines().next().unwrap_or_default().trim().to_string(),
+                        None => s.source.trim().to_string(),
+                    },
+                    None => return false,
+                };
+                if organism.is_empty() {
+                    return false;
+                }
+                if self.source_feature().is_none() {
+                    let location = Location::simple_range(0, self.len());
+                    self.features.insert(
+                        0,
+                        Feature::new(feature_kind!("source"), location, Vec::new()),
+                    );
+                }
+                let source = self.source_feature_mut().unwrap();
+                let key = qualifier_key!("organism");
+                match source.qualifiers.iter_mut().find(|(k, _)| *k == key) {
+                    Some(q) if q.1.as_ref() == Some(&organism) => false,
+                    Some(q) => {
+                        q.1 = Some(organism);
+                        true
+                    }
+                    None => {
+                        source.qualifiers.push((key, Some(organism)));
+                        true
+                    }
+                }
+            }
+            SourceSync::FromFeature => {
+                let organism = match self
+                    .source_feature()
+                    .and_then(|f| f.qualifier_values(qualifier_key!("organism")).next())
+                {
+                    Some(o) => o.to_string(),
+                    None => return false,
+                };
+                let header = self.source.get_or_insert_with(|| Source {
+                    source: String::new(),
+                    organism: None,
+                });
+                let mut lines: Vec<&str> = header
+                    .organism
+                    .as_ref()
+                    .map(|o| o.lines().collect())
+                    .unwrap_or_default();
+                let old_name = lines.first().map(|l| l.trim().to_string());
+                if old_name.as_ref() == Some(&organism) && !header.source.is_empty() {
+                    return false;
+                }
+                // SOURCE is often the same as the organism name, if so keep
+                // it that way
+                if header.source.is_empty() || Some(header.source.trim()) == old_name.as_deref() {
+                    header.source = organism.clone();
+                }
+                if lines.is_empty() {
+                    lines.push(&organism);
+                } else {
+                    lines[0] = &organism;
+                }
+                header.organism = Some(lines.join("\n"));
+                true
+            }
+        }
+    }
+
+    /// Makes sure there's a `source` feature spanning the whole sequence, as
+    /// INSDC requires, adding one at the start of the feature table if
+    /// needed. Its `/organism` is set to `organism`, or taken from the
+    /// header if that's `None` (falling back to `unknown`). Likewise
+    /// `/mol_type` is set to `mol_type`, or derived from the LOCUS molecule
+    /// type (protein records don't get one). The header's SOURCE and
+    /// ORGANISM are then updated to match, as by `sync_source`.
+    pub fn ensure_source_feature(
+        &mut self,
+        organism: Option<&str>,
+        mol_type: Option<&str>,
+    ) -> &mut Feature {
+        let organism = match organism {
+            Some(o) => o.to_string(),
+            None => self
+                .source
+                .as_ref()
+                .and_then(|s| {
+                    s.organism
+                        .as_ref()
+                        .and_then(|o| o.lines().next())
+                        .or(Some(&s.source))
+                })
+                .map(|o| o.trim())
+                .filter(|o| !o.is_empty())
+                .or_else(|| {
+                    self.source_feature()
+                        .and_then(|f| f.qualifier_values(qualifier_key!("organism")).next())
+                })
+                .unwrap_or("unknown")
+                .to_string(),
+        };
+        let mol_type = match mol_type {
+            Some(m) => Some(m.to_string()),
+            None if self.is_protein() => None,
+            None => Some(mol_type_for(self.molecule_type.as_ref()).to_string()),
+        };
+        let location = Location::simple_range(0, self.len());
+        match self.source_feature_mut() {
+            Some(f) => f.location = location,
+            None => self.features.insert(
+                0,
+                Feature::new(feature_kind!("source"), location, Vec::new()),
+            ),
+        }
+        let source = self.source_feature_mut().unwrap();
+        let mut set = |key: QualifierKey, value: String| {
+            match source.qualifiers.iter_mut().find(|(k, _)| *k == key) {
+                Some(q) => q.1 = Some(value),
+                None => source.qualifiers.push((key, Some(value))),
+            }
+        };
+        set(qualifier_key!("organism"), organism);
+        if let Some(mol_type) = mol_type {
+            set(qualifier_key!("mol_type"), mol_type);
+        }
+        self.sync_source(SourceSync::FromFeature);
+        self.source_feature_mut().unwrap()
+    }
+
+    /// The KEYWORDS line split into `Keyword`s. An empty line (`.`) gives
+    /// no keywords.
+    pub fn keyword_list(&self) -> Vec<Keyword> {
+        match self.keywords {
+            Some(ref k) => k
+                .trim()
+                .trim_end_matches('.')
+                .split(';')
+                // long keywords may be wrapped over several lines
+                .map(|k| k.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|k| !k.is_empty())
+                .map(|k| k.parse().unwrap()) // can't fail
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn has_keyword(&self, keyword: &Keyword) -> bool {
+        self.keyword_list().contains(keyword)
+    }
+
+    /// Sets the KEYWORDS line, formatted as GenBank does (`WGS; TSA.`)
+    pub fn set_keyword_list(&mut self, keywords: &[Keyword]) {
+        let joined = keywords
+            .iter()
+            .map(Keyword::as_str)
+            .collect::<Vec<_>>()
+            .join("; ");
+        self.keywords = Some(format!("{}.", joined));
+    }
+
+    /// The NCBI taxonomy ID from the `/db_xref="taxon:..."` qualifier of the
+    /// source feature
+    pub fn taxon_id(&self) -> Option<u64> {
+        self.source_feature()?
+            .qualifier_values(qualifier_key!("db_xref"))
+            .find_map(parse_taxon_xref)
+    }
+
+    /// Sets the `taxon:` db_xref of the source feature, replacing any
+    /// existing one. Returns `false` (and does nothing) if there's no source
+    /// feature.
+    pub fn set_taxon_id(&mut self, id: u64) -> bool {
+        let source = match self.source_feature_mut() {
+            Some(f) => f,
+            None => return false,
+        };
+        let value = Some(format!("{}{}", TAXON_PREFIX, id));
+        let existing = source.qualifiers.iter_mut().find(|(k, v)| {
+            *k == qualifier_key!("db_xref")
+                && v.as_ref().is_some_and(|v| v.starts_with(TAXON_PREFIX))
+        });
+        match existing {
+            Some(q) => q.1 = value,
+            None => source.qualifiers.push((qualifier_key!("db_xref"), value)),
+        }
+        true
+    }
+
+    /// The segment number and total number of segments from the SEGMENT
+    /// line, e.g. `(1, 6)` for `1 of 6`
+    pub fn segment_number(&self) -> Option<(usize, usize)> {
+        let segment = self.segment.as_ref()?;
+        let mut words = segment.split_whitespace();
+        let n = words.next()?.parse().ok()?;
+        if words.next()? != "of" {
+            return None;
+        }
+        let total = words.next()?.parse().ok()?;
+        Some((n, total))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn with_source(qualifiers: &[(&str, &str)]) -> Seq {
+        Seq {
+            seq: b"acgt".to_vec(),
+            features: vec![Feature::new(
+                feature_kind!("source"),
+                Location::simple_range(0, 4),
+                qualifiers
+                    .iter()
+                    .map(|&(k, v)| (k.into(), Some(v.to_string())))
+                    .collect(),
+            )],
+            ..Seq::empty()
+        }
+    }
+
+    #[test]
+    fn organelle() {
+        assert_eq!(with_source(&[]).organelle(), None);
+        let s = with_source(&[("organelle", "plastid:chloroplast")]);
+        assert_eq!(s.organelle(), Some(Organelle::Chloroplast));
+        assert!(s.organelle().unwrap().is_plastid());
+        let s = with_source(&[("organelle", "chloroplast")]);
+        assert_eq!(s.organelle(), Some(Organelle::Chloroplast));
+        let s = with_source(&[("organelle", "mitochondrion:kinetoplast")]);
+        assert!(s.organelle().unwrap().is_mitochondrial());
+        let s = with_source(&[("organelle", "thing")]);
+        assert_eq!(s.organelle(), Some(Organelle::Other("thing".into())));
+        for o in &[Organelle::Kinetoplast, Organelle::Proplastid] {
+            assert_eq!(&o.as_str().parse::<Organelle>().unwrap(), o);
+        }
+    }
+
+    #[test]
+    fn replicon() {
+        let s = with_source(&[("plasmid", "pBR322"), ("chromosome", "II")]);
+        assert_eq!(s.plasmid(), Some("pBR322"));
+        assert_eq!(s.chromosome(), Some("II"));
+        assert_eq!(Seq::empty().plasmid(), None);
+    }
+
+    #[test]
+    fn sync_source() {
+        let mut s = with_source(&[("organism", "Escherichia coli")]);
+        assert!(!s.sync_source(SourceSync::FromHeader));
+        assert!(s.sync_source(SourceSync::FromFeature));
+        assert_eq!(
+            s.source,
+            Some(Source {
+                source: "Escherichia coli".into(),
+                organism: Some("Escherichia coli".into()),
+            })
+        );
+        assert!(!s.sync_source(SourceSync::FromFeature));
+
+        s.source = Some(Source {
+            source: "Escherichia coli".into(),
+            organism: Some("Escherichia coli K-12\nBacteria; Proteobacteria.".into()),
+        });
+        assert!(s.sync_source(SourceSync::FromHeader));
+        assert_eq!(
+            s.features[0]
+                .qualifier_values(qualifier_key!("organism"))
+                .collect::<Vec<_>>(),
+            vec!["Escherichia coli K-12"]
+        );
+        s.features[0].qualifiers[0].1 = Some("Shigella flexneri".into());
+        assert!(s.sync_source(SourceSync::FromFeature));
+        let header = s.source.as_ref().unwrap();
+        assert_eq!(header.source, "Escherichia coli");
+        assert_eq!(
+            header.organism.as_deref(),
+            Some("Shigella flexneri\nBacteria; Proteobacteria.")
+        );
+
+        let mut s = Seq {
+            seq: b"acgt".to_vec(),
+            source: Some(Source {
+                source: "human".into(),
+                organism: Some("Homo sapiens".into()),
+            }),
+            ..Seq::empty()
+        };
+        assert!(s.sync_source(SourceSync::FromHeader));
+        let f = s.source_feature().unwrap();
+        assert_eq!(f.location, Location::simple_range(0, 4));
+        assert_eq!(
+            f.qualifier_values(qualifier_key!("organism")).next(),
+            Some("Homo sapiens")
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let mut s = Seq {
+            keywords: Some("WGS; STANDARD_DRAFT; something\nelse.".into()),
+            ..Seq::empty()
+        };
+        assert_eq!(
+            s.keyword_list(),
+            vec![
+                Keyword::Wgs,
+                Keyword::StandardDraft,
+                Keyword::Other("something else".into())
+            ]
+        );
+        assert!(s.has_keyword(&Keyword::Wgs));
+        assert!(!s.has_keyword(&Keyword::Tsa));
+        s.keywords = Some(".".into());
+        assert!(s.keyword_list().is_empty());
+        s.set_keyword_list(&[Keyword::Tsa, Keyword::Env]);
+        assert_eq!(s.keywords.as_deref(), Some("TSA; ENV."));
+        s.set_keyword_list(&[]);
+        assert_eq!(s.keywords.as_deref(), Some("."));
+        assert_eq!("RefSeq".parse::<Keyword>().unwrap(), Keyword::RefSeq);
+    }
+
+    #[test]
+    fn taxon_id() {
+        let mut s = with_source(&[("db_xref", "GOA:P12345"), ("db_xref", "taxon:562")]);
+        assert_eq!(s.taxon_id(), Some(562));
+        assert!(s.set_taxon_id(511145));
+        assert_eq!(s.taxon_id(), Some(511145));
+        assert_eq!(
+            s.features[0]
+                .qualifier_values(qualifier_key!("db_xref"))
+                .count(),
+            2
+        );
+        let mut s = with_source(&[]);
+        assert_eq!(s.taxon_id(), None);
+        assert!(s.set_taxon_id(9606));
+        assert_eq!(s.taxon_id(), Some(9606));
+        assert!(!Seq::empty().set_taxon_id(9606));
+    }
+
+    #[test]
+    fn segment_number() {
+        let mut s = Seq::empty();
+        assert_eq!(s.segment_number(), None);
+        s.segment = Some("2 of 6".into());
+        assert_eq!(s.segment_number(), Some((2, 6)));
+        s.segment = Some("2/6".into());
+        assert_eq!(s.segment_number(), None);
+    }
+
+    #[test]
+    fn ensure_source_feature() {
+        let mut s = Seq {
+            seq: b"acgtacgt".to_vec(),
+            molecule_type: Some("ss-RNA".into()),
+            source: Some(Source {
+                source: "Homo sapiens (human)".into(),
+                organism: Some("Homo sapiens\nEukaryota; Metazoa".into()),
+            }),
+            ..Seq::empty()
+        };
+        s.ensure_source_feature(None, None);
+        let f = s.source_feature().unwrap();
+        assert_eq!(f.location, Location::simple_range(0, 8));
+        let values = |s: &Seq, key| {
+            s.source_feature()
+                .unwrap()
+                .qualifier_values(key)
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&s, qualifier_key!("organism")), vec!["Homo sapiens"]);
+        assert_eq!(values(&s, qualifier_key!("mol_type")), vec!["genomic RNA"]);
+
+        s.seq.extend(b"aa");
+        s.ensure_source_feature(Some("Mus musculus"), Some("mRNA"));
+        assert_eq!(s.features.len(), 1);
+        assert_eq!(s.features[0].location, Location::simple_range(0, 10));
+        assert_eq!(values(&s, qualifier_key!("organism")), vec!["Mus musculus"]);
+        assert_eq!(values(&s, qualifier_key!("mol_type")), vec!["mRNA"]);
+        let header = s.source.as_ref().unwrap();
+        assert_eq!(header.organism.as_deref(), Some("Mus musculus\nEukaryota; Metazoa"));
+
+        let mut empty = Seq::empty();
+        empty.ensure_source_feature(None, None);
+        assert_eq!(values(&empty, qualifier_key!("organism")), vec!["unknown"]);
+        assert_eq!(values(&empty, qualifier_key!("mol_type")), vec!["unassigned DNA"]);
+        let mol_type = |m: &str| mol_type_for(Some(&MoleculeType::from(m)));
+        assert_eq!(mol_type("DNA"), "genomic DNA");
+        assert_eq!(mol_type("cRNA"), "other RNA");
+        assert_eq!(mol_type("ss-mRNA"), "mRNA");
+        assert_eq!(mol_type("ds-xDNA"), "other DNA");
+    }
+}
@@ -0,0 +1,245 @@
+//! Finding features which overlap or touch each other, e.g. to detect
+//! conflicting annotations after merging feature tables.
+
+use crate::seq::{Feature, Seq};
+use crate::FeatureKind;
+
+/// Index of the regions covered by each feature of a record, for finding
+/// the features near a range quickly
+#[derive(Debug, Clone)]
+pub struct FeatureIndex {
+    // (start, end, feature), sorted
+    intervals: Vec<(i64, i64, usize)>,
+    longest: i64,
+}
+
+impl FeatureIndex {
+    /// Indexes the features of `seq`. Features whose location doesn't refer
+    /// to positions in `seq` (e.g. `one-of` or external ones) are left out.
+    pub fn new(seq: &Seq) -> FeatureIndex {
+        let mut intervals = Vec::new();
+        for (i, f) in seq.features.iter().enumerate() {
+            for (a, b) in covered(seq, f) {
+                intervals.push((a, b, i));
+            }
+        }
+        intervals.sort_unstable();
+        let longest = intervals.iter().map(|(a, b, _)| b - a).max().unwrap_or(0);
+        FeatureIndex { intervals, longest }
+    }
+
+    /// The indices of the features which overlap or touch `start..end`, in
+    /// order
+    pub fn touching(&self, start: i64, end: i64) -> Vec<usize> {
+        let first = self
+            .intervals
+            .partition_point(|&(a, _, _)| a < start - self.longest);
+        let mut res: Vec<_> = self.intervals[first..]
+            .iter()
+            .take_while(|&&(a, _, _)| a <= end)
+            .filter(|&&(_, b, _)| b >= start)
+            .map(|&(_, _, i)| i)
+            .collect();
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+}
+
+/// How the regions covered by two features are related
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Relationship {
+    /// Both cover exactly the same bases
+    Equal,
+    /// The first covers all of the second, and more
+    Contains,
+    /// The second covers all of the first, and more
+    ContainedBy,
+    Overlaps,
+    /// No bases in common, but one ends where the other starts
+    Adjacent,
+}
+
+/// A pair of features which overlap or touch, see `Seq::overlap_report`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Overlap {
+    /// Index of the first feature, which is always less than `b`
+    pub a: usize,
+    pub b: usize,
+    /// The number of bases both features cover
+    pub length: i64,
+    pub relationship: Relationship,
+    pub same_strand: bool,
+}
+
+impl Seq {
+    pub fn feature_index(&self) -> FeatureIndex {
+        FeatureIndex::new(self)
+    }
+
+    /// Finds every pair of features which overlap or are adjacent, only
+    /// looking at features of the given kinds if `kinds` is set. What
+    /// matters is the bases covered, so a feature lying in the intron of
+    /// another doesn't overlap it. On circular sequences, features touching
+    /// the origin from either side are adjacent.
+    pub fn overlap_report(&self, kinds: Option<&[FeatureKind]>) -> Vec<Overlap> {
+        let selected = |f: &Feature| kinds.is_none_or(|k| k.contains(&f.kind));
+        let index = self.feature_index();
+        let covered: Vec<_> = self.features.iter().map(|f| covered(self, f)).collect();
+        let mut res = Vec::new();
+        for (a, f) in self.features.iter().enumerate() {
+            if !selected(f) {
+                continue;
+            }
+            let mut candidates = Vec::new();
+            for &(start, end) in &covered[a] {
+                candidates.extend(index.touching(start, end));
+                if self.is_circular() && start == 0 {
+                    candidates.extend(index.touching(self.len(), self.len()));
+                }
+                if self.is_circular() && end == self.len() {
+                    candidates.extend(index.touching(0, 0));
+                }
+            }
+            candidates.sort_unstable();
+            candidates.dedup();
+            for b in candidates {
+                if b <= a || !selected(&self.features[b]) {
+                    continue;
+                }
+                let relationship = match self.relationship(&covered[a], &covered[b]) {
+                    Some(r) => r,
+                    None => continue,
+                };
+                res.push(Overlap {
+                    a,
+                    b,
+                    length: intersection(&covered[a], &covered[b]),
+                    relationship,
+                    same_strand: f.location.strand() == self.features[b].location.strand(),
+                });
+            }
+        }
+        res
+    }
+
+    fn relationship(&self, a: &[(i64, i64)], b: &[(i64, i64)]) -> Option<Relationship> {
+        let shared = intersection(a, b);
+        let (len_a, len_b) = (total(a), total(b));
+        if shared == 0 {
+            let touches = |x: &[(i64, i64)], y: &[(i64, i64)]| {
+                x.iter().any(|&(_, end)| {
+                    y.iter().any(|&(start, _)| {
+                        end == start || self.is_circular() && end == self.len() && start == 0
+                    })
+                })
+            };
+            return if touches(a, b) || touches(b, a) {
+                Some(Relationship::Adjacent)
+            } else {
+                None
+            };
+        }
+        Some(if shared == len_a && shared == len_b {
+            Relationship::Equal
+        } else if shared == len_b {
+            Relationship::Contains
+        } else if shared == len_a {
+            Relationship::ContainedBy
+        } else {
+            Relationship::Overlaps
+        })
+    }
+}
+
+/// The sorted, non-overlapping ranges of bases covered by `f`, with ranges
+/// extending past the origin of circular sequences wrapped around
+fn covered(seq: &Seq, f: &Feature) -> Vec<(i64, i64)> {
+    let len = seq.len();
+    let mut ranges = Vec::new();
+    for (a, b) in f.location.ranges().unwrap_or_default() {
+        if seq.is_circular() && len > 0 && b > len {
+            ranges.push((a, len));
+            ranges.push((0, b - len));
+        } else if a < b {
+            ranges.push((a, b));
+        }
+    }
+    ranges.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(ranges.len());
+    for (a, b) in ranges {
+        match merged.last_mut() {
+            Some(last) if a <= last.1 => last.1 = last.1.max(b),
+            _ => merged.push((a, b)),
+        }
+    }
+    merged
+}
+
+fn total(ranges: &[(i64, i64)]) -> i64 {
+    ranges.iter().map(|(a, b)| b - a).sum()
+}
+
+fn intersection(x: &[(i64, i64)], y: &[(i64, i64)]) -> i64 {
+    let mut res = 0;
+    for &(a, b) in x {
+        for &(c, d) in y {
+            res += (b.min(d) - a.max(c)).max(0);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::{Location, Topology};
+
+    #[test]
+    fn overlaps() {
+        let f = |kind: &str, l: &str| {
+            Feature::new(kind.into(), Location::from_gb_format(l).unwrap(), vec![])
+        };
+        let mut seq = Seq {
+            seq: vec![b'a'; 100],
+            features: vec![
+                f("gene", "1..50"),
+                f("CDS", "join(1..10,31..50)"),
+                f("misc_feature", "15..20"),
+                f("gene", "complement(51..60)"),
+                f("gene", "45..70"),
+                f("gene", "91..100"),
+            ],
+            ..Seq::empty()
+        };
+        let report: Vec<_> = seq
+            .overlap_report(None)
+            .into_iter()
+            .map(|o| (o.a, o.b, o.length, o.relationship, o.same_strand))
+            .collect();
+        use Relationship::*;
+        assert_eq!(
+            report,
+            vec![
+                (0, 1, 30, Contains, true),
+                (0, 2, 6, Contains, true),
+                (0, 3, 0, Adjacent, false),
+                (0, 4, 6, Overlaps, true),
+                (1, 3, 0, Adjacent, false),
+                (1, 4, 6, Overlaps, true),
+                (3, 4, 10, ContainedBy, false),
+            ]
+        );
+        let genes = [feature_kind!("gene")];
+        assert_eq!(seq.overlap_report(Some(&genes)).len(), 3);
+
+        seq.topology = Topology::Circular;
+        let report = seq.overlap_report(Some(&genes));
+        assert_eq!(report.len(), 4);
+        assert_eq!((report[2].a, report[2].b), (0, 5));
+        assert_eq!(report[2].relationship, Adjacent);
+        assert_eq!(seq.feature_index().touching(55, 55), vec![3, 4]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Reading records from a tokio `AsyncRead`, for parsing them as they
+//! arrive over the network.
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::slice::find_record_end;
+use super::ParserOptions;
+use crate::errors::GbParserError;
+use crate::seq::Seq;
+
+const READ_SIZE: usize = 64 * 1024;
+
+/// Reads `Seq`s from an `AsyncRead`, such as the body of an HTTP response.
+/// Input is read without blocking until a whole record (up to its `//`
+/// line) has arrived, and then parsed as by `SeqReader`, so only one record
+/// is held in memory at a time. Needs the `tokio` feature.
+#[derive(Debug)]
+pub struct AsyncSeqReader<T> {
+    input: T,
+    buffer: Vec<u8>,
+    // how much of `buffer` is known not to contain the end of a record, up
+    // to the start of a line
+    searched: usize,
+    eof: bool,
+    options: ParserOptions,
+}
+
+impl<T: AsyncRead + Unpin> AsyncSeqReader<T> {
+    pub fn new(input: T) -> AsyncSeqReader<T> {
+        AsyncSeqReader::with_options(input, ParserOptions::new())
+    }
+
+    /// Parse each record with the given settings. Lines and offsets (in
+    /// errors and `Provenance`) count from the start of each record.
+    pub fn with_options(input: T, options: ParserOptions) -> AsyncSeqReader<T> {
+        AsyncSeqReader {
+            input,
+            buffer: Vec::new(),
+            searched: 0,
+            eof: false,
+            options,
+        }
+    }
+
+    /// The next record, or `None` at the end of the input
+    pub async fn next(&mut self) -> Option<Result<Seq, GbParserError>> {
+        loop {
+            if let Some(end) = self.record_end() {
+                let record: Vec<u8> = self.buffer.drain(..end).collect();
+                self.searched = 0;
+                return self.options.reader(&record[..]).next();
+            }
+            if self.eof {
+                if self.buffer.iter().all(u8::is_ascii_whitespace) {
+                    return None;
+                }
+                // a truncated record, let `SeqReader` say what's wrong
+                let record = std::mem::take(&mut self.buffer);
+                self.searched = 0;
+                return self.options.reader(&record[..]).next();
+            }
+            let len = self.buffer.len();
+            self.buffer.resize(len + READ_SIZE, 0);
+            let read = self.input.read(&mut self.buffer[len..]).await;
+            self.buffer.truncate(len + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => self.eof = true,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+
+    /// Reads all the remaining records
+    pub async fn read_all(mut self) -> Result<Vec<Seq>, GbParserError> {
+        let mut res = Vec::new();
+        while let Some(seq) = self.next().await {
+            res.push(seq?);
+        }
+        Ok(res)
+    }
+
+    /// Where the first record in the buffer ends, after its `//` line, if
+    /// we have all of it
+    fn record_end(&mut self) -> Option<usize> {
+        match find_record_end(&self.buffer, self.searched) {
+            Ok(end) => Some(end),
+            Err(searched) => {
+                self.searched = searched;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::ReadBuf;
+
+    /// Returns a few bytes at a time, as a network connection might
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> AsyncRead for Trickle<'a> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let n = self.0.len().min(buf.remaining()).min(7);
+            buf.put_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn read_async() {
+        let data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        let expected = crate::reader::parse_slice(&data).unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let seqs = runtime
+            .block_on(AsyncSeqReader::new(Trickle(&data)).read_all())
+            .unwrap();
+        assert_eq!(seqs, expected);
+
+        let truncated = &data[..data.len() / 2];
+        let seqs: Vec<_> = runtime.block_on(async {
+            let mut reader = AsyncSeqReader::new(truncated);
+            let mut seqs = Vec::new();
+            while let Some(seq) = reader.next().await {
+                seqs.push(seq);
+            }
+            seqs
+        });
+        assert!(seqs.len() > 1 && seqs.len() <= expected.len());
+        assert!(seqs.last().unwrap().is_err());
+
+        let vendor = b"LOCUS       pUC19  12 bp    DNA     CIRCULAR    2020-03-01
+origin
+        1 acgtacgtac gt
+//
+";
+        let mut options = ParserOptions::new();
+        options.vendor_fixes(true);
+        let seqs = runtime
+            .block_on(AsyncSeqReader::with_options(Trickle(vendor), options).read_all())
+            .unwrap();
+        assert_eq!(seqs[0].seq, b"acgtacgtacgt");
+    }
+}
@@ -1,25 +1,295 @@
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+
+#[cfg(feature = "tokio")]
+mod async_reader;
 #[macro_use]
 mod errors;
 mod nom_parsers;
+mod push;
+mod slice;
 mod streaming_parser;
+mod strict;
+mod vendor;
 use self::streaming_parser::StreamParser;
-use crate::seq::{Location, Seq};
+use self::streaming_parser::TokenParser;
+pub use self::nom_parsers::{Field, Locus};
+pub use self::push::PushParser;
+pub use self::streaming_parser::{Handler, Token};
+pub use self::slice::{split_records, RecordSlice, RecordSlices};
+pub use self::vendor::VendorFixes;
+#[cfg(feature = "tokio")]
+pub use self::async_reader::AsyncSeqReader;
+use crate::diagnostics::{Diagnostic, DiagnosticSink};
+use crate::seq::{Feature, Location, Seq};
 
 pub use crate::errors::GbParserError;
 
+/// How the lines of a multi-line DEFINITION are joined by `SeqReader`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DefinitionJoining {
+    /// Keep the original line breaks, so the record is written back out
+    /// with the same wrapping. This is the default.
+    #[default]
+    Preserve,
+    /// Join the lines with single spaces, collapsing any other runs of
+    /// whitespace
+    Normalize,
+}
+
+/// What `SeqReader` does when the length in the LOCUS line doesn't match
+/// the length of the sequence
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LengthMismatch {
+    /// Fail with a syntax error. This is the default.
+    #[default]
+    Error,
+    /// Set `len` to the length of the sequence
+    TrustSequence,
+    /// Like `TrustSequence`, with a warning
+    WarnAndTrustSequence,
+    /// Keep the LOCUS line's length, cutting the sequence short or padding
+    /// it with `n` (or `X` for proteins) to match
+    TrustLocus,
+}
+
+/// How `SeqReader` decodes text that isn't valid UTF-8, such as the Latin-1
+/// author names and titles in older files
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TextEncoding {
+    /// Fail with a syntax error. This is the default.
+    #[default]
+    Utf8,
+    /// Take bytes which aren't valid UTF-8 to be Latin-1, so that e.g. `\xe9`
+    /// becomes `é`
+    Latin1Fallback,
+    /// Replace invalid UTF-8 with `\u{FFFD}`, as `String::from_utf8_lossy`
+    /// does
+    Lossy,
+}
+
+/// Limits on what `SeqReader` will read, so that a corrupted or malicious
+/// file can't make it use unbounded memory. Going over a limit fails with
+/// `GbParserError::LimitExceeded`, after which no more records should be
+/// read. There are no limits by default.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Limits {
+    /// The longest sequence (or LOCUS length), in bases or residues
+    pub max_seq_len: Option<usize>,
+    /// The most features in one record
+    pub max_features: Option<usize>,
+    /// The longest qualifier value, in bytes
+    pub max_qualifier_len: Option<usize>,
+    /// The most input held in memory at once. Header fields and features
+    /// are read whole before they're parsed, so this limits their size,
+    /// including features with many small qualifiers.
+    pub max_buffer: Option<usize>,
+}
+
+/// Whether `SeqReader` canonicalizes the case and spelling of feature and
+/// qualifier keys, so that e.g. `cds` and `/Locus_Tag` become `CDS` and
+/// `/locus_tag`. Keys which don't match a standard INSDC key are left alone.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum KeyNormalization {
+    /// Keep keys as they're written. This is the default.
+    #[default]
+    Preserve,
+    /// Replace keys with their canonical spelling
+    Canonicalize,
+    /// Like `Canonicalize`, but record the original spelling in
+    /// `Feature::original_spelling` so the writer can reproduce it
+    CanonicalizeKeepingOriginal,
+}
+
+/// Settings for `SeqReader`, for setting up several readers the same way.
+/// Most have a setter of the same name on `SeqReader`, see there for
+/// details. `vendor_fixes` changes the input, so it's only applied by
+/// `ParserOptions::reader`. Other readers which change the input, like
+/// `SeqReader::decompressing`, are constructed first, with options applied
+/// by `SeqReader::set_options`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ParserOptions {
+    key_normalization: KeyNormalization,
+    definition_joining: DefinitionJoining,
+    recover_errors: bool,
+    length_mismatch: LengthMismatch,
+    lenient_ending: bool,
+    strict: bool,
+    derive_length: bool,
+    display_hints: bool,
+    // `Some` if provenance is tracked, with the name of the input if known
+    provenance_source: Option<Option<String>>,
+    skip_sequence: bool,
+    lazy_sequences: bool,
+    vendor_fixes: bool,
+    limits: Limits,
+    text_encoding: TextEncoding,
+}
+
+impl ParserOptions {
+    /// The defaults, as for `SeqReader::new`
+    pub fn new() -> ParserOptions {
+        ParserOptions::default()
+    }
+
+    /// Whether feature and qualifier keys are canonicalized, see
+    /// `KeyNormalization`
+    pub fn key_normalization(&mut self, normalization: KeyNormalization) -> &mut Self {
+        self.key_normalization = normalization;
+        self
+    }
+
+    /// How multi-line DEFINITIONs are joined, see `DefinitionJoining`
+    pub fn definition_joining(&mut self, joining: DefinitionJoining) -> &mut Self {
+        self.definition_joining = joining;
+        self
+    }
+
+    /// Carry on after a record which can't be parsed, see
+    /// `SeqReader::recover_errors`
+    pub fn recover_errors(&mut self, recover: bool) -> &mut Self {
+        self.recover_errors = recover;
+        self
+    }
+
+    /// What happens when the LOCUS line's length doesn't match the
+    /// sequence, see `LengthMismatch`
+    pub fn length_mismatch(&mut self, policy: LengthMismatch) -> &mut Self {
+        self.length_mismatch = policy;
+        self
+    }
+
+    /// Accept truncated input, or junk after the last record, with a
+    /// warning, see `SeqReader::lenient_ending`
+    pub fn lenient_ending(&mut self, lenient: bool) -> &mut Self {
+        self.lenient_ending = lenient;
+        self
+    }
+
+    /// Reject records which don't follow the format exactly, see
+    /// `SeqReader::strict`
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Take the length from the sequence if the LOCUS line's is missing or
+    /// can't be read, see `SeqReader::derive_length`
+    pub fn derive_length(&mut self, derive: bool) -> &mut Self {
+        self.derive_length = derive;
+        self
+    }
+
+    /// Fill in `Feature::display_hints`, see `SeqReader::read_display_hints`
+    pub fn read_display_hints(&mut self, read: bool) -> &mut Self {
+        self.display_hints = read;
+        self
+    }
+
+    /// Record where each feature was read from, see
+    /// `SeqReader::track_provenance`
+    pub fn track_provenance(&mut self, source: Option<&str>) -> &mut Self {
+        self.provenance_source = Some(source.map(String::from));
+        self
+    }
+
+    /// Leave out the sequences, see `SeqReader::skip_sequence`
+    pub fn skip_sequence(&mut self, skip: bool) -> &mut Self {
+        self.skip_sequence = skip;
+        self
+    }
+
+    /// Leave out the sequences, but note where they are so they can be
+    /// loaded later, see `SeqReader::lazy_seq`. As with
+    /// `SeqReader::lazy_sequences`, `len` is set to the length of the
+    /// sequence.
+    pub fn lazy_sequences(&mut self, lazy: bool) -> &mut Self {
+        self.lazy_sequences = lazy;
+        self
+    }
+
+    /// Fix up files from sequence editors, see `SeqReader::with_vendor_fixes`.
+    /// Only applied by `ParserOptions::reader`, since the input is wrapped
+    /// in `VendorFixes`.
+    pub fn vendor_fixes(&mut self, fix: bool) -> &mut Self {
+        self.vendor_fixes = fix;
+        self
+    }
+
+    /// Limits on how much is read, see `Limits`
+    pub fn limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    /// How text which isn't valid UTF-8 is decoded, see `TextEncoding` and
+    /// `SeqReader::text_encoding`
+    pub fn text_encoding(&mut self, encoding: TextEncoding) -> &mut Self {
+        self.text_encoding = encoding;
+        self
+    }
+
+    /// A reader for `data` with these settings. Unlike
+    /// `SeqReader::with_options`, this also applies `vendor_fixes`; if it
+    /// isn't set, the input is passed through unchanged.
+    pub fn reader<T: Read>(&self, data: T) -> SeqReader<VendorFixes<T>> {
+        let data = if self.vendor_fixes {
+            VendorFixes::new(data)
+        } else {
+            VendorFixes::passthrough(data)
+        };
+        SeqReader::with_options(data, self)
+    }
+
+    /// Settings for a lenient reader, for making the most of damaged or
+    /// carelessly written files: errors are recovered from, the end of the
+    /// input may be truncated or followed by junk, missing or wrong lengths
+    /// are warned about, keys are canonicalized and invalid UTF-8 is read as
+    /// Latin-1
+    pub fn lenient() -> ParserOptions {
+        let mut options = ParserOptions::new();
+        options
+            .recover_errors(true)
+            .lenient_ending(true)
+            .length_mismatch(LengthMismatch::WarnAndTrustSequence)
+            .derive_length(true)
+            .key_normalization(KeyNormalization::Canonicalize)
+            .text_encoding(TextEncoding::Latin1Fallback);
+        options
+    }
+}
+
+/// Iterator over the records of a GenBank file, parsing one record at a
+/// time so that files of any size can be processed in constant memory (apart
+/// from the largest record). Works on any `Read`, e.g. a decompressor for
+/// `.gbff.gz` files.
 #[derive(Debug)]
 pub struct SeqReader<T: Read> {
     parser: StreamParser<T>,
+    lazy_sequences: bool,
+    lazy_seq: Option<LazySeq>,
 }
 
 impl<T: Read> Iterator for SeqReader<T> {
     type Item = Result<Seq, GbParserError>;
 
     fn next(&mut self) -> Option<Result<Seq, GbParserError>> {
+        self.lazy_seq = None;
         match self.parser.read_one_record() {
-            Ok(Some(seq)) => Some(Ok(seq)),
+            Ok(Some(mut seq)) => {
+                if self.lazy_sequences {
+                    self.lazy_seq = self
+                        .parser
+                        .take_skipped_sequence()
+                        .map(|(offset, len)| LazySeq { offset, len });
+                    if let Some(lazy) = self.lazy_seq {
+                        seq.len = Some(lazy.len);
+                    }
+                }
+                Some(Ok(seq))
+            }
             Ok(None) => None,
             Err(e) => Some(Err(e)),
         }
@@ -33,16 +303,421 @@ impl<T: Read> SeqReader<T> {
     pub fn new(data: T) -> SeqReader<T> {
         SeqReader {
             parser: StreamParser::new(data, READ_BUF_SIZE),
+            lazy_sequences: false,
+            lazy_seq: None,
+        }
+    }
+
+    /// Parse a stream with the given settings
+    pub fn with_options(data: T, options: &ParserOptions) -> SeqReader<T> {
+        let mut reader = SeqReader::new(data);
+        reader.set_options(options);
+        reader
+    }
+
+    /// Replace all the settings with `options`
+    pub fn set_options(&mut self, options: &ParserOptions) -> &mut Self {
+        let p = &mut self.parser;
+        p.key_normalization = options.key_normalization;
+        p.definition_joining = options.definition_joining;
+        p.recover_errors = options.recover_errors;
+        p.length_mismatch = options.length_mismatch;
+        p.lenient_ending = options.lenient_ending;
+        p.strict = options.strict;
+        p.derive_length = options.derive_length;
+        p.display_hints = options.display_hints;
+        p.provenance_source = options.provenance_source.clone();
+        p.skip_sequence = options.skip_sequence || options.lazy_sequences;
+        p.limits = options.limits;
+        p.text_encoding = options.text_encoding;
+        self.lazy_sequences = options.lazy_sequences;
+        self
+    }
+
+    /// Set whether feature and qualifier keys are canonicalized, see
+    /// `KeyNormalization`
+    pub fn key_normalization(&mut self, normalization: KeyNormalization) -> &mut Self {
+        self.parser.key_normalization = normalization;
+        self
+    }
+
+    /// Set how multi-line DEFINITIONs are joined, see `DefinitionJoining`
+    pub fn definition_joining(&mut self, joining: DefinitionJoining) -> &mut Self {
+        self.parser.definition_joining = joining;
+        self
+    }
+
+    /// Carry on after a record which can't be parsed, skipping to the `//`
+    /// ending it. The error is still returned, and the iterator then moves
+    /// on to the next record rather than failing over and over. Off by
+    /// default.
+    pub fn recover_errors(&mut self, recover: bool) -> &mut Self {
+        self.parser.recover_errors = recover;
+        self
+    }
+
+    /// Set what happens when the LOCUS line's length doesn't match the
+    /// sequence, see `LengthMismatch`. Either way, `Seq::len` agrees with
+    /// the sequence of the records returned.
+    pub fn length_mismatch(&mut self, policy: LengthMismatch) -> &mut Self {
+        self.parser.length_mismatch = policy;
+        self
+    }
+
+    /// Be lenient about how the input ends, for files that were truncated or
+    /// carelessly concatenated. A last record missing its `//` is accepted
+    /// with a warning, even if its sequence is shorter than the LOCUS line
+    /// says, and anything after the last record that can't be parsed is
+    /// skipped, with a warning giving its length and start. Off by default.
+    pub fn lenient_ending(&mut self, lenient: bool) -> &mut Self {
+        self.parser.lenient_ending = lenient;
+        self
+    }
+
+    /// Reject records which don't follow the INSDC feature table definition
+    /// and GenBank's layout exactly, for checking files before submitting
+    /// them. In strict mode:
+    ///
+    /// * the LOCUS line must have every field in its fixed columns
+    /// * feature keys must be standard, and each feature must have its
+    ///   mandatory qualifiers and only the qualifiers allowed on it (for
+    ///   proteins, only the feature keys are checked)
+    /// * the sequence must be as long as the LOCUS line says, whatever the
+    ///   `length_mismatch` policy
+    ///
+    /// Errors give the line of the LOCUS line or feature concerned. Off by
+    /// default.
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.parser.strict = strict;
+        self
+    }
+
+    /// Accept records whose LOCUS line has no length, or one that can't be
+    /// read (e.g. `2,686 bp`), taking the length from the sequence instead,
+    /// as is needed for some hand-edited files. The rest of the LOCUS line
+    /// is read as well as possible. A length that doesn't match the
+    /// sequence is then a warning rather than an error, as with
+    /// `LengthMismatch::WarnAndTrustSequence`, unless another
+    /// `length_mismatch` policy is set. The warnings can be collected with
+    /// `next_with_diagnostics`. Off by default.
+    pub fn derive_length(&mut self, derive: bool) -> &mut Self {
+        self.parser.derive_length = derive;
+        self
+    }
+
+    /// Fill in `Feature::display_hints` for features with display
+    /// qualifiers from SnapGene, ApE or Benchling, so they can be edited
+    /// and written in another editor's dialect, see
+    /// `SeqWriter::display_dialect`. Off by default.
+    pub fn read_display_hints(&mut self, read: bool) -> &mut Self {
+        self.parser.display_hints = read;
+        self
+    }
+
+    /// Skip the sequences, returning records with only their headers and
+    /// features, which is quicker if the sequence isn't needed. `seq` is
+    /// left empty, with `len` giving the length from the LOCUS line. See
+    /// `lazy_sequences` for reading them later. Off by default.
+    pub fn skip_sequence(&mut self, skip: bool) -> &mut Self {
+        self.parser.skip_sequence = skip;
+        self
+    }
+
+    /// Set how text which isn't valid UTF-8 is decoded, see `TextEncoding`.
+    /// When anything is replaced, byte offsets after it (in errors,
+    /// `Provenance` and `LazySeq`) count the decoded text rather than the
+    /// input, so a `LazySeq` may no longer be loaded from the input.
+    pub fn text_encoding(&mut self, encoding: TextEncoding) -> &mut Self {
+        self.parser.text_encoding = encoding;
+        self
+    }
+
+    /// Set the limits on how much is read, see `Limits`
+    pub fn limits(&mut self, limits: Limits) -> &mut Self {
+        self.parser.limits = limits;
+        self
+    }
+
+    /// Record the `Provenance` (line and byte offset) of each feature.
+    /// `source` describes the input, e.g. its path.
+    pub fn track_provenance(&mut self, source: Option<&str>) -> &mut Self {
+        self.parser.track_provenance(source.map(String::from));
+        self
+    }
+
+    /// Like `next`, but warnings are reported to `sink` rather than logged
+    pub fn next_with_diagnostics(
+        &mut self,
+        sink: &mut dyn DiagnosticSink,
+    ) -> Option<Result<Seq, GbParserError>> {
+        self.parser.set_collect_warnings(true);
+        let res = self.next();
+        for w in self.parser.take_warnings() {
+            sink.report(w);
+        }
+        self.parser.set_collect_warnings(false);
+        res
+    }
+
+    /// Parse all remaining records, keeping any warnings for each record
+    /// rather than logging them. Parsing stops at the first error, which is
+    /// recorded in the last `RecordReport`, unless `recover_errors` is set.
+    pub fn read_all_with_diagnostics(mut self) -> ParseReport {
+        self.parser.set_collect_warnings(true);
+        let mut report = ParseReport {
+            records: Vec::new(),
+            warnings: Vec::new(),
+        };
+        loop {
+            let res = self.parser.read_one_record();
+            let warnings = self.parser.take_warnings();
+            match res {
+                Ok(Some(seq)) => report.records.push(RecordReport {
+                    seq: Some(seq),
+                    warnings,
+                    error: None,
+                }),
+                Ok(None) => {
+                    report.warnings = warnings;
+                    break;
+                }
+                Err(e) => {
+                    let recovered = self.parser.recover_errors
+                        && e.is_syntax_error();
+                    report.records.push(RecordReport {
+                        seq: None,
+                        warnings,
+                        error: Some(e),
+                    });
+                    if !recovered {
+                        break;
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Where the sequence of the record just returned is, if
+    /// `ParserOptions::lazy_sequences` is set and it has one
+    pub fn lazy_seq(&self) -> Option<LazySeq> {
+        self.lazy_seq
+    }
+
+    /// Read the remaining records without their sequences, noting where
+    /// each sequence is instead so it can be loaded later if it's needed.
+    /// The sequence data still has to be scanned to find the end of the
+    /// record, but isn't copied.
+    pub fn lazy_sequences(mut self) -> LazySeqReader<T> {
+        self.parser.skip_sequence = true;
+        self.lazy_sequences = true;
+        LazySeqReader { reader: self }
+    }
+
+    /// Parse the remaining records, passing their contents to `handler` as
+    /// they're read rather than building a `Seq` for each. Useful for
+    /// records too large to hold in memory.
+    pub fn parse_with<H: Handler + ?Sized>(&mut self, handler: &mut H) -> Result<(), GbParserError> {
+        while self.parser.parse_one_record(handler)? {}
+        Ok(())
+    }
+}
+
+/// The outcome of parsing a single record, see `ParseReport`
+#[derive(Debug)]
+pub struct RecordReport {
+    /// `None` if the record couldn't be parsed
+    pub seq: Option<Seq>,
+    /// Recoverable problems found while parsing the record
+    pub warnings: Vec<Diagnostic>,
+    pub error: Option<GbParserError>,
+}
+
+/// The outcome of parsing a whole file, returned by
+/// `SeqReader::read_all_with_diagnostics` and `parse_file_with_diagnostics`
+#[derive(Debug)]
+pub struct ParseReport {
+    pub records: Vec<RecordReport>,
+    /// Warnings that couldn't be attributed to a record, e.g. about junk at
+    /// the end of the file
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl ParseReport {
+    /// True if every record was parsed without errors (there may still be
+    /// warnings)
+    pub fn is_ok(&self) -> bool {
+        self.records.iter().all(|r| r.error.is_none())
+    }
+
+    /// The records that were parsed successfully
+    pub fn seqs(&self) -> impl Iterator<Item = &Seq> {
+        self.records.iter().filter_map(|r| r.seq.as_ref())
+    }
+}
+
+/// A stream which is decompressed on the fly if it's gzip compressed, which
+/// is detected from its first bytes. Files compressed with `bgzip` are also
+/// supported, as they're a series of gzip streams.
+#[cfg(feature = "gzip")]
+#[derive(Debug)]
+pub enum Decompressed<T: Read> {
+    Plain(BufReader<Sniffed<T>>),
+    Gzip(MultiGzDecoder<BufReader<Sniffed<T>>>),
+}
+
+/// A stream with the bytes read to detect what it holds (compression, see
+/// `Decompressed`, or the format, see `RecordReader`) put back in front of it
+pub type Sniffed<T> = std::io::Chain<std::io::Cursor<Vec<u8>>, T>;
+
+#[cfg(feature = "gzip")]
+impl<T: Read> Decompressed<T> {
+    pub fn new(mut data: T) -> std::io::Result<Decompressed<T>> {
+        // a single read may return less than the whole magic number
+        let mut magic = Vec::with_capacity(2);
+        (&mut data).take(2).read_to_end(&mut magic)?;
+        let gzip = magic == [0x1f, 0x8b];
+        let data = BufReader::new(std::io::Cursor::new(magic).chain(data));
+        if gzip {
+            Ok(Decompressed::Gzip(MultiGzDecoder::new(data)))
+        } else {
+            Ok(Decompressed::Plain(data))
         }
     }
 }
 
+#[cfg(feature = "gzip")]
+impl<T: Read> Read for Decompressed<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Decompressed::Plain(r) => r.read(buf),
+            Decompressed::Gzip(r) => r.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<T: Read> SeqReader<Decompressed<T>> {
+    /// Like `new`, but the stream is decompressed first if it's gzipped
+    pub fn decompressing(data: T) -> std::io::Result<Self> {
+        Ok(SeqReader::new(Decompressed::new(data)?))
+    }
+}
+
+impl<T: Read> SeqReader<VendorFixes<T>> {
+    /// Like `new`, but tolerates the deviations from the format found in
+    /// files exported by sequence editors (SnapGene, ApE, Vector NTI,
+    /// Benchling...):
+    ///
+    /// * keywords such as `features` and `origin` in lower case
+    /// * the topology in upper or mixed case, e.g. `CIRCULAR`
+    /// * LOCUS dates such as `01-Jan-2020` or `2020-01-01`
+    /// * feature tables indented with tabs
+    ///
+    /// Missing LOCUS fields and odd spacing are accepted in any case.
+    pub fn with_vendor_fixes(data: T) -> Self {
+        SeqReader::new(VendorFixes::new(data))
+    }
+}
+
+/// Where a record's sequence is in the input it was read from, so that it
+/// can be loaded when it's needed, see `SeqReader::lazy_sequences`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LazySeq {
+    /// Offset of the first line of sequence data, counted from where the
+    /// reader started reading
+    pub offset: u64,
+    /// The number of bases
+    pub len: usize,
+}
+
+impl LazySeq {
+    /// Read the sequence from `input`, which must hold the data the record
+    /// was read from, starting at the same position
+    pub fn load<R: Read + Seek>(&self, mut input: R) -> Result<Vec<u8>, GbParserError> {
+        input.seek(SeekFrom::Start(self.offset))?;
+        let mut input = BufReader::new(input);
+        let mut seq = Vec::with_capacity(self.len);
+        let mut line = Vec::new();
+        while seq.len() < self.len {
+            line.clear();
+            if input.read_until(b'\n', &mut line)? == 0 || line.starts_with(b"//") {
+                break;
+            }
+            seq.extend(line.iter().filter(|&&b| nom_parsers::is_residue(b)));
+        }
+        if seq.len() != self.len {
+            return Err(GbParserError::SyntaxError(format!(
+                "Expected {} bases at offset {}, found {}",
+                self.len,
+                self.offset,
+                seq.len()
+            )));
+        }
+        Ok(seq)
+    }
+}
+
+/// Iterator over records whose sequences are left out, returned by
+/// `SeqReader::lazy_sequences`. Each record comes with the location of its
+/// sequence, if it has one, and its `len` is set to the sequence length.
+#[derive(Debug)]
+pub struct LazySeqReader<T: Read> {
+    reader: SeqReader<T>,
+}
+
+impl<T: Read> Iterator for LazySeqReader<T> {
+    type Item = Result<(Seq, Option<LazySeq>), GbParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.reader.next()?;
+        Some(res.map(|seq| (seq, self.reader.lazy_seq())))
+    }
+}
+
+/// Pull parser returning records from a stream a `Token` at a time, for
+/// callers who want to build their own data structures rather than `Seq`s
+#[derive(Debug)]
+pub struct TokenReader<T: Read> {
+    parser: TokenParser<T>,
+}
+
+impl<T: Read> TokenReader<T> {
+    pub fn new(data: T) -> TokenReader<T> {
+        TokenReader {
+            parser: TokenParser::new(data, READ_BUF_SIZE),
+        }
+    }
+}
+
+impl<T: Read> Iterator for TokenReader<T> {
+    type Item = Result<Token, GbParserError>;
+
+    fn next(&mut self) -> Option<Result<Token, GbParserError>> {
+        self.parser.next_token().transpose()
+    }
+}
+
 /// Convenience method to parse an entire file at once. Uses the streaming parser.
+/// With the `gzip` feature, compressed files are decompressed.
 pub fn parse_file<P: AsRef<::std::path::Path>>(path: P) -> Result<Vec<Seq>, GbParserError> {
     let file = ::std::fs::File::open(path)?;
+    #[cfg(feature = "gzip")]
+    let file = Decompressed::new(file)?;
     SeqReader::new(file).collect()
 }
 
+/// Like `parse_file`, but returns any warnings and errors for each record
+/// instead of logging them
+pub fn parse_file_with_diagnostics<P: AsRef<::std::path::Path>>(
+    path: P,
+) -> Result<ParseReport, GbParserError> {
+    let file = ::std::fs::File::open(path)?;
+    #[cfg(feature = "gzip")]
+    let file = Decompressed::new(file)?;
+    Ok(SeqReader::new(file).read_all_with_diagnostics())
+}
+
 /// Parse an entire genbank file provided as a slice. Might be slightly faster
 /// than the streaming parser used by `parse_file` and `SeqReader::from_stream` 
 /// since less copying of data is required, however not as well tested. I recommend using
@@ -70,4 +745,26 @@ pub (crate) fn parse_location(data: &[u8]) -> Result<Location, GbParserError> {
             ))
         }
     }
-}
\ No newline at end of file
+}
+
+/// Parse the lines of a feature table laid out as in GenBank files, without
+/// the FEATURES line. Used by the EMBL reader.
+pub (crate) fn parse_feature_table(data: &[u8]) -> Result<Vec<Feature>, GbParserError> {
+    // the parsers need something after the last feature to know it's ended
+    let mut input = data.to_vec();
+    input.extend_from_slice(b"//\n");
+    let mut rest = &input[..];
+    let mut features = Vec::new();
+    while let Ok((r, f)) = nom_parsers::feature(rest) {
+        features.push(f);
+        rest = r;
+    }
+    if rest != b"//\n" {
+        let line = rest.split(|&b| b == b'\n').next().unwrap_or_default();
+        return Err(GbParserError::SyntaxError(format!(
+            "Can't parse feature table at: {}",
+            String::from_utf8_lossy(line)
+        )));
+    }
+    Ok(features)
+}
@@ -3,9 +3,11 @@ use nom;
 use nom::types::CompleteByteSlice;
 use nom::{alpha, digit, line_ending, multispace, not_line_ending, space, IResult};
 use crate::seq::{
-    After, Before, Date, Feature, FeatureKind, Location, QualifierKey, Reference, Seq, Source, Topology,
-    REASONABLE_SEQ_LEN, GapLength
+    AccessionRange, After, Alphabet, BaseCount, Before, Date, Division, Feature, FeatureKind,
+    Location, MoleculeType, QualifierKey, Reference, Seq, Source, Topology, REASONABLE_SEQ_LEN,
+    GapLength
 };
+use std::borrow::Cow;
 use std::cmp;
 use std::iter::once;
 use std::str;
@@ -51,15 +53,17 @@ fn space_indent(i: &[u8], indent: usize) -> IResult<&[u8], ()> {
 
 // LOCUS
 
-/// Internal type for convenience, we move everything into the `Seq` struct later
-#[derive(Debug)]
+/// The contents of a LOCUS line. Moved into the `Seq` struct by the parsers,
+/// but returned as-is by `TokenReader`.
+#[derive(Debug, PartialEq, Clone)]
 pub struct Locus {
     pub name: Option<String>,
     pub len: Option<usize>,
     pub topology: Topology,
     pub date: Option<Date>,
-    pub molecule_type: Option<String>,
-    pub division: String,
+    pub alphabet: Alphabet,
+    pub molecule_type: Option<MoleculeType>,
+    pub division: Division,
 }
 
 named!(
@@ -129,6 +133,7 @@ named!(
                     len: Some(len),
                     topology,
                     date,
+                    alphabet: Alphabet::Nucleotide,
                     molecule_type: Some(mol.into()),
                     division: section.unwrap_or("UNK").into(),
                 })
@@ -151,6 +156,7 @@ named!(
                 len: Some(len),
                 topology: Topology::Linear,
                 date: Some(date),
+                alphabet: Alphabet::Nucleotide,
                 molecule_type: Some(mol.into()),
                 division: section.into(),
             })
@@ -158,48 +164,158 @@ named!(
     )
 );
 
+// GenPept, lengths are in residues and there's usually no molecule type
+named!(
+    locus_protein<Locus>,
+    sep!(
+        spaces,
+        do_parse!(
+            name: locus_name
+                >> len: numeric_usize
+                >> tag!("aa")
+                >> topology: opt!(topology)
+                >> section: opt!(to_str!(alpha))
+                >> date: opt!(date)
+                >> (Locus {
+                    name: Some(String::from(name)),
+                    len: Some(len),
+                    topology: topology.unwrap_or(Topology::Linear),
+                    date,
+                    alphabet: Alphabet::Protein,
+                    molecule_type: None,
+                    division: section.unwrap_or("UNK").into(),
+                })
+        )
+    )
+);
+
 // Just give up :)
 named!(
-    locus_tag_only<Locus>,
+    locus_tag_only<(Locus, Option<String>)>,
     do_parse!(
         stuff: opt!(to_str!(not_line_ending))
-            >> ({
-                warn!("Failed to parse: {:?}", stuff.unwrap_or_default()); //TODO: fix
+            >> ((
                 Locus {
                     name: None,
                     len: None,
                     topology: Topology::Linear,
                     date: None,
+                    alphabet: Alphabet::Nucleotide,
                     molecule_type: None,
-                    division: "UNK".into(),
-                }
-            })
+                    division: Division::default(),
+                },
+                Some(format!("Failed to parse: {:?}", stuff.unwrap_or_default())), //TODO: fix
+            ))
     )
 );
 
 // Added this to skip header info, for example in genbank .seq files. Not sure
-// if it's the best/most correct solution
-named!(pub skip_preamble<()>,
+// if it's the best/most correct solution. Returns the skipped lines.
+named!(pub skip_preamble_lines<Vec<String>>,
     fold_many0!(
         do_parse!(
             not!(tag!("LOCUS")) >>
                 ignored_line: to_str!(not_line_ending) >>
                 line_ending >>
                 (ignored_line)
-        ), (),
-        | _, i | warn!("Ignoring line: {}", i)
+        ), Vec::new(),
+        | mut acc: Vec<String>, i: &str | { acc.push(i.into()); acc }
     )
 );
 
+named!(pub skip_preamble<()>,
+    map!(skip_preamble_lines, |lines| for l in lines {
+        warn!("Ignoring line: {}", l)
+    })
+);
+
+// The next line, without its line ending, leaving the input as it was
+named!(pub peek_line<Vec<u8>>,
+    peek!(map!(terminated!(not_line_ending, line_ending), |l: &[u8]| l.to_vec()))
+);
+
+// Also returns a warning if we couldn't make sense of the line
 named!(
-    pub locus<Locus>,
+    pub locus_with_warning<(Locus, Option<String>)>,
     do_parse!(
         tag!("LOCUS") >> space
-        >> metadata: alt!(locus_full | locus_traditional | locus_tag_only)
+        >> metadata: alt!(
+            locus_full => { |l| (l, None) } |
+            locus_traditional => { |l| (l, None) } |
+            locus_protein => { |l| (l, None) } |
+            locus_tag_only
+        )
         >> line_ending >> (metadata)
     )
 );
 
+/// Makes what it can of a LOCUS line whose length is missing or malformed,
+/// e.g. `LOCUS pUC19 DNA circular`, for `SeqReader::derive_length`. Words
+/// are recognised by what they look like rather than where they are.
+/// Returns `None` if there isn't even a name, and otherwise a warning about
+/// the length.
+pub fn locus_without_length(line: &str) -> Option<(Locus, Option<String>)> {
+    let mut words = line.strip_prefix("LOCUS")?.split_whitespace();
+    let mut locus = Locus {
+        name: Some(words.next()?.to_string()),
+        len: None,
+        topology: Topology::Linear,
+        date: None,
+        alphabet: Alphabet::Nucleotide,
+        molecule_type: None,
+        division: Division::default(),
+    };
+    let mut malformed = None;
+    let mut division = None;
+    for word in words {
+        let upper = word.to_ascii_uppercase();
+        match word {
+            "bp" => {}
+            "aa" => locus.alphabet = Alphabet::Protein,
+            "linear" => locus.topology = Topology::Linear,
+            "circular" => locus.topology = Topology::Circular,
+            _ if upper.contains("DNA") || upper.contains("RNA") => {
+                locus.molecule_type = Some(word.into())
+            }
+            _ => {
+                if let Ok((b" ", d)) = date(format!("{} ", word).as_bytes()) {
+                    locus.date = Some(d);
+                } else if let (None, Ok(len)) = (locus.len, word.parse()) {
+                    locus.len = Some(len);
+                } else if word.bytes().any(|b| b.is_ascii_digit()) {
+                    malformed.get_or_insert(word);
+                } else if word.bytes().all(|b| b.is_ascii_uppercase()) {
+                    division.get_or_insert(word);
+                }
+            }
+        }
+    }
+    if let Some(d) = division {
+        locus.division = d.into();
+    }
+    let warning = match (locus.len, malformed) {
+        (Some(_), _) => None,
+        (None, Some(word)) => Some(format!(
+            "Malformed length {:?} in LOCUS line, taking the length from the sequence",
+            word
+        )),
+        (None, None) => {
+            Some("No length in LOCUS line, taking the length from the sequence".to_string())
+        }
+    };
+    Some((locus, warning))
+}
+
+named!(
+    pub locus<Locus>,
+    map!(locus_with_warning, |(l, w)| {
+        if let Some(w) = w {
+            warn!("{}", w);
+        }
+        l
+    })
+);
+
 // Metadata
 
 named_args!(field<'a>(indent: usize, name: &str, keep_ws: bool) <String>,
@@ -256,6 +372,7 @@ named!(
             >> pubmed: opt!(apply!(field, 3, "PUBMED", false))
             >> remark: opt!(apply!(field, 2, "REMARK", true))
             >> (Reference {
+                bases: Reference::parse_bases(&description),
                 description,
                 authors,
                 consortium,
@@ -267,17 +384,30 @@ named!(
     )
 );
 
+/// A top-level header field, as returned by `TokenReader`
 // These are in CAPS so we can stringify! them and match the tags directly
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
 pub enum Field {
     DEFINITION(String),
     ACCESSION(String),
     VERSION(String),
+    NID(String),
     DBLINK(String),
+    DBSOURCE(String),
     KEYWORDS(String),
+    SEGMENT(String),
     SOURCE(Source),
     REFERENCE(Reference),
     COMMENT(String),
+    /// The accession ranges of master records, which usually come after
+    /// the features
+    WGS(String),
+    WGS_SCAFLD(String),
+    TSA(String),
+    TLS(String),
+    /// The counts of each base, as written in the BASE COUNT line
+    BASE_COUNT(String),
     UnrecognisedLine(String),
 }
 
@@ -302,17 +432,32 @@ named!(
     )
 );
 
+named!(
+    pub master_line<Field>,
+    alt!(
+        parse_field!(WGS_SCAFLD, false) |
+        parse_field!(WGS, false) |
+        parse_field!(TSA, false) |
+        parse_field!(TLS, false)
+    )
+);
+
 named!(
     pub any_field<Field>,
     alt!(
         parse_field!(DEFINITION, true) |
         parse_field!(ACCESSION, true)  |
         parse_field!(VERSION, true)    |
+        parse_field!(NID, true)        |
         parse_field!(DBLINK, true)     |
+        parse_field!(DBSOURCE, true)   |
         parse_field!(KEYWORDS, true)   |
+        parse_field!(SEGMENT, true)    |
         source |
         call!(reference) => { Field::REFERENCE } |
         parse_field!(COMMENT, true) |
+        master_line |
+        base_count |
         //TODO: unrecognised lines?
         do_parse!(line: ignored_line >>
                     (Field::UnrecognisedLine(line.into())))
@@ -321,8 +466,25 @@ named!(
 
 named!(fields<Vec<Field>>, many0!(any_field));
 
-/// Create a new Seq with metadata from a Vec<Field>
-pub fn fill_seq_fields(mut seq: Seq, fields: Vec<Field>) -> Result<Seq, String> {
+/// Create a new Seq with metadata from a Vec<Field>. Lines we couldn't parse
+/// are passed to `on_warning`.
+pub fn fill_seq_fields(
+    mut seq: Seq,
+    fields: Vec<Field>,
+    mut on_warning: impl FnMut(String),
+) -> Result<Seq, String> {
+    for item in fields {
+        fill_seq_field(&mut seq, item, &mut on_warning)?;
+    }
+    Ok(seq)
+}
+
+/// Adds a field to `seq`, see `fill_seq_fields`
+pub fn fill_seq_field(
+    seq: &mut Seq,
+    item: Field,
+    mut on_warning: impl FnMut(String),
+) -> Result<(), String> {
     // TODO: Use real errors once we have a way to return them through nom
     // helper function
     fn set_once<T>(field: &str, val: &mut Option<T>, newval: T) -> Result<(), String> {
@@ -334,37 +496,53 @@ pub fn fill_seq_fields(mut seq: Seq, fields: Vec<Field>) -> Result<Seq, String>
             }
         }
     }
+    let ranges = |s: String| s.split_whitespace().map(AccessionRange::parse).collect::<Vec<_>>();
 
-    for item in fields {
-        match item {
-            Field::DEFINITION(item) => set_once("DEFINITION", &mut seq.definition, item)?,
-            Field::ACCESSION(item) => {
-                seq.accession = Some(item);
-            }
-            Field::VERSION(item) => {
-                seq.version = Some(item);
-            }
-            Field::DBLINK(item) => {
-                seq.dblink = Some(item);
-            }
-            Field::KEYWORDS(item) => {
-                seq.keywords = Some(item);
-            }
-            Field::SOURCE(item) => {
-                seq.source = Some(item);
-            }
-            Field::REFERENCE(item) => {
-                seq.references.push(item);
-            }
-            Field::COMMENT(item) => {
-                seq.comments.push(item);
-            }
-            Field::UnrecognisedLine(line) => {
-                warn!("Unable to parse: {}", line);
-            }
+    match item {
+        Field::DEFINITION(item) => set_once("DEFINITION", &mut seq.definition, item)?,
+        Field::ACCESSION(item) => {
+            seq.accession = Some(item);
+        }
+        Field::VERSION(item) => {
+            seq.version = Some(item);
+        }
+        Field::NID(item) => {
+            seq.nid = Some(item);
+        }
+        Field::DBLINK(item) => {
+            seq.dblink = Some(item);
+        }
+        Field::DBSOURCE(item) => {
+            seq.dbsource = Some(item);
+        }
+        Field::KEYWORDS(item) => {
+            seq.keywords = Some(item);
+        }
+        Field::SEGMENT(item) => {
+            seq.segment = Some(item);
+        }
+        Field::SOURCE(item) => {
+            seq.source = Some(item);
+        }
+        Field::REFERENCE(item) => {
+            seq.references.push(item);
+        }
+        Field::COMMENT(item) => {
+            seq.comments.push(item);
+        }
+        Field::WGS(item) => seq.wgs.extend(ranges(item)),
+        Field::WGS_SCAFLD(item) => seq.wgs_scafld.extend(ranges(item)),
+        Field::TSA(item) => seq.tsa.extend(ranges(item)),
+        Field::TLS(item) => seq.tls.extend(ranges(item)),
+        Field::BASE_COUNT(item) => match BaseCount::parse(&item) {
+            Some(counts) => seq.base_count = Some(counts),
+            None => on_warning(format!("Unable to parse BASE COUNT: {}", item)),
+        },
+        Field::UnrecognisedLine(line) => {
+            on_warning(format!("Unable to parse: {}", line));
         }
     }
-    Ok(seq)
+    Ok(())
 }
 
 // Features table
@@ -382,20 +560,20 @@ named!(
     )
 );
 
-/// Used by `qualifier_value_bare` and `contig`
-fn qualifier_value_bare_bytes(input: &[u8], indent: usize) -> IResult<&[u8], Vec<u8>> {
+/// Used by `qualifier_value_bare` and `pos_text`. Borrows from the input
+/// unless the value spans multiple lines.
+fn qualifier_value_bare_bytes(input: &[u8], indent: usize) -> IResult<&[u8], Cow<'_, [u8]>> {
     let mut i = input;
-    let mut res = Vec::with_capacity(300);
+    let mut res = Cow::Borrowed(&input[..0]);
     loop {
-        let mut consumed = 0;
-        for &b in i {
-            match b {
-                b'\r' | b'\n' => break,
-                x => {
-                    res.push(x);
-                    consumed += 1;
-                }
-            }
+        let consumed = i
+            .iter()
+            .position(|&b| b == b'\r' || b == b'\n')
+            .unwrap_or(i.len());
+        if res.is_empty() {
+            res = Cow::Borrowed(&i[..consumed]);
+        } else {
+            res.to_mut().extend_from_slice(&i[..consumed]);
         }
         i = &i[consumed..];
         if i.is_empty() {
@@ -422,7 +600,7 @@ fn pos_text(i: &[u8], indent: usize) -> IResult<&[u8], Location> {
     map_res!(
         i,
         apply!(qualifier_value_bare_bytes, indent),
-        |r: Vec<u8>| match location(CompleteByteSlice(&r)) {
+        |r: Cow<[u8]>| match location(CompleteByteSlice(&r)) {
             Ok((_, p)) => Ok(p),               // TODO: Check we're matching the whole input
             Err(e) => Err(format!("{:?}", e)), // TODO: Pass the error on somehow
         }
@@ -433,9 +611,12 @@ fn pos_text(i: &[u8], indent: usize) -> IResult<&[u8], Location> {
 // in some cases.
 // see: http://www.insdc.org/documents/feature_table.html
 fn qualifier_value_bare(i: &[u8], indent: usize) -> IResult<&[u8], String> {
-    let (i, mut s) = to_string!(i, apply!(qualifier_value_bare_bytes, indent))?;
-    s.shrink_to_fit();
-    Ok((i, s))
+    map_res_custom_error!(
+        i,
+        NomParserError::Utf8Error,
+        apply!(qualifier_value_bare_bytes, indent),
+        |r: Cow<[u8]>| str::from_utf8(&r).map(String::from)
+    )
 }
 
 named_args!(qualifier_value_quoted_escape<'a>(indent: usize) <u8>,
@@ -459,6 +640,20 @@ fn qualifier_value_quoted<'a>(
     kind: &QualifierKind,
 ) -> IResult<&'a [u8], String> {
     let (mut i, _) = tag!(input, "\"")?;
+    // Fast path: most values fit on a single line and contain no escaped
+    // quotes, so we can skip the intermediate buffer and copy them directly
+    if let Some(end) = i.iter().position(|&b| b == b'"' || b == b'\r' || b == b'\n') {
+        if i[end] == b'"' && i.get(end + 1).is_some_and(|&b| b != b'"') {
+            if let Ok((j, _)) = line_ending(&i[end + 1..]) {
+                // a `""` escape split over a line boundary needs the slow path
+                let split_escape = pair!(j, apply!(space_indent, indent), tag!("\""));
+                if let Err(nom::Err::Error(_)) = split_escape {
+                    let (_, s) = to_str!(input, value!(&i[..end]))?;
+                    return Ok((j, s.to_owned()));
+                }
+            }
+        }
+    }
     let mut res = Vec::with_capacity(2048);
     loop {
         let mut consumed = 0;
@@ -555,7 +750,7 @@ named!(
     )
 );
 
-fn qualifier(i: &[u8], indent: usize) -> IResult<&[u8], (QualifierKey, Option<String>)> {
+pub fn qualifier(i: &[u8], indent: usize) -> IResult<&[u8], (QualifierKey, Option<String>)> {
     do_parse!(
         i,
         apply!(space_indent, indent)
@@ -600,26 +795,86 @@ named!(
       )
 );
 
+// The first line of a feature: its kind and location, plus the indent its
+// qualifiers use
 named!(
-    pub feature <Feature>,
+    pub feature_start <(FeatureKind, Location, usize)>,
     do_parse!(
         spaces_before: map!(is_a!(" "), <[_]>::len) >> kind: call!(feature_kind)
             >> spaces_after: map!(is_a!(" "), <[_]>::len)
             >> indent: value!(spaces_before + kind.len() + spaces_after)
             >> location: apply!(pos_text, indent)
-            >> qualifiers: apply!(qualifiers, indent) >> (Feature {
-            kind,
-            location,
-            qualifiers,
-        })
+            >> (kind, location, indent)
     )
 );
 
 named!(
-    features<Vec<Feature>>,
-    do_parse!(features_header >> features: many0!(feature) >> (features))
+    pub feature <Feature>,
+    do_parse!(
+        start: feature_start
+            >> qualifiers: apply!(qualifiers, start.2)
+            >> (Feature::new(start.0, start.1, qualifiers))
+    )
 );
 
+/// Rough number of base pairs per feature in a densely annotated record
+/// (E. coli MG1655 has one feature every ~500 bp)
+const BP_PER_FEATURE: usize = 500;
+
+/// Upper bound on the number of features we'll preallocate space for from the
+/// LOCUS length, so that a large but sparsely annotated record doesn't waste
+/// memory
+const MAX_FEATURES_PREALLOC: usize = 100 * 1000;
+
+/// Count the features in `table`, the data following the FEATURES line, by
+/// their key lines. Also returns whether the end of the table was found.
+fn count_features(table: &[u8]) -> (usize, bool) {
+    // leave out the last line if it's incomplete
+    let table = match table.iter().rposition(|&b| b == b'\n') {
+        Some(end) => &table[..end],
+        None => return (0, false),
+    };
+    let mut count = 0;
+    for line in table.split(|&b| b == b'\n') {
+        match line.iter().position(|&b| b != b' ') {
+            Some(5) => count += 1,
+            Some(0) if line != b"\r" => return (count, true),
+            _ => {}
+        }
+    }
+    (count, false)
+}
+
+/// Guess how many features a record will have, to preallocate the feature
+/// `Vec`. `table` is the data following the FEATURES line, and the features
+/// in it are counted. If that's not the whole table, e.g. because the
+/// streaming parser hasn't read it yet, we fall back on a guess from the
+/// length given in the LOCUS line, since the only other way to find out is
+/// to read the table.
+pub fn estimate_feature_count(table: &[u8], len: Option<usize>) -> usize {
+    match count_features(table) {
+        (count, true) => count,
+        (count, false) => {
+            let guess = len.map_or(0, |len| len / BP_PER_FEATURE + 1);
+            cmp::max(count, cmp::min(guess, MAX_FEATURES_PREALLOC))
+        }
+    }
+}
+
+fn features(input: &[u8], len: Option<usize>) -> IResult<&[u8], Vec<Feature>> {
+    let (i, _) = features_header(input)?;
+    let capacity = estimate_feature_count(i, len);
+    fold_many0!(
+        i,
+        feature,
+        Vec::with_capacity(capacity),
+        |mut acc: Vec<Feature>, item: Feature| {
+            acc.push(item);
+            acc
+        }
+    )
+}
+
 // Feature locations
 
 named!(
@@ -688,7 +943,7 @@ named!(
 named!(
     pos_external<CompleteByteSlice, Location>,
     do_parse!(
-        accession: to_str!(map!(is_not!(": \t\r\n"), |x| x.0)) // convert to &[u8]
+        accession: to_str!(map!(is_not!(":,() \t\r\n"), |x| x.0)) // convert to &[u8]
             >> location:
                 opt!(preceded!(
                     tag!(":"),
@@ -770,9 +1025,15 @@ named_args!(origin(len: Option<usize>)<(Option<String>, Vec<u8>)>,
 
 named!(
     sequence_chunk,
-    delimited!(opt!(pair!(digit, space)), alpha, multispace)
+    delimited!(opt!(pair!(digit, space)), take_while1!(is_residue), multispace)
 );
 
+/// Whether `b` can be part of a sequence: a letter, or `*` for a stop codon
+/// in protein sequences
+pub fn is_residue(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'*'
+}
+
 named_args!(sequence(len: Option<usize>) <Vec<u8>>,
     map_res_custom_error!(
         NomParserError::SequenceLength,
@@ -802,36 +1063,44 @@ named_args!(sequence(len: Option<usize>) <Vec<u8>>,
     )
 );
 
-// I think this is deprecated but sometimes it comes after features,
-// let's just ignore it.
-named!(pub base_count<()>,
-       value!((), apply!(toplevel_field, "BASE COUNT", false))
+// This was dropped from GenBank in 2013, but older records have it after
+// the features
+named!(pub base_count<Field>,
+       map!(apply!(toplevel_field, "BASE COUNT", false), Field::BASE_COUNT)
 );
 
 named!(
     gb<Seq>,
     do_parse!(
         locus: locus
-            >> metadata: map_res!(fields, |f| fill_seq_fields(Seq::empty(), f))
+            >> metadata: map_res!(fields, |f| fill_seq_fields(Seq::empty(), f, |w| warn!("{}", w)))
             >> many0!(ignored_line)
-            >> features: opt!(features)
-            >> opt!(call!(base_count))
+            >> features: opt!(apply!(features, locus.len))
+            >> master: many0!(master_line)
+            >> base_count: opt!(call!(base_count))
             >> contig: opt!(contig_text)
             >> origin: opt!(apply!(origin, locus.len))
             >> opt!(multispace)
             >> tag!("//")
             >> opt!(complete!(multispace))
-            >> (Seq {
-                name: locus.name,
-                date: locus.date,
-                topology: locus.topology,
-                len: locus.len,
-                molecule_type: locus.molecule_type,
-                division: locus.division,
-                seq: origin.map(|o| o.1).unwrap_or_default(),
-                contig,
-                features: features.unwrap_or_default(),
-                ..metadata
+            >> ({
+                let mut seq = Seq {
+                    name: locus.name,
+                    date: locus.date,
+                    topology: locus.topology,
+                    len: locus.len,
+                    alphabet: locus.alphabet,
+                    molecule_type: locus.molecule_type,
+                    division: locus.division,
+                    seq: origin.map(|o| o.1).unwrap_or_default(),
+                    contig,
+                    features: features.unwrap_or_default(),
+                    ..metadata
+                };
+                for field in master.into_iter().chain(base_count) {
+                    let _ = fill_seq_field(&mut seq, field, |w| warn!("{}", w));
+                }
+                seq
             })
     )
 );
@@ -850,6 +1119,23 @@ mod test {
         assert_eq!(d, Ok((&b"\n"[..], Date::from_ymd(2014, 8, 1).unwrap())))
     }
 
+    #[test]
+    fn test_estimate_feature_count() {
+        let table = b"     source          1..100
+                     /organism=\"x\"
+     gene            1..10
+     CDS             1..10
+ORIGIN
+";
+        assert_eq!(estimate_feature_count(table, Some(1000 * 1000)), 3);
+        assert_eq!(estimate_feature_count(&table[..60], None), 1);
+        assert_eq!(estimate_feature_count(&table[..60], Some(4641652)), 9284);
+        assert_eq!(
+            estimate_feature_count(b"", Some(usize::MAX)),
+            MAX_FEATURES_PREALLOC
+        );
+    }
+
     #[test]
     fn test_gap() {
         assert_eq!(location(CompleteByteSlice(b"gap()")), 
@@ -879,7 +1165,7 @@ mod test {
                 }
             }
             // incomplete locus test
-            incomplete_test(lb, b"", &locus);
+            incomplete_test(lb, b"", locus);
         }
         assert_eq!(
             locus(loci[2].as_bytes()).unwrap().1.topology,
@@ -887,6 +1173,64 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_is_residue() {
+        for &b in b"acgtnACGTNmakvLSP*" {
+            assert!(is_residue(b), "{}", b as char);
+        }
+        for &b in b" 0123456789-.\n/" {
+            assert!(!is_residue(b), "{}", b as char);
+        }
+        let (rest, seq) = sequence(b"1 makv lsp*\n       61 ga\n//\n", Some(10)).unwrap();
+        assert_eq!(seq, b"makvlsp*ga");
+        assert_eq!(rest, b"//\n");
+        assert!(sequence(b"1 mak*\n//\n", Some(3)).is_err());
+    }
+
+    #[test]
+    fn test_locus_protein() {
+        let loci = [
+            "LOCUS       NP_034640     182 aa                    ROD       01-NOV-2000\n",
+            "LOCUS       NP_034640                182 aa            linear   ROD 01-NOV-2000\n",
+            "LOCUS       NP_034640     182 aa\n",
+        ];
+        for l in &loci {
+            let (rest, (locus, warning)) = locus_with_warning(l.as_bytes()).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(warning, None, "{}", l);
+            assert_eq!(locus.name.as_deref(), Some("NP_034640"));
+            assert_eq!(locus.len, Some(182));
+            assert_eq!(locus.alphabet, Alphabet::Protein);
+            assert_eq!(locus.topology, Topology::Linear);
+        }
+        let date = |l: &str| {
+            let (_, (locus, _)) = locus_with_warning(l.as_bytes()).unwrap();
+            locus.date
+        };
+        assert_eq!(date(loci[0]), Date::from_ymd(2000, 11, 1).ok());
+        assert_eq!(date(loci[2]), None);
+    }
+
+    #[test]
+    fn test_genpept_roundtrip() {
+        let data = std::fs::read("tests/biopython_tests/protein_refseq.gb").unwrap();
+        let stop = b"LOCUS       X                          4 aa            linear   UNK 01-JAN-2020
+ORIGIN
+        1 mak*
+//
+";
+        for data in &[&data[..], &stop[..]] {
+            let (_, seqs) = gb_records(data).unwrap();
+            assert_eq!(seqs.len(), 1);
+            assert_eq!(seqs[0].alphabet, Alphabet::Protein);
+            let mut written = Vec::new();
+            crate::writer::write(&mut written, &seqs[0]).unwrap();
+            assert!(str::from_utf8(&written).unwrap().lines().next().unwrap().contains(" aa "));
+            let (_, reparsed) = gb_records(&written).unwrap();
+            assert_eq!(reparsed, seqs);
+        }
+    }
+
     #[test]
     fn test_feature_qualifier() {
         let tests = [
@@ -916,7 +1260,7 @@ mod test {
                         "Complete input not consumed: {}",
                         str::from_utf8(i).unwrap()
                     );
-                    assert!(q.0 == QualifierKey::from(t.1));
+                    assert!(q.0 == *t.1);
                     assert!(q.1 == t.2.map(String::from));
                 }
                 Err(x) => {
@@ -977,7 +1321,7 @@ mod test {
                 panic!("{:?}", e);
             }
         }
-        incomplete_test(f, b"     CDS             ", &feature);
+        incomplete_test(f, b"     CDS             ", feature);
     }
 
     #[test]
@@ -1044,7 +1388,7 @@ mod test {
             annotation for this sequence is available on the chromosome or
             linkage group record.
 FEATURES             Location/Qualifiers"#;
-        incomplete_test(f, b"FEATURES             Location/Qualifiers", &any_field);
+        incomplete_test(f, b"FEATURES             Location/Qualifiers", any_field);
     }
 
     #[test]
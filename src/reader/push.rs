@@ -0,0 +1,132 @@
+//! A parser which is given the input a piece at a time, rather than reading
+//! it itself.
+
+use super::slice::find_record_end;
+use super::streaming_parser::Token;
+use super::TokenReader;
+use crate::errors::GbParserError;
+
+/// Push parser for input which arrives in pieces, e.g. in a server handling
+/// uploads. Each piece is passed to `feed`, which returns the `Token`s of
+/// every record completed by it, as `TokenReader` would. Pieces can be split
+/// anywhere, even in the middle of a line; partial records are buffered
+/// until their `//` line arrives.
+#[derive(Debug, Default)]
+pub struct PushParser {
+    buffer: Vec<u8>,
+    // how much of `buffer` is known not to contain the end of a record, up
+    // to the start of a line
+    searched: usize,
+    // tokens of records completed before one which failed to parse, to be
+    // returned by the next call
+    pending: Vec<Token>,
+}
+
+impl PushParser {
+    pub fn new() -> PushParser {
+        PushParser::default()
+    }
+
+    /// Adds `data` to the input, returning the tokens of the records it
+    /// completes. If a record can't be parsed, the error is returned and
+    /// that record is dropped. Any complete records before or after it are
+    /// kept, and are returned by the next call, which can be `feed(&[])`.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Token>, GbParserError> {
+        self.buffer.extend_from_slice(data);
+        let mut tokens = std::mem::take(&mut self.pending);
+        loop {
+            let end = match find_record_end(&self.buffer, self.searched) {
+                Ok(end) => end,
+                Err(searched) => {
+                    self.searched = searched;
+                    return Ok(tokens);
+                }
+            };
+            let record: Vec<u8> = self.buffer.drain(..end).collect();
+            self.searched = 0;
+            match TokenReader::new(&record[..]).collect::<Result<Vec<_>, _>>() {
+                Ok(record_tokens) => tokens.extend(record_tokens),
+                Err(e) => {
+                    self.pending = tokens;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Signals the end of the input, returning the tokens of the last record
+    /// if it lacked a `//` line. Fails if anything but whitespace was left
+    /// over that isn't a complete record, in which case the tokens of the
+    /// records before it are returned by calling `finish` again.
+    pub fn finish(&mut self) -> Result<Vec<Token>, GbParserError> {
+        let mut tokens = self.feed(&[])?;
+        let rest = std::mem::take(&mut self.buffer);
+        self.searched = 0;
+        match TokenReader::new(&rest[..]).collect::<Result<Vec<_>, _>>() {
+            Ok(rest_tokens) => {
+                tokens.extend(rest_tokens);
+                Ok(tokens)
+            }
+            Err(e) => {
+                self.pending = tokens;
+                Err(e)
+            }
+        }
+    }
+
+    /// The number of bytes buffered while waiting for the end of a record
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_parser() {
+        let data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        let expected: Vec<Token> = TokenReader::new(&data[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut parser = PushParser::new();
+        let mut tokens = Vec::new();
+        let mut ends = 0;
+        for piece in data.chunks(37) {
+            let new = parser.feed(piece).unwrap();
+            ends += new.iter().filter(|t| **t == Token::RecordEnd).count();
+            tokens.extend(new);
+        }
+        tokens.extend(parser.finish().unwrap());
+        assert_eq!(tokens, expected);
+        assert_eq!(ends, 6);
+        assert_eq!(parser.buffered(), 0);
+
+        let truncated = &data[..data.len() / 3];
+        let mut parser = PushParser::new();
+        assert!(parser.feed(truncated).is_ok());
+        assert!(parser.buffered() > 0);
+        assert!(parser.finish().is_err());
+        assert_eq!(parser.buffered(), 0);
+    }
+
+    #[test]
+    fn push_parser_error() {
+        let data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        let first = data
+            .split(|&b| b == b'\n')
+            .take(53)
+            .collect::<Vec<_>>()
+            .join(&b'\n');
+        let mut input = first.clone();
+        input.extend_from_slice(b"\nnot a record\n//\n");
+        input.extend_from_slice(&first);
+        input.push(b'\n');
+        let mut parser = PushParser::new();
+        assert!(parser.feed(&input).is_err());
+        let tokens = parser.feed(&[]).unwrap();
+        assert_eq!(tokens.iter().filter(|t| **t == Token::RecordEnd).count(), 2);
+        assert!(parser.finish().unwrap().is_empty());
+    }
+}
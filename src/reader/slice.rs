@@ -0,0 +1,159 @@
+//! Splitting a file that's entirely in memory, or memory mapped, into its
+//! records without copying anything, so that only the parts that are needed
+//! get parsed.
+
+use super::{parse_feature_table, SeqReader};
+use crate::errors::GbParserError;
+use crate::seq::{Feature, Seq};
+
+/// Where the first record in `data[from..]` ends, just after its `//` line.
+/// `from` must be at the start of a line. If there's no `//` line, returns
+/// where the last complete line in `data` ends as the error, so the search
+/// can be continued from there.
+pub(crate) fn find_record_end(data: &[u8], from: usize) -> Result<usize, usize> {
+    let mut start = from;
+    while let Some(i) = data[start..].iter().position(|&b| b == b'\n') {
+        let line_end = start + i + 1;
+        if data[start..].starts_with(b"//") {
+            return Ok(line_end);
+        }
+        start = line_end;
+    }
+    Err(start)
+}
+
+/// Splits `data` into records, see `RecordSlice`
+pub fn split_records(data: &[u8]) -> RecordSlices<'_> {
+    RecordSlices { data }
+}
+
+/// An iterator over the records in a slice, returned by `split_records`.
+/// A record lacking its `//` line at the end of the data is still returned.
+#[derive(Debug, Clone)]
+pub struct RecordSlices<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for RecordSlices<'a> {
+    type Item = RecordSlice<'a>;
+
+    fn next(&mut self) -> Option<RecordSlice<'a>> {
+        if self.data.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        let end = find_record_end(self.data, 0).unwrap_or(self.data.len());
+        let (record, rest) = self.data.split_at(end);
+        self.data = rest;
+        Some(RecordSlice { data: record })
+    }
+}
+
+/// The text of one record, borrowed from the input. Nothing is parsed or
+/// copied until it's asked for: `name` and `sequence` borrow from the
+/// input, and `parse` builds a whole `Seq`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordSlice<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RecordSlice<'a> {
+    /// The record's text, up to and including its `//` line
+    pub fn bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.data.split(|&b| b == b'\n')
+    }
+
+    /// The name in the LOCUS line
+    pub fn name(&self) -> Option<&'a str> {
+        let locus = self.lines().find(|l| l.starts_with(b"LOCUS "))?;
+        let name = locus[6..]
+            .split(|b| b.is_ascii_whitespace())
+            .find(|w| !w.is_empty())?;
+        std::str::from_utf8(name).ok()
+    }
+
+    /// Parses the whole record, as `SeqReader` would
+    pub fn parse(&self) -> Result<Seq, GbParserError> {
+        SeqReader::new(self.data)
+            .next()
+            .unwrap_or_else(|| Err(GbParserError::SyntaxError("Empty record".into())))
+    }
+
+    /// Parses everything but the sequence, which stays empty
+    pub fn parse_without_sequence(&self) -> Result<Seq, GbParserError> {
+        SeqReader::new(self.data)
+            .lazy_sequences()
+            .next()
+            .unwrap_or_else(|| Err(GbParserError::SyntaxError("Empty record".into())))
+            .map(|(seq, _)| seq)
+    }
+
+    /// The lines of the feature table, without the FEATURES line, as they
+    /// appear in the input
+    pub fn feature_table(&self) -> &'a [u8] {
+        let mut offset = 0;
+        let mut start = None;
+        for line in self.lines() {
+            let line_start = offset;
+            offset += line.len() + 1;
+            match start {
+                None if line.starts_with(b"FEATURES ") => start = Some(offset),
+                Some(start) if !line.starts_with(b" ") => {
+                    return &self.data[start..line_start];
+                }
+                _ => {}
+            }
+        }
+        start.map_or(&[], |start| &self.data[start.min(self.data.len())..])
+    }
+
+    /// Parses just the features
+    pub fn features(&self) -> Result<Vec<Feature>, GbParserError> {
+        parse_feature_table(self.feature_table())
+    }
+
+    /// The sequence, as the runs of bases between the spaces and line
+    /// numbers of the ORIGIN section, borrowed from the input
+    pub fn sequence(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.lines()
+            .skip_while(|l| !l.starts_with(b"ORIGIN"))
+            .skip(1)
+            .take_while(|l| !l.starts_with(b"//"))
+            .flat_map(|l| l.split(|b| b.is_ascii_whitespace() || b.is_ascii_digit()))
+            .filter(|run| !run.is_empty())
+    }
+
+    /// The number of bases in the sequence, counted without copying it
+    pub fn sequence_len(&self) -> usize {
+        self.sequence().map(<[u8]>::len).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_slices() {
+        let data = std::fs::read("tests/biopython_tests/cor6_6.gb").unwrap();
+        let expected = crate::reader::parse_slice(&data).unwrap();
+        let records: Vec<_> = split_records(&data).collect();
+        assert_eq!(records.len(), expected.len());
+        for (r, seq) in records.iter().zip(&expected) {
+            assert_eq!(r.name(), seq.name.as_deref());
+            assert_eq!(&r.parse().unwrap(), seq);
+            assert_eq!(r.sequence().collect::<Vec<_>>().concat(), seq.seq);
+            assert_eq!(r.sequence_len(), seq.seq.len());
+            assert_eq!(r.features().unwrap(), seq.features);
+            let header = r.parse_without_sequence().unwrap();
+            assert!(header.seq.is_empty());
+            assert_eq!(header.features, seq.features);
+        }
+        assert_eq!(find_record_end(b"LOCUS\n//\nLOCUS\n", 0), Ok(9));
+        assert_eq!(find_record_end(b"LOCUS\n//\nLOCUS\n", 9), Err(15));
+        assert!(split_records(b"\n \n").next().is_none());
+    }
+}
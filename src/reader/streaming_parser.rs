@@ -1,15 +1,23 @@
 use crate::reader::nom_parsers::{
-    any_field, base_count, contig_text, double_slash, feature, features_header, fill_seq_fields,
-    line_ending_type_hack, locus, origin_tag, skip_preamble,
+    any_field, base_count, contig_text, double_slash, estimate_feature_count, feature,
+    feature_start, features_header, fill_seq_field, fill_seq_fields, is_residue,
+    line_ending_type_hack, locus_with_warning, locus_without_length, master_line, origin_tag,
+    peek_line, qualifier, skip_preamble_lines, Field, Locus,
 };
+use crate::reader::strict::{check_feature, check_locus_columns};
+use itertools::Itertools;
 use nom::{self, AsChar, IResult, Offset};
 use std::cmp;
 use std::io::Error as IoError;
+use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Result as IoResult;
 
 use crate::seq::*;
 
+use crate::diagnostics::Diagnostic;
+use crate::insdc::{feature_kinds, qualifier_keys};
+use crate::reader::{DefinitionJoining, KeyNormalization, LengthMismatch, Limits, TextEncoding};
 use crate::errors::GbParserError;
 
 extern crate circular;
@@ -20,15 +28,69 @@ pub struct StreamParser<T: Read> {
     stream: T,
     capacity: usize,
     is_eof: bool,
+    // `None` if warnings should just be logged
+    warnings: Option<Vec<Diagnostic>>,
+    pub definition_joining: DefinitionJoining,
+    pub key_normalization: KeyNormalization,
+    // `Some` if we're tagging features with their `Provenance`, with the
+    // name of the input if known
+    pub provenance_source: Option<Option<String>>,
+    bytes_consumed: u64,
+    lines_consumed: usize,
+    pub lenient_ending: bool,
+    // whether we've parsed a LOCUS line but not the end of its record
+    in_record: bool,
+    seen_record: bool,
+    // set once we've made up a `//` for a record cut short by EOF
+    added_terminator: bool,
+    // whether to only note where the sequence is rather than reading it
+    pub skip_sequence: bool,
+    // (offset, length) of the last sequence skipped
+    skipped_sequence: Option<(u64, usize)>,
+    pub length_mismatch: LengthMismatch,
+    // set if the last record's length was wrong, but `length_mismatch`
+    // allowed it
+    length_mismatched: bool,
+    // skip to the end of a record which can't be parsed, so that the next
+    // one can be
+    pub recover_errors: bool,
+    // whether the last byte consumed was a newline
+    at_line_start: bool,
+    // reject records which don't follow the spec exactly, see
+    // `SeqReader::strict`
+    pub strict: bool,
+    // take the length from the sequence if the LOCUS line's is missing or
+    // wrong, see `SeqReader::derive_length`
+    pub derive_length: bool,
+    // fill in `Feature::display_hints`, see `SeqReader::read_display_hints`
+    pub display_hints: bool,
+    pub limits: Limits,
+    pub text_encoding: TextEncoding,
+    // input read but not yet decoded into `buffer`, if `text_encoding`
+    // isn't `Utf8`
+    undecoded: Vec<u8>,
 }
 
 // We use this private error type rather than nom's errors, so that we can own
 // the input slice to give "context" even once the slice we were parsing is gone
 
+// longest snippet of the input kept in `GbParserError::SyntaxErrorAt`
+const MAX_SNIPPET_BYTES: usize = 100;
+
+// the free space needed in the buffer to decode some input into it, when
+// each byte can become up to 3 and 3 bytes can be left over from last time
+const MIN_TRANSCODE_SPACE: usize = 12;
+
 const MAX_CONTEXT_BYTES: usize = 50; // maximum length of the Vec in the StreamParser
                                      // variant to avoid cloning massive input
                                      // slices
 
+/// Carried by the `IoError` returned when the buffer would have to grow past
+/// `Limits::max_buffer`, to be turned into `GbParserError::LimitExceeded`
+#[derive(Debug, Error)]
+#[error(display = "{}", _0)]
+struct BufferLimit(String);
+
 enum StreamParserError {
     Io(IoError),
     StreamParser(Option<Vec<u8>>, nom::ErrorKind),
@@ -67,6 +129,202 @@ impl<T: Read> StreamParser<T> {
             capacity,
             buffer: circular::Buffer::with_capacity(capacity),
             is_eof: false,
+            warnings: None,
+            definition_joining: DefinitionJoining::default(),
+            key_normalization: KeyNormalization::default(),
+            provenance_source: None,
+            bytes_consumed: 0,
+            lines_consumed: 0,
+            lenient_ending: false,
+            in_record: false,
+            seen_record: false,
+            added_terminator: false,
+            skip_sequence: false,
+            skipped_sequence: None,
+            length_mismatch: LengthMismatch::Error,
+            length_mismatched: false,
+            recover_errors: false,
+            at_line_start: true,
+            strict: false,
+            derive_length: false,
+            display_hints: false,
+            limits: Limits::default(),
+            text_encoding: TextEncoding::default(),
+            undecoded: Vec::new(),
+        }
+    }
+
+    /// Whether to keep warnings to be returned by `take_warnings`, rather
+    /// than logging them
+    pub fn set_collect_warnings(&mut self, collect: bool) {
+        if !collect {
+            self.warnings = None;
+        } else if self.warnings.is_none() {
+            self.warnings = Some(Vec::new());
+        }
+    }
+
+    /// Returns the warnings collected since the last call
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        self.warnings.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Tag parsed features with their `Provenance`
+    pub fn track_provenance(&mut self, source: Option<String>) {
+        self.provenance_source = Some(source);
+    }
+
+    /// The offset and length of the last record's sequence, if it was
+    /// skipped
+    pub fn take_skipped_sequence(&mut self) -> Option<(u64, usize)> {
+        self.skipped_sequence.take()
+    }
+
+    /// Consume `n` bytes of the buffer, keeping track of where we are in the
+    /// input
+    fn consume(&mut self, n: usize) {
+        let data = &self.buffer.data()[..n];
+        self.lines_consumed += data.iter().filter(|&&b| b == b'\n').count();
+        if n > 0 {
+            self.at_line_start = self.buffer.data()[n - 1] == b'\n';
+        }
+        self.bytes_consumed += n as u64;
+        self.buffer.consume(n);
+    }
+
+    /// Provenance for something starting at the current position
+    fn provenance(&self) -> Option<Provenance> {
+        self.provenance_source
+            .as_ref()
+            .map(|source| Provenance::Parsed {
+                source: source.clone(),
+                line: self.lines_consumed + 1,
+                offset: self.bytes_consumed,
+            })
+    }
+
+    fn warn(&mut self, msg: String) {
+        match self.warnings {
+            Some(ref mut w) => w.push(Diagnostic::new(msg)),
+            None => warn!("{}", msg),
+        }
+    }
+
+    /// Skip any preamble such as the header of Genbank .SEQ files and parse
+    /// the LOCUS line. Returns `None` at EOF.
+    fn parse_locus(&mut self) -> Result<Option<Locus>, GbParserError> {
+        if let Some(lines) = self.try_run_parser(skip_preamble_lines, false)? {
+            for l in lines {
+                self.warn(format!("Ignoring line: {}", l));
+            }
+        }
+        let mut line = None;
+        if self.strict || self.derive_length {
+            line = self.try_run_parser(peek_line, false)?;
+        }
+        if let (true, Some(line)) = (self.strict, &line) {
+            check_locus_columns(line).map_err(GbParserError::SyntaxError)?;
+        }
+        match self.run_parser(locus_with_warning, true) {
+            Ok((mut locus, mut warning)) => {
+                if locus.len.is_none() && self.derive_length {
+                    let line = line.map(|l| String::from_utf8_lossy(&l).into_owned());
+                    if let Some(l) = line.as_deref().and_then(locus_without_length) {
+                        (locus, warning) = l;
+                    }
+                }
+                if let Some(w) = warning {
+                    self.warn(w);
+                }
+                self.in_record = true;
+                self.seen_record = true;
+                Ok(Some(locus))
+            }
+            Err(StreamParserError::EOF) => Ok(None),
+            Err(StreamParserError::StreamParser(..)) if self.lenient_ending && self.seen_record => {
+                self.skip_trailing_data()?;
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Consume the rest of the input, which isn't a record, with a warning
+    fn skip_trailing_data(&mut self) -> IoResult<()> {
+        let mut context = Vec::new();
+        let mut skipped = 0;
+        loop {
+            let n = self.buffer.available_data();
+            if n == 0 && self.fill_buffer()? == 0 {
+                break;
+            }
+            let data = self.buffer.data();
+            let keep = cmp::min(data.len(), MAX_CONTEXT_BYTES - context.len());
+            context.extend_from_slice(&data[..keep]);
+            skipped += data.len();
+            self.consume(data.len());
+        }
+        self.warn(format!(
+            "Ignoring {} bytes of trailing data: [{}]",
+            skipped,
+            String::from_utf8_lossy(&context)
+        ));
+        Ok(())
+    }
+
+    /// Parse the `//` ending a record
+    fn parse_record_end(&mut self) -> Result<(), GbParserError> {
+        // To be permissive, if we made it this far and it's EOF we'll let the
+        // '//' slip
+        if !(self.buffer.empty() && self.is_eof()) {
+            self.run_parser(double_slash, true)?;
+            self.run_parser_many0(line_ending_type_hack)?;
+        }
+        self.in_record = false;
+        Ok(())
+    }
+
+    /// Check we got all the sequence data the LOCUS line promised, once
+    /// `seq_data_step` reached the end of the sequence or the input
+    fn check_seq_len(
+        &mut self,
+        len: Option<usize>,
+        total: usize,
+        step: SeqDataStep,
+    ) -> Result<(), GbParserError> {
+        match step {
+            SeqDataStep::End => match len {
+                Some(len) if len != total && self.added_terminator => {
+                    self.warn(format!(
+                        "Record is truncated: got {} bytes of sequence, LOCUS promised {}",
+                        total, len
+                    ));
+                    Ok(())
+                }
+                Some(len) if len != total => {
+                    let msg = format!("Got {} bytes of sequence, LOCUS promised {}", total, len);
+                    match self.length_mismatch {
+                        _ if self.strict => return Err(GbParserError::SyntaxError(msg)),
+                        LengthMismatch::Error if self.derive_length => self.warn(msg),
+                        LengthMismatch::Error => return Err(GbParserError::SyntaxError(msg)),
+                        LengthMismatch::WarnAndTrustSequence => self.warn(msg),
+                        LengthMismatch::TrustSequence | LengthMismatch::TrustLocus => {}
+                    }
+                    self.length_mismatched = true;
+                    Ok(())
+                }
+                _ => Ok(()),
+            },
+            SeqDataStep::Eof if len == Some(total) => {
+                self.warn(
+                    "Unexpected EOF while parsing sequence data. Length is correct, continuing."
+                        .to_string(),
+                );
+                Ok(())
+            }
+            // We don't know the length, so we can't know if we have everything
+            SeqDataStep::Eof => Err(GbParserError::SyntaxError("Unexpected EOF!".to_string())),
+            SeqDataStep::More => Ok(()),
         }
     }
 
@@ -74,14 +332,35 @@ impl<T: Read> StreamParser<T> {
         if self.is_eof() {
             return Ok(0);
         }
+        let min_space = match self.text_encoding {
+            TextEncoding::Utf8 => 1,
+            _ => MIN_TRANSCODE_SPACE,
+        };
+        if self.text_encoding != TextEncoding::Utf8 && self.buffer.available_space() < min_space {
+            self.buffer.shift();
+        }
         // if we're requesting a buffer refill when the buffer's full, we need
         // to grow it.
-        if self.buffer.available_space() == 0 {
-            self.capacity *= 2;
+        if self.buffer.available_space() < min_space {
+            let capacity = match self.limits.max_buffer {
+                Some(max) if self.capacity >= max => {
+                    let msg = format!("More than {} bytes needed to parse an item", max);
+                    return Err(IoError::new(ErrorKind::InvalidData, BufferLimit(msg)));
+                }
+                Some(max) => cmp::min(self.capacity * 2, max),
+                None => self.capacity * 2,
+            };
+            self.capacity = capacity;
             self.buffer.grow(self.capacity);
             debug!("Increasing read buffer capacity to {} b", self.capacity);
         }
-        let bytes_read = self.stream.read(self.buffer.space())?;
+        let bytes_read = match self.text_encoding {
+            TextEncoding::Utf8 => self.stream.read(self.buffer.space())?,
+            _ => self.read_transcoded()?,
+        };
+        if bytes_read == 0 && self.lenient_ending && self.in_record && !self.added_terminator {
+            return Ok(self.add_terminator());
+        }
         if bytes_read == 0 {
             self.is_eof = true;
         } else {
@@ -90,6 +369,45 @@ impl<T: Read> StreamParser<T> {
         Ok(bytes_read)
     }
 
+    /// Read into the buffer, decoding invalid UTF-8 as `text_encoding` says.
+    /// Returns the number of bytes added to the buffer, which is only 0 at
+    /// the end of the input.
+    fn read_transcoded(&mut self) -> IoResult<usize> {
+        let space = self.buffer.available_space();
+        loop {
+            // every byte read could become 3 in the buffer
+            let start = self.undecoded.len();
+            self.undecoded.resize(space / 3, 0);
+            let n = self.stream.read(&mut self.undecoded[start..])?;
+            self.undecoded.truncate(start + n);
+            let mut decoded = Vec::with_capacity(space);
+            let used = transcode(&self.undecoded, self.text_encoding, n == 0, &mut decoded);
+            self.undecoded.drain(..used);
+            self.buffer.space()[..decoded.len()].copy_from_slice(&decoded);
+            if !decoded.is_empty() || n == 0 {
+                return Ok(decoded.len());
+            }
+        }
+    }
+
+    /// Pretend the input ends with `//`, so that the record being parsed
+    /// when we hit EOF can be finished. Returns the number of bytes added.
+    fn add_terminator(&mut self) -> usize {
+        self.added_terminator = true;
+        self.warn("Missing // at the end of the input, the last record may be incomplete".into());
+        let terminator: &[u8] = match self.buffer.data().last() {
+            None | Some(b'\n') => b"//\n",
+            _ => b"\n//\n",
+        };
+        if self.buffer.available_space() < terminator.len() {
+            self.capacity += terminator.len();
+            self.buffer.grow(self.capacity);
+        }
+        self.buffer.space()[..terminator.len()].copy_from_slice(terminator);
+        self.buffer.fill(terminator.len());
+        terminator.len()
+    }
+
     fn is_eof(&self) -> bool {
         self.is_eof
     }
@@ -123,7 +441,7 @@ impl<T: Read> StreamParser<T> {
 
             match res {
                 Some((length, o)) => {
-                    self.buffer.consume(length);
+                    self.consume(length);
                     return Ok(o);
                 }
                 None => {
@@ -162,10 +480,20 @@ impl<T: Read> StreamParser<T> {
         parser: impl Fn(&[u8]) -> IResult<&[u8], U>,
     ) -> IoResult<Vec<U>> {
         let mut res = Vec::new();
+        self.run_parser_for_each(parser, |o| res.push(o))?;
+        Ok(res)
+    }
+
+    /// Apply a nom parser until it fails, passing each result to `f`
+    fn run_parser_for_each<U>(
+        &mut self,
+        parser: impl Fn(&[u8]) -> IResult<&[u8], U>,
+        mut f: impl FnMut(U),
+    ) -> IoResult<()> {
         loop {
             match self.run_parser(&parser, false) {
                 Ok(o) => {
-                    res.push(o);
+                    f(o);
                 }
                 Err(StreamParserError::Io(e)) => {
                     return Err(e);
@@ -175,107 +503,630 @@ impl<T: Read> StreamParser<T> {
                 }
             }
         }
-        Ok(res)
+        Ok(())
     }
 
-    /// Parses the raw sequence data, ignoring whitespace and line numbers
-    fn parse_seq_data(&mut self, len: Option<usize>) -> Result<Vec<u8>, GbParserError> {
-        let mut s = if let Some(len) = len {
-            Vec::with_capacity(cmp::min(len, REASONABLE_SEQ_LEN))
+    /// Scans the buffered sequence data, ignoring whitespace and line
+    /// numbers, and passes each run of bases to `on_chunk`. Refills the buffer
+    /// unless we reached the end of the sequence. Returns the number of bases
+    /// found.
+    fn seq_data_step(
+        &mut self,
+        mut on_chunk: impl FnMut(&[u8]),
+    ) -> Result<(usize, SeqDataStep), GbParserError> {
+        let mut total = 0;
+        let mut bytes_read = 0;
+        let mut end_of_sequence = false;
+        let mut run_start = None;
+        let data = self.buffer.data();
+        for &b in data {
+            if !is_residue(b) {
+                if let Some(start) = run_start.take() {
+                    on_chunk(&data[start..bytes_read]);
+                    total += bytes_read - start;
+                }
+            }
+            match b {
+                b if is_residue(b) => {
+                    if run_start.is_none() {
+                        run_start = Some(bytes_read);
+                    }
+                }
+                b'/' => {
+                    end_of_sequence = true;
+                    break;
+                }
+                b if b.is_dec_digit() => {}
+                b' ' | b'\r' | b'\n' => {}
+                x => {
+                    // so the error points at the bad character
+                    self.consume(bytes_read);
+                    return Err(GbParserError::SyntaxError(format!(
+                        "Unexpected char '{}' ({}) in sequence",
+                        String::from_utf8_lossy(&[x]), // Only display printable chars
+                        x
+                    )));
+                }
+            }
+            bytes_read += 1;
+        }
+        if let Some(start) = run_start {
+            on_chunk(&data[start..bytes_read]);
+            total += bytes_read - start;
+        }
+        self.consume(bytes_read);
+        if end_of_sequence {
+            Ok((total, SeqDataStep::End))
+        } else if self.fill_buffer()? == 0 {
+            Ok((total, SeqDataStep::Eof))
         } else {
-            Vec::new()
-        };
+            Ok((total, SeqDataStep::More))
+        }
+    }
+
+    /// Parses the raw sequence data, ignoring whitespace and line numbers.
+    /// Each run of bases is passed to `on_chunk` as it's found.
+    fn parse_seq_data(
+        &mut self,
+        len: Option<usize>,
+        mut on_chunk: impl FnMut(&[u8]),
+    ) -> Result<(), GbParserError> {
+        let mut total = 0;
         loop {
-            let mut bytes_read = 0;
-            let mut end_of_sequence = false;
-            for &b in self.buffer.data() {
-                match b {
-                    b if b.is_alpha() => {
-                        s.push(b);
-                    }
-                    b'/' => {
-                        end_of_sequence = true;
-                        break;
-                    }
-                    b if b.is_dec_digit() => {}
-                    b' ' | b'\r' | b'\n' => {}
-                    x => {
-                        return Err(GbParserError::SyntaxError(format!(
-                            "Unexpected char '{}' ({}) in sequence",
-                            String::from_utf8_lossy(&[x]), // Only display printable chars
-                            x
-                        )));
-                    }
+            let (bases, step) = self.seq_data_step(&mut on_chunk)?;
+            total += bases;
+            self.check_seq_limit(total)?;
+            if step != SeqDataStep::More {
+                return self.check_seq_len(len, total, step);
+            }
+        }
+    }
+
+    /// Compare the BASE COUNT line with the sequence. A mismatch is only an
+    /// error when strict.
+    fn check_base_count(
+        &mut self,
+        expected: BaseCount,
+        found: BaseCount,
+    ) -> Result<(), GbParserError> {
+        if expected != found {
+            let msg = format!("BASE COUNT says {}, but the sequence has {}", expected, found);
+            if self.strict {
+                return Err(GbParserError::SyntaxError(msg));
+            }
+            self.warn(msg);
+        }
+        Ok(())
+    }
+
+    fn check_seq_limit(&self, len: usize) -> Result<(), GbParserError> {
+        match self.limits.max_seq_len {
+            Some(max) if len > max => Err(GbParserError::LimitExceeded(format!(
+                "Sequence is longer than {}",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_feature_limits(&self, count: usize, f: &Feature) -> Result<(), GbParserError> {
+        if let Some(max) = self.limits.max_features {
+            if count > max {
+                return Err(GbParserError::LimitExceeded(format!(
+                    "More than {} features",
+                    max
+                )));
+            }
+        }
+        if let Some(max) = self.limits.max_qualifier_len {
+            for (key, value) in &f.qualifiers {
+                if value.as_ref().is_some_and(|v| v.len() > max) {
+                    return Err(GbParserError::LimitExceeded(format!(
+                        "/{} of {} is longer than {}",
+                        key, f.kind, max
+                    )));
                 }
-                bytes_read += 1;
             }
-            self.buffer.consume(bytes_read);
-            if end_of_sequence {
-                // Check that we got everything, if possible
-                if let Some(len) = len {
-                    if len != s.len() {
-                        return Err(GbParserError::SyntaxError(format!(
-                            "Got {} bytes of sequence, LOCUS promised {}",
-                            s.len(),
-                            len
-                        )));
+        }
+        Ok(())
+    }
+
+    /// Consume everything up to and including the next `//` line, after an
+    /// error
+    fn skip_to_record_end(&mut self) -> IoResult<()> {
+        loop {
+            let data = self.buffer.data();
+            match data.iter().position(|&b| b == b'\n') {
+                Some(i) => {
+                    let end = self.at_line_start && data.starts_with(b"//");
+                    self.consume(i + 1);
+                    if end {
+                        break;
                     }
                 }
-                break;
-            }
-            if self.fill_buffer()? == 0 {
-                if len == Some(s.len()) {
-                    warn!("Unexpected EOF while parsing sequence data. Length is correct, continuing.");
+                None if self.fill_buffer()? == 0 => {
+                    let n = self.buffer.available_data();
+                    self.consume(n);
                     break;
-                } else {
-                    // We don't know the length, so we can't know if we have everything
-                    return Err(GbParserError::SyntaxError(format!("Unexpected EOF!")));
                 }
+                None => {}
             }
         }
-        Ok(s)
+        self.in_record = false;
+        Ok(())
     }
 
     pub fn read_one_record(&mut self) -> Result<Option<Seq>, GbParserError> {
-        // skip preamble such as the header of Genbank .SEQ files
-        self.try_run_parser(skip_preamble, false)?;
-        let locus = match self.run_parser(locus, true) {
-            Ok(locus) => locus,
-            Err(StreamParserError::EOF) => {
-                return Ok(None);
+        let mut builder = SeqBuilder { seq: None };
+        let res = self.parse_one_record(&mut builder);
+        if res.as_ref().is_err_and(GbParserError::is_syntax_error) && self.recover_errors {
+            self.skip_to_record_end()?;
+        }
+        if res? {
+            if let Some(ref mut seq) = builder.seq {
+                // lazily loaded sequences are checked when they're loaded
+                if self.length_mismatched && !self.skip_sequence {
+                    self.fix_length(seq);
+                }
+                if let (None, Some((_, len))) = (seq.len, self.skipped_sequence) {
+                    seq.len = Some(len);
+                }
+                if seq.len.is_none() && self.derive_length && !seq.seq.is_empty() {
+                    seq.len = Some(seq.seq.len());
+                }
             }
-            Err(e) => {
-                return Err(e.into());
+            Ok(builder.seq)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Make the length and sequence of a record agree, as `length_mismatch`
+    /// says
+    fn fix_length(&self, seq: &mut Seq) {
+        match (self.length_mismatch, seq.len) {
+            (LengthMismatch::TrustLocus, Some(len)) => {
+                let pad = if seq.is_protein() { b'X' } else { b'n' };
+                seq.seq.resize(len, pad);
             }
+            _ => seq.len = Some(seq.seq.len()),
+        }
+    }
+
+    /// Parse a single record, passing its contents to `handler`. Returns
+    /// `false` if there were no more records.
+    pub fn parse_one_record<H: Handler + ?Sized>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<bool, GbParserError> {
+        self.parse_record_contents(handler)
+            .map_err(|e| match e {
+                GbParserError::SyntaxError(message) => self.located(message),
+                GbParserError::Io(e) => match e.get_ref().and_then(|e| e.downcast_ref()) {
+                    Some(BufferLimit(msg)) => GbParserError::LimitExceeded(msg.clone()),
+                    _ => GbParserError::Io(e),
+                },
+                e => e,
+            })
+    }
+
+    /// Adds the current position to an error message
+    fn located(&self, message: String) -> GbParserError {
+        let data = self.buffer.data();
+        let end = data
+            .iter()
+            .take(MAX_SNIPPET_BYTES)
+            .position(|&b| b == b'\n')
+            .unwrap_or_else(|| data.len().min(MAX_SNIPPET_BYTES));
+        GbParserError::SyntaxErrorAt {
+            message,
+            line: self.lines_consumed + 1,
+            offset: self.bytes_consumed,
+            snippet: String::from_utf8_lossy(&data[..end]).trim_end().to_string(),
+        }
+    }
+
+    fn parse_record_contents<H: Handler + ?Sized>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<bool, GbParserError> {
+        let locus = match self.parse_locus()? {
+            Some(locus) => locus,
+            None => return Ok(false),
         };
         let seq = Seq {
             name: locus.name,
             topology: locus.topology,
             date: locus.date,
             len: locus.len,
+            alphabet: locus.alphabet,
             molecule_type: locus.molecule_type,
             division: locus.division,
             ..Seq::empty()
         };
         let fields = self.run_parser_many0(any_field)?;
-        let mut seq = fill_seq_fields(seq, fields).map_err(GbParserError::SyntaxError)?; //TODO: Proper error handling
-        if self.try_run_parser(features_header, true)?.is_some() {
-            seq.features = self.run_parser_many0(feature)?;
+        let mut warnings = Vec::new();
+        let mut header = fill_seq_fields(seq, fields, |w| warnings.push(w))
+            .map_err(GbParserError::SyntaxError)?; //TODO: Proper error handling
+        for w in warnings {
+            self.warn(w);
+        }
+        if self.definition_joining == DefinitionJoining::Normalize {
+            if let Some(ref mut d) = header.definition {
+                *d = d.split_whitespace().join(" ");
+            }
+        }
+        let len = header.len;
+        // BASE COUNT usually comes after the features, but there may be none
+        let mut expected_counts = header.base_count;
+        self.check_seq_limit(len.unwrap_or(0))?;
+        let protein = header.is_protein();
+        let has_features = self.try_run_parser(features_header, true)?.is_some();
+        if has_features {
+            let estimate = estimate_feature_count(self.buffer.data(), len);
+            header.features.reserve(estimate);
+        }
+        handler.on_locus(header);
+        let mut count = 0;
+        if has_features {
+            loop {
+                let provenance = self.provenance();
+                let (line, offset) = (self.lines_consumed + 1, self.bytes_consumed);
+                match self.run_parser(feature, false) {
+                    Ok(mut f) => {
+                        count += 1;
+                        self.check_feature_limits(count, &f)?;
+                        if self.strict {
+                            check_feature(&f, protein).map_err(|message| {
+                                GbParserError::SyntaxErrorAt {
+                                    message,
+                                    line,
+                                    offset,
+                                    snippet: format!("{} {}", f.kind, f.location.to_gb_format()),
+                                }
+                            })?;
+                        }
+                        f.provenance = provenance;
+                        if self.display_hints {
+                            let hints = f.qualifier_display_hints();
+                            f.display_hints = Some(hints).filter(|h| !h.is_empty());
+                        }
+                        if self.key_normalization != KeyNormalization::Preserve {
+                            canonicalize_keys(
+                                &mut f,
+                                self.key_normalization
+                                    == KeyNormalization::CanonicalizeKeepingOriginal,
+                            );
+                        }
+                        handler.on_feature(f);
+                    }
+                    Err(StreamParserError::Io(e)) => return Err(e.into()),
+                    Err(_) => break,
+                }
+            }
+        }
+        for field in self.run_parser_many0(master_line)? {
+            handler.on_field(field);
+        }
+        if let Some(field) = self.try_run_parser(base_count, true)? {
+            if let Field::BASE_COUNT(ref counts) = field {
+                expected_counts = BaseCount::parse(counts);
+                if expected_counts.is_none() {
+                    self.warn(format!("Unable to parse BASE COUNT: {}", counts));
+                }
+            }
+            handler.on_field(field);
         }
-        self.try_run_parser(base_count, true)?;
-        seq.contig = self.try_run_parser(contig_text, true)?;
+        if let Some(contig) = self.try_run_parser(contig_text, true)? {
+            handler.on_contig(contig);
+        }
+        self.skipped_sequence = None;
+        self.length_mismatched = false;
         if self.try_run_parser(origin_tag, true)?.is_some() {
-            seq.seq = self.parse_seq_data(seq.len)?;
+            let mut counts = BaseCount::default();
+            let counting = expected_counts.is_some();
+            if self.skip_sequence {
+                let offset = self.bytes_consumed;
+                let mut total = 0;
+                self.parse_seq_data(len, |chunk| {
+                    total += chunk.len();
+                    if counting {
+                        counts.add(chunk);
+                    }
+                })?;
+                self.skipped_sequence = Some((offset, total));
+            } else {
+                self.parse_seq_data(len, |chunk| {
+                    if counting {
+                        counts.add(chunk);
+                    }
+                    handler.on_sequence_chunk(chunk)
+                })?;
+            }
+            if let Some(expected) = expected_counts {
+                self.check_base_count(expected, counts)?;
+            }
         }
+        self.parse_record_end()?;
+        handler.on_record_end();
+        Ok(true)
+    }
+}
 
-        // To be permissive, if we made it this far and it's EOF we'll let the
-        // '//' slip
-        if self.buffer.empty() && self.is_eof() {
-            return Ok(Some(seq));
+/// Decodes `input` as UTF-8 into `out`, with invalid bytes replaced as
+/// `encoding` says. Returns how many bytes of `input` were used: a character
+/// cut short at the end is left for next time, unless at `eof`.
+fn transcode(mut input: &[u8], encoding: TextEncoding, eof: bool, out: &mut Vec<u8>) -> usize {
+    let len = input.len();
+    loop {
+        let e = match std::str::from_utf8(input) {
+            Ok(_) => {
+                out.extend_from_slice(input);
+                return len;
+            }
+            Err(e) => e,
+        };
+        let (valid, rest) = input.split_at(e.valid_up_to());
+        out.extend_from_slice(valid);
+        let bad = match e.error_len() {
+            Some(n) => n,
+            None if !eof => return len - rest.len(),
+            None => rest.len(),
+        };
+        match encoding {
+            TextEncoding::Latin1Fallback => {
+                for &b in &rest[..bad] {
+                    let mut utf8 = [0; 2];
+                    out.extend_from_slice(char::from(b).encode_utf8(&mut utf8).as_bytes());
+                }
+            }
+            _ => out.extend_from_slice(char::REPLACEMENT_CHARACTER.to_string().as_bytes()),
         }
+        input = &rest[bad..];
+    }
+}
+
+fn canonicalize_keys(f: &mut Feature, keep_original: bool) {
+    let mut original = OriginalSpelling::default();
+    if let Some(kind) = feature_kinds::canonical(&f.kind) {
+        if kind != f.kind {
+            original.kind = Some(f.kind.to_string());
+            f.kind = kind;
+        }
+    }
+    for (i, q) in f.qualifiers.iter_mut().enumerate() {
+        if let Some(key) = qualifier_keys::canonical(&q.0) {
+            if key != q.0 {
+                original.qualifiers.push((i, q.0.to_string()));
+                q.0 = key;
+            }
+        }
+    }
+    if keep_original && original != OriginalSpelling::default() {
+        f.original_spelling = Some(original);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum SeqDataStep {
+    More,
+    End,
+    Eof,
+}
+
+/// Receives the contents of each record as it's parsed, without a complete
+/// `Seq` being built. See `SeqReader::parse_with`.
+///
+/// For each record, `on_locus` is called first, then `on_feature` for each
+/// feature, `on_field` for any header fields after the features,
+/// `on_contig` if there's a CONTIG line, `on_sequence_chunk` for each run of
+/// sequence data and finally `on_record_end`. All methods do nothing by
+/// default.
+pub trait Handler {
+    /// Called with a `Seq` holding the LOCUS line and the other header fields
+    /// (DEFINITION, REFERENCE etc.), but no features or sequence.
+    fn on_locus(&mut self, _header: Seq) {}
+
+    fn on_feature(&mut self, _feature: Feature) {}
+
+    /// Called with the fields which come after the features, i.e. the WGS,
+    /// WGS_SCAFLD, TSA and TLS lines of master records
+    fn on_field(&mut self, _field: Field) {}
 
-        self.run_parser(double_slash, true)?;
-        self.run_parser_many0(line_ending_type_hack)?;
-        Ok(Some(seq))
+    fn on_contig(&mut self, _contig: Location) {}
+
+    /// Called with consecutive pieces of the sequence, with whitespace and
+    /// line numbers removed. Pieces can be as short as a single base.
+    fn on_sequence_chunk(&mut self, _chunk: &[u8]) {}
+
+    fn on_record_end(&mut self) {}
+}
+
+/// Handler used by `read_one_record` to assemble a `Seq`
+struct SeqBuilder {
+    seq: Option<Seq>,
+}
+
+impl Handler for SeqBuilder {
+    fn on_locus(&mut self, mut header: Seq) {
+        if let Some(len) = header.len {
+            header.seq.reserve(cmp::min(len, REASONABLE_SEQ_LEN));
+        }
+        self.seq = Some(header);
+    }
+
+    fn on_feature(&mut self, feature: Feature) {
+        self.seq.as_mut().unwrap().features.push(feature);
+    }
+
+    fn on_field(&mut self, field: Field) {
+        // only master lines and BASE COUNT are passed here, which can't fail.
+        // We've already warned about BASE COUNTs we couldn't parse.
+        let _ = fill_seq_field(self.seq.as_mut().unwrap(), field, |_| {});
+    }
+
+    fn on_contig(&mut self, contig: Location) {
+        self.seq.as_mut().unwrap().contig = Some(contig);
+    }
+
+    fn on_sequence_chunk(&mut self, chunk: &[u8]) {
+        self.seq.as_mut().unwrap().seq.extend_from_slice(chunk);
+    }
+}
+
+/// One piece of a record, as returned by `TokenReader`
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    /// Always the first token of a record
+    Locus(Locus),
+    /// A header field such as DEFINITION or REFERENCE
+    Field(Field),
+    /// The start of a feature, followed by a `Qualifier` token for each of its
+    /// qualifiers
+    FeatureStart {
+        kind: FeatureKind,
+        location: Location,
+    },
+    Qualifier {
+        key: QualifierKey,
+        value: Option<String>,
+    },
+    Contig(Location),
+    /// A piece of the sequence, with whitespace and line numbers removed. A
+    /// long sequence is split over many tokens.
+    Sequence(Vec<u8>),
+    /// Always the last token of a record
+    RecordEnd,
+}
+
+/// Which part of a record `TokenParser` will look for next
+#[derive(Debug, Clone, Copy)]
+enum TokenState {
+    Start,
+    Header,
+    Features,
+    Qualifiers(usize),
+    Contig,
+    Origin,
+    Sequence(usize),
+    End,
+    Done,
+}
+
+/// Pull parser that returns a record a `Token` at a time
+#[derive(Debug)]
+pub struct TokenParser<T: Read> {
+    parser: StreamParser<T>,
+    state: TokenState,
+    len: Option<usize>,
+}
+
+impl<T: Read> TokenParser<T> {
+    pub fn new(stream: T, capacity: usize) -> TokenParser<T> {
+        TokenParser {
+            parser: StreamParser::new(stream, capacity),
+            state: TokenState::Start,
+            len: None,
+        }
+    }
+
+    /// Returns the next token, or `None` at the end of the input. After an
+    /// error no more tokens are returned.
+    pub fn next_token(&mut self) -> Result<Option<Token>, GbParserError> {
+        let res = self.advance();
+        if res.is_err() {
+            self.state = TokenState::Done;
+        }
+        res
+    }
+
+    fn advance(&mut self) -> Result<Option<Token>, GbParserError> {
+        let p = &mut self.parser;
+        loop {
+            match self.state {
+                TokenState::Start => {
+                    let locus = match p.parse_locus()? {
+                        Some(locus) => locus,
+                        None => {
+                            self.state = TokenState::Done;
+                            return Ok(None);
+                        }
+                    };
+                    self.len = locus.len;
+                    self.state = TokenState::Header;
+                    return Ok(Some(Token::Locus(locus)));
+                }
+                TokenState::Header => {
+                    if let Some(field) = p.try_run_parser(any_field, false)? {
+                        return Ok(Some(Token::Field(field)));
+                    }
+                    self.state = if p.try_run_parser(features_header, true)?.is_some() {
+                        TokenState::Features
+                    } else {
+                        TokenState::Contig
+                    };
+                }
+                TokenState::Features => match p.try_run_parser(feature_start, false)? {
+                    Some((kind, location, indent)) => {
+                        self.state = TokenState::Qualifiers(indent);
+                        return Ok(Some(Token::FeatureStart { kind, location }));
+                    }
+                    None => {
+                        self.state = TokenState::Contig;
+                    }
+                },
+                TokenState::Qualifiers(indent) => {
+                    match p.try_run_parser(|i| qualifier(i, indent), false)? {
+                        Some((key, value)) => {
+                            return Ok(Some(Token::Qualifier { key, value }));
+                        }
+                        None => {
+                            self.state = TokenState::Features;
+                        }
+                    }
+                }
+                TokenState::Contig => {
+                    if let Some(field) = p.try_run_parser(master_line, false)? {
+                        return Ok(Some(Token::Field(field)));
+                    }
+                    if let Some(field) = p.try_run_parser(base_count, true)? {
+                        return Ok(Some(Token::Field(field)));
+                    }
+                    self.state = TokenState::Origin;
+                    if let Some(contig) = p.try_run_parser(contig_text, true)? {
+                        return Ok(Some(Token::Contig(contig)));
+                    }
+                }
+                TokenState::Origin => {
+                    self.state = if p.try_run_parser(origin_tag, true)?.is_some() {
+                        TokenState::Sequence(0)
+                    } else {
+                        TokenState::End
+                    };
+                }
+                TokenState::Sequence(total) => {
+                    let mut chunk = Vec::new();
+                    let (bases, step) = p.seq_data_step(|c| chunk.extend_from_slice(c))?;
+                    let total = total + bases;
+                    if step == SeqDataStep::More {
+                        self.state = TokenState::Sequence(total);
+                    } else {
+                        p.check_seq_len(self.len, total, step)?;
+                        self.state = TokenState::End;
+                    }
+                    if !chunk.is_empty() {
+                        return Ok(Some(Token::Sequence(chunk)));
+                    }
+                }
+                TokenState::End => {
+                    p.parse_record_end()?;
+                    self.state = TokenState::Start;
+                    return Ok(Some(Token::RecordEnd));
+                }
+                TokenState::Done => {
+                    return Ok(None);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,213 @@
+//! Checks made by `SeqReader::strict`, for records which should follow the
+//! INSDC feature table definition and GenBank's LOCUS layout to the letter.
+
+use crate::insdc::{feature_kinds, protein_feature_kinds, qualifier_keys, qualifier_rules};
+use crate::seq::Feature;
+
+/// Checks the LOCUS line (without its line ending) against the fixed
+/// columns given in the GenBank release notes:
+///
+/// ```text
+/// LOCUS       NAME            LENGTH(11) bp ss-MOLTYPE  topology DIV DD-MMM-YYYY
+/// ```
+pub fn check_locus_columns(line: &[u8]) -> Result<(), String> {
+    let line = match std::str::from_utf8(line) {
+        Ok(line) => line.trim_end(),
+        Err(_) => return Err("LOCUS line isn't valid UTF-8".into()),
+    };
+    // (start, end, what's there), 0-based and exclusive
+    let column = |start: usize, end: usize, what: &str| -> Result<&str, String> {
+        line.get(start..end).ok_or_else(|| {
+            format!(
+                "LOCUS line is too short, expected {} in columns {}-{}",
+                what,
+                start + 1,
+                end
+            )
+        })
+    };
+    let blank = |start: usize, end: usize| -> Result<(), String> {
+        if column(start, end, "spaces")?.bytes().all(|b| b == b' ') {
+            Ok(())
+        } else {
+            Err(format!(
+                "Expected spaces in LOCUS columns {}-{}",
+                start + 1,
+                end
+            ))
+        }
+    };
+    let justified = |field: &str, left: bool| {
+        let trimmed = field.trim();
+        !trimmed.is_empty()
+            && !trimmed.contains(' ')
+            && if left {
+                field.starts_with(trimmed)
+            } else {
+                field.ends_with(trimmed)
+            }
+    };
+
+    blank(5, 12)?;
+    if !justified(column(12, 28, "the name")?, true) || line.as_bytes()[28] != b' ' {
+        return Err("LOCUS name must be left-justified in columns 13-28".into());
+    }
+    let length = column(29, 40, "the length")?;
+    if !justified(length, false) || !length.trim().bytes().all(|b| b.is_ascii_digit()) {
+        return Err("LOCUS length must be right-justified digits in columns 30-40".into());
+    }
+    blank(40, 41)?;
+    let units = column(41, 43, "bp or aa")?;
+    if units != "bp" && units != "aa" {
+        return Err(format!(
+            "Expected bp or aa in LOCUS columns 42-43, found {:?}",
+            units
+        ));
+    }
+    blank(43, 44)?;
+    let strandedness = column(44, 47, "the strandedness")?;
+    if !["   ", "ss-", "ds-", "ms-"].contains(&strandedness) {
+        return Err(format!(
+            "Invalid strandedness {:?} in LOCUS columns 45-47",
+            strandedness
+        ));
+    }
+    let mol_type = column(47, 53, "the molecule type")?;
+    if mol_type.trim().is_empty() && units == "bp" {
+        return Err("Missing molecule type in LOCUS columns 48-53".into());
+    }
+    if !mol_type.trim().is_empty() && !justified(mol_type, true) {
+        return Err("LOCUS molecule type must be left-justified in columns 48-53".into());
+    }
+    blank(53, 55)?;
+    let topology = column(55, 63, "the topology")?;
+    if topology != "linear  " && topology != "circular" {
+        return Err(format!(
+            "Expected linear or circular in LOCUS columns 56-63, found {:?}",
+            topology
+        ));
+    }
+    blank(63, 64)?;
+    let division = column(64, 67, "the division")?;
+    if !division.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(format!(
+            "Invalid division {:?} in LOCUS columns 65-67",
+            division
+        ));
+    }
+    blank(67, 68)?;
+    let date = column(68, 79, "the date")?.as_bytes();
+    let date_ok = date.iter().enumerate().all(|(i, &b)| match i {
+        2 | 6 => b == b'-',
+        3..=5 => b.is_ascii_uppercase(),
+        _ => b.is_ascii_digit(),
+    });
+    if !date_ok {
+        return Err("LOCUS date must be DD-MMM-YYYY in columns 69-79".into());
+    }
+    if line.len() > 79 {
+        return Err("Unexpected text after the LOCUS date".into());
+    }
+    Ok(())
+}
+
+/// Checks that the feature key is in the INSDC definition (or is one of
+/// GenPept's, for proteins), and that the qualifiers are allowed on it and
+/// include the mandatory ones. GenPept's qualifiers aren't in the definition,
+/// so they aren't checked for proteins.
+pub fn check_feature(f: &Feature, protein: bool) -> Result<(), String> {
+    if protein {
+        if feature_kinds::is_standard(&f.kind) || protein_feature_kinds::ALL.contains(&f.kind) {
+            return Ok(());
+        }
+        return Err(format!("Unknown feature key {}", f.kind));
+    }
+    let rules =
+        qualifier_rules(&f.kind).ok_or_else(|| format!("Unknown feature key {}", f.kind))?;
+    for (key, _) in &f.qualifiers {
+        if !qualifier_keys::is_standard(key) {
+            return Err(format!("Unknown qualifier /{} on {}", key, f.kind));
+        }
+        if !rules.is_allowed(key) {
+            return Err(format!("Qualifier /{} isn't allowed on {}", key, f.kind));
+        }
+    }
+    for key in rules.mandatory {
+        if !f.qualifiers.iter().any(|(k, _)| k == key) {
+            return Err(format!(
+                "{} is missing the mandatory /{} qualifier",
+                f.kind, key
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Location;
+
+    #[test]
+    fn locus_columns() {
+        let ok = [
+            &b"LOCUS       EU851978                1698 bp    cRNA    linear   VRL 24-OCT-2008"[..],
+            b"LOCUS       NC_000932             154478 bp ds-DNA     circular PLN 15-APR-2009",
+            b"LOCUS       NP_001832                360 aa            linear   PRI 18-DEC-2001",
+        ];
+        for l in &ok {
+            assert_eq!(check_locus_columns(l), Ok(()));
+        }
+        let bad = [
+            // old layout
+            (
+                &b"LOCUS       ATCOR66M      513 bp    mRNA            PLN       02-MAR-1992"[..],
+                "LOCUS name must be left-justified in columns 13-28",
+            ),
+            (
+                b"LOCUS       U00096               4641652 bp    DNA     circular BCT 1-AUG-2014",
+                "LOCUS line is too short, expected the date in columns 69-79",
+            ),
+            (
+                b"LOCUS       U00096               4641652 bp    DNA     circ     BCT 01-AUG-2014",
+                "Expected linear or circular in LOCUS columns 56-63, found \"circ    \"",
+            ),
+            (
+                b"LOCUS       U00096               4641652 bp    DNA     linear   bct 01-AUG-2014",
+                "Invalid division \"bct\" in LOCUS columns 65-67",
+            ),
+        ];
+        for (l, e) in &bad {
+            assert_eq!(check_locus_columns(l).unwrap_err(), *e);
+        }
+    }
+
+    #[test]
+    fn features() {
+        let f = |kind: &str, qualifiers: &[&str]| {
+            Feature::new(
+                kind.into(),
+                Location::simple_range(0, 10),
+                qualifiers.iter().map(|&k| (k.into(), None)).collect(),
+            )
+        };
+        assert!(check_feature(&f("gene", &["gene", "note"]), false).is_ok());
+        assert_eq!(
+            check_feature(&f("gene", &["translation"]), false).unwrap_err(),
+            "Qualifier /translation isn't allowed on gene"
+        );
+        assert_eq!(
+            check_feature(&f("gene", &["ApEinfo_fwdcolor"]), false).unwrap_err(),
+            "Unknown qualifier /ApEinfo_fwdcolor on gene"
+        );
+        assert_eq!(
+            check_feature(&f("misc", &[]), false).unwrap_err(),
+            "Unknown feature key misc"
+        );
+        assert_eq!(
+            check_feature(&f("source", &[]), false).unwrap_err(),
+            "source is missing the mandatory /mol_type qualifier"
+        );
+        assert!(check_feature(&f("Region", &["region_name"]), true).is_ok());
+    }
+}
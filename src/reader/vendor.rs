@@ -0,0 +1,219 @@
+//! Fixing up the GenBank files written by sequence editors such as SnapGene,
+//! ApE, Vector NTI and Benchling, which don't quite follow the format.
+
+use std::io::{BufRead, BufReader, Read, Result as IoResult};
+
+const KEYWORDS: &[&str] = &[
+    "LOCUS",
+    "DEFINITION",
+    "ACCESSION",
+    "VERSION",
+    "DBLINK",
+    "KEYWORDS",
+    "SEGMENT",
+    "SOURCE",
+    "REFERENCE",
+    "COMMENT",
+    "FEATURES",
+    "BASE",
+    "CONTIG",
+    "ORIGIN",
+];
+
+const SUBKEYWORDS: &[&str] = &[
+    "ORGANISM", "AUTHORS", "CONSRTM", "TITLE", "JOURNAL", "PUBMED", "REMARK",
+];
+
+const MONTHS: &[&str] = &[
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Section {
+    Header,
+    Features,
+    Sequence,
+}
+
+/// Wraps a stream of GenBank data, fixing the deviations from the format
+/// commonly found in files exported by sequence editors, line by line, so
+/// that `SeqReader` can read them. See `SeqReader::with_vendor_fixes`.
+///
+/// Byte offsets and line numbers reported by the reader refer to the fixed
+/// data. Lines only change length when a date is rewritten or tabs are
+/// expanded.
+#[derive(Debug)]
+pub struct VendorFixes<T: Read> {
+    input: BufReader<T>,
+    line: Vec<u8>,
+    pos: usize,
+    section: Section,
+    // if not set, lines are passed through unchanged
+    enabled: bool,
+}
+
+impl<T: Read> VendorFixes<T> {
+    pub fn new(input: T) -> VendorFixes<T> {
+        VendorFixes {
+            input: BufReader::new(input),
+            line: Vec::new(),
+            pos: 0,
+            section: Section::Header,
+            enabled: true,
+        }
+    }
+
+    /// Passes `input` through unchanged, for `ParserOptions::reader`
+    pub(crate) fn passthrough(input: T) -> VendorFixes<T> {
+        VendorFixes {
+            enabled: false,
+            ..VendorFixes::new(input)
+        }
+    }
+
+    fn fix_line(&mut self) {
+        let line = &mut self.line;
+        // keywords in lower or mixed case
+        let word_end = line
+            .iter()
+            .position(|b| !b.is_ascii_alphabetic())
+            .unwrap_or(line.len());
+        let word = String::from_utf8_lossy(&line[..word_end]).to_ascii_uppercase();
+        if KEYWORDS.contains(&word.as_str()) {
+            line[..word_end].make_ascii_uppercase();
+            self.section = match word.as_str() {
+                "FEATURES" => Section::Features,
+                "ORIGIN" => Section::Sequence,
+                _ => Section::Header,
+            };
+        } else if line.starts_with(b"//") {
+            self.section = Section::Header;
+        } else if self.section == Section::Header && line.starts_with(b"  ") {
+            let start = line.iter().position(|&b| b != b' ').unwrap_or(0);
+            let end = start
+                + line[start..]
+                    .iter()
+                    .position(|b| !b.is_ascii_alphabetic())
+                    .unwrap_or(line.len() - start);
+            let word = String::from_utf8_lossy(&line[start..end]).to_ascii_uppercase();
+            if start <= 3 && SUBKEYWORDS.contains(&word.as_str()) {
+                line[start..end].make_ascii_uppercase();
+            }
+        }
+        if self.section == Section::Features && line.contains(&b'\t') {
+            *line = expand_feature_tabs(line);
+        }
+        if word == "LOCUS" {
+            *line = fix_locus(line);
+        }
+    }
+}
+
+/// Lays out a feature table line indented with tabs in the usual columns
+fn expand_feature_tabs(line: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(line);
+    let ending = if text.ends_with("\r\n") {
+        "\r\n"
+    } else if text.ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    };
+    let content = text[..text.len() - ending.len()].trim().replace('\t', " ");
+    let fixed = if content.is_empty() || content.starts_with('/') || !text.starts_with('\t') {
+        format!("{:21}{}", "", content)
+    } else {
+        match content.split_once(' ') {
+            Some((key, location)) => format!("     {:<15} {}", key, location.trim_start()),
+            None => format!("     {}", content),
+        }
+    };
+    format!("{}{}", fixed, ending).into_bytes()
+}
+
+/// Fixes the case of the topology and the date, and rewrites dates given as
+/// `YYYY-MM-DD`
+fn fix_locus(line: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(line).into_owned();
+    let mut fixed = String::with_capacity(text.len() + 1);
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let token_start = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+        fixed.push_str(&rest[..token_start]);
+        rest = &rest[token_start..];
+        let token_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..token_end];
+        rest = &rest[token_end..];
+        let lower = token.to_ascii_lowercase();
+        if lower == "linear" || lower == "circular" {
+            fixed.push_str(&lower);
+        } else if let Some(date) = fix_date(token) {
+            // keep the line the same length if there's room
+            if date.len() > token.len() && fixed.ends_with("  ") {
+                fixed.pop();
+            }
+            fixed.push_str(&date);
+        } else {
+            fixed.push_str(token);
+        }
+    }
+    fixed.into_bytes()
+}
+
+/// A date in `DD-MON-YYYY` form, if `token` is a date in another form
+fn fix_date(token: &str) -> Option<String> {
+    let parts: Vec<_> = token.split('-').collect();
+    if parts.len() != 3 || !parts.iter().all(|p| !p.is_empty()) {
+        return None;
+    }
+    let month = parts[1].to_ascii_uppercase();
+    if MONTHS.contains(&month.as_str()) && parts[1] != month {
+        return Some(format!("{}-{}-{}", parts[0], month, parts[2]));
+    }
+    let numbers: Vec<u32> = parts.iter().map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    if parts[0].len() == 4 && (1..=12).contains(&numbers[1]) {
+        let month = MONTHS[numbers[1] as usize - 1];
+        return Some(format!("{:02}-{}-{}", numbers[2], month, numbers[0]));
+    }
+    None
+}
+
+impl<T: Read> Read for VendorFixes<T> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if !self.enabled {
+            return self.input.read(buf);
+        }
+        if self.pos == self.line.len() {
+            self.line.clear();
+            self.pos = 0;
+            if self.input.read_until(b'\n', &mut self.line)? == 0 {
+                return Ok(0);
+            }
+            self.fix_line();
+        }
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locus() {
+        let fix = |l: &str| String::from_utf8(fix_locus(l.as_bytes())).unwrap();
+        assert_eq!(
+            fix("LOCUS       pBR322  12 bp    DNA     CIRCULAR    2000-01-31\n"),
+            "LOCUS       pBR322  12 bp    DNA     circular   31-JAN-2000\n"
+        );
+        assert_eq!(
+            fix("LOCUS       a 12 bp DNA Linear SYN 01-Jan-2000"),
+            "LOCUS       a 12 bp DNA linear SYN 01-JAN-2000"
+        );
+        assert_eq!(fix_date("ds-DNA-x"), None);
+        assert_eq!(fix_date("01-JAN-2000"), None);
+    }
+}
@@ -0,0 +1,468 @@
+//! A set of records which were loaded, or will be written, together.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::errors::GbParserError;
+use crate::reader::SeqReader;
+use crate::seq::{Feature, Location, LocationError, Seq, Topology};
+
+/// Returned (wrapped in `LocationError::External`) when a location refers to
+/// a record which isn't in the set
+#[derive(Debug, Error)]
+#[error(display = "No record named {} in the set", _0)]
+pub struct MissingRecordError(pub String);
+
+#[derive(Debug, Error)]
+pub enum SegmentError {
+    #[error(display = "Record {} has a missing or invalid SEGMENT line", _0)]
+    Invalid(usize),
+    #[error(display = "Records disagree on the number of segments")]
+    Inconsistent,
+    #[error(display = "Segment {} occurs more than once", _0)]
+    Duplicate(usize),
+    #[error(display = "Segment {} is missing", _0)]
+    Missing(usize),
+}
+
+/// Several `Seq`s, typically all the records from one file, with lookup by
+/// name or accession
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RecordSet {
+    pub records: Vec<Seq>,
+}
+
+/// A problem found by `RecordSet::validate`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SetProblem {
+    /// More than one record has this LOCUS name
+    DuplicateName(String),
+    /// More than one record has this accession and version
+    DuplicateAccession(String),
+    /// A location in a record refers to another sequence which isn't in the
+    /// set. `feature` is `None` for the CONTIG line.
+    DanglingReference {
+        record: usize,
+        feature: Option<usize>,
+        target: String,
+    },
+}
+
+impl fmt::Display for SetProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SetProblem::DuplicateName(ref n) => write!(f, "Duplicate record name {}", n),
+            SetProblem::DuplicateAccession(ref a) => write!(f, "Duplicate accession {}", a),
+            SetProblem::DanglingReference {
+                record,
+                feature: Some(feature),
+                ref target,
+            } => write!(
+                f,
+                "Feature {} of record {} refers to {}, which isn't in the set",
+                feature, record, target
+            ),
+            SetProblem::DanglingReference {
+                record,
+                feature: None,
+                ref target,
+            } => write!(
+                f,
+                "CONTIG of record {} refers to {}, which isn't in the set",
+                record, target
+            ),
+        }
+    }
+}
+
+fn external_refs<'a>(l: &'a Location, refs: &mut Vec<&'a str>) {
+    use Location::*;
+    match *l {
+        External(ref name, _) => refs.push(name),
+        Complement(ref l) => external_refs(l, refs),
+        Join(ref ls) | Order(ref ls) | Bond(ref ls) | OneOf(ref ls) => {
+            for l in ls {
+                external_refs(l, refs);
+            }
+        }
+        Range(..) | Between(..) | Gap(..) => {}
+    }
+}
+
+impl Seq {
+    /// The accession.version from the VERSION line, without the GI number
+    /// older records put after it
+    pub fn accession_version(&self) -> Option<&str> {
+        self.version
+            .as_ref()
+            .and_then(|v| v.split_whitespace().next())
+    }
+
+    /// Whether this record is the one meant by `id`, which can be its name,
+    /// accession or accession.version
+    pub fn is_named(&self, id: &str) -> bool {
+        self.name.as_deref() == Some(id)
+            || self.accession_version() == Some(id)
+            || self.accession.as_deref() == Some(id)
+    }
+}
+
+impl RecordSet {
+    pub fn new(records: Vec<Seq>) -> RecordSet {
+        RecordSet { records }
+    }
+
+    /// Parses every record from `data`
+    pub fn from_reader<T: Read>(data: T) -> Result<RecordSet, GbParserError> {
+        SeqReader::new(data)
+            .collect::<Result<_, _>>()
+            .map(RecordSet::new)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<RecordSet, GbParserError> {
+        RecordSet::from_reader(std::fs::File::open(path)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn push(&mut self, seq: Seq) {
+        self.records.push(seq);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Seq> {
+        self.records.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Seq> {
+        self.records.iter_mut()
+    }
+
+    /// Finds a record by name, accession or accession.version, see
+    /// `Seq::is_named`
+    pub fn get(&self, id: &str) -> Option<&Seq> {
+        self.records.iter().find(|s| s.is_named(id))
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Seq> {
+        self.records.iter_mut().find(|s| s.is_named(id))
+    }
+
+    fn fetch(&self, id: &str) -> Result<&Seq, Box<dyn Error>> {
+        self.get(id)
+            .ok_or_else(|| Box::new(MissingRecordError(id.to_string())) as Box<dyn Error>)
+    }
+
+    /// Extracts `l` from `record`, resolving any references to other
+    /// sequences against the records in this set
+    pub fn extract_location(&self, record: &Seq, l: &Location) -> Result<Vec<u8>, LocationError> {
+        record.extract_location_with_fetcher(l, |id| self.fetch(id))
+    }
+
+    /// The bounds of `l`, like `Location::find_bounds`, except that a
+    /// reference to another record in the set gives the bounds within that
+    /// record
+    pub fn find_bounds(&self, l: &Location) -> Result<(i64, i64), LocationError> {
+        match *l {
+            Location::External(ref id, ref inner) => {
+                let target = self
+                    .fetch(id)
+                    .map_err(|e| LocationError::External(l.clone(), e))?;
+                match *inner {
+                    Some(ref inner) => inner.find_bounds(),
+                    None => Ok((0, target.len())),
+                }
+            }
+            Location::Complement(ref inner) => self.find_bounds(inner),
+            _ => l.find_bounds(),
+        }
+    }
+
+    /// Builds the sequence described by the CONTIG line of `record` from the
+    /// other records in the set. Gaps are filled with `n`, with gaps of
+    /// unknown size taken to be 100 bases long. Returns `None` if the record
+    /// has no CONTIG.
+    pub fn assemble_contig(&self, record: &Seq) -> Option<Result<Vec<u8>, LocationError>> {
+        record.assemble_contig_with_fetcher(|id| self.fetch(id))
+    }
+
+    /// Joins the records of a legacy segmented entry (`SEGMENT 1 of n` ...
+    /// `SEGMENT n of n`) into one linear `Seq`, in segment order regardless
+    /// of their order in the set. Segments are separated by `gap_len` `n`s,
+    /// each annotated with a `gap` feature of unknown length, and features
+    /// are moved to their place in the joined sequence. The header is taken
+    /// from the first segment.
+    pub fn assemble_segments(&self, gap_len: usize) -> Result<Seq, SegmentError> {
+        let mut segments = Vec::with_capacity(self.records.len());
+        let mut total = None;
+        for (i, s) in self.records.iter().enumerate() {
+            let (n, m) = s
+                .segment_number()
+                .filter(|&(n, m)| n >= 1 && n <= m)
+                .ok_or(SegmentError::Invalid(i))?;
+            if *total.get_or_insert(m) != m {
+                return Err(SegmentError::Inconsistent);
+            }
+            segments.push((n, s));
+        }
+        segments.sort_by_key(|&(n, _)| n);
+        for (expected, &(n, _)) in (1..).zip(&segments) {
+            if n < expected {
+                return Err(SegmentError::Duplicate(n));
+            }
+            if n > expected {
+                return Err(SegmentError::Missing(expected));
+            }
+        }
+        if let Some(total) = total {
+            if segments.len() < total {
+                return Err(SegmentError::Missing(segments.len() + 1));
+            }
+        }
+
+        let mut res = match segments.first() {
+            Some(&(_, first)) => Seq {
+                topology: Topology::Linear,
+                segment: None,
+                ..first.header()
+            },
+            None => return Ok(Seq::empty()),
+        };
+        for (i, &(_, s)) in segments.iter().enumerate() {
+            let offset = res.seq.len() as i64;
+            if i > 0 {
+                res.features.push(Feature::new(
+                    feature_kind!("gap"),
+                    Location::simple_range(offset, offset + gap_len as i64),
+                    vec![(qualifier_key!("estimated_length"), Some("unknown".into()))],
+                ));
+                res.seq.extend(std::iter::repeat_n(b'n', gap_len));
+            }
+            let offset = res.seq.len() as i64;
+            for f in &s.features {
+                // can't fail on a linear sequence
+                let location = res.relocate_location(f.location.clone(), offset).unwrap();
+                res.features.push(Feature {
+                    location,
+                    ..f.clone()
+                });
+            }
+            res.seq.extend_from_slice(&s.seq);
+        }
+        res.len = Some(res.seq.len());
+        Ok(res)
+    }
+
+    /// Writes every record, one after another
+    pub fn write<T: Write>(&self, mut file: T) -> io::Result<()> {
+        for record in &self.records {
+            record.write(&mut file)?;
+        }
+        Ok(())
+    }
+
+    /// Checks for duplicate names or accessions and for locations referring
+    /// to records which aren't in the set
+    pub fn validate(&self) -> Vec<SetProblem> {
+        let mut problems = Vec::new();
+        let mut seen_names: Vec<&str> = Vec::new();
+        let mut seen_accessions: Vec<&str> = Vec::new();
+        for s in &self.records {
+            if let Some(ref name) = s.name {
+                if seen_names.contains(&name.as_str()) {
+                    problems.push(SetProblem::DuplicateName(name.clone()));
+                }
+                seen_names.push(name);
+            }
+            if let Some(accession) = s.accession_version().or(s.accession.as_deref()) {
+                if seen_accessions.contains(&accession) {
+                    problems.push(SetProblem::DuplicateAccession(accession.to_string()));
+                }
+                seen_accessions.push(accession);
+            }
+        }
+        for (i, s) in self.records.iter().enumerate() {
+            let locations = s.contig.iter().map(|c| (None, c)).chain(
+                s.features
+                    .iter()
+                    .enumerate()
+                    .map(|(j, f)| (Some(j), &f.location)),
+            );
+            for (feature, l) in locations {
+                let mut refs = Vec::new();
+                external_refs(l, &mut refs);
+                for target in refs {
+                    if self.get(target).is_none() {
+                        problems.push(SetProblem::DanglingReference {
+                            record: i,
+                            feature,
+                            target: target.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        problems
+    }
+}
+
+impl IntoIterator for RecordSet {
+    type Item = Seq;
+    type IntoIter = std::vec::IntoIter<Seq>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a RecordSet {
+    type Item = &'a Seq;
+    type IntoIter = std::slice::Iter<'a, Seq>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.iter()
+    }
+}
+
+impl std::iter::FromIterator<Seq> for RecordSet {
+    fn from_iter<I: IntoIterator<Item = Seq>>(iter: I) -> RecordSet {
+        RecordSet::new(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lookup_and_validate() {
+        let set = RecordSet::from_file("tests/biopython_tests/ls_orchid.gb").unwrap();
+        assert_eq!(set.len(), 94);
+        assert!(set.validate().is_empty());
+        let by_name = set.get("Z78533").unwrap();
+        assert_eq!(set.get("Z78533.1"), Some(by_name));
+        assert!(set.get("nope").is_none());
+
+        let mut out = Vec::new();
+        set.write(&mut out).unwrap();
+        assert_eq!(RecordSet::from_reader(&out[..]).unwrap(), set);
+
+        let mut set: RecordSet = set.into_iter().take(2).collect();
+        let dup = set.records[0].clone();
+        set.push(dup);
+        set.records[1].contig = Some(Location::Join(vec![
+            Location::External("Z78533.1".into(), None),
+            Location::External("X00001.1".into(), None),
+        ]));
+        assert_eq!(
+            set.validate(),
+            vec![
+                SetProblem::DuplicateName("Z78533".into()),
+                SetProblem::DuplicateAccession("Z78533.1".into()),
+                SetProblem::DanglingReference {
+                    record: 1,
+                    feature: None,
+                    target: "X00001.1".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_external() {
+        let part = |name: &str, seq: &[u8]| Seq {
+            name: Some(name.into()),
+            accession: Some(name.into()),
+            version: Some(format!("{}.1", name)),
+            seq: seq.to_vec(),
+            ..Seq::empty()
+        };
+        let mut scaffold = Seq {
+            name: Some("scaffold".into()),
+            ..Seq::empty()
+        };
+        scaffold.contig = Some(
+            Location::from_gb_format("join(A.1:1..4,gap(2),complement(B.1:2..3),gap(),B)").unwrap(),
+        );
+        let set = RecordSet::new(vec![part("A", b"acgtac"), part("B", b"ggca"), scaffold]);
+        assert!(set.validate().is_empty());
+        let assembled = set.assemble_contig(&set.records[2]).unwrap().unwrap();
+        let mut expected = b"acgtnngc".to_vec();
+        expected.extend(vec![b'n'; 100]);
+        expected.extend(b"ggca");
+        assert_eq!(assembled, expected);
+        assert!(set.assemble_contig(&set.records[0]).is_none());
+
+        let l = Location::from_gb_format("complement(B.1:2..3)").unwrap();
+        assert_eq!(set.find_bounds(&l).unwrap(), (1, 3));
+        assert_eq!(
+            set.find_bounds(&Location::from_gb_format("A").unwrap())
+                .unwrap(),
+            (0, 6)
+        );
+        assert_eq!(set.extract_location(&set.records[0], &l).unwrap(), b"gc");
+        let missing = Location::from_gb_format("C.1:1..2").unwrap();
+        assert!(set.extract_location(&set.records[0], &missing).is_err());
+        assert!(set.find_bounds(&missing).is_err());
+    }
+
+    #[test]
+    fn assemble_segments() {
+        let one_of = RecordSet::from_file("tests/biopython_tests/one_of.gb").unwrap();
+        assert_eq!(one_of.records[0].segment_number(), Some((1, 6)));
+        let mut out = Vec::new();
+        one_of.write(&mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("\nSEGMENT     1 of 6\n"));
+
+        let segment = |n: usize, seq: &[u8]| Seq {
+            name: Some(format!("SEG{}", n)),
+            segment: Some(format!("{} of 2", n)),
+            seq: seq.to_vec(),
+            features: vec![Feature::new(
+                feature_kind!("exon"),
+                Location::simple_range(1, 3),
+                vec![],
+            )],
+            ..Seq::empty()
+        };
+        let set = RecordSet::new(vec![segment(2, b"ggcc"), segment(1, b"aatt")]);
+        let joined = set.assemble_segments(3).unwrap();
+        assert_eq!(joined.name.as_deref(), Some("SEG1"));
+        assert_eq!(joined.segment, None);
+        assert_eq!(joined.seq, b"aattnnnggcc");
+        assert_eq!(joined.len, Some(11));
+        let locations: Vec<_> = joined
+            .features
+            .iter()
+            .map(|f| (f.kind.to_string(), f.location.to_gb_format()))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                ("exon".to_string(), "2..3".to_string()),
+                ("gap".to_string(), "5..7".to_string()),
+                ("exon".to_string(), "9..10".to_string()),
+            ]
+        );
+
+        let missing = RecordSet::new(vec![segment(2, b"ggcc")]);
+        match missing.assemble_segments(3) {
+            Err(SegmentError::Missing(1)) => {}
+            r => panic!("{:?}", r),
+        }
+        let duplicate = RecordSet::new(vec![segment(1, b"a"), segment(1, b"a")]);
+        match duplicate.assemble_segments(3) {
+            Err(SegmentError::Duplicate(1)) => {}
+            r => panic!("{:?}", r),
+        }
+    }
+}
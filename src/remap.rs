@@ -0,0 +1,172 @@
+//! Moving features to a new coordinate system, e.g. for liftover between
+//! assemblies using an alignment computed elsewhere.
+
+use crate::seq::{simplify, After, Before, Feature, Location, Seq};
+
+/// Maps positions (0-based) in the old sequence to positions in the new
+/// one, or `None` for positions which have no counterpart
+pub trait CoordinateMap {
+    fn map(&self, position: i64) -> Option<i64>;
+
+    /// Maps the bases `start..end`, returning the runs they end up in as
+    /// exclusive ranges, in the order of the original positions. Bases
+    /// which aren't mapped are dropped, and a new range is started wherever
+    /// consecutive bases don't stay consecutive. The default implementation
+    /// calls `map` for each base, implementations backed by blocks of an
+    /// alignment may want to override it.
+    fn map_range(&self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        let mut runs: Vec<(i64, i64)> = Vec::new();
+        let mut last = None;
+        for p in start..end {
+            let mapped = self.map(p);
+            match (mapped, runs.last_mut()) {
+                (Some(m), Some(run)) if last == Some(p - 1) && run.1 == m => run.1 = m + 1,
+                (Some(m), _) => runs.push((m, m + 1)),
+                (None, _) => {}
+            }
+            if mapped.is_some() {
+                last = Some(p);
+            }
+        }
+        runs
+    }
+}
+
+impl<F: Fn(i64) -> Option<i64>> CoordinateMap for F {
+    fn map(&self, position: i64) -> Option<i64> {
+        self(position)
+    }
+}
+
+fn remap_location<M: CoordinateMap + ?Sized>(l: &Location, map: &M) -> Option<Location> {
+    use Location::*;
+    let remap_all = |ls: &[Location]| {
+        let res: Vec<_> = ls.iter().filter_map(|l| remap_location(l, map)).collect();
+        if res.is_empty() {
+            None
+        } else {
+            Some(res)
+        }
+    };
+    let res = match *l {
+        Range((a, Before(before)), (b, After(after))) => {
+            let runs = map.map_range(a, b);
+            let first = *runs.first()?;
+            let last = *runs.last().unwrap();
+            // ends which were cut off become partial
+            let before = before || map.map(a) != Some(first.0);
+            let after = after || map.map(b - 1) != Some(last.1 - 1);
+            let n = runs.len();
+            let mut ranges: Vec<_> = runs
+                .into_iter()
+                .enumerate()
+                .map(|(i, (s, e))| {
+                    Range(
+                        (s, Before(i == 0 && before)),
+                        (e, After(i == n - 1 && after)),
+                    )
+                })
+                .collect();
+            if n == 1 {
+                ranges.pop().unwrap()
+            } else {
+                Join(ranges)
+            }
+        }
+        Between(a, b) => match (map.map(a), map.map(b)) {
+            (Some(a), Some(b)) if b == a + 1 => Between(a, b),
+            _ => return None,
+        },
+        Complement(ref l) => Complement(Box::new(remap_location(l, map)?)),
+        Join(ref ls) => Join(remap_all(ls)?),
+        Order(ref ls) => Order(remap_all(ls)?),
+        Bond(ref ls) => Bond(remap_all(ls)?),
+        OneOf(ref ls) => OneOf(remap_all(ls)?),
+        External(..) | Gap(..) => l.clone(),
+    };
+    simplify(res).ok()
+}
+
+impl Seq {
+    /// Rewrites the location of every feature through `map`. Ranges are
+    /// split where the mapping is discontinuous and truncated (and marked
+    /// partial) where the bases at their ends aren't mapped. Features with
+    /// nothing left are removed and returned. The sequence itself isn't
+    /// changed, so this is typically followed by replacing it.
+    pub fn remap_features<M: CoordinateMap + ?Sized>(&mut self, map: &M) -> Vec<Feature> {
+        let mut removed = Vec::new();
+        let features = std::mem::take(&mut self.features);
+        for f in features {
+            match remap_location(&f.location, map) {
+                Some(location) => self.features.push(Feature { location, ..f }),
+                None => removed.push(f),
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remap() {
+        // 10..20 is deleted and everything from 50 dropped
+        let map = |p: i64| match p {
+            0..=9 => Some(p),
+            10..=19 => None,
+            20..=49 => Some(p - 10),
+            _ => None,
+        };
+        let f = |l: &str| {
+            Feature::new(
+                feature_kind!("misc_feature"),
+                Location::from_gb_format(l).unwrap(),
+                Vec::new(),
+            )
+        };
+        let mut s = Seq {
+            seq: vec![b'a'; 60],
+            features: vec![
+                f("1..5"),
+                f("6..25"),
+                f("complement(15..30)"),
+                f("12..18"),
+                f("45..55"),
+                f("join(1..3,21..23)"),
+                f("30^31"),
+            ],
+            ..Seq::empty()
+        };
+        let removed = s.remap_features(&map);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].location.to_gb_format(), "12..18");
+        let locations: Vec<_> = s
+            .features
+            .iter()
+            .map(|f| f.location.to_gb_format())
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                "1..5",
+                "6..15",
+                "complement(<11..20)",
+                "35..>40",
+                "join(1..3,11..13)",
+                "20^21",
+            ]
+        );
+
+        // a mapping which isn't contiguous splits ranges
+        let swap = |p: i64| Some(if p < 5 { p + 5 } else { p - 5 });
+        let mut s = Seq {
+            seq: vec![b'a'; 10],
+            features: vec![f("3..8")],
+            ..Seq::empty()
+        };
+        s.remap_features(&swap);
+        assert_eq!(s.features[0].location.to_gb_format(), "join(8..10,1..3)");
+    }
+}
@@ -0,0 +1,320 @@
+//! Comparing circular sequences regardless of where their origin is and
+//! which strand they're given on, fingerprinting records for
+//! deduplication, and generating every standard orientation of a record.
+
+use std::cmp::Ordering;
+
+use crate::dna::revcomp;
+use crate::seq::{Feature, Fnv, Seq};
+
+/// One of the variants of a record returned by `Seq::orientations`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Orientation {
+    /// The record as it is
+    Forward,
+    ReverseComplement,
+    /// The record in canonical form, see `Seq::canonicalize`
+    Canonical,
+}
+
+/// The start of the lexicographically least rotation of `s`, using Booth's
+/// algorithm
+fn least_rotation(s: &[u8]) -> usize {
+    let n = s.len();
+    let mut failure = vec![-1i64; 2 * n];
+    let mut k = 0;
+    for j in 1..2 * n {
+        let c = s[j % n];
+        let mut i = failure[j - k - 1];
+        while i != -1 && c != s[(k + i as usize + 1) % n] {
+            if c < s[(k + i as usize + 1) % n] {
+                k = j - i as usize - 1;
+            }
+            i = failure[i as usize];
+        }
+        if i == -1 && c != s[k % n] {
+            if c < s[k % n] {
+                k = j;
+            }
+            failure[j - k] = -1;
+        } else {
+            failure[j - k] = i + 1;
+        }
+    }
+    k
+}
+
+fn rotated(s: &[u8], origin: usize) -> impl Iterator<Item = &u8> {
+    s[origin..].iter().chain(&s[..origin])
+}
+
+impl Seq {
+    /// Whether to flip the sequence and where to put the origin so that it
+    /// reads as the least of all its rotations, on either strand. Linear
+    /// sequences are never rotated.
+    fn canonical_orientation(&self) -> (bool, usize) {
+        let forward = self.seq.to_ascii_lowercase();
+        let reverse = revcomp(&forward);
+        if self.is_protein() {
+            return (false, 0);
+        }
+        if !self.is_circular() || forward.is_empty() {
+            return (reverse < forward, 0);
+        }
+        let f = least_rotation(&forward);
+        let r = least_rotation(&reverse);
+        match rotated(&reverse, r).cmp(rotated(&forward, f)) {
+            Ordering::Less => (true, r),
+            _ => (false, f),
+        }
+    }
+
+    /// Returns a copy in canonical form: on the strand whose sequence sorts
+    /// first (unless it's a protein) and, for circular sequences, with the
+    /// origin which makes the sequence sort first (ignoring case). Two
+    /// records of the same molecule have the same canonical sequence, and
+    /// their features end up with the same coordinates, unless the sequence
+    /// is made of a repeated unit, in which case there are several equally
+    /// good origins.
+    pub fn canonicalize(&self) -> Seq {
+        let (reverse, origin) = self.canonical_orientation();
+        let seq = if reverse {
+            self.revcomp()
+        } else {
+            self.clone()
+        };
+        if origin == 0 {
+            seq
+        } else {
+            seq.set_origin(origin as i64)
+        }
+    }
+
+    /// The standard orientations of the record: as it is, reverse
+    /// complemented and, for circular sequences, in canonical form. Each
+    /// variant is only built when the iterator reaches it, and all but the
+    /// first get a COMMENT saying how they were derived. Proteins only have
+    /// the forward orientation.
+    pub fn orientations(&self) -> impl Iterator<Item = (Orientation, Seq)> + '_ {
+        let variants: &[Orientation] = if self.is_protein() {
+            &[Orientation::Forward]
+        } else if self.is_circular() {
+            &[
+                Orientation::Forward,
+                Orientation::ReverseComplement,
+                Orientation::Canonical,
+            ]
+        } else {
+            &[Orientation::Forward, Orientation::ReverseComplement]
+        };
+        let name = self
+            .name
+            .as_deref()
+            .or(self.accession.as_deref())
+            .unwrap_or("the original record");
+        variants.iter().map(move |&o| {
+            let (mut seq, comment) = match o {
+                Orientation::Forward => return (o, self.clone()),
+                Orientation::ReverseComplement => {
+                    (self.revcomp(), format!("Reverse complement of {}", name))
+                }
+                Orientation::Canonical => {
+                    let (reverse, origin) = self.canonical_orientation();
+                    let comment = format!(
+                        "Canonical form of {}: {}origin moved to position {}",
+                        name,
+                        if reverse { "reverse complemented, " } else { "" },
+                        origin + 1
+                    );
+                    (self.canonicalize(), comment)
+                }
+            };
+            seq.comments.push(comment);
+            (o, seq)
+        })
+    }
+
+    /// Tests whether `self` and `other` are both circular and represent the
+    /// same molecule, regardless of origin and strand. If `compare_features`
+    /// is set, they must also have the same features (kinds, locations and
+    /// qualifiers, in any order) once rotated to match.
+    pub fn equivalent_circular(&self, other: &Seq, compare_features: bool) -> bool {
+        if !self.is_circular() || !other.is_circular() || self.seq.len() != other.seq.len() {
+            return false;
+        }
+        let a = self.canonicalize();
+        let b = other.canonicalize();
+        if !a.seq.eq_ignore_ascii_case(&b.seq) {
+            return false;
+        }
+        !compare_features || feature_keys(&a) == feature_keys(&b)
+    }
+
+    /// A hash of the canonical form of the record. It only depends on the
+    /// sequence (ignoring case), topology and, if `include_features` is set,
+    /// the features' kinds, locations and qualifiers (in any order), so
+    /// rotated or flipped copies of a plasmid, or the same record read from
+    /// different files, get the same fingerprint. The hash function is fixed
+    /// (64 bit FNV-1a), so fingerprints can be stored and compared between
+    /// runs.
+    pub fn fingerprint(&self, include_features: bool) -> u64 {
+        let canonical = self.canonicalize();
+        let mut hash = Fnv::new();
+        hash.write(if self.is_circular() {
+            b"circular"
+        } else {
+            b"linear"
+        });
+        hash.write(b"\n");
+        hash.write(&canonical.seq.to_ascii_lowercase());
+        if include_features {
+            for key in feature_keys(&canonical) {
+                hash.write(b"\n");
+                hash.write(key.as_bytes());
+            }
+        }
+        hash.0
+    }
+}
+
+/// The features of `s` in a form that can be compared regardless of order
+fn feature_keys(s: &Seq) -> Vec<String> {
+    let mut keys: Vec<_> = s.features.iter().map(feature_key).collect();
+    keys.sort_unstable();
+    keys
+}
+
+fn feature_key(f: &Feature) -> String {
+    let mut qualifiers: Vec<_> = f
+        .qualifiers
+        .iter()
+        .map(|(k, v)| match v {
+            Some(v) => format!("{}={}", k, v),
+            None => k.to_string(),
+        })
+        .collect();
+    qualifiers.sort_unstable();
+    let mut key = format!("{}\t{}", f.kind, f.location.to_gb_format());
+    for q in qualifiers {
+        key.push('\t');
+        key.push_str(&q);
+    }
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::{Location, Topology};
+
+    #[test]
+    fn least() {
+        assert_eq!(least_rotation(b"cab"), 1);
+        assert_eq!(least_rotation(b"bbaab"), 2);
+        assert_eq!(least_rotation(b"aaaa"), 0);
+        for s in [&b"tgcatgca"[..], b"gattaca", b"cgcgaat", b"a"] {
+            let best = (0..s.len())
+                .min_by_key(|&i| rotated(s, i).cloned().collect::<Vec<_>>())
+                .unwrap();
+            let k = least_rotation(s);
+            assert!(rotated(s, k).eq(rotated(s, best)), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn equivalent() {
+        let plasmid = |seq: &[u8], start: i64, end: i64| Seq {
+            seq: seq.to_vec(),
+            topology: Topology::Circular,
+            features: vec![Feature::new(
+                feature_kind!("CDS"),
+                Location::simple_range(start, end),
+                vec![(qualifier_key!("gene"), Some("x".into()))],
+            )],
+            ..Seq::empty()
+        };
+        let a = plasmid(b"ggatccttaaac", 2, 6);
+        let rotated = a.set_origin(5);
+        let flipped = a.revcomp().set_origin(3);
+        assert!(a.equivalent_circular(&rotated, true));
+        assert!(a.equivalent_circular(&flipped, true));
+        assert!(rotated.equivalent_circular(&flipped, true));
+        let moved = plasmid(b"ggatccttaaac", 3, 7);
+        assert!(a.equivalent_circular(&moved, false));
+        assert!(!a.equivalent_circular(&moved, true));
+        assert!(!a.equivalent_circular(&plasmid(b"ggatccttaaag", 2, 6), false));
+        let linear = Seq {
+            topology: Topology::Linear,
+            ..a.clone()
+        };
+        assert!(!a.equivalent_circular(&linear, false));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let mut hash = Fnv::new();
+        hash.write(b"a");
+        assert_eq!(hash.0, 0xaf63dc4c8601ec8c);
+
+        let a = Seq {
+            seq: b"ggatccttaaac".to_vec(),
+            topology: Topology::Circular,
+            features: vec![Feature::new(
+                feature_kind!("gene"),
+                Location::simple_range(2, 6),
+                vec![],
+            )],
+            ..Seq::empty()
+        };
+        let mut b = a.revcomp().set_origin(4);
+        b.seq.make_ascii_uppercase();
+        assert_eq!(a.fingerprint(true), b.fingerprint(true));
+        b.features[0]
+            .qualifiers
+            .push((qualifier_key!("gene"), None));
+        assert_eq!(a.fingerprint(false), b.fingerprint(false));
+        assert_ne!(a.fingerprint(true), b.fingerprint(true));
+        let linear = Seq {
+            topology: Topology::Linear,
+            ..a.clone()
+        };
+        assert_ne!(a.fingerprint(false), linear.fingerprint(false));
+        assert_eq!(linear.fingerprint(true), linear.revcomp().fingerprint(true));
+
+        // qualifier order doesn't matter
+        let q = |k: &str, v: &str| (k.into(), Some(v.to_string()));
+        let mut c = a.clone();
+        c.features[0].qualifiers = vec![q("gene", "x"), q("note", "y")];
+        let mut d = c.clone();
+        d.features[0].qualifiers.reverse();
+        assert_eq!(c.fingerprint(true), d.fingerprint(true));
+        assert!(c.equivalent_circular(&d, true));
+        d.features[0].qualifiers[0].1 = Some("z".into());
+        assert_ne!(c.fingerprint(true), d.fingerprint(true));
+    }
+
+    #[test]
+    fn orientations() {
+        let mut plasmid = Seq {
+            name: Some("p1".into()),
+            seq: b"ggatccttaaac".to_vec(),
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        let variants: Vec<_> = plasmid.orientations().collect();
+        assert_eq!(variants.len(), 3);
+        assert_eq!(variants[0], (Orientation::Forward, plasmid.clone()));
+        assert_eq!(variants[1].1.seq, b"gtttaaggatcc");
+        assert_eq!(variants[1].1.comments, vec!["Reverse complement of p1"]);
+        assert_eq!(variants[2].1.seq, plasmid.canonicalize().seq);
+        assert!(variants[2].1.comments[0].starts_with("Canonical form of p1: "));
+
+        plasmid.topology = Topology::Linear;
+        let kinds: Vec<_> = plasmid.orientations().map(|(o, _)| o).collect();
+        assert_eq!(
+            kinds,
+            vec![Orientation::Forward, Orientation::ReverseComplement]
+        );
+    }
+}
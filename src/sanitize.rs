@@ -0,0 +1,279 @@
+//! Repairing common problems in records, for cleaning up legacy
+//! collections in bulk.
+
+use std::fmt;
+
+use crate::seq::{Location, Seq};
+use crate::QualifierKey;
+
+/// Which repairs `Seq::sanitize` makes. All of them are enabled by default.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SanitizePolicy {
+    /// Swap the ends of ranges written backwards, e.g. `10..5`
+    pub swap_reversed: bool,
+    /// On linear records, limit ranges to the sequence. On circular ones,
+    /// locations past the end are wrapped around the origin instead.
+    pub clamp: bool,
+    /// Remove empty joins, and parts of joins which are entirely off the end
+    /// of the sequence. A join left with a single part is replaced by it.
+    pub remove_empty: bool,
+    /// Remove leading and trailing whitespace from qualifier values
+    pub trim_qualifiers: bool,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> SanitizePolicy {
+        SanitizePolicy {
+            swap_reversed: true,
+            clamp: true,
+            remove_empty: true,
+            trim_qualifiers: true,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FixKind {
+    SwappedRange,
+    Clamped,
+    Wrapped,
+    RemovedEmptyPart,
+    /// The feature was removed as nothing was left of its location
+    RemovedFeature,
+    TrimmedQualifier(QualifierKey),
+}
+
+/// A repair made by `Seq::sanitize`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Fix {
+    /// Index of the feature concerned, before any features were removed
+    pub feature: usize,
+    pub kind: FixKind,
+    /// What was changed, e.g. the location before and after
+    pub detail: String,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "feature {}: {:?}: {}",
+            self.feature, self.kind, self.detail
+        )
+    }
+}
+
+impl Seq {
+    /// Repairs the problems selected by `policy` in place, returning every
+    /// fix that was made
+    pub fn sanitize(&mut self, policy: SanitizePolicy) -> Vec<Fix> {
+        let mut fixes = Vec::new();
+        let features = std::mem::take(&mut self.features);
+        for (i, mut f) in features.into_iter().enumerate() {
+            let mut fix = |kind, detail| {
+                fixes.push(Fix {
+                    feature: i,
+                    kind,
+                    detail,
+                })
+            };
+            let before = f.location.to_gb_format();
+            match self.fix_location(f.location.clone(), policy, &mut fix) {
+                Some(l) => f.location = l,
+                None => {
+                    fix(FixKind::RemovedFeature, before);
+                    continue;
+                }
+            }
+            if policy.trim_qualifiers {
+                for (k, v) in &mut f.qualifiers {
+                    if let Some(v) = v {
+                        let trimmed = v.trim();
+                        if trimmed.len() != v.len() {
+                            fix(FixKind::TrimmedQualifier(k.clone()), format!("{:?}", v));
+                            *v = trimmed.to_string();
+                        }
+                    }
+                }
+            }
+            self.features.push(f);
+        }
+        fixes
+    }
+
+    /// Returns `None` if nothing is left of `l`
+    fn fix_location(
+        &self,
+        l: Location,
+        policy: SanitizePolicy,
+        fix: &mut dyn FnMut(FixKind, String),
+    ) -> Option<Location> {
+        // ranges on circular records are wrapped rather than clamped
+        let clamp_to = if policy.clamp && !self.is_circular() && self.len() > 0 {
+            Some(self.len())
+        } else {
+            None
+        };
+        let mut l = fix_parts(l, clamp_to, policy, fix)?;
+        if policy.clamp && self.is_circular() {
+            let past_origin = l
+                .ranges()
+                .is_ok_and(|ranges| ranges.iter().any(|&(a, b)| a < 0 || b > self.len()));
+            if past_origin {
+                let before = l.to_gb_format();
+                if let Ok(wrapped) = self.wrap_location(l.clone()) {
+                    l = wrapped;
+                    fix(
+                        FixKind::Wrapped,
+                        format!("{} -> {}", before, l.to_gb_format()),
+                    );
+                }
+            }
+        }
+        Some(l)
+    }
+}
+
+/// Fixes ranges, clamping them to `clamp_to` if it's set, and removes empty
+/// parts
+fn fix_parts(
+    l: Location,
+    clamp_to: Option<i64>,
+    policy: SanitizePolicy,
+    fix: &mut dyn FnMut(FixKind, String),
+) -> Option<Location> {
+    use Location::*;
+    match l {
+        Range((mut a, before), (mut b, after)) => {
+            if policy.swap_reversed && b <= a {
+                let old = Range((a, before), (b, after)).to_gb_format();
+                let (start, end) = (b - 1, a + 1);
+                a = start;
+                b = end;
+                let new = Range((a, before), (b, after)).to_gb_format();
+                fix(FixKind::SwappedRange, format!("{} -> {}", old, new));
+            }
+            if let Some(len) = clamp_to {
+                let old = Range((a, before), (b, after)).to_gb_format();
+                if a >= len || b <= 0 {
+                    if policy.remove_empty {
+                        fix(FixKind::RemovedEmptyPart, old);
+                        return None;
+                    }
+                } else if a < 0 || b > len {
+                    a = a.max(0);
+                    b = b.min(len);
+                    let new = Range((a, before), (b, after)).to_gb_format();
+                    fix(FixKind::Clamped, format!("{} -> {}", old, new));
+                }
+            }
+            Some(Range((a, before), (b, after)))
+        }
+        Complement(x) => fix_parts(*x, clamp_to, policy, fix).map(|x| Complement(Box::new(x))),
+        Join(xs) => fix_join(xs, Join, clamp_to, policy, fix),
+        Order(xs) => fix_join(xs, Order, clamp_to, policy, fix),
+        Bond(xs) => fix_join(xs, Bond, clamp_to, policy, fix),
+        OneOf(xs) => fix_join(xs, OneOf, clamp_to, policy, fix),
+        l => Some(l),
+    }
+}
+
+fn fix_join(
+    xs: Vec<Location>,
+    build: fn(Vec<Location>) -> Location,
+    clamp_to: Option<i64>,
+    policy: SanitizePolicy,
+    fix: &mut dyn FnMut(FixKind, String),
+) -> Option<Location> {
+    let n = xs.len();
+    let mut parts: Vec<_> = xs
+        .into_iter()
+        .filter_map(|x| fix_parts(x, clamp_to, policy, fix))
+        .collect();
+    if !policy.remove_empty {
+        return Some(build(parts));
+    }
+    if parts.is_empty() {
+        if n == 0 {
+            fix(FixKind::RemovedEmptyPart, build(parts).to_gb_format());
+        }
+        return None;
+    }
+    if parts.len() == 1 && n > 1 {
+        return parts.pop();
+    }
+    Some(build(parts))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::{Feature, Topology};
+
+    #[test]
+    fn sanitize() {
+        let f = |l: Location, note: &str| {
+            Feature::new(
+                feature_kind!("misc_feature"),
+                l,
+                vec![(qualifier_key!("note"), Some(note.into()))],
+            )
+        };
+        let r = Location::simple_range;
+        let mut seq = Seq {
+            seq: b"acgtacgtac".to_vec(),
+            features: vec![
+                f(r(2, 6), "fine"),
+                f(r(8, 3), " padded\n"),
+                f(Location::Join(vec![r(0, 2), r(8, 14), r(12, 15)]), "x"),
+                f(Location::Join(vec![]), "x"),
+                f(Location::Complement(Box::new(r(11, 14))), "x"),
+            ],
+            ..Seq::empty()
+        };
+        let fixes = seq.sanitize(SanitizePolicy::default());
+        let kinds: Vec<_> = fixes.iter().map(|f| (f.feature, f.kind.clone())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (1, FixKind::SwappedRange),
+                (1, FixKind::TrimmedQualifier(qualifier_key!("note"))),
+                (2, FixKind::Clamped),
+                (2, FixKind::RemovedEmptyPart),
+                (3, FixKind::RemovedEmptyPart),
+                (3, FixKind::RemovedFeature),
+                (4, FixKind::RemovedEmptyPart),
+                (4, FixKind::RemovedFeature),
+            ]
+        );
+        assert_eq!(fixes[0].detail, "9..3 -> 3..9");
+        let locations: Vec<_> = seq
+            .features
+            .iter()
+            .map(|f| f.location.to_gb_format())
+            .collect();
+        assert_eq!(locations, vec!["3..6", "3..9", "join(1..2,9..10)"]);
+        assert_eq!(
+            seq.features[1]
+                .qualifier_values(qualifier_key!("note"))
+                .next(),
+            Some("padded")
+        );
+        assert!(seq.sanitize(SanitizePolicy::default()).is_empty());
+
+        let mut plasmid = Seq {
+            topology: Topology::Circular,
+            seq: b"acgtacgtac".to_vec(),
+            features: vec![f(r(8, 12), "x")],
+            ..Seq::empty()
+        };
+        let fixes = plasmid.sanitize(SanitizePolicy::default());
+        assert_eq!(fixes[0].kind, FixKind::Wrapped);
+        assert_eq!(
+            plasmid.features[0].location.to_gb_format(),
+            "join(9..10,1..2)"
+        );
+    }
+}
@@ -1,12 +1,20 @@
 use std::borrow::{Borrow, Cow};
 use std::cmp;
+use std::collections::HashSet;
+#[cfg(any(feature = "chrono", feature = "time"))]
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::io::Write;
 use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::diagnostics::{Diagnostic, DiagnosticSink, LogSink};
+use crate::display::DisplayHints;
 use crate::errors::GbParserError;
+use crate::insdc::{feature_kinds, qualifier_keys};
 use crate::reader::parse_location;
 use crate::dna::revcomp;
 pub use crate::{FeatureKind, QualifierKey};
@@ -33,6 +41,33 @@ impl Date {
             Err(DateError)
         }
     }
+    /// The current date (in UTC), according to the system clock
+    pub fn today() -> Date {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        Date::from_days_since_epoch(secs.div_euclid(86400))
+    }
+
+    /// Converts a day count since 1970-01-01 to a calendar date, see
+    /// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn from_days_since_epoch(days: i64) -> Date {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        Date {
+            year: year as i32,
+            month,
+            day,
+        }
+    }
+
     pub fn year(&self) -> i32 {
         self.year
     }
@@ -65,6 +100,50 @@ impl fmt::Display for Date {
     }
 }
 
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDate> for Date {
+    fn from(d: chrono::NaiveDate) -> Date {
+        use chrono::Datelike;
+        Date {
+            year: d.year(),
+            month: d.month(),
+            day: d.day(),
+        }
+    }
+}
+
+/// Fails if the date doesn't exist, e.g. 31st of February
+#[cfg(feature = "chrono")]
+impl TryFrom<Date> for chrono::NaiveDate {
+    type Error = DateError;
+
+    fn try_from(d: Date) -> Result<chrono::NaiveDate, DateError> {
+        chrono::NaiveDate::from_ymd_opt(d.year, d.month, d.day).ok_or(DateError)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::Date> for Date {
+    fn from(d: time::Date) -> Date {
+        Date {
+            year: d.year(),
+            month: u8::from(d.month()) as u32,
+            day: d.day() as u32,
+        }
+    }
+}
+
+/// Fails if the date doesn't exist, or is out of the range `time` supports
+#[cfg(feature = "time")]
+impl TryFrom<Date> for time::Date {
+    type Error = DateError;
+
+    fn try_from(d: Date) -> Result<time::Date, DateError> {
+        let month = time::Month::try_from(d.month as u8).map_err(|_| DateError)?;
+        time::Date::from_calendar_date(d.year, month, d.day as u8).map_err(|_| DateError)
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum GapLength {
@@ -129,6 +208,9 @@ pub enum Location {
     Gap(GapLength),
 }
 
+// the length gap() and gap(unk100) are assumed to have when assembling
+const UNKNOWN_GAP_LEN: usize = 100;
+
 #[derive(Debug, Error)]
 #[error(display = "Not configured to fetch external sequences")]
 pub struct NoFetcherError;
@@ -149,6 +231,12 @@ pub enum LocationError {
         _0
     )]
     OutOfBounds(Location),
+    #[error(display = "Protein sequences don't have a complement strand: {}", _0)]
+    Complement(Location),
+    #[error(display = "CONTIG gives {} bases, but the LOCUS line says {}", _0, _1)]
+    ContigLength(usize, usize),
+    #[error(display = "Can't wrap a location around an empty sequence: {}", _0)]
+    EmptySequence(Location),
 }
 
 impl Location {
@@ -190,6 +278,121 @@ impl Location {
         }
     }
 
+    /// Returns the exclusive ranges covered by this location, in the order
+    /// they're listed. Strand is ignored and `Between` covers no bases.
+    /// Fails on `OneOf`, as well as `External` and `Gap`, which don't refer
+    /// to this sequence.
+    pub fn ranges(&self) -> Result<Vec<(i64, i64)>, LocationError> {
+        use Location::*;
+        match *self {
+            Range((a, _), (b, _)) => Ok(vec![(a, b)]),
+            Between(..) => Ok(Vec::new()),
+            Complement(ref location) => location.ranges(),
+            Join(ref locations) | Order(ref locations) | Bond(ref locations) => {
+                let mut res = Vec::with_capacity(locations.len());
+                for l in locations {
+                    res.extend(l.ranges()?);
+                }
+                Ok(res)
+            }
+            ref p => Err(LocationError::Ambiguous(p.clone())),
+        }
+    }
+
+    /// Whether the 5' end is marked as partial, taking the strand into
+    /// account
+    pub fn is_five_prime_partial(&self) -> bool {
+        use Location::*;
+        match *self {
+            Range((_, Before(b)), _) => b,
+            Complement(ref l) => l.is_three_prime_partial(),
+            Join(ref ls) | Order(ref ls) => ls.first().is_some_and(Location::is_five_prime_partial),
+            _ => false,
+        }
+    }
+
+    pub fn is_three_prime_partial(&self) -> bool {
+        use Location::*;
+        match *self {
+            Range(_, (_, After(a))) => a,
+            Complement(ref l) => l.is_five_prime_partial(),
+            Join(ref ls) | Order(ref ls) => ls.last().is_some_and(Location::is_three_prime_partial),
+            _ => false,
+        }
+    }
+
+    /// The strand this location is on: reverse if it's complemented as a
+    /// whole, or made of complemented parts only
+    pub fn strand(&self) -> Strand {
+        use Location::*;
+        match *self {
+            Complement(_) => Strand::Reverse,
+            Join(ref ls) | Order(ref ls)
+                if !ls.is_empty() && ls.iter().all(|l| l.strand() == Strand::Reverse) =>
+            {
+                Strand::Reverse
+            }
+            _ => Strand::Forward,
+        }
+    }
+
+    /// The ranges (exons, for a spliced feature) making up this location,
+    /// in the order they're transcribed, each complemented if it's on the
+    /// reverse strand. Partial markers are kept. Fails in the same cases as
+    /// `ranges`.
+    pub fn exons(&self) -> Result<Vec<Location>, LocationError> {
+        fn collect(
+            l: &Location,
+            reverse: bool,
+            res: &mut Vec<Location>,
+        ) -> Result<(), LocationError> {
+            use Location::*;
+            match *l {
+                Range(..) if reverse => res.push(Complement(Box::new(l.clone()))),
+                Range(..) => res.push(l.clone()),
+                Between(..) => {}
+                Complement(ref l) => {
+                    let start = res.len();
+                    collect(l, !reverse, res)?;
+                    res[start..].reverse();
+                }
+                Join(ref ls) | Order(ref ls) | Bond(ref ls) => {
+                    for l in ls {
+                        collect(l, reverse, res)?;
+                    }
+                }
+                ref p => return Err(LocationError::Ambiguous(p.clone())),
+            }
+            Ok(())
+        }
+        let mut res = Vec::new();
+        collect(self, false, &mut res)?;
+        Ok(res)
+    }
+
+    /// The gaps between consecutive `exons`, in the order they're
+    /// transcribed. Exons which touch or overlap have no intron between
+    /// them, and neither do exons out of order, as when a feature crosses
+    /// the origin of a circular sequence (see `Seq::gene_structure`, which
+    /// handles that).
+    pub fn introns(&self) -> Result<Vec<Location>, LocationError> {
+        let exons = self.exons()?;
+        let mut res = Vec::new();
+        for w in exons.windows(2) {
+            let (a, b) = (w[0].find_bounds()?, w[1].find_bounds()?);
+            match (w[0].strand(), w[1].strand()) {
+                (Strand::Forward, Strand::Forward) if b.0 > a.1 => {
+                    res.push(Location::simple_range(a.1, b.0))
+                }
+                (Strand::Reverse, Strand::Reverse) if a.0 > b.1 => res.push(Location::Complement(
+                    Box::new(Location::simple_range(b.1, a.0)),
+                )),
+                _ => {}
+            }
+        }
+        Ok(res)
+    }
+
     // Only returns `Err` if one of the closures does
     fn transform<L, V>(self, loc: &L, val: &V) -> Result<Location, LocationError>
     where
@@ -337,17 +540,288 @@ pub struct Feature {
     pub kind: FeatureKind,
     pub location: Location,
     pub qualifiers: Vec<(QualifierKey, Option<String>)>,
+    /// An identifier which stays the same when the feature is moved or
+    /// edited. Not part of the Genbank format, so it's never written out
+    /// and is `None` after parsing, see `Seq::assign_feature_ids`
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub id: Option<String>,
+    /// Where the feature came from, if known. Only set by the parser when
+    /// asked to, see `SeqReader::track_provenance`. Like `id`, this is
+    /// never written out.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub provenance: Option<Provenance>,
+    /// The original spelling of keys which the parser canonicalized, see
+    /// `SeqReader::key_normalization`. Used by the writer so the feature
+    /// is written back out as it was read.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub original_spelling: Option<OriginalSpelling>,
+    /// How editors should draw the feature. Only set by the parser when
+    /// asked to, see `SeqReader::read_display_hints`, but can be set on any
+    /// feature. The writer emits it as display qualifiers, see
+    /// `SeqWriter::display_dialect`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub display_hints: Option<DisplayHints>,
+}
+
+/// The spelling of a feature's keys as they appeared in the file, for those
+/// which were changed by canonicalization
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OriginalSpelling {
+    pub kind: Option<String>,
+    /// Pairs of an index into `Feature::qualifiers` and the original key
+    pub qualifiers: Vec<(usize, String)>,
+}
+
+/// The origin of a `Feature`, so that annotations in merged records can be
+/// traced back to where they came from
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Provenance {
+    /// Read from a file or stream
+    Parsed {
+        /// Path or other description of the input, if known
+        source: Option<String>,
+        /// Line of the feature's first line, starting from 1
+        line: usize,
+        /// Byte offset of the feature's first line
+        offset: u64,
+    },
+    /// Created by a program rather than read from a file, with a
+    /// description of what created it
+    Generated(String),
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Provenance::Parsed {
+                ref source,
+                line,
+                ..
+            } => write!(
+                f,
+                "{}:{}",
+                source.as_deref().unwrap_or("<input>"),
+                line
+            ),
+            Provenance::Generated(ref by) => write!(f, "generated by {}", by),
+        }
+    }
+}
+
+/// 64-bit FNV-1a, used for generated feature IDs and fingerprints since,
+/// unlike `DefaultHasher`, its output is guaranteed not to change between
+/// releases
+pub(crate) struct Fnv(pub u64);
+
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(data);
+    hash.0
 }
 
 impl Feature {
+    /// A new feature with no `id`, `provenance`, `original_spelling` or
+    /// `display_hints`, i.e. as if it was read from a file with the default
+    /// parser options
+    pub fn new(
+        kind: FeatureKind,
+        location: Location,
+        qualifiers: Vec<(QualifierKey, Option<String>)>,
+    ) -> Feature {
+        Feature {
+            kind,
+            location,
+            qualifiers,
+            id: None,
+            provenance: None,
+            original_spelling: None,
+            display_hints: None,
+        }
+    }
+
     /// Returns all the values for a given QualifierKey. Qualifiers with no
     /// value (ie. `/foo`) are ignored
     pub fn qualifier_values(&self, key: QualifierKey) -> impl Iterator<Item = &str> {
         self.qualifiers
             .iter()
             .filter(move |&(k, _)| k == &key)
-            .filter_map(|&(_, ref v)| v.as_ref().map(String::as_str))
+            .filter_map(|(_, v)| v.as_ref().map(String::as_str))
+    }
+
+    /// Whether the feature has the valueless qualifier `key`, e.g. `/pseudo`
+    pub fn has_flag(&self, key: QualifierKey) -> bool {
+        self.qualifiers.iter().any(|(k, _)| *k == key)
+    }
+
+    /// Adds the valueless qualifier `key` if `on` and it's not already
+    /// present, or removes every occurrence of it if not
+    pub fn set_flag(&mut self, key: QualifierKey, on: bool) {
+        if !on {
+            self.qualifiers.retain(|(k, _)| *k != key);
+        } else if !self.has_flag(key.clone()) {
+            self.qualifiers.push((key, None));
+        }
+    }
+
+    /// Whether this is marked `/pseudo` or `/pseudogene`
+    pub fn is_pseudo(&self) -> bool {
+        self.has_flag(qualifier_key!("pseudo")) || self.has_flag(qualifier_key!("pseudogene"))
+    }
+
+    /// Sets or clears `/pseudo`. Clearing also removes `/pseudogene`.
+    pub fn set_pseudo(&mut self, on: bool) {
+        if !on {
+            self.set_flag(qualifier_key!("pseudogene"), false);
+        }
+        self.set_flag(qualifier_key!("pseudo"), on);
+    }
+
+    pub fn is_ribosomal_slippage(&self) -> bool {
+        self.has_flag(qualifier_key!("ribosomal_slippage"))
+    }
+
+    pub fn set_ribosomal_slippage(&mut self, on: bool) {
+        self.set_flag(qualifier_key!("ribosomal_slippage"), on)
+    }
+
+    pub fn is_trans_splicing(&self) -> bool {
+        self.has_flag(qualifier_key!("trans_splicing"))
+    }
+
+    pub fn set_trans_splicing(&mut self, on: bool) {
+        self.set_flag(qualifier_key!("trans_splicing"), on)
+    }
+
+    /// Whether either end of the location is marked partial (`<` or `>`),
+    /// or the feature has the old `/partial` flag
+    pub fn is_partial(&self) -> bool {
+        self.location.is_five_prime_partial()
+            || self.location.is_three_prime_partial()
+            || self.has_flag("partial".into())
+    }
+
+    /// Returns `id` if it's set, otherwise derives one from the feature
+    /// kind and its `/protein_id` or `/locus_tag`, e.g. `CDS:b0001`. If
+    /// neither is present, a hash of the kind and location is used instead,
+    /// which means the ID will change if the feature moves.
+    pub fn stable_id(&self) -> String {
+        if let Some(ref id) = self.id {
+            return id.clone();
+        }
+        let tag = self
+            .qualifier_values(qualifier_key!("protein_id"))
+            .chain(self.qualifier_values(qualifier_key!("locus_tag")))
+            .next();
+        match tag {
+            Some(tag) => format!("{}:{}", self.kind, tag),
+            None => {
+                let key = format!("{}\t{}", self.kind, self.location.to_gb_format());
+                format!("{}:{:016x}", self.kind, fnv1a(key.as_bytes()))
+            }
+        }
+    }
+
+    /// Whether `self` and `other` are the same annotation, possibly written
+    /// differently: the order of qualifiers, the spelling of standard keys,
+    /// how qualifier values are wrapped and how locations are written (e.g.
+    /// `join(complement(5..6),complement(1..2))` rather than
+    /// `complement(join(1..2,5..6))`) don't matter. `id` and `provenance` are
+    /// ignored.
+    pub fn semantic_eq(&self, other: &Feature) -> bool {
+        self.semantic_key() == other.semantic_key()
+    }
+
+    /// A hash which is the same for features that are `semantic_eq`
+    pub fn semantic_hash<H: Hasher>(&self, state: &mut H) {
+        self.semantic_key().hash(state)
     }
+
+    fn semantic_key(&self) -> SemanticKey {
+        let kind = feature_kinds::canonical(&self.kind).unwrap_or_else(|| self.kind.clone());
+        let location = simplify(self.location.clone()).unwrap_or_else(|_| self.location.clone());
+        let mut qualifiers: Vec<_> = self
+            .qualifiers
+            .iter()
+            .map(|(k, v)| {
+                let k = qualifier_keys::canonical(k).unwrap_or_else(|| k.clone());
+                let v = v.as_deref().filter(|v| !v.is_empty()).map(|v| {
+                    if k == qualifier_key!("translation") {
+                        v.split_whitespace().collect()
+                    } else {
+                        v.split_whitespace().collect::<Vec<_>>().join(" ")
+                    }
+                });
+                (k.to_string(), v)
+            })
+            .collect();
+        qualifiers.sort_unstable();
+        (kind.to_string(), location.to_gb_format(), qualifiers)
+    }
+}
+
+type SemanticKey = (String, String, Vec<(String, Option<String>)>);
+
+/// Wraps a feature so that equality and hashing use `Feature::semantic_eq`
+/// and `Feature::semantic_hash`, for use in sets and maps
+#[derive(Debug, Clone, Copy)]
+pub struct SemanticFeature<'a>(pub &'a Feature);
+
+impl PartialEq for SemanticFeature<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.semantic_eq(other.0)
+    }
+}
+
+impl Eq for SemanticFeature<'_> {}
+
+impl Hash for SemanticFeature<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.semantic_hash(state)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// Whether a record holds nucleotides or amino acids (GenPept). Protein
+/// records count positions in residues, and have no strands.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Alphabet {
+    #[default]
+    Nucleotide,
+    Protein,
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -357,6 +831,18 @@ pub enum Topology {
     Circular,
 }
 
+/// The letter case of a sequence. Sequences are kept in the case they were
+/// read in, as lower case bases are often used to mark masked regions.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Case {
+    Lower,
+    Upper,
+    /// Both upper and lower case letters. When normalizing, this means
+    /// leaving the case as it is.
+    Mixed,
+}
+
 impl fmt::Display for Topology {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let res = match *self {
@@ -367,6 +853,241 @@ impl fmt::Display for Topology {
     }
 }
 
+/// The molecule type in the LOCUS line, as defined by INSDC. Anything else
+/// is kept as `Other`. Serialized as the string in the LOCUS line.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum MoleculeType {
+    /// `NA`, nucleic acid of unknown type
+    Na,
+    Dna,
+    Rna,
+    Mrna,
+    Trna,
+    Rrna,
+    Urna,
+    Crna,
+    /// `ss-DNA`
+    SsDna,
+    /// `ds-DNA`
+    DsDna,
+    /// `ms-DNA`, mixed stranded
+    MsDna,
+    SsRna,
+    DsRna,
+    MsRna,
+    Other(String),
+}
+
+impl MoleculeType {
+    const NAMED: &'static [(&'static str, MoleculeType)] = &[
+        ("NA", MoleculeType::Na),
+        ("DNA", MoleculeType::Dna),
+        ("RNA", MoleculeType::Rna),
+        ("mRNA", MoleculeType::Mrna),
+        ("tRNA", MoleculeType::Trna),
+        ("rRNA", MoleculeType::Rrna),
+        ("uRNA", MoleculeType::Urna),
+        ("cRNA", MoleculeType::Crna),
+        ("ss-DNA", MoleculeType::SsDna),
+        ("ds-DNA", MoleculeType::DsDna),
+        ("ms-DNA", MoleculeType::MsDna),
+        ("ss-RNA", MoleculeType::SsRna),
+        ("ds-RNA", MoleculeType::DsRna),
+        ("ms-RNA", MoleculeType::MsRna),
+    ];
+
+    /// The molecule type as written in the LOCUS line
+    pub fn as_str(&self) -> &str {
+        match self {
+            MoleculeType::Other(s) => s,
+            m => MoleculeType::NAMED
+                .iter()
+                .find(|n| n.1 == *m)
+                .map(|n| n.0)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_dna(&self) -> bool {
+        self.as_str().ends_with("DNA")
+    }
+
+    /// True for any kind of RNA, including `mRNA`, `tRNA` etc.
+    pub fn is_rna(&self) -> bool {
+        self.as_str().ends_with("RNA")
+    }
+}
+
+impl<'a> From<&'a str> for MoleculeType {
+    fn from(s: &'a str) -> MoleculeType {
+        MoleculeType::NAMED
+            .iter()
+            .find(|n| n.0 == s)
+            .map_or_else(|| MoleculeType::Other(s.to_string()), |n| n.1.clone())
+    }
+}
+
+impl From<String> for MoleculeType {
+    fn from(s: String) -> MoleculeType {
+        match MoleculeType::from(s.as_str()) {
+            MoleculeType::Other(_) => MoleculeType::Other(s),
+            m => m,
+        }
+    }
+}
+
+impl From<MoleculeType> for String {
+    fn from(m: MoleculeType) -> String {
+        match m {
+            MoleculeType::Other(s) => s,
+            m => m.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for MoleculeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The GenBank division in the LOCUS line. Anything else, including the
+/// `UNK` used when a record doesn't have one, is kept as `Unknown`.
+/// Serialized as the three letter code.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "String", into = "String"))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Division {
+    /// Primate
+    Pri,
+    /// Rodent
+    Rod,
+    /// Other mammalian
+    Mam,
+    /// Other vertebrate
+    Vrt,
+    /// Invertebrate
+    Inv,
+    /// Plant, fungal and algal
+    Pln,
+    /// Bacterial
+    Bct,
+    /// Viral
+    Vrl,
+    /// Bacteriophage
+    Phg,
+    /// Synthetic and chimeric
+    Syn,
+    /// Unannotated
+    Una,
+    /// Expressed sequence tags
+    Est,
+    /// Patent
+    Pat,
+    /// Sequence tagged sites
+    Sts,
+    /// Genome survey sequences
+    Gss,
+    /// High throughput genomic sequences
+    Htg,
+    /// Unfinished high throughput cDNA sequencing
+    Htc,
+    /// Environmental sampling sequences
+    Env,
+    /// Constructed from other records, see `Seq::contig`
+    Con,
+    /// Transcriptome shotgun assembly
+    Tsa,
+    Unknown(String),
+}
+
+impl Division {
+    const CODES: &'static [(&'static str, Division)] = &[
+        ("PRI", Division::Pri),
+        ("ROD", Division::Rod),
+        ("MAM", Division::Mam),
+        ("VRT", Division::Vrt),
+        ("INV", Division::Inv),
+        ("PLN", Division::Pln),
+        ("BCT", Division::Bct),
+        ("VRL", Division::Vrl),
+        ("PHG", Division::Phg),
+        ("SYN", Division::Syn),
+        ("UNA", Division::Una),
+        ("EST", Division::Est),
+        ("PAT", Division::Pat),
+        ("STS", Division::Sts),
+        ("GSS", Division::Gss),
+        ("HTG", Division::Htg),
+        ("HTC", Division::Htc),
+        ("ENV", Division::Env),
+        ("CON", Division::Con),
+        ("TSA", Division::Tsa),
+    ];
+
+    /// The three letter code
+    pub fn as_str(&self) -> &str {
+        match self {
+            Division::Unknown(s) => s,
+            d => Division::CODES
+                .iter()
+                .find(|c| c.1 == *d)
+                .map(|c| c.0)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether this can be written in a LOCUS line: any of the known
+    /// divisions, or another code of three capital letters such as `UNK`
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Division::Unknown(s) => s.len() == 3 && s.bytes().all(|b| b.is_ascii_uppercase()),
+            _ => true,
+        }
+    }
+}
+
+impl Default for Division {
+    fn default() -> Division {
+        Division::Unknown("UNK".into())
+    }
+}
+
+impl<'a> From<&'a str> for Division {
+    fn from(s: &'a str) -> Division {
+        Division::CODES
+            .iter()
+            .find(|c| c.0 == s)
+            .map_or_else(|| Division::Unknown(s.to_string()), |c| c.1.clone())
+    }
+}
+
+impl From<String> for Division {
+    fn from(s: String) -> Division {
+        match Division::from(s.as_str()) {
+            Division::Unknown(_) => Division::Unknown(s),
+            d => d,
+        }
+    }
+}
+
+impl From<Division> for String {
+    fn from(d: Division) -> String {
+        match d {
+            Division::Unknown(s) => s,
+            d => d.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Division {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Source {
@@ -377,7 +1098,15 @@ pub struct Source {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Reference {
+    /// The REFERENCE line after the keyword, e.g. `1  (bases 1 to 5028)`.
+    /// When writing, the part in brackets is rebuilt from `bases` if it's
+    /// set.
     pub description: String,
+    /// The ranges of bases (or residues) the reference covers, from the
+    /// REFERENCE line, 0-based with exclusive ends like `Location::ranges`.
+    /// Empty if the line doesn't give any, e.g. for `2  (sites)`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bases: Vec<(i64, i64)>,
     pub authors: Option<String>,
     pub consortium: Option<String>,
     pub title: String,
@@ -386,6 +1115,145 @@ pub struct Reference {
     pub remark: Option<String>,
 }
 
+impl Reference {
+    /// Parses the ranges in a REFERENCE line such as
+    /// `1  (bases 1 to 1859; 2000 to 2100)`. Returns nothing unless all of
+    /// them can be parsed.
+    pub fn parse_bases(description: &str) -> Vec<(i64, i64)> {
+        let ranges = description
+            .find("(bases ")
+            .map(|i| i + "(bases ".len())
+            .or_else(|| description.find("(residues ").map(|i| i + "(residues ".len()))
+            .and_then(|start| {
+                let end = start + description[start..].find(')')?;
+                Some(&description[start..end])
+            });
+        let parse = |range: &str| {
+            let (a, b) = range.split_once(" to ")?;
+            let (a, b): (i64, i64) = (a.trim().parse().ok()?, b.trim().parse().ok()?);
+            Some((a - 1, b))
+        };
+        ranges
+            .and_then(|ranges| ranges.split(';').map(parse).collect::<Option<Vec<_>>>())
+            .unwrap_or_default()
+    }
+
+    /// The description, with the ranges in brackets replaced by `bases`
+    /// unless that's empty. The ranges are in bases, or in residues if
+    /// `alphabet` is `Alphabet::Protein`.
+    pub fn description_with_bases(&self, alphabet: Alphabet) -> String {
+        if self.bases.is_empty() {
+            return self.description.clone();
+        }
+        let number = self.description.split(" (").next().unwrap_or_default();
+        let unit = match alphabet {
+            Alphabet::Protein => "residues",
+            Alphabet::Nucleotide => "bases",
+        };
+        let ranges = self
+            .bases
+            .iter()
+            .map(|(a, b)| format!("{} to {}", a + 1, b))
+            .collect::<Vec<_>>()
+            .join("; ");
+        // the brackets are aligned for numbers up to 99
+        format!("{:<2} ({} {})", number.trim_end(), unit, ranges)
+    }
+}
+
+/// A range of accessions, as given in the WGS, WGS_SCAFLD, TSA and TLS lines
+/// of master records, e.g. `AAAA01000001-AAAA01050231`. For a single
+/// accession, `first` and `last` are the same.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AccessionRange {
+    pub first: String,
+    pub last: String,
+}
+
+impl AccessionRange {
+    /// Parses `first-last` or a single accession
+    pub fn parse(s: &str) -> AccessionRange {
+        let s = s.trim();
+        let (first, last) = s.split_once('-').unwrap_or((s, s));
+        AccessionRange {
+            first: first.to_string(),
+            last: last.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AccessionRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}", self.first)
+        } else {
+            write!(f, "{}-{}", self.first, self.last)
+        }
+    }
+}
+
+/// The number of each base in a sequence, ignoring case, as in the BASE
+/// COUNT line of older GenBank records
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct BaseCount {
+    pub a: usize,
+    pub c: usize,
+    pub g: usize,
+    pub t: usize,
+    pub other: usize,
+}
+
+impl BaseCount {
+    pub fn new(seq: &[u8]) -> BaseCount {
+        let mut counts = BaseCount::default();
+        counts.add(seq);
+        counts
+    }
+
+    pub(crate) fn add(&mut self, seq: &[u8]) {
+        for b in seq {
+            match b.to_ascii_lowercase() {
+                b'a' => self.a += 1,
+                b'c' => self.c += 1,
+                b'g' => self.g += 1,
+                b't' => self.t += 1,
+                _ => self.other += 1,
+            }
+        }
+    }
+
+    /// Parses the counts of a BASE COUNT line, e.g. `474 a 356 c 428 g 364 t`.
+    /// Counts of anything but `a`, `c`, `g` and `t` (usually `others`) go
+    /// in `other`. Returns `None` if a count isn't followed by its base.
+    pub fn parse(s: &str) -> Option<BaseCount> {
+        let mut counts = BaseCount::default();
+        let mut words = s.split_whitespace();
+        while let Some(n) = words.next() {
+            let n: usize = n.parse().ok()?;
+            match words.next()? {
+                "a" => counts.a += n,
+                "c" => counts.c += n,
+                "g" => counts.g += n,
+                "t" => counts.t += n,
+                _ => counts.other += n,
+            }
+        }
+        Some(counts)
+    }
+}
+
+impl fmt::Display for BaseCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} a {} c {} g {} t", self.a, self.c, self.g, self.t)?;
+        if self.other > 0 {
+            write!(f, " {} others", self.other)?;
+        }
+        Ok(())
+    }
+}
+
 /// Maximum length for which the buffer holding the sequence will be
 /// preallocated. This isn't a hard limit though... should it be?
 #[doc(hidden)]
@@ -405,21 +1273,54 @@ pub struct Seq {
     /// parsing a file, if sequence data is provided we check that this value is
     /// equal to `seq.len()`
     pub len: Option<usize>,
+    /// Given by the units (`bp` or `aa`) in the LOCUS line
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alphabet: Alphabet,
     // TODO: should this be an option?
-    pub molecule_type: Option<String>,
-    pub division: String,
+    pub molecule_type: Option<MoleculeType>,
+    pub division: Division,
     pub definition: Option<String>,
     pub accession: Option<String>,
     pub version: Option<String>,
+    /// The NID line of records from before 1999, e.g. `g1234567`
+    pub nid: Option<String>,
     pub source: Option<Source>,
     pub dblink: Option<String>,
+    /// Where a GenPept record's protein comes from, usually the accession
+    /// of the nucleotide record it was translated from
+    pub dbsource: Option<String>,
     pub keywords: Option<String>,
+    /// The SEGMENT line of older records split into several entries, e.g.
+    /// `1 of 6`, see `Seq::segment_number`
+    pub segment: Option<String>,
     pub references: Vec<Reference>,
     pub comments: Vec<String>,
-    #[cfg_attr(all(feature = "serde", feature = "serde_bytes"), serde(with = "serde_bytes"))]
+    /// Serialized as bytes with `serde_bytes`, or as a string with the
+    /// `serde_seq_string` feature, see the module of the same name
+    #[cfg_attr(
+        all(feature = "serde", feature = "serde_bytes", not(feature = "serde_seq_string")),
+        serde(with = "serde_bytes")
+    )]
+    #[cfg_attr(feature = "serde_seq_string", serde(with = "serde_seq_string"))]
     pub seq: Vec<u8>,
     pub contig: Option<Location>,
     pub features: Vec<Feature>,
+    /// The contigs of a whole genome shotgun master record (WGS line)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub wgs: Vec<AccessionRange>,
+    /// The scaffolds of a WGS master record (WGS_SCAFLD line)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub wgs_scafld: Vec<AccessionRange>,
+    /// The contigs of a transcriptome shotgun assembly master record (TSA
+    /// line)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tsa: Vec<AccessionRange>,
+    /// The records of a targeted locus study master record (TLS line)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tls: Vec<AccessionRange>,
+    /// From the BASE COUNT line, which is only found in older records. When
+    /// parsing, it's checked against the sequence.
+    pub base_count: Option<BaseCount>,
 }
 
 impl Seq {
@@ -431,9 +1332,11 @@ impl Seq {
             definition: None,
             accession: None,
             molecule_type: None,
-            division: String::from("UNK"),
+            division: Division::default(),
             dblink: None,
+            dbsource: None,
             keywords: None,
+            segment: None,
             source: None,
             version: None,
             comments: vec![],
@@ -442,10 +1345,22 @@ impl Seq {
             topology: Topology::Linear,
             date: None,
             len: None,
+            alphabet: Alphabet::Nucleotide,
             features: vec![],
+            wgs: vec![],
+            wgs_scafld: vec![],
+            tsa: vec![],
+            tls: vec![],
+            nid: None,
+            base_count: None,
         }
     }
 
+    /// Whether this is a GenPept record, with positions counted in residues
+    pub fn is_protein(&self) -> bool {
+        self.alphabet == Alphabet::Protein
+    }
+
     pub fn is_circular(&self) -> bool {
         match self.topology {
             Topology::Circular => true,
@@ -453,11 +1368,39 @@ impl Seq {
         }
     }
 
+    /// The case of the letters in the sequence, or `None` if it has none
+    pub fn case(&self) -> Option<Case> {
+        let letters = self.seq.iter().filter(|b| b.is_ascii_alphabetic());
+        let (mut lower, mut upper) = (false, false);
+        for b in letters {
+            lower |= b.is_ascii_lowercase();
+            upper |= b.is_ascii_uppercase();
+        }
+        match (lower, upper) {
+            (false, false) => None,
+            (true, false) => Some(Case::Lower),
+            (false, true) => Some(Case::Upper),
+            (true, true) => Some(Case::Mixed),
+        }
+    }
+
+    /// Converts the sequence to upper or lower case. `Case::Mixed` leaves
+    /// it unchanged.
+    pub fn normalize_case(&mut self, case: Case) {
+        match case {
+            Case::Lower => self.seq.make_ascii_lowercase(),
+            Case::Upper => self.seq.make_ascii_uppercase(),
+            Case::Mixed => {}
+        }
+    }
+
     /// Returns the "actual" length of the sequence. Note that this may not be
     /// equal to self.seq.len(), in the following circumstances:
     /// - `self.seq` is empty and `self.contig` is not, and the corresponding
-    /// file's LOCUS line specified a length. The returned value is i64 to
-    /// simplifiy arithmetic with coords from `Location`
+    ///   file's LOCUS line specified a length.
+    ///
+    /// The returned value is i64 to simplifiy arithmetic with coords from
+    /// `Location`
     pub fn len(&self) -> i64 {
         if let Some(len) = self.len {
             assert!(self.seq.is_empty() || len == self.seq.len());
@@ -477,6 +1420,7 @@ impl Seq {
     /// values will satisfy the following conditions:
     /// - `0 <= first < len`
     /// - `first < last`
+    ///
     /// This means that in the case of a range which wraps around, `last` >= `len`.
     pub fn unwrap_range(&self, start: i64, end: i64) -> (i64, i64) {
         let len = self.len();
@@ -512,34 +1456,71 @@ impl Seq {
         }
     }
     /// Given a range on this sequence, returns a corresponding `Location`
-    /// Note: this is a rust-style, exclusive range
-    pub fn range_to_location(&self, start: i64, end: i64) -> Location {
+    /// Note: this is a rust-style, exclusive range. Fails with
+    /// `LocationError::OutOfBounds` if a range on a linear sequence isn't
+    /// within it, or `LocationError::EmptySequence` for any range on an
+    /// empty circular sequence.
+    pub fn range_to_location(&self, start: i64, end: i64) -> Result<Location, LocationError> {
         let res = match self.topology {
             Topology::Linear => {
-                assert!(end > start);
-                assert!(start < self.len() && end <= self.len());
+                if end <= start || start < 0 || end > self.len() {
+                    return Err(LocationError::OutOfBounds(Location::simple_range(
+                        start, end,
+                    )));
+                }
                 Location::simple_range(start, end)
             }
             Topology::Circular => {
-                let (start, end) = self.unwrap_range(start, end);
-                if end > self.len() {
-                    assert!(end < self.len() * 2, "Range wraps around more than once!");
-                    Location::Join(vec![
-                        Location::simple_range(start, self.len()),
-                        Location::simple_range(0, end - self.len()),
-                    ])
-                } else {
-                    Location::simple_range(start, end)
+                if self.len() == 0 {
+                    return Err(LocationError::EmptySequence(Location::simple_range(
+                        start, end,
+                    )));
                 }
+                let (start, end) = self.unwrap_range(start, end);
+                self.split_at_origin((start, Before(false)), (end, After(false)))?
             }
         };
-        simplify(res).expect("invalid Location not possible here")
+        simplify(res)
+    }
+
+    /// Splits a range starting within a circular sequence at the origin. If
+    /// it wraps around more than once (e.g. a rolling circle product), the
+    /// whole sequence is repeated.
+    fn split_at_origin(
+        &self,
+        start: (i64, Before),
+        end: (i64, After),
+    ) -> Result<Location, LocationError> {
+        let len = self.len();
+        let (mut a, mut before) = start;
+        let (mut b, after) = end;
+        if len == 0 && b > len {
+            return Err(LocationError::EmptySequence(Location::Range(start, end)));
+        }
+        let mut parts = Vec::new();
+        while b > len {
+            parts.push(Location::Range((a, before), (len, After(false))));
+            a = 0;
+            before = Before(false);
+            b -= len;
+        }
+        parts.push(Location::Range((a, before), (b, after)));
+        if parts.len() == 1 {
+            Ok(parts.pop().unwrap())
+        } else {
+            Ok(Location::Join(parts))
+        }
     }
 
     /// "Wraps" a location on a circular sequence, so that coordinates that
     /// extend beyond the end of the sequence are are wrapped to the origin.
+    /// Ranges that wrap around more than once repeat the whole sequence.
+    /// Fails with `LocationError::EmptySequence` if the sequence is empty.
     pub fn wrap_location(&self, p: Location) -> Result<Location, LocationError> {
         use Location::*;
+        if self.len() == 0 {
+            return Err(LocationError::EmptySequence(p));
+        }
         let res = p
             .transform(
                 &|p| {
@@ -549,22 +1530,20 @@ impl Seq {
                                 a -= self.len();
                                 b -= self.len();
                             }
-                            if b <= self.len() {
-                                Range((a, before), (b, after))
-                            } else {
-                                Join(vec![
-                                    Range((a, before), (self.len(), After(false))),
-                                    Range((0, Before(false)), (b - self.len(), after)),
-                                ])
+                            while a < 0 {
+                                a += self.len();
+                                b += self.len();
                             }
+                            self.split_at_origin((a, before), (b, after))?
                         }
+                        // `n^1` is allowed for the origin
+                        Between(a, b) => Between(a.rem_euclid(self.len()), b.rem_euclid(self.len())),
                         p => p,
                     };
                     Ok(res)
                 },
                 &Ok,
-            )
-            .unwrap();
+            )?;
         simplify(res)
     }
 
@@ -588,6 +1567,9 @@ impl Seq {
         mut shift: i64,
     ) -> Result<Location, LocationError> {
         if self.is_circular() {
+            if self.len() == 0 {
+                return Err(LocationError::EmptySequence(p));
+            }
             while shift < 0 {
                 shift += self.len();
             }
@@ -603,6 +1585,9 @@ impl Seq {
 
     /// Used by `revcomp`
     fn revcomp_location(&self, p: Location) -> Result<Location, LocationError> {
+        if self.is_protein() {
+            return Err(LocationError::Complement(p));
+        }
         let p = p
             .transform(
                 &|mut p| {
@@ -627,7 +1612,15 @@ impl Seq {
                 &|v| Ok(self.len() - v),
             )
             .unwrap(); // can't fail
-        simplify(p).map(Location::complement)
+        // On a circular sequence, spans which cross the origin without being
+        // split into a join would end up with negative coordinates. Wrapping
+        // turns them back into a valid origin-spanning join.
+        let p = if self.is_circular() {
+            self.wrap_location(p)?
+        } else {
+            simplify(p)?
+        };
+        Ok(p.complement())
     }
 
     /// Note: If this fails you won't get the original `Feature`
@@ -640,13 +1633,26 @@ impl Seq {
     }
 
     /// Returns the reverse complement of a `Seq`, skipping any features
-    /// which can't be processed with a warning
+    /// which can't be processed with a warning. Proteins are returned
+    /// unchanged.
     pub fn revcomp(&self) -> Seq {
+        self.revcomp_with_diagnostics(&mut LogSink)
+    }
+
+    /// Like `revcomp`, but skipped features are reported to `sink`
+    pub fn revcomp_with_diagnostics(&self, sink: &mut dyn DiagnosticSink) -> Seq {
+        if self.is_protein() {
+            sink.report(Diagnostic::new("Can't reverse complement a protein"));
+            return self.clone();
+        }
         let mut features = Vec::with_capacity(self.features.len());
-        for f in &self.features {
+        for (i, f) in self.features.iter().enumerate() {
             match self.revcomp_feature(f.clone()) {
                 Ok(f) => features.push(f),
-                Err(e) => warn!("Encountered invalid feature location: {}", e),
+                Err(e) => sink.report(Diagnostic::for_feature(
+                    i,
+                    format!("Encountered invalid feature location: {}", e),
+                )),
             }
         }
         Seq {
@@ -660,7 +1666,7 @@ impl Seq {
     /// account circularity. Note that `end` is exclusive. Use
     /// this instead of `extract_range` if you don't need the
     /// features.
-    pub fn extract_range_seq(&self, start: i64, end: i64) -> Cow<[u8]> {
+    pub fn extract_range_seq(&self, start: i64, end: i64) -> Cow<'_, [u8]> {
         // Here we use usize everywhere for convenience, since we will never
         // have to deal with negative values
         let len = self.len() as usize;
@@ -687,6 +1693,16 @@ impl Seq {
     /// extend beyond this range.  Note that `end` is not
     /// inclusive. Skips ambiguous features with a warning.
     pub fn extract_range(&self, start: i64, end: i64) -> Seq {
+        self.extract_range_with_diagnostics(start, end, &mut LogSink)
+    }
+
+    /// Like `extract_range`, but skipped features are reported to `sink`
+    pub fn extract_range_with_diagnostics(
+        &self,
+        start: i64,
+        end: i64,
+        sink: &mut dyn DiagnosticSink,
+    ) -> Seq {
         let (start, end) = self.unwrap_range(start, end);
         let mut shift = -start;
         if self.is_circular() {
@@ -700,15 +1716,19 @@ impl Seq {
         let features = self
             .features
             .iter()
+            .enumerate()
             .flat_map(
-                |f| match self.relocate_location(f.location.clone(), shift) {
+                |(i, f)| match self.relocate_location(f.location.clone(), shift) {
                     // let `truncate` filter locations outside the range
                     Ok(l) => l.truncate(0, end - start).map(|location| Feature {
                         location,
                         ..f.clone()
                     }),
                     Err(e) => {
-                        warn!("Skipping feature, can't process invalid location: {}", e);
+                        sink.report(Diagnostic::for_feature(
+                            i,
+                            format!("Skipping feature, can't process invalid location: {}", e),
+                        ));
                         None
                     }
                 },
@@ -721,6 +1741,141 @@ impl Seq {
         }
     }
 
+    /// Tiles the whole sequence with windows of `size` bases, starting every
+    /// `step` bases, and returns the start of each with the region extracted
+    /// by `extract_range`. On linear sequences the last window is cut short
+    /// at the end. On circular ones there's a window starting at each step
+    /// before the origin, and those near the end wrap around it.
+    pub fn windows(&self, size: i64, step: i64) -> impl Iterator<Item = (i64, Seq)> + '_ {
+        assert!(size > 0 && step > 0, "Window size and step must be positive");
+        let len = self.len();
+        let size = cmp::min(size, len);
+        let mut next = Some(0).filter(|_| len > 0);
+        std::iter::from_fn(move || {
+            let start = next?;
+            let end = if self.is_circular() {
+                start + size
+            } else {
+                cmp::min(start + size, len)
+            };
+            next = Some(start + step).filter(|&s| s < len && (self.is_circular() || end < len));
+            Some((start, self.extract_range(start, end)))
+        })
+    }
+
+    /// Extracts the region covered by `feature`, extended by `flank` bases
+    /// on either side, as a new linear record. The feature is relocated
+    /// along with any others overlapping the window, and the header fields
+    /// (source, references etc.) are carried over. On linear sequences the
+    /// flanks stop at the ends, on circular ones they can wrap around the
+    /// origin but never cover more than the whole sequence.
+    pub fn extract_feature_record(
+        &self,
+        feature: &Feature,
+        flank: i64,
+    ) -> Result<Seq, LocationError> {
+        assert!(flank >= 0);
+        let len = self.len();
+        let (start, end) = if self.is_circular() {
+            let (start, end) = feature.location.find_bounds()?;
+            let (start, end) = self.unwrap_range(start, end);
+            if end - start + 2 * flank >= len {
+                (start, start)
+            } else {
+                (start - flank, end + flank)
+            }
+        } else {
+            let ranges = feature.location.ranges()?;
+            let start = ranges.iter().map(|r| r.0).min().ok_or(LocationError::Empty)?;
+            let end = ranges.iter().map(|r| r.1).max().unwrap();
+            (cmp::max(start - flank, 0), cmp::min(end + flank, len))
+        };
+        let extracted = self.extract_range(start, end);
+        Ok(Seq {
+            topology: Topology::Linear,
+            len: Some(extracted.seq.len()),
+            seq: extracted.seq,
+            features: extracted.features,
+            ..self.header()
+        })
+    }
+
+    /// A copy of everything but the sequence and features
+    pub(crate) fn header(&self) -> Seq {
+        Seq {
+            name: self.name.clone(),
+            topology: self.topology.clone(),
+            date: self.date.clone(),
+            len: self.len,
+            alphabet: self.alphabet,
+            molecule_type: self.molecule_type.clone(),
+            division: self.division.clone(),
+            definition: self.definition.clone(),
+            accession: self.accession.clone(),
+            version: self.version.clone(),
+            source: self.source.clone(),
+            dblink: self.dblink.clone(),
+            dbsource: self.dbsource.clone(),
+            keywords: self.keywords.clone(),
+            segment: self.segment.clone(),
+            references: self.references.clone(),
+            comments: self.comments.clone(),
+            seq: Vec::new(),
+            contig: self.contig.clone(),
+            features: Vec::new(),
+            wgs: self.wgs.clone(),
+            wgs_scafld: self.wgs_scafld.clone(),
+            tsa: self.tsa.clone(),
+            tls: self.tls.clone(),
+            nid: self.nid.clone(),
+            base_count: self.base_count,
+        }
+    }
+
+    /// Builds a record for every feature of the given kind using
+    /// `extract_feature_record`, e.g. each CDS with 1 kb flanks. Each
+    /// record is named after the feature's `/locus_tag` or `/gene`, or the
+    /// parent's name and the feature's index. Features whose location can't
+    /// be used are skipped with a warning.
+    pub fn feature_records<'a>(
+        &'a self,
+        kind: &'a FeatureKind,
+        flank: i64,
+    ) -> impl Iterator<Item = Seq> + 'a {
+        self.features
+            .iter()
+            .enumerate()
+            .filter(move |(_, f)| f.kind == *kind)
+            .filter_map(move |(i, f)| match self.extract_feature_record(f, flank) {
+                Ok(mut record) => {
+                    let value = |key| f.qualifier_values(key).next().map(String::from);
+                    record.name = value(qualifier_key!("locus_tag"))
+                        .or_else(|| value(qualifier_key!("gene")))
+                        .or_else(|| {
+                            Some(format!("{}_{}", self.name.as_deref().unwrap_or("feature"), i))
+                        });
+                    Some(record)
+                }
+                Err(e) => {
+                    warn!("Skipping feature {}: {}", i, e);
+                    None
+                }
+            })
+    }
+
+    /// Writes the records from `feature_records` one after another
+    pub fn write_feature_records<T: Write>(
+        &self,
+        mut file: T,
+        kind: &FeatureKind,
+        flank: i64,
+    ) -> io::Result<()> {
+        for record in self.feature_records(kind, flank) {
+            record.write(&mut file)?;
+        }
+        Ok(())
+    }
+
     /// Extract the sequence specified by `l`. This version returns
     /// `Err(LocationError::External(_, NoFetcherError))` if it
     /// encounters a reference to an external sequence.
@@ -780,6 +1935,9 @@ impl Seq {
                 }
                 res
             }
+            Complement(_) if self.is_protein() => {
+                return Err(LocationError::Complement(l.clone()))
+            }
             Complement(ref l) => revcomp(&self.extract_location_impl(l, ext_fetcher)?),
             External(ref name, ref ext_l) => {
                 let ext_seq =
@@ -795,23 +1953,189 @@ impl Seq {
         Ok(res)
     }
 
-    /// Returns a new `Seq`, rotated so that `origin` is at the start
+    /// Builds the sequence described by the CONTIG line, calling `fetcher`
+    /// for the records it refers to, as in `extract_location_with_fetcher`.
+    /// Gaps are filled with `n`, with gaps of unknown size taken to be 100
+    /// bases long. Returns `None` if there's no CONTIG.
+    pub fn assemble_contig_with_fetcher<'a, F, S>(
+        &self,
+        mut fetcher: F,
+    ) -> Option<Result<Vec<u8>, LocationError>>
+    where
+        F: (FnMut(&str) -> Result<S, Box<dyn Error>>) + 'a,
+        S: Borrow<Seq> + 'a,
+    {
+        let contig = self.contig.as_ref()?;
+        let mut res = Vec::new();
+        Some(self.assemble_impl(contig, &mut fetcher, &mut res).map(|_| res))
+    }
+
+    fn assemble_impl<'a, F, S>(
+        &self,
+        l: &Location,
+        fetcher: &mut F,
+        res: &mut Vec<u8>,
+    ) -> Result<(), LocationError>
+    where
+        F: (FnMut(&str) -> Result<S, Box<dyn Error>>) + 'a,
+        S: Borrow<Seq> + 'a,
+    {
+        match *l {
+            Location::Join(ref ls) => {
+                for l in ls {
+                    self.assemble_impl(l, fetcher, res)?;
+                }
+            }
+            Location::Gap(GapLength::Known(n)) => res.extend(std::iter::repeat_n(b'n', n as usize)),
+            Location::Gap(_) => res.extend(std::iter::repeat_n(b'n', UNKNOWN_GAP_LEN)),
+            _ => res.extend(self.extract_location_impl(l, fetcher)?),
+        }
+        Ok(())
+    }
+
+    /// Fills in `seq` from the CONTIG line of a record which has one but no
+    /// sequence, such as those in the CON division, using
+    /// `assemble_contig_with_fetcher`. Does nothing if the record already
+    /// has a sequence or has no CONTIG. If the LOCUS line gave a length, the
+    /// assembled sequence must match it.
+    pub fn resolve_contig<'a, F, S>(&mut self, fetcher: F) -> Result<(), LocationError>
+    where
+        F: (FnMut(&str) -> Result<S, Box<dyn Error>>) + 'a,
+        S: Borrow<Seq> + 'a,
+    {
+        if !self.seq.is_empty() {
+            return Ok(());
+        }
+        let seq = match self.assemble_contig_with_fetcher(fetcher) {
+            Some(seq) => seq?,
+            None => return Ok(()),
+        };
+        if let Some(len) = self.len {
+            if len != seq.len() {
+                return Err(LocationError::ContigLength(seq.len(), len));
+            }
+        }
+        self.seq = seq;
+        Ok(())
+    }
+
+    /// Returns a new `Seq`, rotated so that `origin` is at the start.
+    /// Features which come to span the new origin are split into joins, with
+    /// any partialness (`<`/`>`) staying on their outer ends. Features
+    /// covering the whole sequence, such as `source`, are left as they are.
     pub fn set_origin(&self, origin: i64) -> Seq {
+        self.set_origin_with_diagnostics(origin, &mut LogSink)
+    }
+
+    /// Like `set_origin`, but features which can't be relocated are reported
+    /// to `sink`
+    pub fn set_origin_with_diagnostics(&self, origin: i64, sink: &mut dyn DiagnosticSink) -> Seq {
         assert!(self.is_circular());
         assert!(origin < self.len());
         let rotated = self.extract_range_seq(origin, origin);
+        let mut features = Vec::with_capacity(self.features.len());
+        for (i, f) in self.features.iter().enumerate() {
+            if self.covers_whole_seq(&f.location) {
+                features.push(f.clone());
+                continue;
+            }
+            match self.relocate_feature(f.clone(), -origin) {
+                Ok(f) => features.push(f),
+                Err(e) => sink.report(Diagnostic::for_feature(
+                    i,
+                    format!("Skipping feature, can't process invalid location: {}", e),
+                )),
+            }
+        }
         Seq {
             seq: rotated.into(),
-            features: self
-                .features
-                .iter()
-                .cloned()
-                .flat_map(|f| self.relocate_feature(f, -origin))
-                .collect(),
+            features,
             ..self.clone()
         }
     }
 
+    /// Used by `set_origin`
+    fn covers_whole_seq(&self, l: &Location) -> bool {
+        match *l {
+            Location::Range((0, _), (end, _)) => end == self.len(),
+            Location::Complement(ref l) => self.covers_whole_seq(l),
+            _ => false,
+        }
+    }
+
+    /// Adds a feature covering `start..end` (which may wrap around the origin
+    /// of a circular sequence) on the given strand, returning its index.
+    /// The feature is appended, unless `sorted` is set, in which case it's
+    /// inserted after the last feature starting at or before `start`, which
+    /// keeps an already sorted table sorted. Fails if the range isn't on the
+    /// sequence, see `range_to_location`, or if a reverse strand feature is
+    /// added to a protein.
+    pub fn add_feature(
+        &mut self,
+        start: i64,
+        end: i64,
+        strand: Strand,
+        kind: FeatureKind,
+        qualifiers: Vec<(QualifierKey, Option<String>)>,
+        sorted: bool,
+    ) -> Result<usize, LocationError> {
+        let location = self.range_to_location(start, end)?;
+        let location = match strand {
+            Strand::Forward => location,
+            Strand::Reverse if self.is_protein() => return Err(LocationError::Complement(location)),
+            Strand::Reverse => location.complement(),
+        };
+        let feature = Feature::new(kind, location, qualifiers);
+        let index = if sorted {
+            let start = self.unwrap_range(start, end).0;
+            self.features
+                .iter()
+                .rposition(|f| f.location.find_bounds().is_ok_and(|(s, _)| s <= start))
+                .map_or(0, |i| i + 1)
+        } else {
+            self.features.len()
+        };
+        self.features.insert(index, feature);
+        Ok(index)
+    }
+
+    /// Sets `id` on every feature which doesn't have one yet, using
+    /// `Feature::stable_id`. If this would result in duplicates (say, two
+    /// mRNAs with the same `/locus_tag`), later features get the suffixes
+    /// `.2`, `.3` etc. Once assigned, IDs are carried along by `revcomp`,
+    /// `set_origin`, `extract_range` and so on.
+    pub fn assign_feature_ids(&mut self) {
+        let mut seen: HashSet<String> = self
+            .features
+            .iter()
+            .filter_map(|f| f.id.clone())
+            .collect();
+        for f in &mut self.features {
+            if f.id.is_some() {
+                continue;
+            }
+            let base = f.stable_id();
+            let mut id = base.clone();
+            let mut n = 1;
+            while seen.contains(&id) {
+                n += 1;
+                id = format!("{}.{}", base, n);
+            }
+            seen.insert(id.clone());
+            f.id = Some(id);
+        }
+    }
+
+    /// Finds a feature by `Feature::stable_id`
+    pub fn feature_by_id(&self, id: &str) -> Option<&Feature> {
+        self.features.iter().find(|f| f.stable_id() == id)
+    }
+
+    /// Finds a feature by `Feature::stable_id`
+    pub fn feature_by_id_mut(&mut self, id: &str) -> Option<&mut Feature> {
+        self.features.iter_mut().find(|f| f.stable_id() == id)
+    }
+
     pub fn write<T: Write>(&self, file: T) -> io::Result<()> {
         crate::writer::write(file, self)
     }
@@ -853,7 +2177,7 @@ fn flatten_join(v: Vec<Location>) -> Vec<Location> {
 
 /// This doesn't simplify everything yet...
 /// TODO: return original Location somehow on failure
-fn simplify(p: Location) -> Result<Location, LocationError> {
+pub(crate) fn simplify(p: Location) -> Result<Location, LocationError> {
     p.transform(&simplify_shallow, &Ok)
 }
 
@@ -888,11 +2212,247 @@ fn simplify_shallow(p: Location) -> Result<Location, LocationError> {
     }
 }
 
+/// Serializes a sequence as a string rather than an array of numbers, which
+/// makes e.g. JSON output much smaller and readable. Deserialization also
+/// accepts bytes and arrays, so data written without it can still be read.
+/// Used for `Seq::seq` with the `serde_seq_string` feature, or with
+/// `#[serde(with = "gb_io::seq::serde_seq_string")]`.
+#[cfg(feature = "serde")]
+pub mod serde_seq_string {
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+    use std::str;
+
+    pub fn serialize<S: Serializer>(seq: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match str::from_utf8(seq) {
+            Ok(s) => serializer.serialize_str(s),
+            Err(_) => Err(serde::ser::Error::custom("sequence isn't ASCII")),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_any(SeqVisitor)
+    }
+
+    struct SeqVisitor;
+
+    impl<'de> Visitor<'de> for SeqVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a sequence as a string or bytes")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            Ok(v.as_bytes().to_vec())
+        }
+
+        fn visit_string<E: de::Error>(self, v: String) -> Result<Vec<u8>, E> {
+            Ok(v.into_bytes())
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut res = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element()? {
+                res.push(b);
+            }
+            Ok(res)
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::tests::init;
 
+    #[test]
+    fn windows() {
+        let mut s = Seq {
+            seq: b"aaaaaccccc".to_vec(),
+            features: vec![Feature::new(
+                feature_kind!("gene"),
+                Location::simple_range(3, 7),
+                vec![],
+            )],
+            ..Seq::empty()
+        };
+        let windows: Vec<_> = s
+            .windows(4, 3)
+            .map(|(start, w)| (start, w.seq, w.features.len()))
+            .collect();
+        assert_eq!(
+            windows,
+            vec![
+                (0, b"aaaa".to_vec(), 1),
+                (3, b"aacc".to_vec(), 1),
+                (6, b"cccc".to_vec(), 1),
+            ]
+        );
+        assert_eq!(s.windows(5, 5).count(), 2);
+        assert_eq!(s.windows(20, 5).count(), 1);
+
+        s.topology = Topology::Circular;
+        let windows: Vec<_> = s.windows(4, 3).collect();
+        assert_eq!(windows.len(), 4);
+        assert_eq!(windows[3].0, 9);
+        assert_eq!(windows[3].1.seq, b"caaa");
+        assert!(windows[3].1.features.is_empty());
+        assert_eq!(Seq::empty().windows(4, 3).count(), 0);
+    }
+
+    #[test]
+    fn divisions() {
+        assert_eq!(Division::from("BCT"), Division::Bct);
+        assert_eq!(Division::default().as_str(), "UNK");
+        for d in Division::CODES.iter().map(|c| c.0).chain(Some("UNK")) {
+            assert_eq!(Division::from(d).to_string(), d);
+            assert_eq!(String::from(Division::from(d.to_string())), d);
+        }
+        assert!(Division::Con.is_valid() && Division::default().is_valid());
+        assert!(!Division::from("bct").is_valid());
+        assert!(!Division::from("BACT").is_valid());
+    }
+
+    #[test]
+    fn molecule_types() {
+        assert_eq!(MoleculeType::from("mRNA"), MoleculeType::Mrna);
+        assert_eq!(MoleculeType::from("ss-DNA"), MoleculeType::SsDna);
+        assert_eq!(MoleculeType::from("snRNA"), MoleculeType::Other("snRNA".into()));
+        for m in MoleculeType::NAMED.iter().map(|n| n.0).chain(Some("snRNA")) {
+            assert_eq!(MoleculeType::from(m).to_string(), m);
+            assert_eq!(String::from(MoleculeType::from(m.to_string())), m);
+        }
+        assert!(MoleculeType::MsDna.is_dna() && !MoleculeType::MsDna.is_rna());
+        assert!(MoleculeType::Trna.is_rna());
+        assert!(MoleculeType::Other("tmRNA".into()).is_rna());
+        assert!(!MoleculeType::Na.is_dna() && !MoleculeType::Na.is_rna());
+    }
+
+    #[test]
+    fn reference_bases() {
+        let bases = Reference::parse_bases;
+        assert_eq!(bases("1  (bases 1 to 5028)"), vec![(0, 5028)]);
+        assert_eq!(
+            bases("2  (bases 1 to 1859; 2000 to 2100)"),
+            vec![(0, 1859), (1999, 2100)]
+        );
+        assert_eq!(bases("1  (residues 1 to 360)"), vec![(0, 360)]);
+        assert!(bases("3  (sites)").is_empty());
+        assert!(bases("1").is_empty());
+        let mut r = Reference {
+            description: "2  (bases 1 to 1859; 2000 to 2100)".into(),
+            bases: vec![(0, 1859), (1999, 2100)],
+            authors: None,
+            consortium: None,
+            title: String::new(),
+            journal: None,
+            pubmed: None,
+            remark: None,
+        };
+        let na = Alphabet::Nucleotide;
+        assert_eq!(r.description_with_bases(na), r.description);
+        r.bases = vec![(9, 20)];
+        assert_eq!(r.description_with_bases(na), "2  (bases 10 to 20)");
+        r.description = "12".into();
+        assert_eq!(r.description_with_bases(na), "12 (bases 10 to 20)");
+        assert_eq!(
+            r.description_with_bases(Alphabet::Protein),
+            "12 (residues 10 to 20)"
+        );
+        r.description = "1  (residues 1 to 360)".into();
+        assert_eq!(
+            r.description_with_bases(Alphabet::Protein),
+            "1  (residues 10 to 20)"
+        );
+        r.bases.clear();
+        assert_eq!(r.description_with_bases(Alphabet::Protein), r.description);
+    }
+
+    #[test]
+    fn semantic_eq() {
+        let f = |l: &str, q: Vec<(&str, &str)>| {
+            Feature::new(
+                feature_kind!("CDS"),
+                Location::from_gb_format(l).unwrap(),
+                q.into_iter()
+                    .map(|(k, v)| (k.into(), Some(v.into())))
+                    .collect(),
+            )
+        };
+        let a = f(
+            "complement(join(1..2,5..6))",
+            vec![("gene", "abc"), ("product", "a long\nname"), ("translation", "MK\nV")],
+        );
+        let mut b = f(
+            "join(complement(5..6),complement(1..2))",
+            vec![("translation", "MKV"), ("Gene", "abc"), ("product", "a long name")],
+        );
+        b.kind = "cds".into();
+        b.id = Some("x".into());
+        assert!(a.semantic_eq(&b));
+        let c = f("complement(join(1..2,5..6))", vec![("gene", "abd")]);
+        assert!(!a.semantic_eq(&c));
+        assert!(!a.semantic_eq(&f("join(1..2,5..6)", vec![])));
+
+        let set: HashSet<_> = [&a, &b, &c].iter().map(|f| SemanticFeature(f)).collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_dates() {
+        let d = chrono::NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+        let date = Date::from(d);
+        assert_eq!(date, Date::from_ymd(2020, 2, 29).unwrap());
+        assert_eq!(chrono::NaiveDate::try_from(date), Ok(d));
+        let invalid = Date::from_ymd(2021, 2, 29).unwrap();
+        assert_eq!(chrono::NaiveDate::try_from(invalid), Err(DateError));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_dates() {
+        let d = time::Date::from_calendar_date(2020, time::Month::March, 1).unwrap();
+        let date = Date::from(d);
+        assert_eq!(date, Date::from_ymd(2020, 3, 1).unwrap());
+        assert_eq!(time::Date::try_from(date), Ok(d));
+        let invalid = Date::from_ymd(2020, 4, 31).unwrap();
+        assert_eq!(time::Date::try_from(invalid), Err(DateError));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_seq_string() {
+        extern crate serde_json;
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Wrapper(#[serde(with = "super::serde_seq_string")] Vec<u8>);
+        let json = serde_json::to_string(&Wrapper(b"acgt".to_vec())).unwrap();
+        assert_eq!(json, "\"acgt\"");
+        assert_eq!(serde_json::from_str::<Wrapper>(&json).unwrap().0, b"acgt");
+        assert_eq!(serde_json::from_str::<Wrapper>("[97,99]").unwrap().0, b"ac");
+        assert!(serde_json::to_string(&Wrapper(vec![0xff])).is_err());
+        #[cfg(feature = "serde_seq_string")]
+        {
+            let seq = Seq {
+                seq: b"acgt".to_vec(),
+                ..Seq::empty()
+            };
+            let json = serde_json::to_string(&seq).unwrap();
+            assert!(json.contains("\"seq\":\"acgt\""));
+            assert_eq!(serde_json::from_str::<Seq>(&json).unwrap(), seq);
+        }
+    }
+
     #[test]
     fn test_merge_adj() {
         use Location::*;
@@ -1046,13 +2606,13 @@ mod test {
 
     #[test]
     fn test_extract_linear() {
-        let features = vec![Feature {
-            location: Location::simple_range(0, 100),
-            kind: FeatureKind::from(""),
-            qualifiers: Vec::new(),
-        }];
+        let features = vec![Feature::new(
+            FeatureKind::from(""),
+            Location::simple_range(0, 100),
+            Vec::new(),
+        )];
         let s = Seq {
-            seq: ::std::iter::repeat(b'A').take(100).collect(),
+            seq: vec![b'A'; 100],
             topology: Topology::Linear,
             features,
             ..Seq::empty()
@@ -1071,11 +2631,11 @@ mod test {
 
     #[test]
     fn test_extract_circular() {
-        let whole_seq = Feature {
-            location: Location::simple_range(0, 10),
-            kind: FeatureKind::from(""),
-            qualifiers: Vec::new(),
-        };
+        let whole_seq = Feature::new(
+            FeatureKind::from(""),
+            Location::simple_range(0, 10),
+            Vec::new(),
+        );
         let make_pos = |from: i64, to: i64| -> Location {
             if to > 10 {
                 Location::Join(vec![
@@ -1090,15 +2650,11 @@ mod test {
         for i in 0..10 {
             for j in 1..3 {
                 let s = Seq {
-                    seq: ::std::iter::repeat(b'A').take(10).collect(),
+                    seq: vec![b'A'; 10],
                     topology: Topology::Circular,
                     features: vec![
                         whole_seq.clone(),
-                        Feature {
-                            location: make_pos(i, i + j),
-                            kind: FeatureKind::from(""),
-                            qualifiers: Vec::new(),
-                        },
+                        Feature::new(FeatureKind::from(""), make_pos(i, i + j), Vec::new()),
                     ],
                     ..Seq::empty()
                 };
@@ -1128,11 +2684,11 @@ mod test {
         let s = Seq {
             topology: Topology::Circular,
             seq: (0..10).collect(),
-            features: vec![Feature {
-                location: Location::simple_range(0, 4),
-                kind: feature_kind!(""),
-                qualifiers: vec![],
-            }],
+            features: vec![Feature::new(
+                feature_kind!(""),
+                Location::simple_range(0, 4),
+                vec![],
+            )],
             ..Seq::empty()
         };
         let res = s.extract_range(4, 10);
@@ -1147,6 +2703,126 @@ mod test {
         assert_eq!(&res.features[0].location, &Location::simple_range(0, 4));
     }
 
+    #[test]
+    fn extract_feature_record() {
+        let f = |l: &str| {
+            Feature::new(
+                feature_kind!("CDS"),
+                Location::from_gb_format(l).unwrap(),
+                vec![],
+            )
+        };
+        let mut s = Seq {
+            name: Some("test".into()),
+            seq: (0..20).collect(),
+            features: vec![f("6..10"), f("1..4"), f("complement(11..14)"), f("19..20")],
+            ..Seq::empty()
+        };
+        let res = s.extract_feature_record(&s.features[0], 2).unwrap();
+        assert_eq!(res.name.as_deref(), Some("test"));
+        assert_eq!(res.seq, (3..12).collect::<Vec<_>>());
+        assert_eq!(res.len, Some(9));
+        let locations: Vec<_> = res.features.iter().map(|f| f.location.to_gb_format()).collect();
+        assert_eq!(locations, vec!["3..7", "1", "complement(8..9)"]);
+        let res = s.extract_feature_record(&s.features[1], 5).unwrap();
+        assert_eq!(res.seq, (0..9).collect::<Vec<_>>());
+
+        s.topology = Topology::Circular;
+        let res = s.extract_feature_record(&s.features[3], 2).unwrap();
+        assert_eq!(res.topology, Topology::Linear);
+        assert_eq!(res.seq, vec![16, 17, 18, 19, 0, 1]);
+        let res = s.extract_feature_record(&s.features[3], 10).unwrap();
+        assert_eq!(res.seq.len(), 20);
+    }
+
+    #[test]
+    fn feature_records() {
+        let f = |kind: &str, l: &str, tag: Option<&str>| {
+            Feature::new(
+                kind.into(),
+                Location::from_gb_format(l).unwrap(),
+                tag.map(|t| (qualifier_key!("locus_tag"), Some(t.into())))
+                    .into_iter()
+                    .collect(),
+            )
+        };
+        let s = Seq {
+            name: Some("parent".into()),
+            seq: vec![b'a'; 100],
+            features: vec![
+                f("source", "1..100", None),
+                f("CDS", "11..20", Some("t1")),
+                f("gene", "11..20", Some("t1")),
+                f("CDS", "complement(41..60)", None),
+            ],
+            ..Seq::empty()
+        };
+        let records: Vec<_> = s.feature_records(&feature_kind!("CDS"), 5).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].name.as_deref(), Some("t1"));
+        assert_eq!(records[0].len, Some(20));
+        assert_eq!(records[0].features.len(), 3);
+        assert_eq!(records[1].name.as_deref(), Some("parent_3"));
+        assert_eq!(records[1].features[1].location.to_gb_format(), "complement(6..25)");
+
+        let mut out = Vec::new();
+        s.write_feature_records(&mut out, &feature_kind!("CDS"), 5).unwrap();
+        let parsed = crate::reader::parse_slice(&out).unwrap();
+        assert_eq!(parsed.len(), 2);
+        for (p, r) in parsed.iter().zip(&records) {
+            assert_eq!((&p.name, &p.features, &p.seq), (&r.name, &r.features, &r.seq));
+        }
+    }
+
+    #[test]
+    fn feature_ids() {
+        let f = |l: &str, q: Vec<(QualifierKey, Option<String>)>| {
+            Feature::new(
+                feature_kind!("CDS"),
+                Location::from_gb_format(l).unwrap(),
+                q,
+            )
+        };
+        let tag = |t: &str| vec![(qualifier_key!("locus_tag"), Some(t.to_string()))];
+        let mut s = Seq {
+            seq: vec![b'a'; 100],
+            topology: Topology::Circular,
+            features: vec![
+                f("1..10", tag("b0001")),
+                f("20..30", tag("b0001")),
+                f("complement(40..50)", vec![]),
+            ],
+            ..Seq::empty()
+        };
+        let hashed = s.features[2].stable_id();
+        assert!(hashed.starts_with("CDS:"));
+        assert_eq!(s.feature_by_id(&hashed), Some(&s.features[2]));
+        s.assign_feature_ids();
+        let ids: Vec<_> = s.features.iter().map(|f| f.id.clone().unwrap()).collect();
+        assert_eq!(ids[..2], ["CDS:b0001".to_string(), "CDS:b0001.2".to_string()]);
+        assert_eq!(ids[2], hashed);
+        // IDs survive moving the features around
+        let rotated = s.set_origin(45).revcomp();
+        assert_eq!(
+            rotated.feature_by_id(&hashed).unwrap().location.to_gb_format(),
+            "join(96..100,1..6)"
+        );
+        assert!(rotated.feature_by_id("CDS:b0001.2").is_some());
+    }
+
+    #[test]
+    fn ranges() {
+        let r = |l| Location::from_gb_format(l).unwrap().ranges();
+        assert_eq!(r("1..5").unwrap(), vec![(0, 5)]);
+        assert_eq!(r("2^3").unwrap(), vec![]);
+        assert_eq!(
+            r("complement(join(8..10,1..2))").unwrap(),
+            vec![(7, 10), (0, 2)]
+        );
+        assert!(r("one-of(1,5)").is_err());
+        assert!(r("J00194.1:100..202").is_err());
+    }
+
     #[test]
     fn truncate() {
         assert_eq!(
@@ -1181,14 +2857,14 @@ mod test {
 
     #[test]
     fn test_extract_circular_split() {
-        let features = vec![Feature {
-            location: Location::Join(vec![
+        let features = vec![Feature::new(
+            FeatureKind::from(""),
+            Location::Join(vec![
                 Location::simple_range(0, 2),
                 Location::simple_range(4, 9),
             ]),
-            kind: FeatureKind::from(""),
-            qualifiers: Vec::new(),
-        }];
+            Vec::new(),
+        )];
         let s = Seq {
             seq: (0..10).collect(),
             topology: Topology::Circular,
@@ -1204,14 +2880,14 @@ mod test {
 
     #[test]
     fn extract_range_wrapped_linear() {
-        let features = vec![Feature {
-            location: Location::Join(vec![
+        let features = vec![Feature::new(
+            FeatureKind::from(""),
+            Location::Join(vec![
                 Location::simple_range(5, 9),
                 Location::simple_range(0, 4),
             ]),
-            kind: FeatureKind::from(""),
-            qualifiers: Vec::new(),
-        }];
+            Vec::new(),
+        )];
         let s = Seq {
             seq: (0..10).collect(),
             features,
@@ -1227,29 +2903,17 @@ mod test {
         let seq = Seq {
             seq: "0123456789".into(),
             features: vec![
-                Feature {
-                    location: Location::simple_range(2, 7),
-                    kind: feature_kind!(""),
-                    qualifiers: Vec::new(),
-                },
-                Feature {
-                    location: Location::simple_range(0, 10),
-                    kind: feature_kind!(""),
-                    qualifiers: Vec::new(),
-                },
-                Feature {
-                    location: Location::Join(vec![
+                Feature::new(feature_kind!(""), Location::simple_range(2, 7), Vec::new()),
+                Feature::new(feature_kind!(""), Location::simple_range(0, 10), Vec::new()),
+                Feature::new(
+                    feature_kind!(""),
+                    Location::Join(vec![
                         Location::simple_range(7, 10),
                         Location::simple_range(0, 4),
                     ]),
-                    kind: feature_kind!(""),
-                    qualifiers: Vec::new(),
-                },
-                Feature {
-                    location: Location::single(0),
-                    kind: feature_kind!(""),
-                    qualifiers: Vec::new(),
-                },
+                    Vec::new(),
+                ),
+                Feature::new(feature_kind!(""), Location::single(0), Vec::new()),
             ],
             topology: Topology::Circular,
             ..Seq::empty()
@@ -1263,6 +2927,34 @@ mod test {
             assert_eq!(rotated2.features, seq.features);
         }
     }
+    #[test]
+    fn set_origin_spanning() {
+        let mut s = Seq {
+            seq: "0123456789".into(),
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        let rotate = |s: &mut Seq, l: &str, origin: i64| {
+            s.features = vec![Feature::new(
+                feature_kind!("misc_feature"),
+                Location::from_gb_format(l).unwrap(),
+                Vec::new(),
+            )];
+            let mut diagnostics = Vec::new();
+            let rotated = s.set_origin_with_diagnostics(origin, &mut diagnostics);
+            assert!(diagnostics.is_empty());
+            rotated.features[0].location.to_gb_format()
+        };
+        assert_eq!(rotate(&mut s, "3..8", 5), "join(8..10,1..3)");
+        assert_eq!(rotate(&mut s, "<3..>8", 5), "join(<8..10,1..>3)");
+        assert_eq!(rotate(&mut s, "complement(<3..8)", 5), "complement(join(<8..10,1..3))");
+        assert_eq!(rotate(&mut s, "join(<8..10,1..2)", 5), "<3..7");
+        assert_eq!(rotate(&mut s, "5^6", 5), "10^1");
+        assert_eq!(rotate(&mut s, "order(2..3,7..9)", 5), "order(7..8,2..4)");
+        assert_eq!(rotate(&mut s, "1..10", 5), "1..10");
+        assert_eq!(rotate(&mut s, "complement(1..10)", 5), "complement(1..10)");
+    }
+
     #[test]
     fn range_to_location() {
         let s = Seq {
@@ -1270,17 +2962,34 @@ mod test {
             topology: Topology::Linear,
             ..Seq::empty()
         };
-        assert_eq!(s.range_to_location(0, 10), Location::simple_range(0, 10));
+        assert_eq!(s.range_to_location(0, 10).unwrap(), Location::simple_range(0, 10));
+        assert!(matches!(
+            s.range_to_location(5, 11),
+            Err(LocationError::OutOfBounds(_))
+        ));
         let s = Seq {
             topology: Topology::Circular,
             ..s
         };
-        assert_eq!(s.range_to_location(5, 10), Location::simple_range(5, 10));
-        assert_eq!(s.range_to_location(5, 11).to_gb_format(), "join(6..10,1)");
+        let r = |a, b| s.range_to_location(a, b).unwrap();
+        assert_eq!(r(5, 10), Location::simple_range(5, 10));
+        assert_eq!(r(5, 11).to_gb_format(), "join(6..10,1)");
+        assert_eq!(r(5, 15).to_gb_format(), "join(6..10,1..5)");
+        // rolling circle
+        assert_eq!(r(5, 25).to_gb_format(), "join(6..10,1..10,1..5)");
+        assert_eq!(r(0, 30).to_gb_format(), "join(1..10,1..10,1..10)");
         assert_eq!(
-            s.range_to_location(5, 15).to_gb_format(),
-            "join(6..10,1..5)"
+            s.extract_location(&r(5, 25)).unwrap(),
+            s.extract_range_seq(5, 25).into_owned()
         );
+        let empty = Seq {
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        assert!(matches!(
+            empty.range_to_location(0, 5),
+            Err(LocationError::EmptySequence(_))
+        ));
     }
     #[test]
     fn unwrap_range_linear() {
@@ -1360,6 +3069,44 @@ mod test {
         );
     }
 
+    #[test]
+    fn resolve_contig() {
+        let part = Seq {
+            seq: b"acgtac".to_vec(),
+            ..Seq::empty()
+        };
+        let contig = Location::from_gb_format("join(A:1..4,gap(3),complement(A:5..6))").unwrap();
+        let mut con = Seq {
+            len: Some(9),
+            contig: Some(contig),
+            ..Seq::empty()
+        };
+        let mut fetched = Vec::new();
+        con.resolve_contig(|n| {
+            fetched.push(n.to_string());
+            Ok(&part)
+        })
+        .unwrap();
+        assert_eq!(fetched, vec!["A", "A"]);
+        assert_eq!(con.seq, b"acgtnnngt");
+        assert_eq!(con.len(), 9);
+
+        con.seq.clear();
+        con.len = Some(10);
+        assert!(matches!(
+            con.resolve_contig(|_| Ok(&part)),
+            Err(LocationError::ContigLength(9, 10))
+        ));
+        assert!(matches!(
+            con.resolve_contig(|_| Err::<&Seq, _>(Box::new(NoFetcherError))),
+            Err(LocationError::External(..))
+        ));
+        assert!(con.seq.is_empty());
+        con.contig = None;
+        con.resolve_contig(|_| Ok(&part)).unwrap();
+        assert!(con.seq.is_empty());
+    }
+
     #[test]
     fn wrap_location() {
         let s = Seq {
@@ -1393,6 +3140,140 @@ mod test {
                 .to_gb_format(),
             "join(9..10,1..>1)"
         );
+        let wrap = |l: &str| {
+            s.wrap_location(Location::from_gb_format(l).unwrap())
+                .unwrap()
+                .to_gb_format()
+        };
+        assert_eq!(wrap("0"), "10");
+        assert_eq!(wrap("10^11"), "10^1");
+        assert_eq!(wrap("11^12"), "1^2");
+        assert_eq!(wrap("complement(9..12)"), "complement(join(9..10,1..2))");
+        assert_eq!(wrap("complement(11^12)"), "complement(1^2)");
+        assert_eq!(wrap("join(6..8,9..12)"), "join(6..10,1..2)");
+        assert_eq!(
+            wrap("join(complement(11..12),complement(9..10))"),
+            "complement(join(9..10,1..2))"
+        );
+        assert_eq!(wrap("order(9..12,14..15)"), "order(join(9..10,1..2),4..5)");
+        assert_eq!(wrap("bond(12,15)"), "bond(2,5)");
+        assert_eq!(wrap("one-of(12,15)"), "one-of(2,5)");
+        assert_eq!(wrap("J00194.1:100..202"), "J00194.1:100..202");
+        assert_eq!(wrap("gap(5)"), "gap(5)");
+        // between
+        assert_eq!(wrap("5^6"), "5^6");
+        assert_eq!(wrap("20^21"), "10^1");
+        assert_eq!(wrap("complement(10^11)"), "complement(10^1)");
+        assert_eq!(
+            s.wrap_location(Location::Between(-1, 0)).unwrap().to_gb_format(),
+            "10^1"
+        );
+        // bond, one-of and external
+        assert_eq!(wrap("bond(9..12,15)"), "bond(join(9..10,1..2),5)");
+        assert_eq!(wrap("one-of(9..12,14..15)"), "one-of(join(9..10,1..2),4..5)");
+        assert_eq!(wrap("J00194.1:9..12"), "J00194.1:9..12");
+        // origin edges
+        assert_eq!(wrap("1..10"), "1..10");
+        assert_eq!(wrap("10"), "10");
+        assert_eq!(wrap("11..20"), "1..10");
+        assert_eq!(wrap("10..11"), "join(10,1)");
+        assert_eq!(
+            s.wrap_location(Location::simple_range(-1, 1)).unwrap().to_gb_format(),
+            "join(10,1)"
+        );
+        let empty = Seq {
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        for l in &["1", "1..2", "1^2", "bond(1,2)", "one-of(1,2)", "J00194.1:1..2"] {
+            let l = Location::from_gb_format(l).unwrap();
+            assert!(matches!(
+                empty.wrap_location(l),
+                Err(LocationError::EmptySequence(_))
+            ));
+        }
+        assert!(matches!(
+            empty.relocate_location(Location::single(0), 1),
+            Err(LocationError::EmptySequence(_))
+        ));
+    }
+
+    #[test]
+    fn skipped_features_are_reported() {
+        let s = Seq {
+            seq: b"acgtacgtac".to_vec(),
+            features: vec![
+                Feature::new(
+                    feature_kind!("misc_feature"),
+                    Location::simple_range(0, 4),
+                    Vec::new(),
+                ),
+                Feature::new(
+                    feature_kind!("misc_feature"),
+                    Location::Join(Vec::new()),
+                    Vec::new(),
+                ),
+            ],
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        let mut diagnostics = Vec::new();
+        let rc = s.revcomp_with_diagnostics(&mut diagnostics);
+        assert_eq!(rc.features.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].feature, Some(1));
+        let mut diagnostics = Vec::new();
+        let extracted = s.extract_range_with_diagnostics(0, 10, &mut diagnostics);
+        assert_eq!(extracted.features.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].feature, Some(1));
+    }
+
+    #[test]
+    fn revcomp_protein_is_reported() {
+        let s = Seq {
+            seq: b"MKV".to_vec(),
+            alphabet: Alphabet::Protein,
+            features: vec![Feature::new(
+                feature_kind!("Protein"),
+                Location::simple_range(0, 3),
+                Vec::new(),
+            )],
+            ..Seq::empty()
+        };
+        let mut diagnostics = Vec::new();
+        let rc = s.revcomp_with_diagnostics(&mut diagnostics);
+        assert_eq!(rc, s);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].feature, None);
+    }
+
+    #[test]
+    fn revcomp_origin_spanning() {
+        let s = Seq {
+            seq: b"acgtacgtac".to_vec(),
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        let rc = |l: &str| {
+            s.revcomp_location(Location::from_gb_format(l).unwrap())
+                .unwrap()
+                .to_gb_format()
+        };
+        assert_eq!(rc("join(9..10,1..2)"), "complement(join(9..10,1..2))");
+        assert_eq!(rc("complement(join(9..10,1..2))"), "join(9..10,1..2)");
+        // spans which weren't split at the origin
+        assert_eq!(rc("9..12"), "complement(join(9..10,1..2))");
+        assert_eq!(rc("<9..>12"), "complement(join(<9..10,1..>2))");
+        assert_eq!(rc("join(8..10,1..2,4..5)"), "complement(join(6..7,9..10,1..3))");
+        assert_eq!(rc("10^1"), "complement(10^1)");
+        for l in &["join(9..10,1..2)", "join(8..10,1..2,4..5)", "join(<9..10,1..>2)"] {
+            let l = Location::from_gb_format(l).unwrap();
+            let twice = s
+                .revcomp_location(s.revcomp_location(l.clone()).unwrap())
+                .unwrap();
+            assert_eq!(twice, l);
+        }
     }
 
     #[test]
@@ -1402,11 +3283,7 @@ mod test {
             topology: Topology::Linear,
             features: locations
                 .into_iter()
-                .map(|p| Feature {
-                    location: p,
-                    kind: feature_kind!(""),
-                    qualifiers: Vec::new(),
-                })
+                .map(|p| Feature::new(feature_kind!(""), p, Vec::new()))
                 .collect(),
             ..Seq::empty()
         };
@@ -1461,4 +3338,102 @@ mod test {
             "complement(join(4..5,1..2))",
         );
     }
+
+    #[test]
+    fn add_feature() {
+        let mut s = Seq {
+            seq: b"aaaaacccccgggggttttt".to_vec(),
+            topology: Topology::Circular,
+            ..Seq::empty()
+        };
+        let mut add =
+            |a, b, strand, kind, q, sort| s.add_feature(a, b, strand, kind, q, sort).unwrap();
+        assert_eq!(add(10, 15, Strand::Forward, feature_kind!("gene"), vec![], true), 0);
+        let q = vec![(qualifier_key!("gene"), Some("x".into()))];
+        assert_eq!(add(2, 5, Strand::Reverse, feature_kind!("CDS"), q, true), 0);
+        assert_eq!(add(18, 3, Strand::Forward, feature_kind!("CDS"), vec![], true), 2);
+        assert_eq!(add(0, 20, Strand::Forward, feature_kind!("source"), vec![], false), 3);
+        let locations: Vec<_> = s.features.iter().map(|f| f.location.to_gb_format()).collect();
+        assert_eq!(
+            locations,
+            vec!["complement(3..5)", "11..15", "join(19..20,1..3)", "1..20"]
+        );
+        assert_eq!(s.features[0].qualifier_values(qualifier_key!("gene")).next(), Some("x"));
+        let mut p = Seq {
+            seq: b"MKV".to_vec(),
+            alphabet: Alphabet::Protein,
+            ..Seq::empty()
+        };
+        let res = p.add_feature(0, 3, Strand::Reverse, feature_kind!("Region"), vec![], false);
+        assert!(matches!(res, Err(LocationError::Complement(_))));
+        assert!(p.features.is_empty());
+    }
+
+    #[test]
+    fn days_since_epoch() {
+        let date = |d| {
+            let d = Date::from_days_since_epoch(d);
+            (d.year(), d.month(), d.day())
+        };
+        assert_eq!(date(0), (1970, 1, 1));
+        assert_eq!(date(-1), (1969, 12, 31));
+        assert_eq!(date(11016), (2000, 2, 29));
+        assert_eq!(date(19782), (2024, 2, 29));
+    }
+
+    #[test]
+    fn exons_and_introns() {
+        let formatted = |ls: Vec<Location>| -> Vec<String> {
+            ls.iter().map(Location::to_gb_format).collect()
+        };
+        let l = Location::from_gb_format("join(<1..10,21..30,31..40,51..>60)").unwrap();
+        assert_eq!(l.strand(), Strand::Forward);
+        assert_eq!(formatted(l.exons().unwrap()), vec!["<1..10", "21..30", "31..40", "51..>60"]);
+        assert_eq!(formatted(l.introns().unwrap()), vec!["11..20", "41..50"]);
+        for s in &["complement(join(1..10,21..30))", "join(complement(21..30),complement(1..10))"] {
+            let l = Location::from_gb_format(s).unwrap();
+            assert_eq!(l.strand(), Strand::Reverse);
+            assert_eq!(
+                formatted(l.exons().unwrap()),
+                vec!["complement(21..30)", "complement(1..10)"]
+            );
+            assert_eq!(formatted(l.introns().unwrap()), vec!["complement(11..20)"]);
+        }
+        let origin = Location::from_gb_format("join(91..100,1..10)").unwrap();
+        assert!(origin.introns().unwrap().is_empty());
+        assert!(Location::from_gb_format("one-of(1,5)").unwrap().exons().is_err());
+    }
+
+    #[test]
+    fn flags() {
+        let mut f = Feature::new(
+            feature_kind!("CDS"),
+            Location::from_gb_format("complement(<1..10)").unwrap(),
+            vec![(qualifier_key!("pseudogene"), Some("processed".into()))],
+        );
+        assert!(f.is_pseudo());
+        assert!(f.is_partial());
+        assert!(!f.location.is_five_prime_partial());
+        assert!(f.location.is_three_prime_partial());
+        f.set_pseudo(false);
+        assert!(!f.is_pseudo());
+        assert!(f.qualifiers.is_empty());
+        f.set_ribosomal_slippage(true);
+        f.set_ribosomal_slippage(true);
+        f.set_trans_splicing(true);
+        assert_eq!(
+            f.qualifiers,
+            vec![
+                (qualifier_key!("ribosomal_slippage"), None),
+                (qualifier_key!("trans_splicing"), None)
+            ]
+        );
+        assert!(f.is_ribosomal_slippage() && f.is_trans_splicing());
+        f.set_ribosomal_slippage(false);
+        assert!(!f.is_ribosomal_slippage());
+        f.location = Location::simple_range(0, 10);
+        assert!(!f.is_partial());
+        f.set_flag("partial".into(), true);
+        assert!(f.is_partial());
+    }
 }
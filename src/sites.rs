@@ -0,0 +1,131 @@
+//! Searching for short sequences such as restriction sites.
+
+use crate::dna::{iupac_match, revcomp};
+use crate::seq::{Seq, Strand};
+
+/// An occurrence of a pattern. `start` is the 0-based position of its
+/// leftmost base on the forward strand, which on a circular sequence may be
+/// followed by bases wrapping around the origin.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Site {
+    pub start: i64,
+    pub strand: Strand,
+}
+
+impl Seq {
+    fn find_forward(&self, pattern: &[u8]) -> Vec<i64> {
+        let len = self.seq.len();
+        if pattern.is_empty() || len == 0 {
+            return Vec::new();
+        }
+        let protein = self.is_protein();
+        let starts = if self.is_circular() {
+            len
+        } else {
+            (len + 1).saturating_sub(pattern.len())
+        };
+        (0..starts)
+            .filter(|&i| {
+                pattern.iter().enumerate().all(|(j, &p)| {
+                    let base = self.seq[(i + j) % len];
+                    if protein {
+                        p.eq_ignore_ascii_case(&base)
+                    } else {
+                        iupac_match(p, base)
+                    }
+                })
+            })
+            .map(|i| i as i64)
+            .collect()
+    }
+
+    /// Finds every occurrence of `pattern`, which may contain IUPAC
+    /// ambiguity codes, on both strands. Matches on circular sequences may
+    /// span the origin. Palindromic patterns are only reported on the
+    /// forward strand. On protein records, the pattern is matched literally
+    /// (ignoring case) and there is only one strand.
+    pub fn find_sites(&self, pattern: &[u8]) -> Vec<Site> {
+        let mut sites: Vec<_> = self
+            .find_forward(pattern)
+            .into_iter()
+            .map(|start| Site {
+                start,
+                strand: Strand::Forward,
+            })
+            .collect();
+        let reverse = revcomp(pattern);
+        if !self.is_protein() && !reverse.eq_ignore_ascii_case(pattern) {
+            sites.extend(self.find_forward(&reverse).into_iter().map(|start| Site {
+                start,
+                strand: Strand::Reverse,
+            }));
+            sites.sort_by_key(|s| s.start);
+        }
+        sites
+    }
+
+    /// The patterns which occur exactly once, counting both strands, along
+    /// with where they occur
+    pub fn unique_sites<'a>(&self, patterns: &[&'a [u8]]) -> Vec<(&'a [u8], Site)> {
+        patterns
+            .iter()
+            .filter_map(|&p| match *self.find_sites(p) {
+                [site] => Some((p, site)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Topology;
+
+    #[test]
+    fn sites() {
+        let mut s = Seq {
+            // EcoRI, KpnI, BamHI across the origin and a DrdI-like GACNNNGTC
+            seq: b"atccttgaattcaaggtaccgacaaagtcgg".to_vec(),
+            ..Seq::empty()
+        };
+        let ecori: &[u8] = b"GAATTC";
+        let bamhi: &[u8] = b"GGATCC";
+        let kpni: &[u8] = b"GGTACC";
+        let nonpal: &[u8] = b"GACNNNGTC";
+        assert_eq!(
+            s.find_sites(ecori),
+            vec![Site {
+                start: 6,
+                strand: Strand::Forward
+            }]
+        );
+        assert!(s.find_sites(bamhi).is_empty());
+        assert_eq!(
+            s.find_sites(b"TTGAA"),
+            vec![
+                Site {
+                    start: 4,
+                    strand: Strand::Forward
+                },
+                Site {
+                    start: 9,
+                    strand: Strand::Reverse
+                }
+            ]
+        );
+
+        s.topology = Topology::Circular;
+        assert_eq!(
+            s.find_sites(bamhi),
+            vec![Site {
+                start: 29,
+                strand: Strand::Forward
+            }]
+        );
+        let unique = s.unique_sites(&[ecori, bamhi, kpni, nonpal, b"TTGAA"]);
+        let found: Vec<_> = unique.iter().map(|u| u.0).collect();
+        assert_eq!(found, vec![ecori, bamhi, kpni, nonpal]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Mapping between INSDC feature keys and Sequence Ontology terms, as used
+//! for the type column of GFF3.
+
+use crate::seq::FeatureKind;
+
+/// A Sequence Ontology term
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct SoTerm {
+    /// e.g. `SO:0000316`
+    pub accession: &'static str,
+    /// e.g. `CDS`
+    pub name: &'static str,
+}
+
+// Where several feature keys map to the same term, the first one listed is
+// the one the reverse mapping returns.
+const TERMS: &[(&str, &str, &str)] = &[
+    ("3'UTR", "SO:0000205", "three_prime_UTR"),
+    ("5'UTR", "SO:0000204", "five_prime_UTR"),
+    ("gap", "SO:0000730", "gap"),
+    ("assembly_gap", "SO:0000730", "gap"),
+    ("C_region", "SO:0001834", "C_region"),
+    ("CDS", "SO:0000316", "CDS"),
+    ("centromere", "SO:0000577", "centromere"),
+    ("D-loop", "SO:0000297", "D_loop"),
+    ("D_segment", "SO:0000458", "D_gene_segment"),
+    ("exon", "SO:0000147", "exon"),
+    ("gene", "SO:0000704", "gene"),
+    ("iDNA", "SO:0000723", "iDNA"),
+    ("intron", "SO:0000188", "intron"),
+    ("J_segment", "SO:0000470", "J_gene_segment"),
+    ("mat_peptide", "SO:0000419", "mature_protein_region"),
+    ("misc_binding", "SO:0000409", "binding_site"),
+    ("misc_difference", "SO:0000413", "sequence_difference"),
+    ("misc_feature", "SO:0001411", "biological_region"),
+    ("misc_recomb", "SO:0000298", "recombination_feature"),
+    ("misc_RNA", "SO:0000673", "transcript"),
+    (
+        "misc_structure",
+        "SO:0000002",
+        "sequence_secondary_structure",
+    ),
+    ("mobile_element", "SO:0001037", "mobile_genetic_element"),
+    ("modified_base", "SO:0000305", "modified_DNA_base"),
+    ("mRNA", "SO:0000234", "mRNA"),
+    ("N_region", "SO:0001835", "N_region"),
+    ("ncRNA", "SO:0000655", "ncRNA"),
+    ("old_sequence", "SO:0000413", "sequence_difference"),
+    ("operon", "SO:0000178", "operon"),
+    ("oriT", "SO:0000724", "oriT"),
+    ("polyA_site", "SO:0000553", "polyA_site"),
+    ("prim_transcript", "SO:0000185", "primary_transcript"),
+    ("precursor_RNA", "SO:0000185", "primary_transcript"),
+    ("primer_bind", "SO:0005850", "primer_binding_site"),
+    ("propeptide", "SO:0001062", "propeptide"),
+    ("protein_bind", "SO:0000410", "protein_binding_site"),
+    ("regulatory", "SO:0005836", "regulatory_region"),
+    ("rep_origin", "SO:0000296", "origin_of_replication"),
+    ("repeat_region", "SO:0000657", "repeat_region"),
+    ("rRNA", "SO:0000252", "rRNA"),
+    ("S_region", "SO:0001836", "S_region"),
+    ("sig_peptide", "SO:0000418", "signal_peptide"),
+    ("source", "SO:0000001", "region"),
+    ("stem_loop", "SO:0000313", "stem_loop"),
+    ("STS", "SO:0000331", "STS"),
+    ("telomere", "SO:0000624", "telomere"),
+    ("tmRNA", "SO:0000584", "tmRNA"),
+    ("transit_peptide", "SO:0000725", "transit_peptide"),
+    ("tRNA", "SO:0000253", "tRNA"),
+    ("unsure", "SO:0001086", "sequence_uncertainty"),
+    ("V_region", "SO:0001833", "V_region"),
+    ("V_segment", "SO:0000466", "V_gene_segment"),
+    ("variation", "SO:0001060", "sequence_variant"),
+];
+
+// Keys retired from the feature table which are still common in older
+// records. These are only used when mapping to SO; the reverse mapping
+// never produces them.
+const LEGACY_TERMS: &[(&str, &str, &str)] = &[
+    ("enhancer", "SO:0000165", "enhancer"),
+    ("polyA_signal", "SO:0000551", "polyA_signal_sequence"),
+    ("promoter", "SO:0000167", "promoter"),
+    ("RBS", "SO:0000139", "ribosome_entry_site"),
+    ("terminator", "SO:0000141", "terminator"),
+];
+
+/// Returns the SO term for a feature key, or `None` if there isn't a known
+/// mapping
+pub fn term_for(kind: &FeatureKind) -> Option<SoTerm> {
+    TERMS
+        .iter()
+        .chain(LEGACY_TERMS)
+        .find(|t| t.0 == &**kind)
+        .map(|&(_, accession, name)| SoTerm { accession, name })
+}
+
+/// Returns the feature key for an SO term, given either its accession
+/// (`SO:0000316`) or its name (`CDS`)
+pub fn kind_for(term: &str) -> Option<FeatureKind> {
+    TERMS
+        .iter()
+        .find(|t| t.1 == term || t.2 == term)
+        .map(|t| t.0.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::insdc::feature_kinds;
+
+    #[test]
+    fn mapping() {
+        let cds = term_for(&feature_kinds::CDS).unwrap();
+        assert_eq!(cds.accession, "SO:0000316");
+        assert_eq!(cds.name, "CDS");
+        assert_eq!(kind_for("SO:0000316"), Some(feature_kinds::CDS));
+        assert_eq!(
+            kind_for("three_prime_UTR"),
+            Some(feature_kinds::THREE_PRIME_UTR)
+        );
+        assert_eq!(kind_for("SO:0000185"), Some(feature_kinds::PRIM_TRANSCRIPT));
+        assert_eq!(
+            term_for(&"promoter".into()).unwrap().accession,
+            "SO:0000167"
+        );
+        assert_eq!(kind_for("promoter"), None);
+        assert_eq!(term_for(&"my_feature".into()), None);
+        for kind in feature_kinds::ALL {
+            assert!(term_for(kind).is_some(), "{}", kind);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Summary statistics over the features of a record, as used for genome
+//! QC, and estimates of how much memory records use.
+
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+use crate::seq::{
+    AccessionRange, Division, Feature, FeatureKind, Location, MoleculeType, Provenance,
+    QualifierKey, Seq,
+};
+
+/// Statistics for a single feature kind
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct KindStats {
+    /// Number of features of this kind
+    pub count: usize,
+    /// Number of bases covered by at least one feature of this kind.
+    /// Overlapping features are only counted once.
+    pub bases_covered: i64,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct FeatureStats {
+    pub kinds: BTreeMap<FeatureKind, KindStats>,
+    /// Fraction of the sequence covered by CDS features
+    pub coding_density: f64,
+    /// Mean length of `gene` features, `None` if there aren't any
+    pub mean_gene_length: Option<f64>,
+}
+
+/// Estimated heap usage of a record in bytes, see `Seq::memory_footprint`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct MemoryFootprint {
+    pub sequence: usize,
+    /// The features themselves and their locations, IDs and provenance
+    pub features: usize,
+    /// Qualifier keys and values. Keys are interned, so only the space
+    /// taken in each feature's list of qualifiers is counted.
+    pub qualifiers: usize,
+    /// Header fields, references and comments
+    pub header: usize,
+}
+
+impl MemoryFootprint {
+    pub fn total(&self) -> usize {
+        self.sequence + self.features + self.qualifiers + self.header
+    }
+}
+
+fn option_size(s: &Option<String>) -> usize {
+    s.as_ref().map_or(0, String::capacity)
+}
+
+fn location_size(l: &Location) -> usize {
+    use Location::*;
+    let many = |ls: &Vec<Location>| {
+        ls.capacity() * size_of::<Location>() + ls.iter().map(location_size).sum::<usize>()
+    };
+    match l {
+        Complement(l) => size_of::<Location>() + location_size(l),
+        Join(ls) | Order(ls) | Bond(ls) | OneOf(ls) => many(ls),
+        External(name, l) => {
+            name.capacity()
+                + l.as_ref()
+                    .map_or(0, |l| size_of::<Location>() + location_size(l))
+        }
+        Range(..) | Between(..) | Gap(_) => 0,
+    }
+}
+
+fn feature_size(f: &Feature) -> usize {
+    let provenance = match f.provenance {
+        Some(Provenance::Parsed {
+            source: Some(ref s),
+            ..
+        }) => s.capacity(),
+        Some(Provenance::Generated(ref s)) => s.capacity(),
+        _ => 0,
+    };
+    let spelling = f.original_spelling.as_ref().map_or(0, |s| {
+        option_size(&s.kind)
+            + s.qualifiers.capacity() * size_of::<(usize, String)>()
+            + s.qualifiers.iter().map(|(_, k)| k.capacity()).sum::<usize>()
+    });
+    location_size(&f.location) + option_size(&f.id) + provenance + spelling
+}
+
+/// Total length of the union of `ranges`
+fn union_len(mut ranges: Vec<(i64, i64)>) -> i64 {
+    ranges.sort_unstable();
+    let mut total = 0;
+    let mut current: Option<(i64, i64)> = None;
+    for (a, b) in ranges {
+        current = match current {
+            Some((x, y)) if a <= y => Some((x, y.max(b))),
+            Some((x, y)) => {
+                total += y - x;
+                Some((a, b))
+            }
+            None => Some((a, b)),
+        };
+    }
+    total + current.map_or(0, |(x, y)| y - x)
+}
+
+impl Seq {
+    /// Estimates how much heap memory the record uses, by part. This
+    /// includes spare capacity, but not the overhead of the allocator, nor
+    /// interned feature kinds and qualifier keys, which are shared by all
+    /// records.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let features = self.features.capacity() * size_of::<Feature>()
+            + self.features.iter().map(feature_size).sum::<usize>();
+        let qualifiers = self
+            .features
+            .iter()
+            .map(|f| {
+                f.qualifiers.capacity() * size_of::<(QualifierKey, Option<String>)>()
+                    + f.qualifiers
+                        .iter()
+                        .map(|(_, v)| v.as_ref().map_or(0, String::capacity))
+                        .sum::<usize>()
+            })
+            .sum();
+        let references: usize = self
+            .references
+            .iter()
+            .map(|r| {
+                r.description.capacity()
+                    + r.bases.capacity() * size_of::<(i64, i64)>()
+                    + r.title.capacity()
+                    + option_size(&r.authors)
+                    + option_size(&r.consortium)
+                    + option_size(&r.journal)
+                    + option_size(&r.pubmed)
+                    + option_size(&r.remark)
+            })
+            .sum();
+        let source = self
+            .source
+            .as_ref()
+            .map_or(0, |s| s.source.capacity() + option_size(&s.organism));
+        let header = option_size(&self.name)
+            + match self.molecule_type {
+                Some(MoleculeType::Other(ref m)) => m.capacity(),
+                _ => 0,
+            }
+            + match self.division {
+                Division::Unknown(ref d) => d.capacity(),
+                _ => 0,
+            }
+            + option_size(&self.definition)
+            + option_size(&self.accession)
+            + option_size(&self.version)
+            + option_size(&self.nid)
+            + option_size(&self.dblink)
+            + option_size(&self.dbsource)
+            + option_size(&self.keywords)
+            + option_size(&self.segment)
+            + source
+            + self.references.capacity() * size_of::<crate::seq::Reference>()
+            + references
+            + self.comments.capacity() * size_of::<String>()
+            + self.comments.iter().map(String::capacity).sum::<usize>()
+            + self.contig.as_ref().map_or(0, location_size)
+            + [&self.wgs, &self.wgs_scafld, &self.tsa, &self.tls]
+                .iter()
+                .map(|ranges| {
+                    ranges.capacity() * size_of::<AccessionRange>()
+                        + ranges
+                            .iter()
+                            .map(|r| r.first.capacity() + r.last.capacity())
+                            .sum::<usize>()
+                })
+                .sum::<usize>();
+        MemoryFootprint {
+            sequence: self.seq.capacity(),
+            features,
+            qualifiers,
+            header,
+        }
+    }
+
+    /// Counts features and the bases they cover, per kind. Features whose
+    /// location can't be resolved to ranges on this sequence are counted
+    /// but don't contribute to coverage or gene length.
+    pub fn feature_stats(&self) -> FeatureStats {
+        let mut ranges: BTreeMap<FeatureKind, Vec<(i64, i64)>> = BTreeMap::new();
+        let mut kinds: BTreeMap<FeatureKind, KindStats> = BTreeMap::new();
+        let mut gene_lengths = Vec::new();
+        for f in &self.features {
+            kinds.entry(f.kind.clone()).or_default().count += 1;
+            match f.location.ranges() {
+                Ok(r) => {
+                    if f.kind == feature_kind!("gene") {
+                        gene_lengths.push(r.iter().map(|(a, b)| b - a).sum::<i64>());
+                    }
+                    ranges.entry(f.kind.clone()).or_default().extend(r);
+                }
+                Err(e) => debug!("Not counting coverage of feature: {}", e),
+            }
+        }
+        for (kind, r) in ranges {
+            kinds.get_mut(&kind).unwrap().bases_covered = union_len(r);
+        }
+        let coding = kinds
+            .get(&feature_kind!("CDS"))
+            .map_or(0, |k| k.bases_covered);
+        FeatureStats {
+            kinds,
+            coding_density: if self.len() > 0 {
+                coding as f64 / self.len() as f64
+            } else {
+                0.0
+            },
+            mean_gene_length: if gene_lengths.is_empty() {
+                None
+            } else {
+                Some(gene_lengths.iter().sum::<i64>() as f64 / gene_lengths.len() as f64)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::{Feature, Location};
+
+    #[test]
+    fn test_union_len() {
+        assert_eq!(union_len(vec![]), 0);
+        assert_eq!(union_len(vec![(0, 5), (3, 8), (10, 12)]), 10);
+        assert_eq!(union_len(vec![(10, 12), (0, 5), (5, 6)]), 8);
+    }
+
+    #[test]
+    fn stats() {
+        let f = |kind: FeatureKind, l: &str| {
+            Feature::new(kind, Location::from_gb_format(l).unwrap(), Vec::new())
+        };
+        let s = Seq {
+            seq: vec![b'a'; 100],
+            features: vec![
+                f(feature_kind!("gene"), "1..30"),
+                f(feature_kind!("CDS"), "1..30"),
+                f(feature_kind!("gene"), "complement(21..60)"),
+                f(feature_kind!("CDS"), "complement(join(21..30,41..60))"),
+                f(feature_kind!("misc_feature"), "one-of(1,5)"),
+            ],
+            ..Seq::empty()
+        };
+        let stats = s.feature_stats();
+        assert_eq!(
+            stats.kinds[&feature_kind!("CDS")],
+            KindStats {
+                count: 2,
+                bases_covered: 50
+            }
+        );
+        assert_eq!(stats.kinds[&feature_kind!("gene")].bases_covered, 60);
+        assert_eq!(stats.kinds[&feature_kind!("misc_feature")].count, 1);
+        assert_eq!(stats.coding_density, 0.5);
+        assert_eq!(stats.mean_gene_length, Some(35.0));
+    }
+
+    #[test]
+    fn memory_footprint() {
+        let mut seq = Seq {
+            seq: Vec::with_capacity(100),
+            definition: Some("definition".into()),
+            features: vec![Feature::new(
+                feature_kind!("gene"),
+                Location::from_gb_format("join(1..2,5..6)").unwrap(),
+                vec![(qualifier_key!("gene"), Some("abc".into()))],
+            )],
+            ..Seq::empty()
+        };
+        seq.features.shrink_to_fit();
+        if let Location::Join(ref mut parts) = seq.features[0].location {
+            parts.shrink_to_fit();
+        }
+        let footprint = seq.memory_footprint();
+        assert_eq!(footprint.sequence, 100);
+        assert_eq!(
+            footprint.features,
+            size_of::<Feature>() + 2 * size_of::<Location>()
+        );
+        assert_eq!(
+            footprint.qualifiers,
+            size_of::<(QualifierKey, Option<String>)>() + 3
+        );
+        assert_eq!(footprint.header, "definition".len() + "UNK".len());
+        assert_eq!(footprint.total(), 100 + footprint.features + footprint.qualifiers + 13);
+    }
+}
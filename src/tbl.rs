@@ -0,0 +1,309 @@
+//! Reading the 5-column feature tables (`.tbl` files) used by `table2asn`
+//! and Sequin, which annotation pipelines often produce alongside a FASTA
+//! file of the sequences.
+
+use std::io::BufRead;
+
+use crate::errors::GbParserError;
+use crate::seq::{After, Before, Feature, FeatureKind, Location, QualifierKey, Seq};
+
+/// The features given for one sequence, from its `>Feature` line to the
+/// next one
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct FeatureTable {
+    /// The sequence id from the `>Feature` line, e.g. `lcl|contig1`
+    pub seq_id: String,
+    /// The name after the id, if any
+    pub table_name: Option<String>,
+    pub features: Vec<Feature>,
+}
+
+impl FeatureTable {
+    /// Whether the table is for `seq`, i.e. its id is the name, accession
+    /// or version of `seq`, with or without a leading `lcl|`
+    pub fn matches(&self, seq: &Seq) -> bool {
+        let id = self.seq_id.strip_prefix("lcl|").unwrap_or(&self.seq_id);
+        [&seq.name, &seq.accession, &seq.version]
+            .iter()
+            .any(|s| s.as_deref() == Some(id) || s.as_deref() == Some(&self.seq_id))
+    }
+}
+
+/// Reads the tables of a feature table file one at a time. Each feature
+/// starts with a line giving its first interval and key, e.g.
+/// `<1\t>1050\tgene`, followed by any more intervals and then its
+/// qualifiers, e.g. `\t\t\tgene\tadhA`. Intervals given from the higher
+/// position to the lower are on the minus strand, and `<` and `>` mark
+/// either end as partial. `[offset=n]` lines add `n` to the positions
+/// after them. Blank lines are ignored.
+#[derive(Debug)]
+pub struct TblReader<T: BufRead> {
+    input: T,
+    // the `>Feature` line of the next table, once we've read it
+    header: Option<String>,
+    line_number: usize,
+}
+
+impl<T: BufRead> TblReader<T> {
+    pub fn new(input: T) -> TblReader<T> {
+        TblReader {
+            input,
+            header: None,
+            line_number: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> GbParserError {
+        GbParserError::SyntaxError(format!("Line {}: {}", self.line_number, message))
+    }
+
+    fn read_table(&mut self, header: &str) -> Result<FeatureTable, GbParserError> {
+        let mut words = header.split_whitespace();
+        let mut table = FeatureTable {
+            seq_id: words.next().unwrap_or_default().to_string(),
+            table_name: words.next().map(String::from),
+            features: Vec::new(),
+        };
+        let mut feature: Option<PendingFeature> = None;
+        let mut offset = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.input.read_line(&mut line)? == 0 {
+                break;
+            }
+            self.line_number += 1;
+            let line = line.trim_end_matches(['\r', '\n']);
+            if let Some(header) = line.strip_prefix(">Feature") {
+                self.header = Some(header.to_string());
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(n) = line
+                .trim()
+                .strip_prefix("[offset=")
+                .and_then(|l| l.strip_suffix(']'))
+            {
+                offset = n.parse().map_err(|_| self.error("Invalid offset"))?;
+                continue;
+            }
+            let columns: Vec<_> = line.split('\t').collect();
+            if !columns[0].trim().is_empty() {
+                let stop = columns.get(1).copied().unwrap_or_default();
+                let interval = interval(columns[0].trim(), stop.trim(), offset)
+                    .ok_or_else(|| self.error("Invalid interval"))?;
+                match columns.get(2).map(|k| k.trim()).filter(|k| !k.is_empty()) {
+                    Some(kind) => {
+                        table
+                            .features
+                            .extend(feature.take().map(PendingFeature::finish));
+                        feature = Some(PendingFeature {
+                            kind: kind.into(),
+                            intervals: vec![interval],
+                            qualifiers: Vec::new(),
+                        });
+                    }
+                    None => match feature {
+                        Some(ref mut f) if f.qualifiers.is_empty() => f.intervals.push(interval),
+                        _ => return Err(self.error("Interval without a feature")),
+                    },
+                }
+            } else {
+                let mut columns = columns.into_iter().skip_while(|c| c.trim().is_empty());
+                let key = columns.next().unwrap_or_default().trim();
+                let value = columns.collect::<Vec<_>>().join("\t");
+                let value = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+                match feature {
+                    Some(ref mut f) => f.qualifiers.push((key.into(), value)),
+                    None => return Err(self.error("Qualifier without a feature")),
+                }
+            }
+        }
+        table.features.extend(feature.map(PendingFeature::finish));
+        Ok(table)
+    }
+}
+
+impl<T: BufRead> Iterator for TblReader<T> {
+    type Item = Result<FeatureTable, GbParserError>;
+
+    fn next(&mut self) -> Option<Result<FeatureTable, GbParserError>> {
+        let header = match self.header.take() {
+            Some(h) => h,
+            None => {
+                // find the first table, skipping blank lines
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    match self.input.read_line(&mut line) {
+                        Ok(0) => return None,
+                        Ok(_) => self.line_number += 1,
+                        Err(e) => return Some(Err(e.into())),
+                    }
+                    if let Some(header) = line.strip_prefix(">Feature") {
+                        break header.trim_end().to_string();
+                    }
+                    if !line.trim().is_empty() {
+                        return Some(Err(self.error("Expected a >Feature line")));
+                    }
+                }
+            }
+        };
+        Some(self.read_table(&header))
+    }
+}
+
+/// Parse a whole feature table file, see `TblReader`
+pub fn parse_tbl(data: &[u8]) -> Result<Vec<FeatureTable>, GbParserError> {
+    TblReader::new(data).collect()
+}
+
+/// Adds the features of each table to the record it's for (see
+/// `FeatureTable::matches`), e.g. the records of the FASTA file that came
+/// with it. Returns the tables which aren't for any of the records.
+pub fn merge_feature_tables(seqs: &mut [Seq], tables: Vec<FeatureTable>) -> Vec<FeatureTable> {
+    let mut unmatched = Vec::new();
+    for table in tables {
+        match seqs.iter_mut().find(|s| table.matches(s)) {
+            Some(seq) => seq.merge_feature_table(table),
+            None => unmatched.push(table),
+        }
+    }
+    unmatched
+}
+
+impl Seq {
+    /// Adds the features of `table` after those already there
+    pub fn merge_feature_table(&mut self, table: FeatureTable) {
+        self.features.extend(table.features);
+    }
+}
+
+struct PendingFeature {
+    kind: FeatureKind,
+    intervals: Vec<Location>,
+    qualifiers: Vec<(QualifierKey, Option<String>)>,
+}
+
+impl PendingFeature {
+    fn finish(mut self) -> Feature {
+        let location = if self.intervals.len() == 1 {
+            self.intervals.pop().unwrap()
+        } else if self
+            .intervals
+            .iter()
+            .all(|l| matches!(l, Location::Complement(_)))
+        {
+            // minus strand intervals are listed 5' to 3', i.e. backwards
+            let parts = self.intervals.into_iter().rev().map(|l| match l {
+                Location::Complement(l) => *l,
+                l => l,
+            });
+            Location::Complement(Box::new(Location::Join(parts.collect())))
+        } else {
+            Location::Join(self.intervals)
+        };
+        Feature::new(self.kind, location, self.qualifiers)
+    }
+}
+
+/// The location of an interval given by its start and stop columns, e.g.
+/// `<1` and `1050`, or `1050` and `1` on the minus strand. `5^` and `6`
+/// give the site between 5 and 6.
+fn interval(start: &str, stop: &str, offset: i64) -> Option<Location> {
+    let position = |s: &str| -> Option<(i64, bool)> {
+        let partial = s.starts_with(['<', '>']);
+        let n: i64 = s.trim_start_matches(['<', '>']).parse().ok()?;
+        Some((n + offset, partial))
+    };
+    if let Some(start) = start.strip_suffix('^') {
+        let (a, _) = position(start)?;
+        let (b, _) = position(stop)?;
+        return Some(Location::Between(a - 1, b - 1));
+    }
+    let (a, partial_a) = position(start)?;
+    let (b, partial_b) = position(stop)?;
+    Some(if a <= b {
+        Location::Range((a - 1, Before(partial_a)), (b, After(partial_b)))
+    } else {
+        let range = Location::Range((b - 1, Before(partial_b)), (a, After(partial_a)));
+        Location::Complement(Box::new(range))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fasta::parse_fasta;
+
+    #[test]
+    fn read_tbl() {
+        let data = b">Feature lcl|contig1 Table1
+<1\t>1050\tgene
+\t\t\tgene\tadhA
+1\t600\tCDS
+700\t1050
+\t\t\tproduct\talcohol dehydrogenase
+\t\t\tpseudo
+
+>Feature contig2
+[offset=100]
+500\t401\tCDS
+300\t<201
+\t\t\tnote\tminus
+10^\t11\tmisc_feature
+";
+        let tables = parse_tbl(&data[..]).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].seq_id, "lcl|contig1");
+        assert_eq!(tables[0].table_name.as_deref(), Some("Table1"));
+        let locations = |t: &FeatureTable| -> Vec<_> {
+            t.features
+                .iter()
+                .map(|f| (f.kind.to_string(), f.location.to_gb_format()))
+                .collect()
+        };
+        assert_eq!(
+            locations(&tables[0]),
+            vec![
+                ("gene".to_string(), "<1..>1050".to_string()),
+                ("CDS".to_string(), "join(1..600,700..1050)".to_string()),
+            ]
+        );
+        let cds = &tables[0].features[1];
+        assert_eq!(
+            cds.qualifiers,
+            vec![
+                (
+                    qualifier_key!("product"),
+                    Some("alcohol dehydrogenase".to_string())
+                ),
+                (qualifier_key!("pseudo"), None),
+            ]
+        );
+        assert_eq!(
+            locations(&tables[1]),
+            vec![
+                (
+                    "CDS".to_string(),
+                    "complement(join(<301..400,501..600))".to_string()
+                ),
+                ("misc_feature".to_string(), "110^111".to_string()),
+            ]
+        );
+
+        let mut seqs = parse_fasta(b">contig1\nacgt\n>contig3\nacgt\n").unwrap();
+        let unmatched = merge_feature_tables(&mut seqs, tables);
+        assert_eq!(seqs[0].features.len(), 2);
+        assert!(seqs[1].features.is_empty());
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].seq_id, "contig2");
+
+        assert!(parse_tbl(b">Feature x\n\t\t\tnote\tx\n").is_err());
+        assert!(parse_tbl(b"1\t2\tgene\n").is_err());
+        assert!(parse_tbl(b">Feature x\n1\tfoo\tgene\n").is_err());
+    }
+}
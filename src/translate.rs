@@ -0,0 +1,607 @@
+//! Translation of coding sequences using the NCBI genetic codes, or custom
+//! ones registered in a `GeneticCodes`.
+
+use std::borrow::Cow;
+
+use crate::seq::{Feature, LocationError, Seq};
+
+/// A genetic code, either one of those listed by NCBI, as referred to by the
+/// `/transl_table` qualifier, or a custom one
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GeneticCode {
+    pub id: u8,
+    pub name: Cow<'static, str>,
+    /// The amino acid for each codon, with codons in the order TTT, TTC,
+    /// TTA, TTG, TCT, ... GGG. Stops are `*`.
+    pub amino_acids: [u8; 64],
+    /// `M` for each codon which can be used as an initiator
+    pub starts: [u8; 64],
+}
+
+static CODES: &[GeneticCode] = &[
+    GeneticCode {
+        id: 1,
+        name: Cow::Borrowed("Standard"),
+        amino_acids: *b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"---M------**--*----M---------------M----------------------------",
+    },
+    GeneticCode {
+        id: 2,
+        name: Cow::Borrowed("Vertebrate Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+        starts: *b"----------**--------------------MMMM----------**---M------------",
+    },
+    GeneticCode {
+        id: 3,
+        name: Cow::Borrowed("Yeast Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"----------**----------------------MM---------------M------------",
+    },
+    GeneticCode {
+        id: 4,
+        name: Cow::Borrowed("Mold, Protozoan, and Coelenterate Mitochondrial and Mycoplasma/Spiroplasma"),
+        amino_acids: *b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"--MM------**-------M------------MMMM---------------M------------",
+    },
+    GeneticCode {
+        id: 5,
+        name: Cow::Borrowed("Invertebrate Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+        starts: *b"---M------**--------------------MMMM---------------M------------",
+    },
+    GeneticCode {
+        id: 6,
+        name: Cow::Borrowed("Ciliate, Dasycladacean and Hexamita Nuclear"),
+        amino_acids: *b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"--------------*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 9,
+        name: Cow::Borrowed("Echinoderm and Flatworm Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts: *b"----------**-----------------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 10,
+        name: Cow::Borrowed("Euplotid Nuclear"),
+        amino_acids: *b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"----------**-----------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 11,
+        name: Cow::Borrowed("Bacterial, Archaeal and Plant Plastid"),
+        amino_acids: *b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"---M------**--*----M------------MMMM---------------M------------",
+    },
+    GeneticCode {
+        id: 12,
+        name: Cow::Borrowed("Alternative Yeast Nuclear"),
+        amino_acids: *b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"----------**--*----M---------------M----------------------------",
+    },
+    GeneticCode {
+        id: 13,
+        name: Cow::Borrowed("Ascidian Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+        starts: *b"---M------**----------------------MM---------------M------------",
+    },
+    GeneticCode {
+        id: 14,
+        name: Cow::Borrowed("Alternative Flatworm Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts: *b"-----------*-----------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 16,
+        name: Cow::Borrowed("Chlorophycean Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"----------*---*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 21,
+        name: Cow::Borrowed("Trematode Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts: *b"----------**-----------------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 22,
+        name: Cow::Borrowed("Scenedesmus obliquus Mitochondrial"),
+        amino_acids: *b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"------*---*---*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 23,
+        name: Cow::Borrowed("Thraustochytrium Mitochondrial"),
+        amino_acids: *b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"--*-------**--*-----------------M--M---------------M------------",
+    },
+    GeneticCode {
+        id: 24,
+        name: Cow::Borrowed("Rhabdopleuridae Mitochondrial"),
+        amino_acids: *b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        starts: *b"---M------**-------M---------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 25,
+        name: Cow::Borrowed("Candidate Division SR1 and Gracilibacteria"),
+        amino_acids: *b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"---M------**-----------------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 26,
+        name: Cow::Borrowed("Pachysolen tannophilus Nuclear"),
+        amino_acids: *b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: *b"----------**--*----M---------------M----------------------------",
+    },
+];
+
+fn base_index(b: u8) -> Option<usize> {
+    match b {
+        b'T' | b't' | b'U' | b'u' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'A' | b'a' => Some(2),
+        b'G' | b'g' => Some(3),
+        _ => None,
+    }
+}
+
+fn codon_index(codon: &[u8]) -> Option<usize> {
+    Some(base_index(codon[0])? * 16 + base_index(codon[1])? * 4 + base_index(codon[2])?)
+}
+
+impl GeneticCode {
+    /// The standard code, table 1
+    pub fn standard() -> &'static GeneticCode {
+        &CODES[0]
+    }
+
+    /// Bacterial, archaeal and plant plastid code, table 11
+    pub fn bacterial() -> &'static GeneticCode {
+        GeneticCode::by_id(11).unwrap()
+    }
+
+    /// Looks up a code by its NCBI table number
+    pub fn by_id(id: u8) -> Option<&'static GeneticCode> {
+        CODES.iter().find(|c| c.id == id)
+    }
+
+    /// All the built-in codes
+    pub fn all() -> &'static [GeneticCode] {
+        CODES
+    }
+
+    /// Creates a custom code from the amino acid and start codon tables,
+    /// laid out as in `amino_acids` and `starts`. Returns `None` unless both
+    /// cover all 64 codons.
+    pub fn new<S>(id: u8, name: S, amino_acids: &[u8], starts: &[u8]) -> Option<GeneticCode>
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        let mut res = GeneticCode {
+            id,
+            name: name.into(),
+            amino_acids: [0; 64],
+            starts: [0; 64],
+        };
+        if amino_acids.len() != 64 || starts.len() != 64 {
+            return None;
+        }
+        res.amino_acids.copy_from_slice(amino_acids);
+        res.starts.copy_from_slice(starts);
+        Some(res)
+    }
+
+    /// Makes `codon` translate to `amino_acid`, e.g. `b'O'` for a TAG codon
+    /// reassigned to pyrrolysine. Returns `false` if `codon` isn't made of
+    /// unambiguous bases.
+    pub fn reassign(&mut self, codon: &[u8], amino_acid: u8) -> bool {
+        match codon_index(codon).filter(|_| codon.len() == 3) {
+            Some(i) => {
+                self.amino_acids[i] = amino_acid;
+                if self.starts[i] == b'*' && amino_acid != b'*' {
+                    self.starts[i] = b'-';
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Translates a single codon. Codons containing anything other than
+    /// unambiguous bases translate to `X`.
+    pub fn translate_codon(&self, codon: &[u8]) -> u8 {
+        assert_eq!(codon.len(), 3);
+        codon_index(codon).map_or(b'X', |i| self.amino_acids[i])
+    }
+
+    pub fn is_start(&self, codon: &[u8]) -> bool {
+        codon.len() == 3 && codon_index(codon).is_some_and(|i| self.starts[i] == b'M')
+    }
+
+    /// Translates `seq` codon by codon. Any trailing partial codon is
+    /// ignored. If `initiator` is true, an alternative start codon at the
+    /// beginning is translated as `M`.
+    pub fn translate(&self, seq: &[u8], initiator: bool) -> Vec<u8> {
+        let mut res: Vec<u8> = seq
+            .chunks_exact(3)
+            .map(|c| self.translate_codon(c))
+            .collect();
+        if initiator && self.is_start(&seq[..seq.len().min(3)]) {
+            res[0] = b'M';
+        }
+        res
+    }
+}
+
+/// A set of genetic codes to look up `/transl_table` in. It starts out with
+/// the built-in NCBI tables, to which custom codes can be added.
+#[derive(Debug, Clone)]
+pub struct GeneticCodes {
+    codes: Vec<GeneticCode>,
+}
+
+impl Default for GeneticCodes {
+    fn default() -> GeneticCodes {
+        GeneticCodes::new()
+    }
+}
+
+impl GeneticCodes {
+    /// The built-in codes
+    pub fn new() -> GeneticCodes {
+        GeneticCodes {
+            codes: CODES.to_vec(),
+        }
+    }
+
+    /// Adds a code, replacing and returning any existing code with the same
+    /// id
+    pub fn register(&mut self, code: GeneticCode) -> Option<GeneticCode> {
+        match self.codes.iter_mut().find(|c| c.id == code.id) {
+            Some(c) => Some(std::mem::replace(c, code)),
+            None => {
+                self.codes.push(code);
+                None
+            }
+        }
+    }
+
+    pub fn by_id(&self, id: u8) -> Option<&GeneticCode> {
+        self.codes.iter().find(|c| c.id == id)
+    }
+
+    /// Looks up a code by name, ignoring case
+    pub fn by_name(&self, name: &str) -> Option<&GeneticCode> {
+        self.codes.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Looks up a code by id if `s` is a number, or else by name
+    pub fn get(&self, s: &str) -> Option<&GeneticCode> {
+        let s = s.trim();
+        match s.parse() {
+            Ok(id) => self.by_id(id),
+            Err(_) => self.by_name(s),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GeneticCode> {
+        self.codes.iter()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TranslationError {
+    #[error(display = "{}", _0)]
+    Location(#[cause] LocationError),
+    #[error(display = "Unknown genetic code: {}", _0)]
+    UnknownCode(String),
+    #[error(display = "Invalid codon_start: {}", _0)]
+    InvalidCodonStart(String),
+    #[error(display = "Can't translate a protein sequence")]
+    Protein,
+}
+
+impl Feature {
+    /// The genetic code given by `/transl_table`, or the standard code if
+    /// there isn't one
+    pub fn genetic_code(&self) -> Result<&'static GeneticCode, TranslationError> {
+        match self.qualifier_values(qualifier_key!("transl_table")).next() {
+            None => Ok(GeneticCode::standard()),
+            Some(v) => v
+                .trim()
+                .parse()
+                .ok()
+                .and_then(GeneticCode::by_id)
+                .ok_or_else(|| TranslationError::UnknownCode(v.into())),
+        }
+    }
+
+    /// Like `genetic_code`, but `/transl_table` is looked up in `codes`,
+    /// either by id or by name
+    pub fn genetic_code_in<'a>(
+        &self,
+        codes: &'a GeneticCodes,
+    ) -> Result<&'a GeneticCode, TranslationError> {
+        match self.qualifier_values(qualifier_key!("transl_table")).next() {
+            None => Ok(codes.by_id(1).unwrap_or_else(|| GeneticCode::standard())),
+            Some(v) => codes
+                .get(v)
+                .ok_or_else(|| TranslationError::UnknownCode(v.into())),
+        }
+    }
+
+    /// The offset of the first complete codon, from `/codon_start`
+    pub fn codon_start(&self) -> Result<usize, TranslationError> {
+        match self.qualifier_values(qualifier_key!("codon_start")).next() {
+            None => Ok(0),
+            Some(v) => match v.trim() {
+                "1" => Ok(0),
+                "2" => Ok(1),
+                "3" => Ok(2),
+                _ => Err(TranslationError::InvalidCodonStart(v.into())),
+            },
+        }
+    }
+}
+
+impl Seq {
+    /// The conceptual translation of a CDS, using its `/transl_table` and
+    /// `/codon_start`. A terminal stop codon isn't included. Note that
+    /// `/transl_except` isn't taken into account.
+    pub fn translate_feature(&self, f: &Feature) -> Result<Vec<u8>, TranslationError> {
+        self.translate_with_code(f, f.genetic_code()?)
+    }
+
+    /// Like `translate_feature`, but looks up `/transl_table` in `codes`,
+    /// which may contain custom genetic codes
+    pub fn translate_feature_in(
+        &self,
+        f: &Feature,
+        codes: &GeneticCodes,
+    ) -> Result<Vec<u8>, TranslationError> {
+        self.translate_with_code(f, f.genetic_code_in(codes)?)
+    }
+
+    /// Sets `/translation` on every CDS to its conceptual translation, adding
+    /// it where it's missing and replacing it where it's out of date (say,
+    /// after editing the sequence). Pseudo CDSs are skipped, as are those
+    /// with `/transl_except`, which `translate_feature` doesn't handle, and
+    /// those which can't be translated (with a warning). Returns the number
+    /// of CDSs changed.
+    pub fn update_translations(&mut self) -> usize {
+        let mut changed = 0;
+        for i in 0..self.features.len() {
+            let f = &self.features[i];
+            if f.kind != feature_kind!("CDS")
+                || f.is_pseudo()
+                || f.qualifiers.iter().any(|(k, _)| *k == qualifier_key!("transl_except"))
+            {
+                continue;
+            }
+            let translation = match self.translate_feature(f) {
+                Ok(t) => String::from_utf8(t).unwrap(), // always ASCII
+                Err(e) => {
+                    warn!("Can't translate CDS {}: {}", f.location, e);
+                    continue;
+                }
+            };
+            let key = qualifier_key!("translation");
+            let f = &mut self.features[i];
+            match f.qualifiers.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => {
+                    let current: Option<String> = v
+                        .as_ref()
+                        .map(|v| v.chars().filter(|c| !c.is_whitespace()).collect());
+                    if current.as_ref() != Some(&translation) {
+                        *v = Some(translation);
+                        changed += 1;
+                    }
+                }
+                None => {
+                    f.qualifiers.push((key, Some(translation)));
+                    changed += 1;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Translates every CDS, other than pseudo ones, in parallel. Returns the
+    /// `stable_id` of each CDS with its translation, in the order of the
+    /// features. CDSs which can't be translated are skipped with a warning.
+    /// Needs the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    pub fn translate_all_cds(&self) -> Vec<(String, Vec<u8>)> {
+        use rayon::prelude::*;
+        self.features
+            .par_iter()
+            .filter(|f| f.kind == feature_kind!("CDS") && !f.is_pseudo())
+            .filter_map(|f| match self.translate_feature(f) {
+                Ok(protein) => Some((f.stable_id(), protein)),
+                Err(e) => {
+                    warn!("Can't translate CDS {}: {}", f.location, e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn translate_with_code(
+        &self,
+        f: &Feature,
+        code: &GeneticCode,
+    ) -> Result<Vec<u8>, TranslationError> {
+        if self.is_protein() {
+            return Err(TranslationError::Protein);
+        }
+        let start = f.codon_start()?;
+        let nt = self.extract_location(&f.location)?;
+        let initiator = start == 0 && !f.location.is_five_prime_partial();
+        let mut res = code.translate(nt.get(start..).unwrap_or(&[]), initiator);
+        if res.last() == Some(&b'*') && !f.location.is_three_prime_partial() {
+            res.pop();
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Location;
+
+    #[test]
+    fn codes() {
+        for code in GeneticCode::all() {
+            assert_eq!(code.translate_codon(b"ATG"), b'M');
+            assert!(code.is_start(b"ATG"));
+        }
+        let standard = GeneticCode::standard();
+        assert_eq!(standard.translate(b"ATGGCCTAA", true), b"MA*");
+        assert_eq!(standard.translate(b"TTGGCNTA", true), b"MX");
+        assert_eq!(standard.translate(b"TTGGCC", false), b"LA");
+        assert_eq!(
+            GeneticCode::by_id(2).unwrap().translate(b"TGAAGA", false),
+            b"W*"
+        );
+        assert!(GeneticCode::by_id(7).is_none());
+    }
+
+    #[test]
+    fn translate_feature() {
+        let f = |l: &str, q: Vec<(&str, &str)>| {
+            Feature::new(
+                feature_kind!("CDS"),
+                Location::from_gb_format(l).unwrap(),
+                q.into_iter()
+                    .map(|(k, v)| (k.into(), Some(v.into())))
+                    .collect(),
+            )
+        };
+        let seq = Seq {
+            seq: b"TTGAAATGACCCTTACGCAT".to_vec(),
+            ..Seq::empty()
+        };
+        assert_eq!(seq.translate_feature(&f("1..9", vec![])).unwrap(), b"MK");
+        assert_eq!(seq.translate_feature(&f("<1..9", vec![])).unwrap(), b"LK");
+        assert_eq!(seq.translate_feature(&f("1..>9", vec![])).unwrap(), b"MK*");
+        assert_eq!(
+            seq.translate_feature(&f("1..9", vec![("codon_start", "2")]))
+                .unwrap(),
+            b"*N"
+        );
+        assert_eq!(
+            seq.translate_feature(&f("1..9", vec![("transl_table", "2")]))
+                .unwrap(),
+            b"LKW"
+        );
+        // ATG CGT AAG GGT
+        assert_eq!(
+            seq.translate_feature(&f("complement(9..20)", vec![]))
+                .unwrap(),
+            b"MRKG"
+        );
+        assert!(seq
+            .translate_feature(&f("1..9", vec![("transl_table", "99")]))
+            .is_err());
+    }
+
+    #[test]
+    fn update_translations() {
+        let cds = |l: &str, q: Vec<(&str, &str)>| {
+            Feature::new(
+                feature_kind!("CDS"),
+                Location::from_gb_format(l).unwrap(),
+                q.into_iter()
+                    .map(|(k, v)| (k.into(), Some(v.into())))
+                    .collect(),
+            )
+        };
+        let mut seq = Seq {
+            seq: b"TTGAAATGACCCTTACGCAT".to_vec(),
+            features: vec![
+                cds("1..9", vec![]),
+                cds("complement(9..20)", vec![("translation", "MR\nKG")]),
+                cds("complement(9..20)", vec![("translation", "MRKA")]),
+                cds("1..9", vec![("pseudo", "")]),
+                cds("1..9", vec![("transl_table", "99")]),
+            ],
+            ..Seq::empty()
+        };
+        assert_eq!(seq.update_translations(), 2);
+        let translations: Vec<_> = seq
+            .features
+            .iter()
+            .map(|f| f.qualifier_values(qualifier_key!("translation")).next())
+            .collect();
+        assert_eq!(
+            translations,
+            vec![Some("MK"), Some("MR\nKG"), Some("MRKG"), None, None]
+        );
+        assert_eq!(seq.update_translations(), 0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn translate_all_cds() {
+        let cds = |l: &str, tag: &str| {
+            Feature::new(
+                feature_kind!("CDS"),
+                Location::from_gb_format(l).unwrap(),
+                vec![(qualifier_key!("locus_tag"), Some(tag.into()))],
+            )
+        };
+        let mut pseudo = cds("1..9", "t3");
+        pseudo.set_pseudo(true);
+        let seq = Seq {
+            seq: b"TTGAAATGACCCTTACGCAT".to_vec(),
+            features: vec![cds("1..9", "t1"), cds("complement(9..20)", "t2"), pseudo],
+            ..Seq::empty()
+        };
+        assert_eq!(
+            seq.translate_all_cds(),
+            vec![
+                ("CDS:t1".to_string(), b"MK".to_vec()),
+                ("CDS:t2".to_string(), b"MRKG".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn registry() {
+        let mut codes = GeneticCodes::new();
+        assert_eq!(codes.iter().count(), GeneticCode::all().len());
+        assert_eq!(codes.get("11"), GeneticCode::by_id(11));
+        assert_eq!(codes.get(" vertebrate mitochondrial").map(|c| c.id), Some(2));
+        assert!(codes.get("99").is_none());
+
+        // amber codon read as pyrrolysine
+        let mut amber = GeneticCode::standard().clone();
+        amber.id = 99;
+        amber.name = "Amber suppressor".into();
+        assert!(amber.reassign(b"TAG", b'O'));
+        assert!(!amber.reassign(b"TNG", b'O'));
+        assert!(codes.register(amber).is_none());
+        assert_eq!(codes.by_name("amber SUPPRESSOR").map(|c| c.id), Some(99));
+        assert!(GeneticCode::new(100, "Too short", b"FF", b"--").is_none());
+
+        let cds = Feature::new(
+            feature_kind!("CDS"),
+            Location::simple_range(0, 12),
+            vec![(qualifier_key!("transl_table"), Some("99".into()))],
+        );
+        let seq = Seq {
+            seq: b"ATGTAGAAATAA".to_vec(),
+            ..Seq::empty()
+        };
+        assert_eq!(seq.translate_feature_in(&cds, &codes).unwrap(), b"MOK");
+        assert!(seq.translate_feature(&cds).is_err());
+        let replaced = codes.register(GeneticCode {
+            id: 99,
+            name: "Amber as stop".into(),
+            ..GeneticCode::standard().clone()
+        });
+        assert_eq!(replaced.map(|c| c.name), Some("Amber suppressor".into()));
+        assert_eq!(seq.translate_feature_in(&cds, &codes).unwrap(), b"M*K");
+    }
+}
@@ -0,0 +1,137 @@
+//! Consistency checks on annotations, to catch records that would be
+//! rejected on submission, or that were corrupted by editing, before they're
+//! written out.
+
+use crate::diagnostics::Diagnostic;
+use crate::seq::{Feature, Seq};
+
+impl Seq {
+    /// Checks the record's features and returns the problems found. Nothing
+    /// is checked for protein records.
+    ///
+    /// For each CDS (other than pseudo ones), this checks that:
+    ///
+    /// * `/codon_start` is 1, 2 or 3, and is only something other than 1
+    ///   when the 5' end is partial
+    /// * the coding region is a whole number of codons, unless the 3' end is
+    ///   partial or there's a `/transl_except` (which may complete a stop
+    ///   codon)
+    /// * a complete CDS is long enough to hold a start and a stop codon
+    /// * `/translation` has as many residues as the location encodes
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut problems = Vec::new();
+        if self.is_protein() {
+            return problems;
+        }
+        for (i, f) in self.features.iter().enumerate() {
+            if f.kind == feature_kind!("CDS") && !f.is_pseudo() {
+                check_cds(i, f, &mut problems);
+            }
+        }
+        problems
+    }
+}
+
+fn check_cds(i: usize, f: &Feature, problems: &mut Vec<Diagnostic>) {
+    let five_prime_partial = f.location.is_five_prime_partial();
+    let three_prime_partial = f.location.is_three_prime_partial();
+    let offset = match f.codon_start() {
+        Ok(offset) => offset,
+        Err(e) => {
+            problems.push(Diagnostic::for_feature(i, e.to_string()));
+            return;
+        }
+    };
+    if offset != 0 && !five_prime_partial {
+        problems.push(Diagnostic::for_feature(
+            i,
+            format!("codon_start is {} but the 5' end isn't partial", offset + 1),
+        ));
+    }
+    let len: i64 = match f.location.ranges() {
+        Ok(ranges) => ranges.iter().map(|(a, b)| b - a).sum(),
+        Err(_) => return,
+    };
+    let coding = (len - offset as i64).max(0);
+    let transl_except = f
+        .qualifiers
+        .iter()
+        .any(|(k, _)| *k == qualifier_key!("transl_except"));
+    if coding % 3 != 0 && !three_prime_partial && !transl_except {
+        problems.push(Diagnostic::for_feature(
+            i,
+            format!(
+                "Coding length {} isn't a multiple of 3 but the 3' end isn't partial",
+                coding
+            ),
+        ));
+    }
+    if !five_prime_partial && !three_prime_partial && len < 6 {
+        problems.push(Diagnostic::for_feature(
+            i,
+            format!("Complete CDS of {} bp can't hold a start and a stop codon", len),
+        ));
+    }
+    if let Some(translation) = f.qualifier_values(qualifier_key!("translation")).next() {
+        let residues = translation.chars().filter(|c| !c.is_whitespace()).count() as i64;
+        // a complete CDS ends with a stop codon, which isn't translated
+        let expected = if three_prime_partial {
+            (coding + 2) / 3
+        } else {
+            (coding + 2) / 3 - 1
+        };
+        if residues != expected.max(0) {
+            problems.push(Diagnostic::for_feature(
+                i,
+                format!(
+                    "Translation has {} residues but the location encodes {}",
+                    residues,
+                    expected.max(0)
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seq::Location;
+
+    #[test]
+    fn cds_partialness() {
+        let cds = |l: &str, q: Vec<(&str, &str)>| {
+            Feature::new(
+                feature_kind!("CDS"),
+                Location::from_gb_format(l).unwrap(),
+                q.into_iter()
+                    .map(|(k, v)| (k.into(), Some(v.into())))
+                    .collect(),
+            )
+        };
+        let check = |f: Feature| {
+            let seq = Seq {
+                seq: b"atggccaaataggg".to_vec(),
+                features: vec![f],
+                ..Seq::empty()
+            };
+            seq.validate().len()
+        };
+        assert_eq!(check(cds("1..12", vec![("translation", "MAK")])), 0);
+        assert_eq!(check(cds("<2..12", vec![("codon_start", "3")])), 0);
+        assert_eq!(check(cds("<1..>11", vec![("translation", "MAKX")])), 0);
+        assert_eq!(check(cds("complement(<1..12)", vec![])), 0);
+        // codon_start without a partial 5' end
+        assert_eq!(check(cds("2..12", vec![("codon_start", "3")])), 1);
+        assert_eq!(check(cds("complement(<2..12)", vec![("codon_start", "3")])), 1);
+        assert_eq!(check(cds("1..12", vec![("codon_start", "4")])), 1);
+        // not a whole number of codons
+        assert_eq!(check(cds("1..11", vec![])), 1);
+        assert_eq!(check(cds("1..11", vec![("transl_except", "(pos:10..11,aa:TERM)")])), 0);
+        assert_eq!(check(cds("1..3", vec![])), 1);
+        assert_eq!(check(cds("1..12", vec![("translation", "MAKV")])), 1);
+        let mut pseudo = cds("1..11", vec![]);
+        pseudo.set_pseudo(true);
+        assert_eq!(check(pseudo), 0);
+    }
+}
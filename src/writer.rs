@@ -1,5 +1,9 @@
 use itertools::Itertools;
-use crate::seq::{Date, QualifierKey, Seq};
+use crate::display;
+use crate::seq::{Alphabet, BaseCount, Case, Date, MoleculeType, QualifierKey, Seq};
+use crate::insdc::{feature_kinds, qualifier_keys};
+use crate::seq::Fnv;
+use std::borrow::Cow;
 use std::convert::AsRef;
 use std::io::{self, Write};
 
@@ -7,6 +11,7 @@ use std::io::{self, Write};
 const MAX_WIDTH: usize = 79;
 const QUALIFIER_INDENT: &str = "                     ";
 const FIELD_INDENT: &str = "            ";
+const EMBL_QUALIFIER_INDENT: &str = "FT                   ";
 // Commented out some of these, need to check if they're still in
 // use
 const FTQUAL_NO_QUOTE: &[QualifierKey] = &[
@@ -33,11 +38,64 @@ const POS_QUAL: &[QualifierKey] = &[
 
 
 
+/// The longest name which fits in the LOCUS line's fixed columns, together
+/// with the sequence length and a space
+const LOCUS_NAME_WIDTH: usize = 28;
+
+/// What to do with names too long for the LOCUS line's fixed columns
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LocusNamePolicy {
+    /// Write the full name, shifting the rest of the line to the right.
+    /// NCBI accepts this for long names, and most parsers (including `gb-io`
+    /// and Biopython) can read it.
+    #[default]
+    Long,
+    /// Cut the name short to fit
+    Truncate,
+    /// Cut the name short and end it with `_` and a 6 digit hash of the full
+    /// name, so that distinct long names stay distinct
+    HashSuffix,
+    /// Fail with `io::ErrorKind::InvalidInput`
+    Error,
+}
+
+/// Which date to write in the LOCUS line
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub enum LocusDate {
+    /// The record's own date, or 01-JAN-1970 if it has none
+    #[default]
+    Keep,
+    /// The current date
+    Today,
+    Fixed(Date),
+}
+
+/// Which INSDC archive's conventions to follow
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Dialect {
+    /// NCBI GenBank
+    #[default]
+    Ncbi,
+    /// DDBJ, which is the same as GenBank but for a `BASE COUNT` line
+    /// before the sequence
+    Ddbj,
+    /// ENA, which uses the EMBL flat file format. Only nucleotide records can
+    /// be written this way.
+    Ena,
+}
+
 #[derive(Debug)]
 pub struct SeqWriter<W: Write> {
     stream: W,
-    truncate_locus: bool,
+    locus_name_policy: LocusNamePolicy,
+    recompute_length: bool,
+    locus_date: LocusDate,
+    update_translations: bool,
     escape_locus: bool,
+    rewrap_definition: bool,
+    dialect: Dialect,
+    sequence_case: Case,
+    display_dialect: Option<display::Dialect>,
 }
 
 impl<W: Write> SeqWriter<W> {
@@ -45,27 +103,94 @@ impl<W: Write> SeqWriter<W> {
     pub fn new(stream: W) -> Self {
         Self {
             stream,
-            truncate_locus: false,
+            locus_name_policy: LocusNamePolicy::Long,
+            recompute_length: false,
+            locus_date: LocusDate::Keep,
+            update_translations: false,
             escape_locus: true,
+            rewrap_definition: false,
+            dialect: Dialect::Ncbi,
+            sequence_case: Case::Mixed,
+            display_dialect: None,
         }
     }
 
     /// Set the behaviour regarding locus name truncation.
     ///
-    /// If `true` (the default when creating a `SeqWriter`, for backwards
-    /// compatibility), the locus fields (`name` and `molecule_type`) will be
+    /// If `true`, the locus fields (`name` and `molecule_type`) will be
     /// truncated if they are too long so that the LOCUS line is no longer
     /// than 79 characters.
     ///
-    /// By setting `truncate` to `false`, the full strings are written to the
-    /// LOCUS line, resulting in fields that may not in usual positions if
-    /// the name or molecule types are too long. Most parsers (including `gb-io`
-    /// and Biopython) should however be able to process these.
+    /// By setting `truncate` to `false` (the default), the full strings are
+    /// written to the LOCUS line, resulting in fields that may not in usual
+    /// positions if the name or molecule types are too long. Most parsers
+    /// (including `gb-io` and Biopython) should however be able to process
+    /// these.
+    ///
+    /// This is a shortcut for `locus_name_policy` with
+    /// `LocusNamePolicy::Truncate` or `LocusNamePolicy::Long`.
     pub fn truncate_locus(&mut self, truncate: bool) -> &mut Self {
-        self.truncate_locus = truncate;
+        self.locus_name_policy(if truncate {
+            LocusNamePolicy::Truncate
+        } else {
+            LocusNamePolicy::Long
+        })
+    }
+
+    /// Set what to do with names (and molecule types) too long for the
+    /// usual LOCUS line layout. With anything but `LocusNamePolicy::Long`,
+    /// a molecule type longer than 7 characters is left out, or is an error
+    /// with `LocusNamePolicy::Error`.
+    pub fn locus_name_policy(&mut self, policy: LocusNamePolicy) -> &mut Self {
+        self.locus_name_policy = policy;
+        self
+    }
+
+    /// Set whether the length in the LOCUS line is taken from the sequence
+    /// itself.
+    ///
+    /// If `false` (the default), `len` is written, and it's an error
+    /// (`io::ErrorKind::InvalidData`) for it to disagree with the sequence,
+    /// as it may after editing `seq` directly.
+    ///
+    /// If `true`, the length of the sequence is written instead. Records
+    /// without sequence data (such as CONTIG records) still use `len`.
+    pub fn recompute_length(&mut self, recompute: bool) -> &mut Self {
+        self.recompute_length = recompute;
+        self
+    }
+
+    /// Set which date is written in the LOCUS line, e.g. `LocusDate::Today`
+    /// to mark the record as modified
+    pub fn locus_date(&mut self, date: LocusDate) -> &mut Self {
+        self.locus_date = date;
+        self
+    }
+
+    /// Set whether `/translation` is (re)computed for every CDS before
+    /// writing, see `Seq::update_translations`. Defaults to `false`.
+    pub fn update_translations(&mut self, update: bool) -> &mut Self {
+        self.update_translations = update;
         self
     }
 
+    /// The length to write in the LOCUS line
+    fn locus_length(&self, record: &Seq) -> io::Result<usize> {
+        match record.len {
+            Some(len) if record.seq.is_empty() => Ok(len),
+            _ if self.recompute_length => Ok(record.seq.len()),
+            Some(len) if len != record.seq.len() => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "LOCUS length {} doesn't match the sequence length {}",
+                    len,
+                    record.seq.len()
+                ),
+            )),
+            _ => Ok(record.seq.len()),
+        }
+    }
+
     /// Set the behaviour regarding locus name escaping.
     ///
     /// If `true` (the default when creating a `SeqWriter`, for backwards
@@ -79,34 +204,110 @@ impl<W: Write> SeqWriter<W> {
         self
     }
 
+    /// Set the behaviour regarding DEFINITION line wrapping.
+    ///
+    /// If `false` (the default), any line breaks in the definition are kept,
+    /// so a parsed record is written back out with its original wrapping.
+    ///
+    /// If `true`, the definition is re-wrapped to fit the usual line width,
+    /// and a period is added to the end if it doesn't already have one, as
+    /// GenBank requires.
+    pub fn rewrap_definition(&mut self, rewrap: bool) -> &mut Self {
+        self.rewrap_definition = rewrap;
+        self
+    }
+
+    /// Set which archive's conventions are followed, `Dialect::Ncbi` by
+    /// default. With `Dialect::Ena`, the LOCUS line settings are ignored.
+    pub fn dialect(&mut self, dialect: Dialect) -> &mut Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Set the editor dialect that `Feature::display_hints` are written in.
+    /// Features with display hints have their display qualifiers replaced
+    /// with ones in `dialect`. If it isn't set, hints that still match the
+    /// qualifiers are left as they are, so a record can be read with
+    /// `SeqReader::read_display_hints` and written back unchanged, and other
+    /// hints are written in ApE's dialect.
+    pub fn display_dialect(&mut self, dialect: display::Dialect) -> &mut Self {
+        self.display_dialect = Some(dialect);
+        self
+    }
+
+    /// Set the case the sequence is written in. With `Case::Mixed` (the
+    /// default), it's written as it is, keeping any soft-masking. GenBank
+    /// files are normally in lower case.
+    pub fn sequence_case(&mut self, case: Case) -> &mut Self {
+        self.sequence_case = case;
+        self
+    }
+
+    /// The sequence in the case it's to be written in
+    fn cased_sequence<'a>(&self, record: &'a Seq) -> Cow<'a, [u8]> {
+        match self.sequence_case {
+            Case::Mixed => Cow::Borrowed(&record.seq),
+            Case::Lower => Cow::Owned(record.seq.to_ascii_lowercase()),
+            Case::Upper => Cow::Owned(record.seq.to_ascii_uppercase()),
+        }
+    }
+
     /// Generate the locus line for the record.
     ///
     /// Ported from Biopython (InsdcIO.py).
-    fn locus_line(&self, record: &Seq) -> String {
+    fn locus_line(&self, record: &Seq) -> io::Result<String> {
         let mut locus = record.name.clone().unwrap_or_else(|| {
             record
                 .accession
                 .clone()
                 .unwrap_or_else(|| "UNTITLED".into())
         });
-        let length = format!("{}", record.len());
-        if self.truncate_locus && locus.len() + 1 + length.len() > 28 {
-            locus = locus[..27 - length.len()].into();
+        let length = format!("{}", self.locus_length(record)?);
+        let width = (LOCUS_NAME_WIDTH - 1).saturating_sub(length.len());
+        if locus.len() > width {
+            match self.locus_name_policy {
+                LocusNamePolicy::Long => {}
+                LocusNamePolicy::Truncate => locus = truncated(&locus, width).into(),
+                LocusNamePolicy::HashSuffix => {
+                    let mut hash = Fnv::new();
+                    hash.write(locus.as_bytes());
+                    let suffix = format!("_{:06x}", hash.0 & 0xffffff);
+                    let keep = width.saturating_sub(suffix.len());
+                    locus = format!("{}{}", truncated(&locus, keep), suffix);
+                }
+                LocusNamePolicy::Error => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("LOCUS name is too long: {}", locus),
+                    ))
+                }
+            }
         }
 
         if self.escape_locus && locus.split_whitespace().count() > 1 {
             locus = locus.split_whitespace().join("_");
         }
 
-        let units = "bp"; // TODO: 'aa' support
+        let units = match record.alphabet {
+            Alphabet::Nucleotide => "bp",
+            Alphabet::Protein => "aa",
+        };
 
-        let mol_type = match record.molecule_type {
-            Some(ref m) if m.len() > 7 && self.truncate_locus => "",
-            Some(ref m) => m,
-            None => ""
+        let molecule_type = record.molecule_type.as_ref().map(MoleculeType::as_str);
+        let mol_type = match (molecule_type, self.locus_name_policy) {
+            (Some(m), LocusNamePolicy::Long) => m,
+            (Some(m), LocusNamePolicy::Error) if m.len() > 7 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("LOCUS molecule type is too long: {}", m),
+                ))
+            }
+            (Some(m), _) if m.len() > 7 => "",
+            (Some(m), _) => m,
+            (None, _) => ""
         };
 
-        if locus.len() + 1 + length.len() >= 28 {
+        if locus.len() + 1 + length.len() >= LOCUS_NAME_WIDTH {
             let length = format!(" {}", length);
             locus.push_str(&length);
         } else {
@@ -115,43 +316,76 @@ impl<W: Write> SeqWriter<W> {
             locus.push_str(rest);
         }
 
-        format!(
+        let date = match self.locus_date {
+            LocusDate::Keep => record
+                .date
+                .clone()
+                .unwrap_or_else(|| Date::from_ymd(1970, 1, 1).unwrap()),
+            LocusDate::Today => Date::today(),
+            LocusDate::Fixed(ref date) => date.clone(),
+        };
+
+        if !record.division.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid division: {:?}", record.division.as_str()),
+            ));
+        }
+
+        Ok(format!(
             "LOCUS       {} {}    {:<7} {:<8} {} {}\n",
             locus,
             units,
             mol_type,
             record.topology,
             record.division,
-            record
-                .date
-                .as_ref()
-                .unwrap_or(&Date::from_ymd(1970, 1, 1).unwrap())
-        )
+            date
+        ))
     }
 
     /// Write the sequence to the stream.
     pub fn write(&mut self, record: &Seq) -> io::Result<()> {
+        let updated;
+        let record = if self.update_translations {
+            let mut r = record.clone();
+            r.update_translations();
+            updated = r;
+            &updated
+        } else {
+            record
+        };
+
+        if self.dialect == Dialect::Ena {
+            return self.write_embl(record);
+        }
+
         // LOCUS
 
-        let locus_line = self.locus_line(record);
+        let locus_line = self.locus_line(record)?;
         write!(&mut self.stream, "{}", locus_line)?;
 
         // Fields
 
-        write_field_maybe(&mut self.stream, &record.definition, "DEFINITION")?;
+        match record.definition {
+            Some(ref d) if self.rewrap_definition => {
+                write_field(&mut self.stream, &rewrapped_definition(d), "DEFINITION")?
+            }
+            _ => write_field_maybe(&mut self.stream, &record.definition, "DEFINITION")?,
+        }
         write_field_maybe(&mut self.stream, &record.accession, "ACCESSION")?;
         write_field_maybe(&mut self.stream, &record.version, "VERSION")?;
+        write_field_maybe(&mut self.stream, &record.nid, "NID")?;
         write_field_maybe(&mut self.stream, &record.dblink, "DBLINK")?;
+        write_field_maybe(&mut self.stream, &record.dbsource, "DBSOURCE")?;
         write_field_maybe(&mut self.stream, &record.keywords, "KEYWORDS")?;
+        write_field_maybe(&mut self.stream, &record.segment, "SEGMENT")?;
         if let Some(ref source) = record.source {
             write_field(&mut self.stream, &source.source, "SOURCE")?;
             write_field_maybe(&mut self.stream, &source.organism, "  ORGANISM")?;
         }
         for r in &record.references {
-            write_field(&mut self.stream, &r.description, "REFERENCE")?;
-            for a in &r.authors {
-                write_field(&mut self.stream, a, "  AUTHORS")?;
-            }
+            write_field(&mut self.stream, &r.description_with_bases(record.alphabet), "REFERENCE")?;
+            write_field_maybe(&mut self.stream, &r.authors, "  AUTHORS")?;
             write_field_maybe(&mut self.stream, &r.consortium, "  CONSRTM")?;
             write_field(&mut self.stream, &r.title, "  TITLE")?;
             write_field_maybe(&mut self.stream, &r.journal, "  JOURNAL")?;
@@ -166,43 +400,20 @@ impl<W: Write> SeqWriter<W> {
 
         if !record.features.is_empty() {
             self.stream.write_all(b"FEATURES             Location/Qualifiers\n")?;
-            for f in &record.features {
-                let first_indent = format!("     {:<15} ", f.kind);
-                let location = f.location.to_gb_format();
-                wrap_location(
-                    &mut self.stream,
-                    &location,
-                    MAX_WIDTH,
-                    first_indent.as_str(),
-                    QUALIFIER_INDENT,
-                )?;
-                for &(ref key, ref val) in &f.qualifiers {
-                    match *val {
-                        None => writeln!(&mut self.stream, "{}/{}", QUALIFIER_INDENT, key)?,
-                        Some(ref val) => {
-                            let quote = !FTQUAL_NO_QUOTE.iter().any(|x| x == key);
-                            let first_indent = format!("{}/{}=", QUALIFIER_INDENT, key);
-                            if POS_QUAL.iter().any(|x| x == key) {
-                                wrap_location(
-                                    &mut self.stream,
-                                    val,
-                                    MAX_WIDTH,
-                                    first_indent.as_str(),
-                                    QUALIFIER_INDENT,
-                                )?;
-                            } else {
-                                wrap_text(
-                                    &mut self.stream,
-                                    val,
-                                    MAX_WIDTH,
-                                    first_indent.as_str(),
-                                    QUALIFIER_INDENT,
-                                    quote,
-                                )?;
-                            }
-                        }
-                    }
-                }
+            self.write_features(record, "     ", QUALIFIER_INDENT)?;
+        }
+
+        // Master records
+
+        let master = [
+            (&record.wgs, "WGS"),
+            (&record.wgs_scafld, "WGS_SCAFLD"),
+            (&record.tsa, "TSA"),
+            (&record.tls, "TLS"),
+        ];
+        for (ranges, keyword) in master {
+            for range in ranges {
+                write_field(&mut self.stream, &range.to_string(), keyword)?;
             }
         }
 
@@ -220,10 +431,31 @@ impl<W: Write> SeqWriter<W> {
 
         // ORIGIN
 
+        // DDBJ always has BASE COUNT, GenBank only kept it from older records
+        let counts = if record.is_protein() {
+            None
+        } else if !record.seq.is_empty()
+            && (self.dialect == Dialect::Ddbj || record.base_count.is_some())
+        {
+            Some(BaseCount::new(&record.seq))
+        } else {
+            record.base_count
+        };
+        if let Some(counts) = counts {
+            write!(
+                &mut self.stream,
+                "BASE COUNT  {:>7} a{:>7} c{:>7} g{:>7} t",
+                counts.a, counts.c, counts.g, counts.t
+            )?;
+            if counts.other > 0 {
+                write!(&mut self.stream, "{:>7} others", counts.other)?;
+            }
+            writeln!(&mut self.stream)?;
+        }
         if !record.seq.is_empty() {
             let mut line = Vec::with_capacity(79);
             write!(&mut line, "ORIGIN      ")?;
-            for (i, &b) in record.seq.iter().enumerate() {
+            for (i, &b) in self.cased_sequence(record).iter().enumerate() {
                 if i % 60 == 0 {
                     line.push(b'\n');
                     self.stream.write_all(&line)?;
@@ -241,6 +473,215 @@ impl<W: Write> SeqWriter<W> {
         writeln!(&mut self.stream, "//")?;
         Ok(())
     }
+
+    /// Write the feature table, with `prefix` in front of each feature key
+    /// and `indent` in front of the other lines
+    fn write_features(&mut self, record: &Seq, prefix: &str, indent: &str) -> io::Result<()> {
+        for f in &record.features {
+            let rewritten = f.with_display_qualifiers(self.display_dialect);
+            let f = rewritten.as_ref().unwrap_or(f);
+            let spelling = f.original_spelling.as_ref();
+            let kind = spelling
+                .and_then(|s| s.kind.as_ref())
+                .filter(|k| feature_kinds::canonical(k).as_ref() == Some(&f.kind))
+                .map_or(&*f.kind, |k| k.as_str());
+            let first_indent = format!("{}{:<15} ", prefix, kind);
+            let location = f.location.to_gb_format();
+            wrap_location(
+                &mut self.stream,
+                &location,
+                MAX_WIDTH,
+                first_indent.as_str(),
+                indent,
+            )?;
+            for (i, (key, val)) in f.qualifiers.iter().enumerate() {
+                // only use the original spelling if it's still the same key
+                let written_key = spelling
+                    .and_then(|s| s.qualifiers.iter().find(|q| q.0 == i))
+                    .filter(|q| qualifier_keys::canonical(&q.1).as_ref() == Some(key))
+                    .map_or(&**key, |q| q.1.as_str());
+                match *val {
+                    None => writeln!(&mut self.stream, "{}/{}", indent, written_key)?,
+                    Some(ref val) => {
+                        let quote = !FTQUAL_NO_QUOTE.iter().any(|x| x == key);
+                        let first_indent = format!("{}/{}=", indent, written_key);
+                        if POS_QUAL.iter().any(|x| x == key) {
+                            wrap_location(
+                                &mut self.stream,
+                                val,
+                                MAX_WIDTH,
+                                first_indent.as_str(),
+                                indent,
+                            )?;
+                        } else {
+                            wrap_text(
+                                &mut self.stream,
+                                val,
+                                MAX_WIDTH,
+                                first_indent.as_str(),
+                                indent,
+                                quote,
+                                true,
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the record in EMBL format, as ENA uses
+    fn write_embl(&mut self, record: &Seq) -> io::Result<()> {
+        if record.is_protein() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Protein records can't be written in EMBL format",
+            ));
+        }
+        let length = self.locus_length(record)?;
+        let seq = self.cased_sequence(record);
+        let out = &mut self.stream;
+
+        // ID, following ENA's submission format for anything unknown
+        let accession = record
+            .accession
+            .as_ref()
+            .and_then(|a| a.split_whitespace().next())
+            .or(record.name.as_deref())
+            .unwrap_or("XXX");
+        let version = record
+            .version
+            .as_ref()
+            .and_then(|v| v.split_whitespace().next()?.rsplit_once('.'))
+            .map_or("XXX", |(_, v)| v);
+        let mol_type = record
+            .features
+            .iter()
+            .find(|f| f.kind == feature_kind!("source"))
+            .and_then(|f| f.qualifier_values(qualifier_key!("mol_type")).next())
+            .or(record.molecule_type.as_ref().map(MoleculeType::as_str))
+            .unwrap_or("unassigned DNA");
+        writeln!(
+            out,
+            "ID   {}; SV {}; {}; {}; STD; {}; {} BP.",
+            accession,
+            version,
+            record.topology,
+            mol_type,
+            embl_division(record.division.as_str()),
+            length
+        )?;
+        writeln!(out, "XX")?;
+        if let Some(ref accession) = record.accession {
+            let accessions = accession.split_whitespace().map(|a| format!("{};", a)).join(" ");
+            write_embl_field(&mut *out, &accessions, "AC")?;
+            writeln!(out, "XX")?;
+        }
+        if let Some(ref definition) = record.definition {
+            write_embl_field(&mut *out, &definition.split_whitespace().join(" "), "DE")?;
+            writeln!(out, "XX")?;
+        }
+        if let Some(ref keywords) = record.keywords {
+            write_embl_field(&mut *out, &keywords.split_whitespace().join(" "), "KW")?;
+            writeln!(out, "XX")?;
+        }
+        if let Some(ref source) = record.source {
+            write_embl_field(&mut *out, &source.source, "OS")?;
+            // the first line of ORGANISM is the name, then the lineage
+            let lineage = source
+                .organism
+                .as_ref()
+                .and_then(|o| o.split_once('\n'))
+                .map(|(_, lineage)| lineage.split_whitespace().join(" "));
+            if let Some(lineage) = lineage {
+                write_embl_field(&mut *out, &lineage, "OC")?;
+            }
+            writeln!(out, "XX")?;
+        }
+        for (i, r) in record.references.iter().enumerate() {
+            writeln!(out, "RN   [{}]", i + 1)?;
+            if !r.bases.is_empty() {
+                let positions = r.bases.iter().map(|(a, b)| format!("{}-{}", a + 1, b)).join(", ");
+                write_embl_field(&mut *out, &positions, "RP")?;
+            }
+            if let Some(ref pubmed) = r.pubmed {
+                writeln!(out, "RX   PUBMED; {}.", pubmed.trim())?;
+            }
+            if let Some(ref consortium) = r.consortium {
+                write_embl_field(&mut *out, &format!("{};", consortium.trim()), "RG")?;
+            }
+            if let Some(ref authors) = r.authors {
+                let authors = authors.split_whitespace().join(" ");
+                write_embl_field(&mut *out, &format!("{};", authors), "RA")?;
+            }
+            let title = r.title.split_whitespace().join(" ");
+            if title.is_empty() {
+                writeln!(out, "RT   ;")?;
+            } else {
+                write_embl_field(&mut *out, &format!("\"{}\";", title), "RT")?;
+            }
+            if let Some(ref journal) = r.journal {
+                write_embl_field(&mut *out, &journal.split_whitespace().join(" "), "RL")?;
+            }
+            writeln!(out, "XX")?;
+        }
+        for comment in &record.comments {
+            write_embl_field(&mut *out, comment, "CC")?;
+            writeln!(out, "XX")?;
+        }
+
+        if !record.features.is_empty() {
+            self.stream
+                .write_all(b"FH   Key             Location/Qualifiers\nFH\n")?;
+            self.write_features(record, "FT   ", EMBL_QUALIFIER_INDENT)?;
+            writeln!(&mut self.stream, "XX")?;
+        }
+        let out = &mut self.stream;
+
+        if let Some(ref contig) = record.contig {
+            wrap_location(&mut *out, &contig.to_gb_format(), MAX_WIDTH, "CO   ", "CO   ")?;
+            writeln!(out, "XX")?;
+        }
+
+        if !record.seq.is_empty() {
+            let counts = BaseCount::new(&record.seq);
+            writeln!(
+                out,
+                "SQ   Sequence {} BP; {} A; {} C; {} G; {} T; {} other;",
+                record.seq.len(),
+                counts.a,
+                counts.c,
+                counts.g,
+                counts.t,
+                counts.other
+            )?;
+            for (i, line) in seq.chunks(60).enumerate() {
+                let blocks = line
+                    .chunks(10)
+                    .map(String::from_utf8_lossy)
+                    .join(" ");
+                writeln!(out, "     {:<65}{:>10}", blocks, i * 60 + line.len())?;
+            }
+        }
+        writeln!(out, "//")?;
+        Ok(())
+    }
+}
+
+/// The EMBL code for a GenBank division, for the few that differ
+fn embl_division(division: &str) -> &str {
+    match division {
+        "BCT" => "PRO",
+        "PRI" => "HUM",
+        "UNA" => "UNC",
+        _ => division,
+    }
+}
+
+fn write_embl_field<T: Write>(file: T, field: &str, code: &str) -> io::Result<()> {
+    let prefix = format!("{}   ", code);
+    wrap_text(file, field, MAX_WIDTH + 1, &prefix, &prefix, false, false)
 }
 
 pub fn write<T: Write>(file: T, record: &Seq) -> io::Result<()> {
@@ -259,9 +700,29 @@ where
         keyword.as_str(),
         FIELD_INDENT,
         false,
+        false,
     )
 }
 
+/// The longest prefix of `s` no longer than `len` bytes, not splitting any
+/// characters
+fn truncated(s: &str, len: usize) -> &str {
+    let mut end = len.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Joins all lines of a DEFINITION and makes sure it ends with a period
+fn rewrapped_definition(definition: &str) -> String {
+    let mut res = definition.split_whitespace().join(" ");
+    if !res.is_empty() && !res.ends_with('.') {
+        res.push('.');
+    }
+    res
+}
+
 fn write_field_maybe<T, U>(mut file: T, field: &Option<U>, keyword: &str) -> io::Result<()>
 where
     T: Write,
@@ -310,6 +771,7 @@ fn wrap_get_line<'a>(line: &mut String, input: &'a str, max_len: usize, quote: b
     let mut last_space_in = None;
     let mut last_space_out = 0; // need to keep track of this separately because
                                 // of escape sequences
+    let mut split_quote = false;
     let mut i = input.char_indices();
     while line.len() < max_len {
         if let Some((idx, ch)) = i.next() {
@@ -326,6 +788,7 @@ fn wrap_get_line<'a>(line: &mut String, input: &'a str, max_len: usize, quote: b
                     // if we're one character away from the end of the line,
                     // wrap now to avoid splitting the escape sequence
                     if line.len() >= max_len - 1 {
+                        split_quote = true;
                         break;
                     } else {
                         line.push_str("\"\"");
@@ -342,15 +805,23 @@ fn wrap_get_line<'a>(line: &mut String, input: &'a str, max_len: usize, quote: b
         }
     }
     // So it's time to split the line. First check if it's really necessary,
-    // maybe the next character is a newline or space or the end
-    if let Some((idx_next, next)) = i.next() {
-        if next == ' ' || next == '\n' {
-            return &input[idx_next + 1..];
+    // maybe the next character is a newline or space or the end. If we
+    // stopped at a quote, `i` has already gone past it, so that's the next
+    // character and it still needs writing.
+    if !split_quote {
+        if let Some((idx_next, next)) = i.next() {
+            match next {
+                // like wrapping at the last space, leave the space for the
+                // caller to keep or drop
+                ' ' => return &input[idx_next..],
+                '\n' => return &input[idx_next + 1..],
+                _ => {}
+            }
+        } else {
+            // it's the end, no need to wrap
+            assert!(consumed == input.len());
+            return &input[consumed..];
         }
-    } else {
-        // it's the end, no need to wrap
-        assert!(consumed == input.len());
-        return &input[consumed..];
     }
     // try to wrap at last space
     if let Some(last_space_in) = last_space_in {
@@ -369,6 +840,7 @@ fn wrap_text<T: Write>(
     first_indent: &str,
     subsequent_indent: &str,
     quote: bool,
+    keep_break_space: bool,
 ) -> io::Result<()> {
     let mut line = String::with_capacity(max_width);
     let mut indent = first_indent;
@@ -377,7 +849,16 @@ fn wrap_text<T: Write>(
     }
     text = wrap_get_line(&mut line, text, max_width - indent.len(), quote);
     write!(file, "{}{}", indent, line)?;
-    while !text.is_empty() {
+    loop {
+        // Header fields are parsed with line breaks kept, so there's no need
+        // to keep the space we wrapped at. Qualifier values are joined
+        // without a separator though.
+        if !keep_break_space {
+            text = text.strip_prefix(' ').unwrap_or(text);
+        }
+        if text.is_empty() {
+            break;
+        }
         indent = subsequent_indent;
         line.clear();
         text = wrap_get_line(&mut line, text, max_width - subsequent_indent.len(), quote);
@@ -400,20 +881,121 @@ pub mod tests {
 
     use super::*;
     use crate::reader::SeqReader;
-    use crate::seq::{Feature, Location, Seq};
+    use crate::seq::{Division, Feature, Location, Seq};
     use std::io::Read;
     use std::io::{BufRead, BufReader};
 
+    #[test]
+    fn definition_wrapping() {
+        let definition = "Escherichia coli str. K-12 substr. MG1655, complete genome, with\nsome extra words to make it wrap";
+        let seq = Seq {
+            definition: Some(definition.into()),
+            seq: b"acgt".to_vec(),
+            ..Seq::empty()
+        };
+        let definition_lines = |out: &[u8]| -> Vec<String> {
+            BufReader::new(out)
+                .lines()
+                .map(Result::unwrap)
+                .skip(1)
+                .take_while(|l| l.starts_with("DEFINITION") || l.starts_with(' '))
+                .collect()
+        };
+
+        let mut out = Vec::new();
+        SeqWriter::new(&mut out).write(&seq).unwrap();
+        assert_eq!(
+            definition_lines(&out),
+            vec![
+                "DEFINITION  Escherichia coli str. K-12 substr. MG1655, complete genome, with",
+                "            some extra words to make it wrap",
+            ]
+        );
+
+        let mut out = Vec::new();
+        SeqWriter::new(&mut out)
+            .rewrap_definition(true)
+            .write(&seq)
+            .unwrap();
+        let lines = definition_lines(&out);
+        assert_eq!(
+            lines,
+            vec![
+                "DEFINITION  Escherichia coli str. K-12 substr. MG1655, complete genome, with",
+                "            some extra words to make it wrap.",
+            ]
+        );
+        assert!(lines.iter().all(|l| l.len() < 80));
+
+        let mut reader = SeqReader::new(&out[..]);
+        reader.definition_joining(crate::reader::DefinitionJoining::Normalize);
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(
+            record.definition.unwrap(),
+            "Escherichia coli str. K-12 substr. MG1655, complete genome, with some extra words to make it wrap."
+        );
+    }
+
+    #[test]
+    fn key_normalization() {
+        use crate::reader::KeyNormalization;
+        let data = b"LOCUS       a                          4 bp    DNA     linear   UNK 01-JAN-2020
+FEATURES             Location/Qualifiers
+     cds             1..3
+                     /Locus_Tag=\"b0001\"
+                     /note=\"x\"
+     Promoter        1..3
+                     /Note=\"y\"
+ORIGIN
+        1 acgt
+//
+";
+        let read = |normalization| {
+            let mut reader = SeqReader::new(&data[..]);
+            reader.key_normalization(normalization);
+            reader.next().unwrap().unwrap()
+        };
+        let write = |seq: &Seq| {
+            let mut out = Vec::new();
+            SeqWriter::new(&mut out).write(seq).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let features = |text: &str| {
+            let start = text.find("FEATURES").unwrap();
+            text[start..text.find("ORIGIN").unwrap()].to_string()
+        };
+
+        let seq = read(KeyNormalization::Canonicalize);
+        assert_eq!(seq.features[0].kind, feature_kind!("CDS"));
+        assert_eq!(seq.features[0].qualifiers[0].0, qualifier_key!("locus_tag"));
+        assert_eq!(&*seq.features[1].kind, "Promoter");
+        assert_eq!(seq.features[1].qualifiers[0].0, qualifier_key!("note"));
+        assert!(seq.features[0].original_spelling.is_none());
+        assert!(write(&seq).contains("     CDS             1..3\n                     /locus_tag="));
+
+        let mut seq = read(KeyNormalization::CanonicalizeKeepingOriginal);
+        assert_eq!(seq.features[0].kind, feature_kind!("CDS"));
+        assert_eq!(
+            features(&write(&seq)),
+            features(std::str::from_utf8(data).unwrap())
+        );
+        // edited keys are written with the new spelling
+        seq.features[1].qualifiers[0].0 = qualifier_key!("gene");
+        assert!(write(&seq).contains("                     /gene=\"y\""));
+
+        assert_eq!(&*read(KeyNormalization::Preserve).features[0].kind, "cds");
+    }
+
     #[test]
     fn multiline_spaces_retained_after_roundtrip() {
         // ensures that qualifier values that are split across multiple lines when writing
         // don't lose any spaces when they're parsed again
         let product = "Lipopolysaccharide export system ATP-binding protein LptB".to_string();
-        let feat = Feature {
-            kind: feature_kind!("CDS"),
-            location: Location::simple_range(100, 200),
-            qualifiers: vec![(qualifier_key!("product"), Some(product.clone()))],
-        };
+        let feat = Feature::new(
+            feature_kind!("CDS"),
+            Location::simple_range(100, 200),
+            vec![(qualifier_key!("product"), Some(product.clone()))],
+        );
         let mut seq = Seq::empty();
         seq.features = vec![feat];
         let mut out = Vec::new();
@@ -434,6 +1016,31 @@ pub mod tests {
         assert_eq!(product, roundtrip_product);
     }
 
+    #[test]
+    fn quotes_escaped_after_roundtrip() {
+        // quotes are written as "" and shouldn't be lost when one lands at
+        // the end of a line
+        for padding in 50..60 {
+            let note = format!("{} said \"hello\"", "x".repeat(padding));
+            let mut seq = Seq::empty();
+            seq.features = vec![Feature::new(
+                feature_kind!("misc_feature"),
+                Location::simple_range(0, 1),
+                vec![(qualifier_key!("note"), Some(note.clone()))],
+            )];
+            let mut out = Vec::new();
+            SeqWriter::new(&mut out).write(&seq).unwrap();
+            assert!(String::from_utf8_lossy(&out).contains("\"\"hello\"\""));
+            let record = SeqReader::new(&out[..]).next().unwrap().unwrap();
+            let roundtrip_note = record.features[0]
+                .qualifier_values(qualifier_key!("note"))
+                .next()
+                .unwrap()
+                .replace('\n', "");
+            assert_eq!(note, roundtrip_note);
+        }
+    }
+
     #[test]
     fn truncate_locus() {
         let mut seq = Seq::empty();
@@ -465,4 +1072,201 @@ pub mod tests {
             "LOCUS       1122217.SAMN02441331.KB899611_14 0 bp            linear UNK 01-JAN-1970\n"
         );
     }
+
+    #[test]
+    fn locus_name_policy() {
+        let mut seq = Seq {
+            seq: b"acgt".to_vec(),
+            ..Seq::empty()
+        };
+        seq.name = Some(String::from("1122217.SAMN02441331.KB899611_14"));
+        let locus = |seq: &Seq, policy| {
+            let mut out = Vec::new();
+            SeqWriter::new(&mut out)
+                .locus_name_policy(policy)
+                .write(seq)
+                .map(|_| String::from_utf8(out).unwrap())
+        };
+
+        let hashed = locus(&seq, LocusNamePolicy::HashSuffix).unwrap();
+        let name = hashed.split_whitespace().nth(1).unwrap();
+        assert_eq!(name.len(), 26);
+        assert!(name.starts_with("1122217.SAMN0244133_"));
+        let err = locus(&seq, LocusNamePolicy::Error).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let long = locus(&seq, LocusNamePolicy::Long).unwrap();
+        let parsed = crate::reader::parse_slice(long.as_bytes()).unwrap();
+        assert_eq!(parsed[0].name, seq.name);
+        assert_eq!(parsed[0].len, Some(4));
+
+        seq.name = Some("short".into());
+        seq.molecule_type = Some("genomic DNA".into());
+        assert!(locus(&seq, LocusNamePolicy::Error).is_err());
+        assert!(locus(&seq, LocusNamePolicy::HashSuffix)
+            .unwrap()
+            .starts_with("LOCUS       short                      4 bp            linear"));
+    }
+
+    #[test]
+    fn invalid_division() {
+        let mut seq = Seq {
+            division: Division::Bct,
+            ..Seq::empty()
+        };
+        let mut out = Vec::new();
+        write(&mut out, &seq).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(" linear BCT "));
+        seq.division = "bacterial".into();
+        let err = write(&mut Vec::new(), &seq).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn locus_length_and_date() {
+        let mut seq = Seq {
+            name: Some("x".into()),
+            seq: b"acgt".to_vec(),
+            len: Some(4),
+            ..Seq::empty()
+        };
+        seq.seq.extend(b"gg");
+        let locus = |seq: &Seq, recompute, date| {
+            let mut out = Vec::new();
+            SeqWriter::new(&mut out)
+                .recompute_length(recompute)
+                .locus_date(date)
+                .write(seq)
+                .map(|_| String::from_utf8(out).unwrap().lines().next().unwrap().to_string())
+        };
+        let err = locus(&seq, false, LocusDate::Keep).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let date = Date::from_ymd(2024, 2, 29).unwrap();
+        let line = locus(&seq, true, LocusDate::Fixed(date)).unwrap();
+        assert!(line.contains(" 6 bp "), "{}", line);
+        assert!(line.ends_with(" 29-FEB-2024"), "{}", line);
+
+        let today = locus(&seq, true, LocusDate::Today).unwrap();
+        assert!(today.ends_with(&Date::today().to_string()));
+        assert!(!today.ends_with("1970"));
+
+        let contig = Seq {
+            seq: vec![],
+            ..seq
+        };
+        assert!(locus(&contig, true, LocusDate::Keep).unwrap().contains(" 4 bp "));
+    }
+
+    #[test]
+    fn update_translations() {
+        let seq = Seq {
+            seq: b"atgaaataa".to_vec(),
+            features: vec![Feature::new(
+                feature_kind!("CDS"),
+                Location::simple_range(0, 9),
+                vec![],
+            )],
+            ..Seq::empty()
+        };
+        let mut out = Vec::new();
+        SeqWriter::new(&mut out).update_translations(true).write(&seq).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\n                     /translation=\"MK\"\n"), "{}", out);
+        assert!(seq.features[0].qualifiers.is_empty());
+    }
+
+    #[test]
+    fn dialects() {
+        let seq = Seq {
+            name: Some("X56734".into()),
+            accession: Some("X56734 S46826".into()),
+            version: Some("X56734.1".into()),
+            definition: Some("Trifolium repens mRNA for\nbeta-glucosidase".into()),
+            molecule_type: Some("mRNA".into()),
+            division: Division::Pln,
+            seq: b"acgtnacgtacgtac".to_vec(),
+            features: vec![Feature::new(
+                feature_kind!("CDS"),
+                Location::simple_range(0, 9),
+                vec![(qualifier_key!("gene"), Some("bglA".into()))],
+            )],
+            references: vec![crate::seq::Reference {
+                description: "1  (bases 1 to 15)".into(),
+                bases: vec![(0, 15)],
+                authors: Some("Oxtoby,E. and\nDunn,M.A.".into()),
+                consortium: None,
+                title: "Direct Submission".into(),
+                journal: Some("Submitted (12-JUN-1990)".into()),
+                pubmed: None,
+                remark: None,
+            }],
+            ..Seq::empty()
+        };
+        let write = |dialect| {
+            let mut out = Vec::new();
+            SeqWriter::new(&mut out).dialect(dialect).write(&seq).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert!(!write(Dialect::Ncbi).contains("BASE COUNT"));
+        assert!(write(Dialect::Ddbj).contains(
+            "\nBASE COUNT        4 a      4 c      3 g      3 t      1 others\nORIGIN"
+        ));
+        let embl = write(Dialect::Ena);
+        assert_eq!(
+            embl,
+            "ID   X56734; SV 1; linear; mRNA; STD; PLN; 15 BP.
+XX
+AC   X56734; S46826;
+XX
+DE   Trifolium repens mRNA for beta-glucosidase
+XX
+RN   [1]
+RP   1-15
+RA   Oxtoby,E. and Dunn,M.A.;
+RT   \"Direct Submission\";
+RL   Submitted (12-JUN-1990)
+XX
+FH   Key             Location/Qualifiers
+FH
+FT   CDS             1..9
+FT                   /gene=\"bglA\"
+XX
+SQ   Sequence 15 BP; 4 A; 4 C; 3 G; 3 T; 1 other;
+     acgtnacgta cgtac                                                         15
+//
+"
+        );
+        assert!(embl.lines().all(|l| l.len() <= 80));
+
+        let protein = Seq {
+            alphabet: crate::seq::Alphabet::Protein,
+            ..Seq::empty()
+        };
+        let mut out = Vec::new();
+        let err = SeqWriter::new(&mut out).dialect(Dialect::Ena).write(&protein);
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn sequence_case() {
+        let data = b"LOCUS       a                         12 bp    DNA     linear   UNK 01-JAN-2020
+ORIGIN
+        1 ACGTacgtACGT
+//
+";
+        let mut seq = crate::reader::parse_slice(&data[..]).unwrap().remove(0);
+        assert_eq!(seq.seq, b"ACGTacgtACGT");
+        assert_eq!(seq.case(), Some(Case::Mixed));
+        let write = |seq: &Seq, case| {
+            let mut out = Vec::new();
+            SeqWriter::new(&mut out).sequence_case(case).write(seq).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert!(write(&seq, Case::Mixed).contains("        1 ACGTacgtAC GT\n"));
+        assert!(write(&seq, Case::Lower).contains("        1 acgtacgtac gt\n"));
+        assert_eq!(seq.case(), Some(Case::Mixed));
+        seq.normalize_case(Case::Upper);
+        assert_eq!(seq.case(), Some(Case::Upper));
+        assert_eq!(Seq::empty().case(), None);
+    }
 }